@@ -26,7 +26,7 @@ pub mod overloads;
 pub use micheline::Micheline;
 use num_bigint::{BigInt, BigUint};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     rc::Rc,
 };
 /// Reexported from [tezos_crypto_rs::hash]. Typechecked values of the Michelson
@@ -34,7 +34,7 @@ use std::{
 pub use tezos_crypto_rs::hash::ChainId;
 use typed_arena::Arena;
 
-use crate::{bls, lexer::Prim};
+use crate::{bls, lexer::Prim, sapling, timelock};
 
 pub use annotations::{Annotation, Annotations, FieldAnnotation, NO_ANNS};
 pub use big_map::BigMap;
@@ -100,6 +100,10 @@ pub enum Type {
     Bls12381Fr,
     Bls12381G1,
     Bls12381G2,
+    Chest,
+    ChestKey,
+    SaplingState(u16),
+    SaplingTransaction(u16),
 }
 
 impl Type {
@@ -110,7 +114,8 @@ impl Type {
         match self {
             Nat | Int | Bool | Mutez | String | Unit | Never | Operation | Address | ChainId
             | Bytes | Key | Signature | KeyHash | Timestamp | Bls12381Fr | Bls12381G1
-            | Bls12381G2 => 1,
+            | Bls12381G2 | Chest | ChestKey => 1,
+            SaplingState(_) | SaplingTransaction(_) => 1,
             Pair(p) | Or(p) | Map(p) | BigMap(p) | Lambda(p) => {
                 1 + p.0.size_for_gas() + p.1.size_for_gas()
             }
@@ -224,6 +229,16 @@ impl<'a> IntoMicheline<'a> for &'_ Type {
             Bls12381Fr => Micheline::prim0(Prim::bls12_381_fr),
             Bls12381G1 => Micheline::prim0(Prim::bls12_381_g1),
             Bls12381G2 => Micheline::prim0(Prim::bls12_381_g2),
+            Chest => Micheline::prim0(Prim::chest),
+            ChestKey => Micheline::prim0(Prim::chest_key),
+            SaplingState(ms) => {
+                Micheline::prim1(arena, Prim::sapling_state, Micheline::Int((*ms).into()))
+            }
+            SaplingTransaction(ms) => Micheline::prim1(
+                arena,
+                Prim::sapling_transaction,
+                Micheline::Int((*ms).into()),
+            ),
 
             Option(x) => Micheline::prim1(
                 arena,
@@ -328,6 +343,10 @@ pub enum TypedValue<'a> {
     // G1 and G2 are a bit too large to lug them about on-stack
     Bls12381G1(Box<bls::G1>),
     Bls12381G2(Box<bls::G2>),
+    Chest(Box<timelock::Chest>),
+    ChestKey(Box<timelock::ChestKey>),
+    SaplingState(sapling::SaplingState),
+    SaplingTransaction(Box<sapling::Transaction>),
 }
 
 impl<'a> IntoMicheline<'a> for TypedValue<'a> {
@@ -389,6 +408,16 @@ impl<'a> IntoMicheline<'a> for TypedValue<'a> {
             TV::Bls12381Fr(x) => V::Bytes(x.to_bytes().to_vec()),
             TV::Bls12381G1(x) => V::Bytes(x.to_bytes().to_vec()),
             TV::Bls12381G2(x) => V::Bytes(x.to_bytes().to_vec()),
+            TV::Chest(x) => V::Bytes(x.encode()),
+            TV::ChestKey(x) => V::Bytes(x.encode()),
+            // NB: like in the protocol, a pending diff can't be represented,
+            // it must be dumped to the storage first, see
+            // [sapling::dump_sapling_state_updates].
+            TV::SaplingState(x) => match x.id {
+                Some(id) => V::Int(id.0),
+                None => V::Seq(&[]),
+            },
+            TV::SaplingTransaction(x) => V::Bytes(x.encode()),
             TV::Contract(x) => go(TV::Address(x)),
             TV::Operation(operation_info) => match operation_info.operation {
                 Operation::TransferTokens(tt) => Micheline::prim3(
@@ -514,6 +543,21 @@ impl<'a> TypedValue<'a> {
     pub fn new_bls12381_g2(x: bls::G2) -> Self {
         Self::Bls12381G2(Box::new(x))
     }
+
+    /// Convenience function to construct a new [Self::Chest]. Allocates a new [Box].
+    pub fn new_chest(x: timelock::Chest) -> Self {
+        Self::Chest(Box::new(x))
+    }
+
+    /// Convenience function to construct a new [Self::ChestKey]. Allocates a new [Box].
+    pub fn new_chest_key(x: timelock::ChestKey) -> Self {
+        Self::ChestKey(Box::new(x))
+    }
+
+    /// Convenience function to construct a new [Self::SaplingTransaction]. Allocates a new [Box].
+    pub fn new_sapling_transaction(x: sapling::Transaction) -> Self {
+        Self::SaplingTransaction(Box::new(x))
+    }
 }
 
 /// Enum representing typechecked Michelson instructions. Some instructions may
@@ -638,6 +682,14 @@ pub enum Instruction<'a> {
     },
     CreateContract(Rc<ContractScript<'a>>, &'a Micheline<'a>),
     Map(overloads::Map, Vec<Self>),
+    View {
+        name: String,
+        input_ty: Type,
+        output_ty: Type,
+    },
+    OpenChest,
+    SaplingEmptyState(u16),
+    SaplingVerifyUpdate,
 }
 
 /// A full typechecked contract script.
@@ -649,6 +701,22 @@ pub struct ContractScript<'a> {
     pub storage: Type,
     /// Script code. Corresponds to the script's `code` field.
     pub code: Instruction<'a>,
+    /// On-chain views, indexed by name. Corresponds to the script's `view`
+    /// fields.
+    pub views: HashMap<String, View<'a>>,
+}
+
+/// A typechecked on-chain view, defined by a `view` field of a contract
+/// script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View<'a> {
+    /// View input type.
+    pub input_type: Type,
+    /// View output type.
+    pub output_type: Type,
+    /// View code. Expects `pair input_type storage` on the stack, and leaves
+    /// `output_type`.
+    pub code: Rc<[Instruction<'a>]>, // see Note: Rc in lambdas
 }
 
 #[cfg(test)]
@@ -861,6 +929,11 @@ pub mod test_strategies {
             T::BigMap(_) => panic!("Cannot generate typed value for big_map"),
             T::Lambda(_) => panic!("Cannot generate typed value for lambda"),
             T::Never =>  panic!("Cannot generate typed value for never"),
+            T::Chest => panic!("Cannot generate typed value for chest"),
+            T::ChestKey => panic!("Cannot generate typed value for chest_key"),
+            T::SaplingState(_) => panic!("Cannot generate typed value for sapling_state"),
+            T::SaplingTransaction(_) =>
+                panic!("Cannot generate typed value for sapling_transaction"),
             // NOTE: if you append clauses here, you likely need to update other generators too
         }
     }
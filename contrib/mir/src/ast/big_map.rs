@@ -17,10 +17,24 @@ use typed_arena::Arena;
 
 use super::{Micheline, Type, TypedValue};
 
+mod durable;
+
+pub use durable::DurableLazyStorage;
+
 /// Id of big map in the lazy storage.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BigMapId(pub BigInt);
 
+impl BigMapId {
+    /// Whether the id refers to a temporary big map. Like in the Tezos
+    /// protocol, temporary big maps have negative ids. They only live until
+    /// the end of the operation, and are copied when they end up in the
+    /// contract storage.
+    pub fn is_temporary(&self) -> bool {
+        self.0.sign() == num_bigint::Sign::Minus
+    }
+}
+
 impl Display for BigMapId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -164,7 +178,7 @@ pub trait LazyStorage<'a> {
     /// Get key and value types of the map.
     ///
     /// This returns None if the map with such ID is not present in the storage.
-    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(&Type, &Type)>, LazyStorageError>;
+    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(Type, Type)>, LazyStorageError>;
 
     /// Allocate a new empty big map.
     fn big_map_new(
@@ -293,11 +307,11 @@ impl<'a> LazyStorage<'a> for InMemoryLazyStorage<'a> {
         Ok(())
     }
 
-    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(&Type, &Type)>, LazyStorageError> {
+    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(Type, Type)>, LazyStorageError> {
         Ok(self
             .big_maps
             .get(id)
-            .map(|info| (&info.key_type, &info.value_type)))
+            .map(|info| (info.key_type.clone(), info.value_type.clone())))
     }
 
     fn big_map_new(
@@ -330,29 +344,6 @@ impl<'a> LazyStorage<'a> for InMemoryLazyStorage<'a> {
     }
 }
 
-/// This will also implement [LazyStorage].
-///
-/// Worth mentioning, that this type will eventually wrap `&mut impl Runtime`
-/// (or rather `&mut R (where R: Runtime)` which has to be a mere reference,
-/// this `Runtime` will provide access to rollup's persistent storage. And this
-/// potentially indicates some problems for us.
-///
-/// * We will have to put this storage to the context, meaning that in `Ctx` we
-///   will have to account for lifetimes and for the `R` generic argument.
-/// * One cannot put `&mut impl Runtime` twice into context because of borrowing
-///   restrictions. So trying to have e.g. `RollupStorage` as one context field,
-///   and some `OriginatedContractParameterGetter` that also works via `Runtime`
-///   as another context field won't be possible. We may end up with the entire
-///   `Ctx` implementing all the traits that our engine should be polymorphic
-///   over (`LazyStorage` is probably one of them), which is ugly and
-///   boilerplat-y.
-/// * The caller may have a similar problem - he will have to give
-///   typechecker/interpreter's context exclusive access to `Runtime`. We will
-///   have to make sure it doesn't restrict the caller from e.g. writing
-///   something to the storage between typechecker and interpreter calls.
-#[allow(dead_code)]
-struct RollupStorage();
-
 #[cfg(test)]
 mod test_big_map_operations {
     use super::*;
@@ -463,6 +454,10 @@ impl<'a> TypedValue<'a> {
             Bls12381Fr(_) => {}
             Bls12381G1(_) => {}
             Bls12381G2(_) => {}
+            Chest(_) => {}
+            ChestKey(_) => {}
+            SaplingState(_) => {}
+            SaplingTransaction(_) => {}
             Pair(p) => {
                 p.0.collect_big_maps(put_res);
                 p.1.collect_big_maps(put_res);
@@ -542,7 +537,10 @@ pub fn dump_big_map_updates<'a>(
     // * If a contract produces an operation with a big map, we immediately
     // deduplicate big map ID there too (the Tezos protocol implementation does
     // not).
-    // * There is no need to implement temporary lazy storage for now.
+    //
+    // Temporary big maps (see `BigMapId::is_temporary`) are never updated
+    // in-place, they are always copied to a fresh big map, since the storage
+    // they live in is cleared at the end of the operation.
 
     // The `finished_with_maps` vector above is supposed to contain all big maps
     // remaining on stack at the end of contract execution. After this function
@@ -602,8 +600,15 @@ pub fn dump_big_map_updates<'a>(
             storage.big_map_bulk_update(&new_id, mem::take(&mut map.overlay))?;
             map.id = Some(new_id)
         }
-        // The only remaining big map we update in the lazy storage in-place.
-        storage.big_map_bulk_update(&id, mem::take(&mut main_map.overlay))?
+        if id.is_temporary() {
+            let new_id = storage.big_map_copy(&id)?;
+            storage.big_map_bulk_update(&new_id, mem::take(&mut main_map.overlay))?;
+            main_map.id = Some(new_id)
+        } else {
+            // The only remaining big map we update in the lazy storage
+            // in-place.
+            storage.big_map_bulk_update(&id, mem::take(&mut main_map.overlay))?
+        }
     }
 
     Ok(())
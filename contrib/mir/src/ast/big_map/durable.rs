@@ -0,0 +1,562 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! [LazyStorage] backed by the durable storage of a smart rollup kernel.
+
+use num_bigint::BigInt;
+use std::{cell::RefCell, collections::BTreeMap, fmt};
+use tezos_smart_rollup_host::{
+    path::{Path, PATH_MAX_SIZE},
+    runtime::{Runtime, RuntimeError, ValueType},
+};
+use typed_arena::Arena;
+
+use super::{BigMapId, LazyStorage, LazyStorageError};
+use crate::ast::{IntoMicheline, Micheline, Type, TypedValue};
+use crate::context::Ctx;
+use crate::global_constants::ScriptExprHash;
+use crate::typechecker::typecheck_value;
+
+/// Path in the durable storage, built by [DurablePath::join] from a valid
+/// root path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DurablePath(String);
+
+impl DurablePath {
+    fn root(path: &impl Path) -> Self {
+        // path-encoded bytes are always ASCII
+        DurablePath(String::from_utf8_lossy(path.as_bytes()).into_owned())
+    }
+
+    /// Append a step to the path. Fails if the step contains characters not
+    /// allowed in paths, or the path becomes too long.
+    fn join(&self, step: impl fmt::Display) -> Result<Self, LazyStorageError> {
+        let res = format!("{}/{}", self.0, step);
+        let step = &res[self.0.len() + 1..];
+        if step.is_empty()
+            || !step
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+        {
+            return Err(LazyStorageError::OtherError(format!(
+                "invalid durable path step: {step}"
+            )));
+        }
+        if res.len() > PATH_MAX_SIZE {
+            return Err(LazyStorageError::OtherError(format!(
+                "durable path is too long: {res}"
+            )));
+        }
+        Ok(DurablePath(res))
+    }
+}
+
+impl fmt::Display for DurablePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// SAFETY: the root comes from a valid [Path], and [DurablePath::join] only
+// appends valid steps while keeping the size within [PATH_MAX_SIZE].
+unsafe impl Path for DurablePath {
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+fn runtime_error(err: RuntimeError) -> LazyStorageError {
+    LazyStorageError::OtherError(format!("durable storage error: {err}"))
+}
+
+fn non_existent(id: &BigMapId) -> LazyStorageError {
+    LazyStorageError::OtherError(format!("non-existent big map by id {id}"))
+}
+
+/// [LazyStorage] implementation keeping big maps in the durable storage of a
+/// smart rollup, accessed via [Runtime]. Big maps written by one kernel run
+/// are available to the following ones, so a kernel can host Michelson
+/// contracts with persistent big maps.
+///
+/// The layout under the root path mimics the one of the Tezos protocol
+/// context:
+///
+/// ```txt
+/// <root>/next_id                              next persistent id
+/// <root>/index/<id>/key_type                  binary-encoded key type
+/// <root>/index/<id>/value_type                binary-encoded value type
+/// <root>/index/<id>/contents/<key hash>       binary-encoded value
+/// <root>/temporary/next_id                    next temporary id
+/// <root>/temporary/index/<id>/...             same as above
+/// ```
+///
+/// where `<key hash>` is the [ScriptExprHash] of the packed key, i.e. the
+/// same hash the protocol uses to index big map contents.
+///
+/// Temporary big maps (see [BigMapId::is_temporary]) are allocated with
+/// [DurableLazyStorage::big_map_new_temporary] or
+/// [DurableLazyStorage::big_map_copy_temporary], and are removed all at once
+/// by [DurableLazyStorage::clear_temporary], which the kernel is expected to
+/// call at the end of each operation. [LazyStorage::big_map_new] and
+/// [LazyStorage::big_map_copy] always allocate persistent big maps, and
+/// [super::dump_big_map_updates] never updates temporary big maps in-place.
+///
+/// Note that the storage holds exclusive access to the [Runtime]. To use the
+/// host for other purposes between typechecker and interpreter calls, the
+/// storage can be dropped and constructed again over the same root path, as
+/// it keeps no state besides a cache of big map types.
+///
+/// Values read from the storage are typechecked without consuming gas from
+/// the execution [Ctx].
+pub struct DurableLazyStorage<'h, R> {
+    host: &'h mut R,
+    root: DurablePath,
+    types: RefCell<BTreeMap<BigMapId, (Type, Type)>>,
+}
+
+impl<'h, R: Runtime> DurableLazyStorage<'h, R> {
+    /// Construct a storage keeping big maps under the given durable storage
+    /// path. If there are big maps under this path already, they are
+    /// available through the new storage.
+    pub fn new(host: &'h mut R, root: &impl Path) -> Self {
+        DurableLazyStorage {
+            host,
+            root: DurablePath::root(root),
+            types: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Allocate a new empty temporary big map.
+    pub fn big_map_new_temporary(
+        &mut self,
+        key_type: &Type,
+        value_type: &Type,
+    ) -> Result<BigMapId, LazyStorageError> {
+        let id = self.fresh_id(true)?;
+        self.write_types(&id, key_type, value_type)?;
+        Ok(id)
+    }
+
+    /// Allocate a new temporary big map, filling it with the contents from
+    /// another map in the storage, either persistent or temporary.
+    pub fn big_map_copy_temporary(&mut self, id: &BigMapId) -> Result<BigMapId, LazyStorageError> {
+        self.copy(id, true)
+    }
+
+    /// Remove all temporary big maps and reset the temporary id counter.
+    pub fn clear_temporary(&mut self) -> Result<(), LazyStorageError> {
+        let path = self.temporary_root()?;
+        if self.host.store_has(&path).map_err(runtime_error)?.is_some() {
+            self.host.store_delete(&path).map_err(runtime_error)?;
+        }
+        self.types.borrow_mut().retain(|id, _| !id.is_temporary());
+        Ok(())
+    }
+
+    fn temporary_root(&self) -> Result<DurablePath, LazyStorageError> {
+        self.root.join("temporary")
+    }
+
+    fn map_path(&self, id: &BigMapId) -> Result<DurablePath, LazyStorageError> {
+        let base = if id.is_temporary() {
+            self.temporary_root()?
+        } else {
+            self.root.clone()
+        };
+        base.join("index")?.join(id)
+    }
+
+    fn entry_path(&self, id: &BigMapId, key: &TypedValue) -> Result<DurablePath, LazyStorageError> {
+        let arena = Arena::new();
+        let packed = key
+            .clone()
+            .into_micheline_optimized_legacy(&arena)
+            .encode_for_pack();
+        self.map_path(id)?
+            .join("contents")?
+            .join(ScriptExprHash::hash_bytes(&packed))
+    }
+
+    fn has_value(&self, path: &DurablePath) -> Result<bool, LazyStorageError> {
+        Ok(matches!(
+            self.host.store_has(path).map_err(runtime_error)?,
+            Some(ValueType::Value | ValueType::ValueWithSubtree)
+        ))
+    }
+
+    fn read(&self, path: &DurablePath) -> Result<Option<Vec<u8>>, LazyStorageError> {
+        if !self.has_value(path)? {
+            return Ok(None);
+        }
+        self.host
+            .store_read_all(path)
+            .map(Some)
+            .map_err(runtime_error)
+    }
+
+    fn write(&mut self, path: &DurablePath, bytes: &[u8]) -> Result<(), LazyStorageError> {
+        self.host
+            .store_write_all(path, bytes)
+            .map_err(runtime_error)
+    }
+
+    fn fresh_id(&mut self, temporary: bool) -> Result<BigMapId, LazyStorageError> {
+        let (path, first, step): (_, i32, i32) = if temporary {
+            (self.temporary_root()?.join("next_id")?, -1, -1)
+        } else {
+            (self.root.join("next_id")?, 0, 1)
+        };
+        let id = match self.read(&path)? {
+            Some(bytes) => BigInt::from_signed_bytes_le(&bytes),
+            None => first.into(),
+        };
+        self.write(&path, &(&id + step).to_signed_bytes_le())?;
+        Ok(BigMapId(id))
+    }
+
+    fn write_types(
+        &mut self,
+        id: &BigMapId,
+        key_type: &Type,
+        value_type: &Type,
+    ) -> Result<(), LazyStorageError> {
+        let map_path = self.map_path(id)?;
+        let arena = Arena::new();
+        self.write(
+            &map_path.join("key_type")?,
+            &key_type.into_micheline_optimized_legacy(&arena).encode(),
+        )?;
+        self.write(
+            &map_path.join("value_type")?,
+            &value_type.into_micheline_optimized_legacy(&arena).encode(),
+        )?;
+        self.types
+            .borrow_mut()
+            .insert(id.clone(), (key_type.clone(), value_type.clone()));
+        Ok(())
+    }
+
+    fn read_type(&self, path: &DurablePath) -> Result<Option<Type>, LazyStorageError> {
+        let bytes = match self.read(path)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let arena = Arena::new();
+        let ty = Micheline::decode_raw(&arena, &bytes)
+            .map_err(|e| LazyStorageError::DecodingError(e.to_string()))?
+            .parse_ty(&mut Ctx::default())
+            .map_err(|e| LazyStorageError::DecodingError(e.to_string()))?;
+        Ok(Some(ty))
+    }
+
+    fn types(&self, id: &BigMapId) -> Result<Option<(Type, Type)>, LazyStorageError> {
+        if let Some(types) = self.types.borrow().get(id) {
+            return Ok(Some(types.clone()));
+        }
+        let map_path = self.map_path(id)?;
+        let key_type = match self.read_type(&map_path.join("key_type")?)? {
+            Some(ty) => ty,
+            None => return Ok(None),
+        };
+        let value_type = self
+            .read_type(&map_path.join("value_type")?)?
+            .ok_or_else(|| {
+                LazyStorageError::DecodingError(format!("missing value type of big map {id}"))
+            })?;
+        let types = (key_type, value_type);
+        self.types.borrow_mut().insert(id.clone(), types.clone());
+        Ok(Some(types))
+    }
+
+    fn copy(&mut self, id: &BigMapId, temporary: bool) -> Result<BigMapId, LazyStorageError> {
+        let types = self.types(id)?.ok_or_else(|| non_existent(id))?;
+        let new_id = self.fresh_id(temporary)?;
+        let (from, to) = (self.map_path(id)?, self.map_path(&new_id)?);
+        self.host.store_copy(&from, &to).map_err(runtime_error)?;
+        self.types.borrow_mut().insert(new_id.clone(), types);
+        Ok(new_id)
+    }
+}
+
+impl<'a, R: Runtime> LazyStorage<'a> for DurableLazyStorage<'_, R> {
+    fn big_map_get(
+        &self,
+        arena: &'a Arena<Micheline<'a>>,
+        id: &BigMapId,
+        key: &TypedValue,
+    ) -> Result<Option<TypedValue<'a>>, LazyStorageError> {
+        let (_, value_type) = self.types(id)?.ok_or_else(|| non_existent(id))?;
+        let bytes = match self.read(&self.entry_path(id, key)?)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let value = Micheline::decode_raw(arena, &bytes)
+            .map_err(|e| LazyStorageError::DecodingError(e.to_string()))?;
+        typecheck_value(&value, &mut Ctx::default(), &value_type)
+            .map(Some)
+            .map_err(|e| LazyStorageError::DecodingError(e.to_string()))
+    }
+
+    fn big_map_mem(&self, id: &BigMapId, key: &TypedValue) -> Result<bool, LazyStorageError> {
+        self.has_value(&self.entry_path(id, key)?)
+    }
+
+    fn big_map_update(
+        &mut self,
+        id: &BigMapId,
+        key: TypedValue<'a>,
+        value: Option<TypedValue<'a>>,
+    ) -> Result<(), LazyStorageError> {
+        let path = self.entry_path(id, &key)?;
+        match value {
+            None => self.host.store_delete_value(&path).map_err(runtime_error),
+            Some(value) => {
+                let arena = Arena::new();
+                let bytes = value.into_micheline_optimized_legacy(&arena).encode();
+                self.write(&path, &bytes)
+            }
+        }
+    }
+
+    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(Type, Type)>, LazyStorageError> {
+        self.types(id)
+    }
+
+    fn big_map_new(
+        &mut self,
+        key_type: &Type,
+        value_type: &Type,
+    ) -> Result<BigMapId, LazyStorageError> {
+        let id = self.fresh_id(false)?;
+        self.write_types(&id, key_type, value_type)?;
+        Ok(id)
+    }
+
+    fn big_map_copy(&mut self, id: &BigMapId) -> Result<BigMapId, LazyStorageError> {
+        self.copy(id, false)
+    }
+
+    fn big_map_remove(&mut self, id: &BigMapId) -> Result<(), LazyStorageError> {
+        let path = self.map_path(id)?;
+        if self.host.store_has(&path).map_err(runtime_error)?.is_some() {
+            self.host.store_delete(&path).map_err(runtime_error)?;
+        }
+        self.types.borrow_mut().remove(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tezos_smart_rollup_core::MAX_FILE_CHUNK_SIZE;
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::ast::big_map::{dump_big_map_updates, BigMap};
+    use crate::parser::test_helpers::parse;
+
+    const ROOT: RefPath = RefPath::assert_from(b"/mir/big_maps");
+
+    #[test]
+    fn get_mem_update() {
+        let arena = &Arena::new();
+        let host = &mut MockHost::default();
+        let storage = &mut DurableLazyStorage::new(host, &ROOT);
+        let map_id = storage.big_map_new(&Type::Int, &Type::String).unwrap();
+        assert_eq!(map_id, BigMapId(0.into()));
+        let big_string = TypedValue::String("x".repeat(3 * MAX_FILE_CHUNK_SIZE + 1));
+        storage
+            .big_map_update(
+                &map_id,
+                TypedValue::int(1),
+                Some(TypedValue::String("a".into())),
+            )
+            .unwrap();
+        storage
+            .big_map_update(&map_id, TypedValue::int(2), Some(big_string.clone()))
+            .unwrap();
+        storage
+            .big_map_update(
+                &map_id,
+                TypedValue::int(1),
+                Some(TypedValue::String("b".into())),
+            )
+            .unwrap();
+        storage
+            .big_map_update(
+                &map_id,
+                TypedValue::int(3),
+                Some(TypedValue::String("c".into())),
+            )
+            .unwrap();
+        storage
+            .big_map_update(&map_id, TypedValue::int(3), None)
+            .unwrap();
+        storage
+            .big_map_update(&map_id, TypedValue::int(4), None)
+            .unwrap();
+
+        assert_eq!(
+            storage.big_map_get(arena, &map_id, &TypedValue::int(1)),
+            Ok(Some(TypedValue::String("b".into())))
+        );
+        assert_eq!(
+            storage.big_map_get(arena, &map_id, &TypedValue::int(2)),
+            Ok(Some(big_string))
+        );
+        assert_eq!(
+            storage.big_map_get(arena, &map_id, &TypedValue::int(3)),
+            Ok(None)
+        );
+        assert_eq!(storage.big_map_mem(&map_id, &TypedValue::int(1)), Ok(true));
+        assert_eq!(storage.big_map_mem(&map_id, &TypedValue::int(3)), Ok(false));
+        assert_eq!(
+            storage.big_map_get_type(&map_id),
+            Ok(Some((Type::Int, Type::String)))
+        );
+        assert_eq!(storage.big_map_get_type(&BigMapId(1.into())), Ok(None));
+    }
+
+    #[test]
+    fn persists_across_instances() {
+        let arena = &Arena::new();
+        let host = &mut MockHost::default();
+        let key = TypedValue::new_pair(TypedValue::nat(1), TypedValue::String("k".into()));
+        let key_type = Type::new_pair(Type::Nat, Type::String);
+        let value_type = Type::new_list(Type::Int);
+        let value = TypedValue::List(vec![TypedValue::int(1), TypedValue::int(2)].into());
+        let map_id = {
+            let mut storage = DurableLazyStorage::new(host, &ROOT);
+            let map_id = storage.big_map_new(&key_type, &value_type).unwrap();
+            storage
+                .big_map_update(&map_id, key.clone(), Some(value.clone()))
+                .unwrap();
+            map_id
+        };
+
+        let mut storage = DurableLazyStorage::new(host, &ROOT);
+        assert_eq!(
+            storage.big_map_get_type(&map_id),
+            Ok(Some((key_type.clone(), value_type.clone())))
+        );
+        assert_eq!(storage.big_map_get(arena, &map_id, &key), Ok(Some(value)));
+        assert_eq!(
+            storage.big_map_new(&key_type, &value_type),
+            Ok(BigMapId(1.into()))
+        );
+    }
+
+    #[test]
+    fn copy_and_remove() {
+        let arena = &Arena::new();
+        let host = &mut MockHost::default();
+        let storage = &mut DurableLazyStorage::new(host, &ROOT);
+        let map_id = storage.big_map_new(&Type::Int, &Type::Int).unwrap();
+        storage
+            .big_map_update(&map_id, TypedValue::int(1), Some(TypedValue::int(1)))
+            .unwrap();
+        let copy_id = storage.big_map_copy(&map_id).unwrap();
+        assert_eq!(copy_id, BigMapId(1.into()));
+        storage
+            .big_map_update(&copy_id, TypedValue::int(1), Some(TypedValue::int(2)))
+            .unwrap();
+        assert_eq!(
+            storage.big_map_get(arena, &map_id, &TypedValue::int(1)),
+            Ok(Some(TypedValue::int(1)))
+        );
+        assert_eq!(
+            storage.big_map_get(arena, &copy_id, &TypedValue::int(1)),
+            Ok(Some(TypedValue::int(2)))
+        );
+
+        storage.big_map_remove(&map_id).unwrap();
+        assert_eq!(storage.big_map_get_type(&map_id), Ok(None));
+        assert_eq!(
+            storage.big_map_get_type(&copy_id),
+            Ok(Some((Type::Int, Type::Int)))
+        );
+        assert_eq!(
+            storage.big_map_get(arena, &map_id, &TypedValue::int(1)),
+            Err(LazyStorageError::OtherError(
+                "non-existent big map by id 0".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn temporary_big_maps() {
+        let host = &mut MockHost::default();
+        let storage = &mut DurableLazyStorage::new(host, &ROOT);
+        let map_id = storage.big_map_new(&Type::Int, &Type::Int).unwrap();
+        storage
+            .big_map_update(&map_id, TypedValue::int(1), Some(TypedValue::int(1)))
+            .unwrap();
+        let temp_id = storage.big_map_copy_temporary(&map_id).unwrap();
+        assert_eq!(temp_id, BigMapId((-1).into()));
+        assert!(temp_id.is_temporary());
+        assert_eq!(
+            storage.big_map_new_temporary(&Type::Int, &Type::Int),
+            Ok(BigMapId((-2).into()))
+        );
+
+        // a temporary big map ending up in the storage gets copied
+        let mut map = BigMap {
+            id: Some(temp_id.clone()),
+            overlay: BTreeMap::from([(TypedValue::int(2), Some(TypedValue::int(2)))]),
+            key_type: Type::Int,
+            value_type: Type::Int,
+        };
+        dump_big_map_updates(storage, &[], &mut [&mut map]).unwrap();
+        let new_id = BigMapId(1.into());
+        assert_eq!(map.id, Some(new_id.clone()));
+        assert_eq!(storage.big_map_mem(&new_id, &TypedValue::int(1)), Ok(true));
+        assert_eq!(storage.big_map_mem(&new_id, &TypedValue::int(2)), Ok(true));
+        assert_eq!(
+            storage.big_map_mem(&temp_id, &TypedValue::int(2)),
+            Ok(false)
+        );
+
+        storage.clear_temporary().unwrap();
+        assert_eq!(storage.big_map_get_type(&temp_id), Ok(None));
+        assert_eq!(
+            storage.big_map_get_type(&new_id),
+            Ok(Some((Type::Int, Type::Int)))
+        );
+        assert_eq!(
+            storage.big_map_new_temporary(&Type::Int, &Type::Int),
+            Ok(BigMapId((-1).into()))
+        );
+    }
+
+    #[test]
+    fn typecheck_with_durable_storage() {
+        let host = &mut MockHost::default();
+        let map_id = DurableLazyStorage::new(host, &ROOT)
+            .big_map_new(&Type::Int, &Type::Unit)
+            .unwrap();
+        let mut ctx = Ctx::default();
+        ctx.big_map_storage = Box::new(DurableLazyStorage::new(host, &ROOT));
+        assert_eq!(
+            parse("Pair 0 { Elt 1 Unit }")
+                .unwrap()
+                .typecheck_value(&mut ctx, &parse("big_map int unit").unwrap()),
+            Ok(TypedValue::BigMap(BigMap {
+                id: Some(map_id),
+                overlay: BTreeMap::from([(TypedValue::int(1), Some(TypedValue::Unit))]),
+                key_type: Type::Int,
+                value_type: Type::Unit,
+            }))
+        );
+        assert!(parse("0")
+            .unwrap()
+            .typecheck_value(&mut ctx, &parse("big_map int int").unwrap())
+            .is_err());
+    }
+}
@@ -62,9 +62,11 @@ impl PartialOrd for TypedValue<'_> {
             // non-comparable types
             (
                 List(..) | Set(..) | Map(..) | BigMap(..) | Contract(..) | Operation(_)
-                | Ticket(..) | Lambda(..) | Bls12381Fr(..) | Bls12381G1(..) | Bls12381G2(..),
+                | Ticket(..) | Lambda(..) | Bls12381Fr(..) | Bls12381G1(..) | Bls12381G2(..)
+                | Chest(..) | ChestKey(..),
                 _,
             ) => None,
+            (SaplingState(..) | SaplingTransaction(..), _) => None,
         }
     }
 }
@@ -177,12 +177,7 @@ pub trait IntoMicheline<'a> {
 /// supported. Useful for total match in the typechecker.
 macro_rules! micheline_unsupported_types {
     () => {
-        Prim::chest
-            | Prim::chest_key
-            | Prim::tx_rollup_l2_address
-            | Prim::sapling_state
-            | Prim::sapling_transaction
-            | Prim::sapling_transaction_deprecated
+        Prim::tx_rollup_l2_address | Prim::sapling_transaction_deprecated
     };
 }
 
@@ -254,10 +249,6 @@ macro_rules! micheline_fields {
 macro_rules! micheline_unsupported_instructions {
     () => {
         Prim::EMPTY_MAP
-            | Prim::SAPLING_EMPTY_STATE
-            | Prim::SAPLING_VERIFY_UPDATE
-            | Prim::OPEN_CHEST
-            | Prim::VIEW
             | Prim::CREATE_ACCOUNT
             | Prim::STEPS_TO_QUOTA
             | Prim::TICKET_DEPRECATED
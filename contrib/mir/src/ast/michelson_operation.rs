@@ -9,7 +9,9 @@
 
 use std::rc::Rc;
 
-use super::{Address, ContractScript, FieldAnnotation, KeyHash, Micheline, Or, Type, TypedValue};
+use super::{
+    Address, AddressHash, ContractScript, FieldAnnotation, KeyHash, Micheline, Or, Type, TypedValue,
+};
 
 /// Representation of token transfer operation, created by `TRANSFER_TOKENS`
 /// instruction.
@@ -51,6 +53,9 @@ pub struct Emit<'a> {
 /// Representation of create contract operation, created by `CREATE_CONTRACT` instruction.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CreateContract<'a> {
+    /// Address of the contract to be originated, as returned by the
+    /// `CREATE_CONTRACT` instruction.
+    pub address: AddressHash,
     /// Contract's optional delegate.
     pub delegate: Option<KeyHash>,
     /// Contract's inital balance.
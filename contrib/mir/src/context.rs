@@ -12,7 +12,11 @@ use crate::ast::big_map::{InMemoryLazyStorage, LazyStorage};
 use crate::ast::michelson_address::entrypoint::Entrypoints;
 use crate::ast::michelson_address::AddressHash;
 use crate::ast::michelson_key_hash::KeyHash;
+use crate::ast::{Micheline, TypedValue};
 use crate::gas::Gas;
+use crate::global_constants::ScriptExprHash;
+use crate::sapling::{self, InMemorySaplingStorage, SaplingStorage, SaplingVerifier};
+use crate::tracer::Tracer;
 use num_bigint::{BigInt, BigUint};
 use std::collections::HashMap;
 use tezos_crypto_rs::hash::OperationListHash;
@@ -54,6 +58,24 @@ pub struct Ctx<'a> {
     /// also [Self::set_known_contracts]. Defaults to returning [None] for any
     /// address.
     pub lookup_contract: Box<dyn FnMut(&AddressHash) -> Option<Entrypoints>>,
+    /// A function that resolves on-chain views of other contracts, used by the
+    /// `VIEW` instruction. For a given contract address, the function must
+    /// return either [None], meaning the contract doesn't exist, or
+    /// [`Some(callee)`] with the script of the contract along with its current
+    /// storage and balance, see [ViewCallee]. The interpreter then looks up
+    /// and typechecks the view, and runs it read-only. Defaults to returning
+    /// [None] for any address.
+    pub lookup_view: Box<dyn FnMut(&AddressHash) -> Option<ViewCallee<'a>> + 'a>,
+    /// A function that resolves global constants, used by
+    /// [Micheline::expand_constants](crate::ast::Micheline::expand_constants).
+    /// For a given expression hash, the function must return either [None],
+    /// meaning no constant is registered under this hash, or
+    /// [`Some(bytes)`] with the registered expression, serialized with
+    /// [Micheline::encode](crate::ast::Micheline::encode). The expression is
+    /// checked against the hash by the caller. See also
+    /// [Self::register_global_constants]. Defaults to returning [None] for any
+    /// hash.
+    pub lookup_constant: Box<dyn FnMut(&ScriptExprHash) -> Option<Vec<u8>> + 'a>,
     /// A function that maps public key hashes (i.e. effectively implicit
     /// account addresses) to their corresponding voting powers. Note that if
     /// you provide a custom function here, you also must define
@@ -79,10 +101,39 @@ pub struct Ctx<'a> {
     /// admit a custom implementation of [LazyStorage] trait. Defaults to a new,
     /// empty, [InMemoryLazyStorage].
     pub big_map_storage: Box<dyn LazyStorage<'a> + 'a>,
+    /// Storage for `sapling_state`s. Defaults to a new, empty,
+    /// [InMemorySaplingStorage].
+    pub sapling_storage: Box<dyn SaplingStorage + 'a>,
+    /// Cryptographic backend verifying Sapling transactions for the
+    /// `SAPLING_VERIFY_UPDATE` instruction, see [SaplingVerifier]. With the
+    /// `zcash` feature, defaults to
+    /// `ZcashSaplingVerifier::from_installed_params()`, failing on use if the
+    /// parameters can't be loaded, otherwise to
+    /// [crate::sapling::UnavailableSaplingVerifier], with which the
+    /// instruction is rejected by the typechecker.
+    pub sapling_verifier: Box<dyn SaplingVerifier + 'a>,
+    /// Optional [Tracer] called by the interpreter before and after every
+    /// instruction, see [crate::tracer]. Defaults to [None].
+    pub tracer: Option<Box<dyn Tracer<'a> + 'a>>,
     origination_counter: u32,
     operation_counter: u128,
 }
 
+/// Everything required to run an on-chain view of another contract, as
+/// returned by [Ctx::lookup_view].
+#[derive(Debug, Clone)]
+pub struct ViewCallee<'a> {
+    /// Script of the contract the view belongs to. The view is looked up and
+    /// typechecked from it by the interpreter, which charges gas for both, as
+    /// the Tezos protocol does. Global constants must already be expanded.
+    pub script: &'a Micheline<'a>,
+    /// Current storage of the contract the view belongs to. Must have the
+    /// storage type of that contract.
+    pub storage: TypedValue<'a>,
+    /// Current balance of the contract the view belongs to.
+    pub balance: i64,
+}
+
 impl Ctx<'_> {
     /// Increment the internal operation counter and return it. Used as a nonce
     /// for operations.
@@ -115,6 +166,17 @@ impl Ctx<'_> {
         self.voting_powers = Box::new(move |x| map.get(x).unwrap_or(&0u32.into()).clone());
     }
 
+    /// Set a reasonable implementation for [Self::lookup_constant] by providing
+    /// the serialized expressions to register as global constants. Their hashes
+    /// are computed with [ScriptExprHash::hash_bytes].
+    pub fn register_global_constants(&mut self, v: impl IntoIterator<Item = Vec<u8>>) {
+        let map: HashMap<ScriptExprHash, Vec<u8>> = v
+            .into_iter()
+            .map(|bytes| (ScriptExprHash::hash_bytes(&bytes), bytes))
+            .collect();
+        self.lookup_constant = Box::new(move |hash| map.get(hash).cloned());
+    }
+
     /// Increment origination counter and return its new value. Used as a nonce
     /// to generate unique contract addresses for the `CREATE_CONTRACT`
     /// instruction.
@@ -127,6 +189,11 @@ impl Ctx<'_> {
     pub fn set_origination_counter(&mut self, v: u32) {
         self.origination_counter = v;
     }
+
+    /// Current origination and operation counters, without incrementing them.
+    pub(crate) fn counters(&self) -> (u32, u128) {
+        (self.origination_counter, self.operation_counter)
+    }
 }
 
 impl Default for Ctx<'_> {
@@ -144,9 +211,14 @@ impl Default for Ctx<'_> {
             sender: "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi".try_into().unwrap(),
             source: "tz1TSbthBCECxmnABv73icw7yyyvUWFLAoSP".try_into().unwrap(),
             lookup_contract: Box::new(|_| None),
+            lookup_view: Box::new(|_| None),
+            lookup_constant: Box::new(|_| None),
             voting_powers: Box::new(|_| 0u32.into()),
             total_voting_power: 0u32.into(),
             big_map_storage: Box::new(InMemoryLazyStorage::new()),
+            sapling_storage: Box::new(InMemorySaplingStorage::new()),
+            sapling_verifier: sapling::default_verifier(),
+            tracer: None,
             operation_counter: 0,
             operation_group_hash: OperationListHash::from_base58_check(
                 "onvsLP3JFZia2mzZKWaFuFkWg2L5p3BDUhzh5Kr6CiDDN3rtQ1D",
@@ -64,6 +64,12 @@ impl Gas {
         self.milligas_amount
             .expect("Access to gas after exhaustion")
     }
+
+    /// Check whether gas was exhausted, i.e. some [Gas::consume] call
+    /// previously failed.
+    pub fn is_exhausted(&self) -> bool {
+        self.milligas_amount.is_none()
+    }
 }
 
 trait AsGasCost {
@@ -157,6 +163,9 @@ pub mod tc_cost {
     // corresponds to cost_DECODING_BLS_G2 in the protocol.
     pub const BLS_G2: u32 = 69000;
 
+    // corresponds to cost_DECODING_Chest_key in the protocol.
+    pub const CHEST_KEY: u32 = 9550;
+
     // corresponds to cost_B58CHECK_DECODING_PUBLIC_KEY_HASH_bls in the
     // protocol. the protocol computes cost as
     // `max(bls,ed25519,p256,secp256k1)`, which happens to be `bls`
@@ -173,6 +182,11 @@ pub mod tc_cost {
     // corresponds to cost_DECODING_CHAIN_ID in the protocol
     pub const CHAIN_ID_OPTIMIZED: u32 = 50;
 
+    // corresponds to cost_DECODING_Chest in the protocol.
+    pub fn chest(bytes_len: usize) -> Result<u32, OutOfGas> {
+        ((Checked::from(bytes_len) >> 5) + 3750).as_gas_cost()
+    }
+
     pub fn timestamp_decoding(l: usize) -> Result<u32, OutOfGas> {
         use integer_sqrt::IntegerSquareRoot;
         let v0: Checked<usize> = Checked::from(l.integer_sqrt()) * l;
@@ -250,6 +264,32 @@ pub mod tc_cost {
         // corresponds to Cost_of.Typechecking.proof_argument in the protocol
         (Checked::from(size) * 50).as_gas_cost()
     }
+
+    // corresponds to Global_constants_costs.expand_constants_branch_cost in
+    // the protocol
+    pub const EXPAND_CONSTANT_BRANCH: u32 = 4096;
+
+    // corresponds to Global_constants_costs.expand_no_constants_branch_cost in
+    // the protocol, `nodes` is the number of Micheline nodes traversed
+    pub fn expand_no_constant_branch(nodes: usize) -> Result<u32, OutOfGas> {
+        // log2 in the protocol is 1 + the number of significant bits
+        let log2 = 1 + (usize::BITS - (nodes + 1).leading_zeros()) as usize;
+        let w3 = Checked::from(nodes) * log2;
+        (w3 * 4 + (w3 >> 1) + (w3 >> 2) + 100).as_gas_cost()
+    }
+
+    // corresponds to Global_constants_costs.expr_to_address_in_context_cost in
+    // the protocol, i.e. the cost of hashing a serialized expression
+    pub fn expr_hash(bytes_len: usize) -> Result<u32, OutOfGas> {
+        let v0 = Checked::from(bytes_len);
+        (v0 + (v0 >> 2) + 200).as_gas_cost()
+    }
+
+    // corresponds to Storage_costs.read_access in the protocol, for keys of
+    // the global constants table, which are 8 path segments long
+    pub fn global_constant_read(read_bytes: usize) -> Result<u32, OutOfGas> {
+        (Checked::from(read_bytes) * 2 + 240_000).as_gas_cost()
+    }
 }
 
 /// Get byte size of [BigInt] or [BigUint].
@@ -284,6 +324,8 @@ pub mod interpret_cost {
 
     use super::{AsGasCost, BigIntByteSize, Log2i, OutOfGas};
     use crate::ast::{Key, KeyHash, Micheline, Or, Ticket, TypedValue};
+    use crate::sapling::Transaction;
+    use crate::timelock::Chest;
 
     pub const DIP: u32 = 10;
     pub const DROP: u32 = 10;
@@ -388,6 +430,8 @@ pub mod interpret_cost {
     pub const LOOP_LEFT_ENTER: u32 = 10; // corresponds to KLoop_in_left in the Tezos protocol
     pub const LOOP_EXIT: u32 = 10;
     pub const CREATE_CONTRACT: u32 = 60;
+    pub const VIEW: u32 = 1460;
+    pub const SAPLING_EMPTY_STATE: u32 = 300;
 
     pub fn join_tickets(t1: &Ticket, t2: &Ticket) -> Result<u32, OutOfGas> {
         compare(&t1.content, &t2.content)?;
@@ -668,7 +712,11 @@ pub mod interpret_cost {
                 | V::Lambda(_)
                 | V::Bls12381Fr(_)
                 | V::Bls12381G1(_)
-                | V::Bls12381G2(_),
+                | V::Bls12381G2(_)
+                | V::Chest(_)
+                | V::ChestKey(_)
+                | V::SaplingState(_)
+                | V::SaplingTransaction(_),
                 _,
             ) => incomparable(),
         })
@@ -723,6 +771,13 @@ pub mod interpret_cost {
         (80 + lookup_cost).as_gas_cost()
     }
 
+    /// Cost of looking up a view by name among the `views` of a contract,
+    /// corresponds to `view_get` in the Tezos protocol, which is a `map_get`
+    /// on the views map.
+    pub fn view_get(name: &str, views: usize) -> Result<u32, OutOfGas> {
+        map_get(&TypedValue::String(name.to_owned()), views)
+    }
+
     pub fn set_mem(k: &TypedValue, map_size: usize) -> Result<u32, OutOfGas> {
         // NB: same considerations as for map_get
         let compare_cost = compare(k, k)?;
@@ -879,6 +934,24 @@ pub mod interpret_cost {
         (Checked::from(680) + (size * 3)).as_gas_cost()
     }
 
+    pub fn open_chest(chest: &Chest, time: &BigUint) -> Result<u32, OutOfGas> {
+        // NB: the protocol takes the floor of the logarithm, and subtraction
+        // saturates at zero.
+        let log_time = (time + 1u32).bits() - 1;
+        let log_time = Checked::from(log_time.saturating_sub(1) as usize);
+        let size = Checked::from(chest.plaintext_size());
+        (log_time * 22528 + (size >> 2) + size * 3 + 919_000).as_gas_cost()
+    }
+
+    pub fn sapling_verify_update(tx: &Transaction) -> Result<u32, OutOfGas> {
+        // NB: the protocol charges for hashing the bound data separately from
+        // the verification itself, which is linear in inputs and outputs.
+        let inputs = Checked::from(tx.inputs().len());
+        let outputs = Checked::from(tx.outputs().len());
+        let verify = inputs * 5_767_168 + outputs * 4_718_592 + 432_500;
+        (Checked::from(blake2b(tx.bound_data())? as usize) + verify).as_gas_cost()
+    }
+
     pub fn pairing_check(size: usize) -> Result<u32, OutOfGas> {
         (450_000 + 342_500 * Checked::from(size)).as_gas_cost()
     }
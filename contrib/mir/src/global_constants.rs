@@ -0,0 +1,428 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Expansion of global constants, i.e. `constant "expr..."` nodes referring
+//! to expressions registered on-chain.
+//!
+//! Constants are resolved with [Ctx::lookup_constant] and expanded by
+//! [Micheline::expand_constants], which has to be called before typechecking
+//! a script that uses them. Expansion follows the protocol, including gas
+//! costs and the limit on the size of the resulting expression.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use cryptoxide::hashing::blake2b_256;
+use tezos_crypto_rs::base58::{FromBase58Check, FromBase58CheckError, ToBase58Check};
+use typed_arena::Arena;
+
+use crate::ast::Micheline;
+use crate::context::Ctx;
+use crate::gas::{tc_cost, BigIntByteSize, OutOfGas};
+use crate::lexer::Prim;
+use crate::serializer::DecodeError;
+
+/// Maximum number of nodes in an expression after expanding constants.
+pub const MAX_MICHELINE_NODE_COUNT: usize = 50_000;
+
+/// Maximum total size in bytes of strings, bytes and integers in an expression
+/// after expanding constants.
+pub const MAX_MICHELINE_BYTES_LIMIT: usize = 50_000;
+
+/// Hash of a serialized Micheline expression, represented as `expr...` in
+/// base58-check. Global constants are identified by these.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptExprHash(pub [u8; 32]);
+
+impl ScriptExprHash {
+    const BASE58_PREFIX: &'static [u8] = &[13, 44, 64, 27];
+
+    /// Compute the hash of an expression serialized with
+    /// [Micheline::encode].
+    pub fn hash_bytes(bytes: &[u8]) -> Self {
+        ScriptExprHash(blake2b_256(bytes))
+    }
+
+    /// Parse the base58-check representation of the hash.
+    pub fn from_base58_check(s: &str) -> Result<Self, FromBase58CheckError> {
+        let bytes = s.from_base58check()?;
+        let expected_len = Self::BASE58_PREFIX.len() + 32;
+        if bytes.len() != expected_len {
+            return Err(FromBase58CheckError::MismatchedLength {
+                expected: expected_len,
+                actual: bytes.len(),
+            });
+        }
+        match bytes.strip_prefix(Self::BASE58_PREFIX) {
+            Some(hash) => Ok(ScriptExprHash(hash.try_into().unwrap())),
+            None => Err(FromBase58CheckError::InvalidBase58),
+        }
+    }
+
+    /// Construct the base58-check representation of the hash.
+    pub fn to_base58_check(&self) -> String {
+        [Self::BASE58_PREFIX, &self.0]
+            .concat()
+            .to_base58check()
+            .expect("should always be convertible to base58")
+    }
+}
+
+impl Display for ScriptExprHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_base58_check())
+    }
+}
+
+/// Errors that can happen when expanding global constants.
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum ConstantError {
+    /// A `constant` primitive doesn't have exactly one string argument with a
+    /// valid expression hash, or has annotations.
+    #[error("badly formed constant expression")]
+    BadlyFormedConstant,
+    /// No constant is registered with the given hash.
+    #[error("global constant not found: {0}")]
+    NonexistentGlobal(ScriptExprHash),
+    /// The expression returned for the given hash doesn't match the hash.
+    #[error("global constant doesn't match its hash: {0}")]
+    HashMismatch(ScriptExprHash),
+    /// The expression registered with the given hash can't be decoded.
+    #[error("failed to decode global constant {0}: {1}")]
+    DecodeError(ScriptExprHash, DecodeError),
+    /// The expression is too large after expanding constants, see
+    /// [MAX_MICHELINE_NODE_COUNT] and [MAX_MICHELINE_BYTES_LIMIT].
+    #[error("expression is too large after expanding constants")]
+    ExpressionTooLarge,
+    /// Ran out of gas while expanding constants.
+    #[error(transparent)]
+    OutOfGas(#[from] OutOfGas),
+}
+
+impl<'a> Micheline<'a> {
+    /// Replace all `constant "expr..."` nodes with the expressions registered
+    /// under the given hashes, resolved with [Ctx::lookup_constant].
+    /// Expressions are expanded recursively, as constants may refer to other
+    /// constants. Each resolved expression is checked against its hash, which
+    /// also rules out cycles.
+    ///
+    /// Gas is consumed the same way as in the protocol. Expressions without
+    /// constants are returned as is, but still have to be traversed, which
+    /// costs gas too.
+    pub fn expand_constants(
+        &self,
+        ctx: &mut Ctx,
+        arena: &'a Arena<Micheline<'a>>,
+    ) -> Result<Micheline<'a>, ConstantError> {
+        ctx.gas
+            .consume(tc_cost::expand_no_constant_branch(node_count(self))?)?;
+        let mut expander = Expander {
+            ctx,
+            arena,
+            cache: HashMap::new(),
+        };
+        match expander.expand(self)? {
+            None => Ok(self.clone()),
+            Some(res) => {
+                if too_large(&res) {
+                    Err(ConstantError::ExpressionTooLarge)
+                } else {
+                    Ok(res)
+                }
+            }
+        }
+    }
+}
+
+struct Expander<'a, 'c, 'b> {
+    ctx: &'c mut Ctx<'b>,
+    arena: &'a Arena<Micheline<'a>>,
+    cache: HashMap<ScriptExprHash, Micheline<'a>>,
+}
+
+impl<'a> Expander<'a, '_, '_> {
+    /// Expand constants in `m`. Returns [None] if there are none, to avoid
+    /// reallocating unchanged nodes.
+    fn expand(&mut self, m: &Micheline<'a>) -> Result<Option<Micheline<'a>>, ConstantError> {
+        Ok(match m {
+            Micheline::App(Prim::constant, args, anns) => {
+                self.ctx.gas.consume(tc_cost::EXPAND_CONSTANT_BRANCH)?;
+                let hash = match args {
+                    [Micheline::String(s)] if anns.is_empty() => {
+                        ScriptExprHash::from_base58_check(s)
+                            .map_err(|_| ConstantError::BadlyFormedConstant)?
+                    }
+                    _ => return Err(ConstantError::BadlyFormedConstant),
+                };
+                let node = match self.cache.get(&hash) {
+                    Some(node) => node.clone(),
+                    None => {
+                        let node = self.fetch(&hash)?;
+                        self.cache.insert(hash, node.clone());
+                        node
+                    }
+                };
+                // charge for traversing the retrieved node
+                self.ctx
+                    .gas
+                    .consume(tc_cost::expand_no_constant_branch(node_count(&node))?)?;
+                Some(self.expand(&node)?.unwrap_or(node))
+            }
+            Micheline::App(prim, args, anns) => self
+                .expand_all(args)?
+                .map(|args| Micheline::App(*prim, args, anns.clone())),
+            Micheline::Seq(items) => self.expand_all(items)?.map(Micheline::Seq),
+            Micheline::Int(_) | Micheline::String(_) | Micheline::Bytes(_) => None,
+        })
+    }
+
+    fn expand_all(
+        &mut self,
+        items: &[Micheline<'a>],
+    ) -> Result<Option<&'a [Micheline<'a>]>, ConstantError> {
+        let expanded = items
+            .iter()
+            .map(|item| self.expand(item))
+            .collect::<Result<Vec<_>, _>>()?;
+        if expanded.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let res = expanded
+            .into_iter()
+            .zip(items)
+            .map(|(new, old)| new.unwrap_or_else(|| old.clone()))
+            .collect::<Vec<_>>();
+        Ok(Some(Micheline::alloc_iter(self.arena, res.into_iter())))
+    }
+
+    /// Look up the constant, verify its hash and decode it.
+    fn fetch(&mut self, hash: &ScriptExprHash) -> Result<Micheline<'a>, ConstantError> {
+        // checking whether the key exists is charged separately from reading
+        // the value
+        self.ctx.gas.consume(tc_cost::global_constant_read(0)?)?;
+        let bytes = (self.ctx.lookup_constant)(hash)
+            .ok_or_else(|| ConstantError::NonexistentGlobal(hash.clone()))?;
+        self.ctx
+            .gas
+            .consume(tc_cost::global_constant_read(bytes.len())?)?;
+        self.ctx.gas.consume(tc_cost::expr_hash(bytes.len())?)?;
+        if ScriptExprHash::hash_bytes(&bytes) != *hash {
+            return Err(ConstantError::HashMismatch(hash.clone()));
+        }
+        Micheline::decode_raw(self.arena, &bytes)
+            .map_err(|e| ConstantError::DecodeError(hash.clone(), e))
+    }
+}
+
+/// Number of nodes, as counted by the protocol.
+fn node_count(m: &Micheline) -> usize {
+    1 + match m {
+        Micheline::App(_, args, _) | Micheline::Seq(args) => args.iter().map(node_count).sum(),
+        Micheline::Int(_) | Micheline::String(_) | Micheline::Bytes(_) => 0,
+    }
+}
+
+/// Check the expression against [MAX_MICHELINE_NODE_COUNT] and
+/// [MAX_MICHELINE_BYTES_LIMIT]. Annotations are counted as strings, like in
+/// the protocol.
+fn too_large(m: &Micheline) -> bool {
+    fn go(m: &Micheline, nodes: &mut usize, bytes: &mut usize) {
+        *nodes += 1;
+        match m {
+            Micheline::Int(i) => *bytes += i.byte_size() as usize,
+            Micheline::String(s) => *bytes += s.len(),
+            Micheline::Bytes(b) => *bytes += b.len(),
+            Micheline::App(_, args, anns) => {
+                for ann in anns.iter() {
+                    *nodes += 1;
+                    *bytes += ann.to_string().len();
+                }
+                args.iter().for_each(|arg| go(arg, nodes, bytes))
+            }
+            Micheline::Seq(items) => items.iter().for_each(|item| go(item, nodes, bytes)),
+        }
+    }
+    let (mut nodes, mut bytes) = (0, 0);
+    go(m, &mut nodes, &mut bytes);
+    nodes > MAX_MICHELINE_NODE_COUNT || bytes > MAX_MICHELINE_BYTES_LIMIT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::TypedValue;
+    use crate::gas::Gas;
+    use crate::parser::test_helpers::parse;
+
+    /// Register the given expressions as global constants, returning their
+    /// hashes.
+    fn register(ctx: &mut Ctx, constants: &[&str]) -> Vec<ScriptExprHash> {
+        let bytes: Vec<_> = constants
+            .iter()
+            .map(|c| parse(c).unwrap().encode())
+            .collect();
+        let hashes = bytes
+            .iter()
+            .map(|b| ScriptExprHash::hash_bytes(b))
+            .collect();
+        ctx.register_global_constants(bytes);
+        hashes
+    }
+
+    #[track_caller]
+    fn expand(ctx: &mut Ctx, src: &str) -> Result<(), ConstantError> {
+        parse(src)
+            .unwrap()
+            .expand_constants(ctx, &Arena::new())
+            .map(|_| ())
+    }
+
+    #[test]
+    fn hash_base58() {
+        // the hash of `PACK Unit`, as computed by octez-client
+        let hash = ScriptExprHash::hash_bytes(&[0x05, 0x03, 0x0b]);
+        assert_eq!(
+            hash.to_string(),
+            "expruaDPoTWXcTR6fiQPy4KZSW72U6Swc1rVmMiP1KdwmCceeEpVjd"
+        );
+        assert_eq!(
+            ScriptExprHash::from_base58_check(&hash.to_string()).unwrap(),
+            hash
+        );
+        assert!(ScriptExprHash::from_base58_check("tz1TSbthBCECxmnABv73icw7yyyvUWFLAoSP").is_err());
+    }
+
+    #[test]
+    fn expand_nested() {
+        let hash = |src: &str| ScriptExprHash::hash_bytes(&parse(src).unwrap().encode());
+        let add_one = hash("{ PUSH nat 1 ; ADD }");
+        let add_two_src = format!(r#"{{ constant "{add_one}" ; constant "{add_one}" }}"#);
+        let (add_two, nat) = (hash(&add_two_src), hash("nat"));
+        let src = format!(
+            r#"{{ parameter (constant "{nat}") ; storage nat ;
+                  code {{ UNPAIR ; ADD ; constant "{add_two}" ; NIL operation ; PAIR }} }}"#
+        );
+        let arena = Arena::new();
+        let mut ctx = Ctx::default();
+        register(&mut ctx, &["{ PUSH nat 1 ; ADD }", &add_two_src, "nat"]);
+        let expanded = parse(&src)
+            .unwrap()
+            .expand_constants(&mut ctx, &arena)
+            .unwrap();
+        assert_eq!(
+            expanded,
+            parse(
+                "{ parameter nat ; storage nat ;
+                   code { UNPAIR ; ADD ; { { PUSH nat 1 ; ADD } ; { PUSH nat 1 ; ADD } } ;
+                          NIL operation ; PAIR } }"
+            )
+            .unwrap()
+        );
+        let (_, storage) = expanded
+            .typecheck_script(&mut ctx)
+            .unwrap()
+            .interpret(&mut ctx, &arena, parse("1").unwrap(), parse("2").unwrap())
+            .unwrap();
+        assert_eq!(storage, TypedValue::nat(5));
+    }
+
+    #[test]
+    fn expand_without_constants() {
+        let arena = Arena::new();
+        let mut ctx = Ctx::default();
+        let code = parse("{ DROP ; PUSH int 1 }").unwrap();
+        let initial = ctx.gas.milligas();
+        assert_eq!(code.expand_constants(&mut ctx, &arena), Ok(code));
+        // 5 nodes, log2(5 + 1) in the protocol is 4, hence
+        // 5 * 4 * (4 + 1/2 + 1/4) + 100
+        assert_eq!(initial - ctx.gas.milligas(), 195);
+    }
+
+    #[test]
+    fn expand_gas() {
+        let arena = Arena::new();
+        let mut ctx = Ctx::default();
+        let hash = &register(&mut ctx, &["Unit"])[0];
+        let src = format!(r#"{{ constant "{hash}" ; constant "{hash}" }}"#);
+        let code = parse(&src).unwrap();
+        let initial = ctx.gas.milligas();
+        let expanded = code.expand_constants(&mut ctx, &arena).unwrap();
+        assert_eq!(expanded, parse("{ Unit ; Unit }").unwrap());
+        let traverse = |nodes: usize| tc_cost::expand_no_constant_branch(nodes).unwrap();
+        let bytes_len = parse("Unit").unwrap().encode().len();
+        assert_eq!(
+            initial - ctx.gas.milligas(),
+            traverse(5)
+                + 2 * (tc_cost::EXPAND_CONSTANT_BRANCH + traverse(1))
+                // the constant is only fetched once
+                + tc_cost::global_constant_read(0).unwrap()
+                + tc_cost::global_constant_read(bytes_len).unwrap()
+                + tc_cost::expr_hash(bytes_len).unwrap()
+        );
+    }
+
+    #[test]
+    fn expand_errors() {
+        let mut ctx = Ctx::default();
+        let unit = ScriptExprHash::hash_bytes(&parse("Unit").unwrap().encode());
+
+        assert_eq!(
+            expand(&mut ctx, &format!(r#"constant "{unit}""#)),
+            Err(ConstantError::NonexistentGlobal(unit.clone()))
+        );
+        for src in [
+            "constant 1".to_owned(),
+            r#"constant "foo""#.to_owned(),
+            format!(r#"constant "{unit}" "{unit}""#),
+            format!(r#"constant %a "{unit}""#),
+        ] {
+            assert_eq!(
+                expand(&mut ctx, &src),
+                Err(ConstantError::BadlyFormedConstant)
+            );
+        }
+
+        // resolver returning something that doesn't match the hash
+        ctx.lookup_constant = Box::new(|_| Some(parse("Unit").unwrap().encode()));
+        let other = ScriptExprHash::hash_bytes(&[0]);
+        assert_eq!(
+            expand(&mut ctx, &format!(r#"constant "{other}""#)),
+            Err(ConstantError::HashMismatch(other))
+        );
+
+        // 0xff is not a valid tag
+        let garbage = ScriptExprHash::hash_bytes(&[0xff]);
+        ctx.lookup_constant = Box::new(|_| Some(vec![0xff]));
+        assert_eq!(
+            expand(&mut ctx, &format!(r#"constant "{garbage}""#)),
+            Err(ConstantError::DecodeError(
+                garbage,
+                DecodeError::UnknownTag(0xff)
+            ))
+        );
+
+        ctx.gas = Gas::new(tc_cost::EXPAND_CONSTANT_BRANCH);
+        assert_eq!(
+            expand(&mut ctx, &format!(r#"constant "{unit}""#)),
+            Err(ConstantError::OutOfGas(OutOfGas))
+        );
+    }
+
+    #[test]
+    fn expand_too_large() {
+        let mut ctx = Ctx::default();
+        let big = format!("{{ {} }}", vec!["Unit"; 20_000].join(" ; "));
+        let hash = &register(&mut ctx, &[&big])[0];
+        let twice = format!(r#"{{ constant "{hash}" ; constant "{hash}" }}"#);
+        assert_eq!(expand(&mut ctx, &twice), Ok(()));
+        let thrice = format!(r#"{{ constant "{hash}" ; constant "{hash}" ; constant "{hash}" }}"#);
+        assert_eq!(
+            expand(&mut ctx, &thrice),
+            Err(ConstantError::ExpressionTooLarge)
+        );
+    }
+}
@@ -22,11 +22,13 @@ use typed_arena::Arena;
 use crate::ast::big_map::{BigMap, LazyStorageError};
 use crate::ast::*;
 use crate::bls;
-use crate::context::Ctx;
-use crate::gas::{interpret_cost, OutOfGas};
+use crate::context::{Ctx, ViewCallee};
+use crate::gas::{interpret_cost, tc_cost, OutOfGas};
 use crate::irrefutable_match::irrefutable_match;
+use crate::sapling::{SaplingError, SaplingState};
 use crate::stack::*;
-use crate::typechecker::{typecheck_contract_address, typecheck_value};
+use crate::timelock;
+use crate::typechecker::{typecheck_contract_address, typecheck_value, TcError};
 
 /// Errors possible during interpretation.
 #[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
@@ -49,6 +51,12 @@ pub enum InterpretError<'a> {
     /// An error occurred when working with `big_map` storage.
     #[error("lazy storage error: {0}")]
     LazyStorageError(#[from] LazyStorageError),
+    /// An error occurred when verifying a sapling transaction.
+    #[error("sapling error: {0}")]
+    SaplingError(#[from] SaplingError),
+    /// The view called by a `VIEW` instruction failed to typecheck.
+    #[error("ill-typed view {0}: {1}")]
+    IllTypedView(String, TcError),
 }
 
 /// Errors possible when interpreting a full contract script.
@@ -83,6 +91,20 @@ impl<'a> ContractScript<'a> {
     {
         let parameter = typecheck_value(&parameter, ctx, &self.parameter)?;
         let storage = typecheck_value(&storage, ctx, &self.storage)?;
+        Ok(self.interpret_typed(ctx, arena, parameter, storage)?)
+    }
+
+    /// Interpret a typechecked contract script using the provided parameter and
+    /// storage, which are already typechecked. Note the interpreter assumes
+    /// they have the types expected by the script, otherwise this function
+    /// will panic.
+    pub fn interpret_typed(
+        &self,
+        ctx: &mut Ctx<'a>,
+        arena: &'a Arena<Micheline<'a>>,
+        parameter: TypedValue<'a>,
+        storage: TypedValue<'a>,
+    ) -> Result<(impl Iterator<Item = OperationInfo<'a>>, TypedValue<'a>), InterpretError<'a>> {
         let tc_val = TypedValue::new_pair(parameter, storage);
         let mut stack = stk![tc_val];
         self.code.interpret(ctx, arena, &mut stack)?;
@@ -115,7 +137,14 @@ impl<'a> Instruction<'a> {
         arena: &'a Arena<Micheline<'a>>,
         stack: &mut IStack<'a>,
     ) -> Result<(), InterpretError<'a>> {
-        interpret_one(self, ctx, arena, stack)
+        if let Some(tracer) = &mut ctx.tracer {
+            tracer.before_instruction(self, stack, &ctx.gas);
+        }
+        let res = interpret_one(self, ctx, arena, stack);
+        if let Some(tracer) = &mut ctx.tracer {
+            tracer.after_instruction(self, stack, &ctx.gas, res.as_ref().map(|_| ()));
+        }
+        res
     }
 }
 
@@ -1695,12 +1724,11 @@ fn interpret_one<'a>(
             let amount = pop!(V::Mutez);
             let storage = pop!();
             let origination_counter = ctx.origination_counter();
-            stack.push(TypedValue::Address(compute_contract_address(
-                &ctx.operation_group_hash,
-                origination_counter,
-            )));
+            let address = compute_contract_address(&ctx.operation_group_hash, origination_counter);
+            stack.push(TypedValue::Address(address.clone()));
             stack.push(TypedValue::new_operation(
                 Operation::CreateContract(CreateContract {
+                    address: address.hash,
                     delegate: opt_keyhash,
                     amount,
                     storage,
@@ -1710,12 +1738,133 @@ fn interpret_one<'a>(
                 counter,
             ))
         }
+        I::View {
+            name,
+            input_ty,
+            output_ty,
+        } => {
+            ctx.gas.consume(interpret_cost::VIEW)?;
+            let input = pop!();
+            let address = pop!(V::Address);
+            let callee = match &address.hash {
+                AddressHash::Kt1(_) => (ctx.lookup_view)(&address.hash),
+                AddressHash::Implicit(_) | AddressHash::Sr1(_) => None,
+            };
+            let view = match &callee {
+                Some(callee) => callee
+                    .script
+                    .typecheck_view_by_name(ctx, name)
+                    .map_err(|err| match err {
+                        TcError::OutOfGas(err) => InterpretError::OutOfGas(err),
+                        err => InterpretError::IllTypedView(name.clone(), err),
+                    })?,
+                None => None,
+            };
+            let result = match (callee, view) {
+                (Some(callee), Some(view))
+                    if view_types_match(ctx, &view, input_ty, output_ty)? =>
+                {
+                    Some(run_view(ctx, arena, address.hash, callee, &view, input)?)
+                }
+                _ => None,
+            };
+            stack.push(V::new_option(result));
+        }
+        I::OpenChest => {
+            let chest_key = pop!(V::ChestKey);
+            let chest = pop!(V::Chest);
+            let time = irrefutable_match!(&stack[0]; V::Nat);
+            ctx.gas.consume(interpret_cost::open_chest(&chest, time)?)?;
+            // If the time doesn't fit into an integer no proof can be correct,
+            // as the verification requires an integer.
+            let res = timelock::time_of_nat(time)
+                .and_then(|time| timelock::open_chest(&chest, &chest_key, time));
+            stack[0] = V::new_option(res.map(V::Bytes));
+        }
+        I::SaplingEmptyState(memo_size) => {
+            ctx.gas.consume(interpret_cost::SAPLING_EMPTY_STATE)?;
+            stack.push(V::SaplingState(SaplingState::empty(*memo_size)));
+        }
+        I::SaplingVerifyUpdate => {
+            let tx = pop!(V::SaplingTransaction);
+            let state = irrefutable_match!(
+                std::mem::replace(&mut stack[0], V::Unit);
+                V::SaplingState
+            );
+            ctx.gas
+                .consume(interpret_cost::sapling_verify_update(&tx)?)?;
+            // The anti-replay string binds the transaction to this contract
+            // on this chain.
+            let anti_replay = format!(
+                "{}{}",
+                ctx.self_address.to_base58_check(),
+                ctx.chain_id.to_base58_check()
+            );
+            let res = state.verify_update(
+                &tx,
+                anti_replay.as_bytes(),
+                ctx.sapling_storage.as_ref(),
+                ctx.sapling_verifier.as_ref(),
+            )?;
+            stack[0] = V::new_option(res.map(|(balance, state)| {
+                V::new_pair(
+                    V::Bytes(tx.bound_data().to_vec()),
+                    V::new_pair(V::Int(balance.into()), V::SaplingState(state)),
+                )
+            }));
+        }
         I::Seq(nested) => interpret(nested, ctx, arena, stack)?,
     }
     Ok(())
 }
 
-fn compute_contract_address(operation_group_hash: &[u8; 32], o_index: u32) -> Address {
+/// Run an on-chain view of another contract. The view runs in the context of
+/// the callee: `SELF_ADDRESS` and `BALANCE` refer to the callee, `SENDER` to the
+/// caller, and `AMOUNT` is zero. The context is restored afterwards, whether
+/// the view succeeds or not.
+fn run_view<'a>(
+    ctx: &mut Ctx<'a>,
+    arena: &'a Arena<Micheline<'a>>,
+    callee_address: AddressHash,
+    callee: ViewCallee<'a>,
+    view: &View<'a>,
+    input: TypedValue<'a>,
+) -> Result<TypedValue<'a>, InterpretError<'a>> {
+    let caller = std::mem::replace(&mut ctx.self_address, callee_address);
+    let sender = std::mem::replace(&mut ctx.sender, caller);
+    let amount = std::mem::replace(&mut ctx.amount, 0);
+    let balance = std::mem::replace(&mut ctx.balance, callee.balance);
+    let mut stack = stk![TypedValue::new_pair(input, callee.storage)];
+    let res = interpret(&view.code, ctx, arena, &mut stack);
+    ctx.self_address = std::mem::replace(&mut ctx.sender, sender);
+    ctx.amount = amount;
+    ctx.balance = balance;
+    res?;
+    Ok(stack.pop().unwrap_or_else(|| unreachable_state()))
+}
+
+/// Check that the types of a view match the ones expected by the `VIEW`
+/// instruction. Like in the Tezos protocol, the output types are compared first,
+/// and each comparison is charged as typechecking type equality.
+fn view_types_match(
+    ctx: &mut Ctx,
+    view: &View,
+    input_ty: &Type,
+    output_ty: &Type,
+) -> Result<bool, OutOfGas> {
+    for (expected, actual) in [(output_ty, &view.output_type), (input_ty, &view.input_type)] {
+        ctx.gas.consume(tc_cost::ty_eq(
+            expected.size_for_gas(),
+            actual.size_for_gas(),
+        )?)?;
+        if expected != actual {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub(crate) fn compute_contract_address(operation_group_hash: &[u8; 32], o_index: u32) -> Address {
     use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
     let mut input: [u8; 36] = [0; 36];
     input[..32].copy_from_slice(operation_group_hash);
@@ -5632,6 +5781,110 @@ mod interpreter_tests {
         assert!(Ctx::default().gas.milligas() > ctx.gas.milligas());
     }
 
+    #[test]
+    fn open_chest() {
+        use crate::timelock::tests::*;
+        let run = |time: BigUint, chest_hex: &str, key_hex: &str| {
+            let mut stack = stk![
+                V::Nat(time),
+                V::new_chest(chest(chest_hex)),
+                V::new_chest_key(chest_key(key_hex))
+            ];
+            let ctx = &mut Ctx::default();
+            assert_eq!(interpret_one(&OpenChest, ctx, &mut stack), Ok(()));
+            (stack, Ctx::default().gas.milligas() - ctx.gas.milligas())
+        };
+        assert_eq!(
+            run(10u32.into(), CHEST, CHEST_KEY),
+            // log2(1 + 10) = 3, plaintext size is 5
+            (
+                stk![V::new_option(Some(V::Bytes(b"hello".to_vec())))],
+                (3 - 1) * 22528 + (5 >> 2) + 5 * 3 + 919000
+            )
+        );
+        assert_eq!(
+            run(10u32.into(), CHEST, OTHER_CHEST_KEY).0,
+            stk![V::new_option(None)]
+        );
+        assert_eq!(
+            run(11u32.into(), CHEST, CHEST_KEY).0,
+            stk![V::new_option(None)]
+        );
+        // time doesn't fit into an integer
+        assert_eq!(
+            run(BigUint::from(1u32) << 64, CHEST, CHEST_KEY).0,
+            stk![V::new_option(None)]
+        );
+    }
+
+    #[test]
+    fn sapling_empty_state() {
+        let mut stack = stk![];
+        let ctx = &mut Ctx::default();
+        assert_eq!(
+            interpret_one(&SaplingEmptyState(8), ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(stack, stk![V::SaplingState(SaplingState::empty(8))]);
+        assert_eq!(
+            Ctx::default().gas.milligas() - ctx.gas.milligas(),
+            interpret_cost::SAPLING_EMPTY_STATE
+        );
+    }
+
+    #[test]
+    fn sapling_verify_update() {
+        use crate::sapling::{tests::*, SaplingDiff, EMPTY_ROOT};
+        let tx = transaction(vec![input(1)], vec![output(2, 8)], -3, EMPTY_ROOT);
+        let run = |verifier: MockVerifier| {
+            let mut stack = stk![
+                V::SaplingState(SaplingState::empty(8)),
+                V::new_sapling_transaction(tx.clone())
+            ];
+            let ctx = &mut Ctx::default();
+            ctx.sapling_verifier = Box::new(verifier);
+            assert_eq!(interpret_one(&SaplingVerifyUpdate, ctx, &mut stack), Ok(()));
+            (stack, Ctx::default().gas.milligas() - ctx.gas.milligas())
+        };
+        let expected_state = SaplingState {
+            diff: SaplingDiff {
+                commitments_and_ciphertexts: vec![([2; 32], tx.outputs()[0].ciphertext.clone())],
+                nullifiers: vec![[1; 32]],
+            },
+            ..SaplingState::empty(8)
+        };
+        assert_eq!(
+            run(MockVerifier(true)),
+            (
+                stk![V::new_option(Some(V::new_pair(
+                    V::Bytes(b"bound".to_vec()),
+                    V::new_pair(V::int(-3), V::SaplingState(expected_state))
+                )))],
+                // blake2b of 5 bytes of bound data, plus one input and one output
+                430 + 5 + 5767168 + 4718592 + 432500
+            )
+        );
+        assert_eq!(run(MockVerifier(false)).0, stk![V::new_option(None)]);
+    }
+
+    #[test]
+    fn sapling_verify_update_unavailable() {
+        use crate::sapling::{tests::*, UnavailableSaplingVerifier, EMPTY_ROOT};
+        let tx = transaction(vec![], vec![output(2, 8)], 0, EMPTY_ROOT);
+        let mut stack = stk![
+            V::SaplingState(SaplingState::empty(8)),
+            V::new_sapling_transaction(tx)
+        ];
+        let mut ctx = Ctx::default();
+        ctx.sapling_verifier = Box::new(UnavailableSaplingVerifier);
+        assert_eq!(
+            interpret_one(&SaplingVerifyUpdate, &mut ctx, &mut stack),
+            Err(InterpretError::SaplingError(
+                SaplingError::VerifierUnavailable
+            ))
+        );
+    }
+
     mod mul {
         use super::*;
 
@@ -6444,6 +6697,7 @@ mod interpreter_tests {
         let cs = cs_mich.typecheck_script(&mut ctx).unwrap();
         let expected_op = TypedValue::new_operation(
             Operation::CreateContract(super::CreateContract {
+                address: "KT1CvVk9uuEpf5t88frj41xMzHc5M6FHqxZw".try_into().unwrap(),
                 delegate: None,
                 amount: 100,
                 storage: TypedValue::Unit,
@@ -6494,4 +6748,115 @@ mod interpreter_tests {
             addr::Address::try_from("KT1UvfyLytrt71jh63YV4Yex5SmbNXpWHxtg").unwrap(),
         );
     }
+
+    #[test]
+    fn view_instr() {
+        use crate::context::ViewCallee;
+        use crate::parser::test_helpers::parse_contract_script;
+
+        let callee_addr = addr::Address::try_from("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye").unwrap();
+        let script: &Micheline = Box::leak(Box::new(
+            parse_contract_script(concat!(
+                "parameter unit; storage nat; code { CDR; NIL operation; PAIR };",
+                r#"view "info" nat (pair address nat mutez) { UNPAIR; ADD; BALANCE; SWAP; SENDER; PAIR 3 };"#,
+            ))
+            .unwrap(),
+        ));
+        let mut ctx = Ctx::default();
+        let caller = ctx.self_address.clone();
+        let callee_hash = callee_addr.hash.clone();
+        ctx.lookup_view = Box::new(move |addr| {
+            (addr == &callee_hash).then(|| ViewCallee {
+                script,
+                storage: V::nat(5),
+                balance: 100,
+            })
+        });
+        let output_ty = Type::new_pair(Type::Address, Type::new_pair(Type::Nat, Type::Mutez));
+        let view = |name: &str, input_ty: Type| Instruction::View {
+            name: name.to_owned(),
+            input_ty,
+            output_ty: output_ty.clone(),
+        };
+
+        let mut stack = stk![V::Address(callee_addr.clone()), V::nat(2)];
+        assert_eq!(
+            interpret_one(&view("info", Type::Nat), &mut ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(
+            stack,
+            stk![V::new_option(Some(V::new_pair(
+                V::Address(addr::Address {
+                    hash: caller.clone(),
+                    entrypoint: Entrypoint::default()
+                }),
+                V::new_pair(V::nat(7), V::Mutez(100))
+            )))]
+        );
+        // context is restored after the view
+        assert_eq!(ctx.self_address, caller);
+        assert_eq!(ctx.balance, 0);
+
+        // unknown view
+        let mut stack = stk![V::Address(callee_addr.clone()), V::nat(2)];
+        assert_eq!(
+            interpret_one(&view("other", Type::Nat), &mut ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(stack, stk![V::new_option(None)]);
+
+        // input type mismatch
+        let mut stack = stk![V::Address(callee_addr.clone()), V::int(2)];
+        assert_eq!(
+            interpret_one(&view("info", Type::Int), &mut ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(stack, stk![V::new_option(None)]);
+
+        // looking up and typechecking the view is charged on top of the
+        // instruction itself, even if the view is then not run
+        let gas_used = |ctx: Ctx| Gas::default().milligas() - ctx.gas.milligas();
+        let mut view_ctx = Ctx::default();
+        view_ctx.lookup_view = std::mem::replace(&mut ctx.lookup_view, Box::new(|_| None));
+        let mut stack = stk![V::Address(callee_addr.clone()), V::int(2)];
+        assert_eq!(
+            interpret_one(&view("info", Type::Int), &mut view_ctx, &mut stack),
+            Ok(())
+        );
+        let tc_gas_used = gas_used(view_ctx);
+        assert!(tc_gas_used > interpret_cost::VIEW);
+        let mut no_view_ctx = Ctx::default();
+        let mut stack = stk![V::Address(callee_addr.clone()), V::int(2)];
+        assert_eq!(
+            interpret_one(&view("info", Type::Int), &mut no_view_ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(gas_used(no_view_ctx), interpret_cost::VIEW);
+        let mut low_gas_ctx = Ctx::default();
+        low_gas_ctx.gas = Gas::new(tc_gas_used - 1);
+        low_gas_ctx.lookup_view = Box::new(move |_| {
+            Some(ViewCallee {
+                script,
+                storage: V::nat(5),
+                balance: 100,
+            })
+        });
+        let mut stack = stk![V::Address(callee_addr), V::int(2)];
+        assert_eq!(
+            interpret_one(&view("info", Type::Int), &mut low_gas_ctx, &mut stack),
+            Err(InterpretError::OutOfGas(OutOfGas))
+        );
+
+        // implicit account
+        let mut stack = stk![
+            V::Address(addr::Address::try_from("tz1Nw5nr152qddEjKT2dKBH8XcBMDAg72iLw").unwrap()),
+            V::nat(2)
+        ];
+        assert_eq!(
+            interpret_one(&view("info", Type::Nat), &mut ctx, &mut stack),
+            Ok(())
+        );
+        assert_eq!(stack, stk![V::new_option(None)]);
+    }
 }
@@ -0,0 +1,992 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! A minimal in-process ledger applying the operations emitted by contracts.
+//!
+//! [Ledger] holds originated contracts along with their balances and storage,
+//! as well as balances of implicit accounts. A transfer is applied like on L1:
+//! the destination contract is run, then the operations it emits are applied
+//! depth-first, each internal call seeing the calling contract as `SENDER`.
+//! If any of these fails, the whole transfer is rolled back, including the
+//! `big_map`s written to [Ctx::big_map_storage] and the operation and
+//! origination counters of the [Ctx].
+//!
+//! This is mostly useful for testing multi-contract interactions.
+
+#![allow(clippy::type_complexity)]
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use typed_arena::Arena;
+
+use crate::ast::big_map::{dump_big_map_updates, BigMapId, LazyStorage, LazyStorageError};
+use crate::ast::michelson_address::entrypoint::Entrypoints;
+use crate::ast::{
+    Address, AddressHash, ContractScript, Emit, Entrypoint, KeyHash, Micheline, Operation, Or,
+    TransferTokens, Type, TypedValue,
+};
+use crate::context::{Ctx, ViewCallee};
+use crate::global_constants::ConstantError;
+use crate::interpreter::{compute_contract_address, InterpretError};
+use crate::lexer::Prim;
+use crate::typechecker::{typecheck_value, TcError};
+
+/// Errors possible when applying operations to the [Ledger].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum LedgerError<'a> {
+    /// The destination contract does not exist in the ledger.
+    #[error("contract not found: {0:?}")]
+    ContractNotFound(AddressHash),
+    /// The destination contract doesn't have the requested entrypoint.
+    #[error("entrypoint not found: {0:?}")]
+    EntrypointNotFound(Address),
+    /// Smart rollups can't be interacted with.
+    #[error("smart rollups are not supported: {0:?}")]
+    UnsupportedDestination(AddressHash),
+    /// A contract with the same address was already originated.
+    #[error("contract already exists: {0:?}")]
+    ContractAlreadyExists(AddressHash),
+    /// The sender can't afford the transferred amount.
+    #[error("balance of {address:?} too low: {balance} < {amount}")]
+    BalanceTooLow {
+        /// The sender address.
+        address: AddressHash,
+        /// The sender balance.
+        balance: i64,
+        /// The amount it tried to spend.
+        amount: i64,
+    },
+    /// Crediting the destination would overflow its balance.
+    #[error("balance overflow: {0:?}")]
+    BalanceOverflow(AddressHash),
+    /// Failed to typecheck a script or a value.
+    #[error("typechecking failed: {0}")]
+    TcError(#[from] TcError),
+    /// Failed to expand global constants in a script.
+    #[error("failed to expand constants: {0}")]
+    ConstantError(#[from] ConstantError),
+    /// Failed during the interpretation of a contract.
+    #[error("runtime failure: {0}")]
+    InterpretError(InterpretError<'a>),
+    /// Failed to write `big_map`s to the lazy storage.
+    #[error("lazy storage error: {0}")]
+    LazyStorageError(#[from] LazyStorageError),
+}
+
+impl<'a> From<InterpretError<'a>> for LedgerError<'a> {
+    fn from(x: InterpretError<'a>) -> Self {
+        Self::InterpretError(x)
+    }
+}
+
+/// An originated contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contract<'a> {
+    /// Typechecked contract script.
+    pub script: Rc<ContractScript<'a>>,
+    /// Raw [Micheline] representation of the script, used to resolve
+    /// entrypoints.
+    pub micheline_code: &'a Micheline<'a>,
+    /// Entrypoints of the contract.
+    pub entrypoints: Entrypoints,
+    /// Current storage.
+    pub storage: TypedValue<'a>,
+}
+
+/// An account known to the [Ledger], either implicit or originated.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Account<'a> {
+    /// Current balance.
+    pub balance: i64,
+    /// Current delegate.
+    pub delegate: Option<KeyHash>,
+    /// The contract, if this is an originated account.
+    pub contract: Option<Contract<'a>>,
+}
+
+/// An event emitted by a contract via the `EMIT` instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmittedEvent<'a> {
+    /// Address of the emitting contract.
+    pub emitter: AddressHash,
+    /// The event itself.
+    pub event: Emit<'a>,
+}
+
+/// In-process ledger, see the [module-level documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct Ledger<'a> {
+    accounts: HashMap<AddressHash, Account<'a>>,
+}
+
+impl<'a> Ledger<'a> {
+    /// Construct a new, empty, ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the account at the given address, if it is known.
+    pub fn account(&self, address: &AddressHash) -> Option<&Account<'a>> {
+        self.accounts.get(address)
+    }
+
+    /// Get the balance at the given address. Unknown accounts have zero
+    /// balance.
+    pub fn balance(&self, address: &AddressHash) -> i64 {
+        self.accounts.get(address).map_or(0, |acc| acc.balance)
+    }
+
+    /// Get the current storage of the contract at the given address.
+    pub fn storage(&self, address: &AddressHash) -> Option<&TypedValue<'a>> {
+        self.contract(address).map(|c| &c.storage)
+    }
+
+    /// Get the contract at the given address.
+    pub fn contract(&self, address: &AddressHash) -> Option<&Contract<'a>> {
+        self.accounts.get(address)?.contract.as_ref()
+    }
+
+    /// Forcibly set the balance of the given account. Mostly useful for
+    /// funding implicit accounts.
+    pub fn set_balance(&mut self, address: AddressHash, balance: i64) {
+        self.accounts.entry(address).or_default().balance = balance;
+    }
+
+    /// Originate a contract from its script with the given initial storage and
+    /// balance. The balance is not debited from anywhere. The address is
+    /// computed the same way as for `CREATE_CONTRACT`, using
+    /// [Ctx::operation_group_hash] and [Ctx::origination_counter]. Global
+    /// constants in the script are expanded using [Ctx::lookup_constant].
+    pub fn originate(
+        &mut self,
+        ctx: &mut Ctx<'a>,
+        arena: &'a Arena<Micheline<'a>>,
+        micheline_code: &'a Micheline<'a>,
+        storage: Micheline<'a>,
+        balance: i64,
+    ) -> Result<AddressHash, LedgerError<'a>> {
+        let micheline_code = arena.alloc(micheline_code.expand_constants(ctx, arena)?);
+        let script = micheline_code.typecheck_script(ctx)?;
+        let storage = typecheck_value(&storage, ctx, &script.storage)?;
+        let counter = ctx.origination_counter();
+        let address = compute_contract_address(&ctx.operation_group_hash, counter).hash;
+        self.add_contract(
+            ctx,
+            address.clone(),
+            Rc::new(script),
+            micheline_code,
+            storage,
+        )?;
+        self.accounts.get_mut(&address).unwrap().balance = balance;
+        Ok(address)
+    }
+
+    /// Transfer `amount` from `source` to `destination` with the given
+    /// parameter, then apply all the resulting internal operations
+    /// depth-first. The parameter must have the type of the destination
+    /// entrypoint.
+    ///
+    /// If anything fails, the ledger is left unchanged. Otherwise, returns the
+    /// events emitted during the transfer, in order of emission.
+    pub fn transfer(
+        &mut self,
+        ctx: &mut Ctx<'a>,
+        arena: &'a Arena<Micheline<'a>>,
+        source: AddressHash,
+        destination: Address,
+        amount: i64,
+        parameter: Micheline<'a>,
+    ) -> Result<Vec<EmittedEvent<'a>>, LedgerError<'a>> {
+        let param_ty = match &destination.hash {
+            AddressHash::Implicit(_) => crate::ast::Type::Unit,
+            AddressHash::Kt1(_) => self
+                .contract(&destination.hash)
+                .ok_or_else(|| LedgerError::ContractNotFound(destination.hash.clone()))?
+                .entrypoints
+                .get(&destination.entrypoint)
+                .ok_or_else(|| LedgerError::EntrypointNotFound(destination.clone()))?
+                .clone(),
+            AddressHash::Sr1(_) => {
+                return Err(LedgerError::UnsupportedDestination(destination.hash))
+            }
+        };
+        let param = typecheck_value(&parameter, ctx, &param_ty)?;
+        let snapshot = self.accounts.clone();
+        let counters = ctx.counters();
+        let old_source = std::mem::replace(&mut ctx.source, source.clone());
+        let mut events = vec![];
+        let mut journal = BigMapJournal::default();
+        let op = TransferTokens {
+            param,
+            destination_address: destination,
+            amount,
+        };
+        let res = self.apply_transfer(ctx, arena, source, op, &mut events, &mut journal);
+        ctx.source = old_source;
+        match res {
+            Ok(()) => {
+                journal.commit(ctx.big_map_storage.as_mut())?;
+                Ok(events)
+            }
+            Err(err) => {
+                self.accounts = snapshot;
+                ctx.set_origination_counter(counters.0);
+                ctx.set_operation_counter(counters.1);
+                journal.rollback(ctx.big_map_storage.as_mut())?;
+                Err(err)
+            }
+        }
+    }
+
+    fn add_contract(
+        &mut self,
+        ctx: &mut Ctx<'a>,
+        address: AddressHash,
+        script: Rc<ContractScript<'a>>,
+        micheline_code: &'a Micheline<'a>,
+        storage: TypedValue<'a>,
+    ) -> Result<(), LedgerError<'a>> {
+        if self.contract(&address).is_some() {
+            return Err(LedgerError::ContractAlreadyExists(address));
+        }
+        let entrypoints = parameter_ty(micheline_code)
+            .expect("typechecked script must have a parameter")
+            .get_entrypoints(ctx)?;
+        self.accounts.entry(address).or_default().contract = Some(Contract {
+            script,
+            micheline_code,
+            entrypoints,
+            storage,
+        });
+        Ok(())
+    }
+
+    fn move_funds(
+        &mut self,
+        from: &AddressHash,
+        to: &AddressHash,
+        amount: i64,
+    ) -> Result<(), LedgerError<'a>> {
+        let balance = self.balance(from);
+        if balance < amount {
+            return Err(LedgerError::BalanceTooLow {
+                address: from.clone(),
+                balance,
+                amount,
+            });
+        }
+        self.accounts.entry(from.clone()).or_default().balance -= amount;
+        let to_acc = self.accounts.entry(to.clone()).or_default();
+        to_acc.balance = to_acc
+            .balance
+            .checked_add(amount)
+            .ok_or_else(|| LedgerError::BalanceOverflow(to.clone()))?;
+        Ok(())
+    }
+
+    fn apply(
+        &mut self,
+        ctx: &mut Ctx<'a>,
+        arena: &'a Arena<Micheline<'a>>,
+        sender: AddressHash,
+        operation: Operation<'a>,
+        events: &mut Vec<EmittedEvent<'a>>,
+        journal: &mut BigMapJournal<'a>,
+    ) -> Result<(), LedgerError<'a>> {
+        match operation {
+            Operation::TransferTokens(op) => {
+                self.apply_transfer(ctx, arena, sender, op, events, journal)?
+            }
+            Operation::SetDelegate(sd) => {
+                self.accounts.entry(sender).or_default().delegate = sd.0;
+            }
+            Operation::Emit(event) => events.push(EmittedEvent {
+                emitter: sender,
+                event,
+            }),
+            Operation::CreateContract(cc) => {
+                self.add_contract(
+                    ctx,
+                    cc.address.clone(),
+                    cc.code,
+                    cc.micheline_code,
+                    cc.storage,
+                )?;
+                self.move_funds(&sender, &cc.address, cc.amount)?;
+                self.accounts.get_mut(&cc.address).unwrap().delegate = cc.delegate;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_transfer(
+        &mut self,
+        ctx: &mut Ctx<'a>,
+        arena: &'a Arena<Micheline<'a>>,
+        sender: AddressHash,
+        op: TransferTokens<'a>,
+        events: &mut Vec<EmittedEvent<'a>>,
+        journal: &mut BigMapJournal<'a>,
+    ) -> Result<(), LedgerError<'a>> {
+        let destination = op.destination_address;
+        if let AddressHash::Sr1(_) = destination.hash {
+            return Err(LedgerError::UnsupportedDestination(destination.hash));
+        }
+        let contract = match &destination.hash {
+            AddressHash::Kt1(_) => Some(
+                self.contract(&destination.hash)
+                    .ok_or_else(|| LedgerError::ContractNotFound(destination.hash.clone()))?
+                    .clone(),
+            ),
+            _ => None,
+        };
+        self.move_funds(&sender, &destination.hash, op.amount)?;
+        // Transfers to implicit accounts only move funds.
+        let Some(contract) = contract else {
+            return Ok(());
+        };
+        let mut param = wrap_parameter(contract.micheline_code, &destination, op.param)?;
+        let mut storage = contract.storage;
+        let mut started_with_map_ids = vec![];
+        param.view_big_map_ids::<()>(&mut started_with_map_ids);
+        storage.view_big_map_ids::<()>(&mut started_with_map_ids);
+
+        let old_sender = std::mem::replace(&mut ctx.sender, sender);
+        let old_self = std::mem::replace(&mut ctx.self_address, destination.hash.clone());
+        let old_amount = std::mem::replace(&mut ctx.amount, op.amount);
+        let old_balance = std::mem::replace(&mut ctx.balance, self.balance(&destination.hash));
+        let old_lookup_contract = std::mem::replace(&mut ctx.lookup_contract, self.contracts_fn());
+        let old_lookup_view = std::mem::replace(&mut ctx.lookup_view, self.views_fn());
+        let res = contract
+            .script
+            .interpret_typed(ctx, arena, param, storage)
+            .map(|(ops, storage)| (ops.collect::<Vec<_>>(), storage));
+        ctx.sender = old_sender;
+        ctx.self_address = old_self;
+        ctx.amount = old_amount;
+        ctx.balance = old_balance;
+        ctx.lookup_contract = old_lookup_contract;
+        ctx.lookup_view = old_lookup_view;
+        let (mut ops, mut storage) = res?;
+
+        // Like on L1, big maps are written to the lazy storage at the end of
+        // the contract execution, those in the emitted operations included.
+        let mut finished_with_maps = vec![];
+        storage.view_big_maps_mut(&mut finished_with_maps);
+        for op in &mut ops {
+            match &mut op.operation {
+                Operation::TransferTokens(t) => t.param.view_big_maps_mut(&mut finished_with_maps),
+                Operation::CreateContract(cc) => {
+                    cc.storage.view_big_maps_mut(&mut finished_with_maps)
+                }
+                Operation::SetDelegate(_) | Operation::Emit(_) => {}
+            }
+        }
+        dump_big_map_updates(
+            &mut JournaledStorage {
+                storage: ctx.big_map_storage.as_mut(),
+                journal,
+                arena,
+            },
+            &started_with_map_ids,
+            &mut finished_with_maps,
+        )?;
+
+        // NB: the contract must exist, we've just looked it up.
+        self.accounts
+            .get_mut(&destination.hash)
+            .and_then(|acc| acc.contract.as_mut())
+            .unwrap()
+            .storage = storage;
+        for op in ops {
+            self.apply(
+                ctx,
+                arena,
+                destination.hash.clone(),
+                op.operation,
+                events,
+                journal,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn contracts_fn(&self) -> Box<dyn FnMut(&AddressHash) -> Option<Entrypoints>> {
+        let map: HashMap<_, _> = self
+            .accounts
+            .iter()
+            .filter_map(|(addr, acc)| {
+                Some((addr.clone(), acc.contract.as_ref()?.entrypoints.clone()))
+            })
+            .collect();
+        Box::new(move |addr| map.get(addr).cloned())
+    }
+
+    fn views_fn(&self) -> Box<dyn FnMut(&AddressHash) -> Option<ViewCallee<'a>> + 'a> {
+        let accounts = self.accounts.clone();
+        Box::new(move |addr| {
+            let acc = accounts.get(addr)?;
+            let contract = acc.contract.as_ref()?;
+            Some(ViewCallee {
+                script: contract.micheline_code,
+                storage: contract.storage.clone(),
+                balance: acc.balance,
+            })
+        })
+    }
+}
+
+/// Changes made to the lazy storage during a transfer, allowing to undo them
+/// if the transfer fails.
+#[derive(Default)]
+struct BigMapJournal<'a> {
+    /// Big maps allocated during the transfer.
+    created: Vec<BigMapId>,
+    /// Big maps removed during the transfer. These are only removed from the
+    /// storage once the transfer succeeds.
+    removed: Vec<BigMapId>,
+    /// Previous values of the updated entries of the other big maps, in order
+    /// of update.
+    updated: Vec<(BigMapId, TypedValue<'a>, Option<TypedValue<'a>>)>,
+}
+
+impl<'a> BigMapJournal<'a> {
+    fn commit(self, storage: &mut (dyn LazyStorage<'a> + 'a)) -> Result<(), LazyStorageError> {
+        for id in &self.removed {
+            storage.big_map_remove(id)?;
+        }
+        Ok(())
+    }
+
+    fn rollback(self, storage: &mut (dyn LazyStorage<'a> + 'a)) -> Result<(), LazyStorageError> {
+        for (id, key, value) in self.updated.into_iter().rev() {
+            storage.big_map_update(&id, key, value)?;
+        }
+        for id in &self.created {
+            storage.big_map_remove(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// [LazyStorage] recording the changes made to the underlying storage in a
+/// [BigMapJournal].
+struct JournaledStorage<'a, 'b> {
+    storage: &'b mut (dyn LazyStorage<'a> + 'a),
+    journal: &'b mut BigMapJournal<'a>,
+    arena: &'a Arena<Micheline<'a>>,
+}
+
+impl<'a> LazyStorage<'a> for JournaledStorage<'a, '_> {
+    fn big_map_get(
+        &self,
+        arena: &'a Arena<Micheline<'a>>,
+        id: &BigMapId,
+        key: &TypedValue,
+    ) -> Result<Option<TypedValue<'a>>, LazyStorageError> {
+        self.storage.big_map_get(arena, id, key)
+    }
+
+    fn big_map_mem(&self, id: &BigMapId, key: &TypedValue) -> Result<bool, LazyStorageError> {
+        self.storage.big_map_mem(id, key)
+    }
+
+    fn big_map_update(
+        &mut self,
+        id: &BigMapId,
+        key: TypedValue<'a>,
+        value: Option<TypedValue<'a>>,
+    ) -> Result<(), LazyStorageError> {
+        if !self.journal.created.contains(id) {
+            let old = self.storage.big_map_get(self.arena, id, &key)?;
+            self.journal.updated.push((id.clone(), key.clone(), old));
+        }
+        self.storage.big_map_update(id, key, value)
+    }
+
+    fn big_map_get_type(&self, id: &BigMapId) -> Result<Option<(Type, Type)>, LazyStorageError> {
+        self.storage.big_map_get_type(id)
+    }
+
+    fn big_map_new(
+        &mut self,
+        key_type: &Type,
+        value_type: &Type,
+    ) -> Result<BigMapId, LazyStorageError> {
+        let id = self.storage.big_map_new(key_type, value_type)?;
+        self.journal.created.push(id.clone());
+        Ok(id)
+    }
+
+    fn big_map_copy(&mut self, id: &BigMapId) -> Result<BigMapId, LazyStorageError> {
+        let id = self.storage.big_map_copy(id)?;
+        self.journal.created.push(id.clone());
+        Ok(id)
+    }
+
+    fn big_map_remove(&mut self, id: &BigMapId) -> Result<(), LazyStorageError> {
+        match self.journal.created.iter().position(|x| x == id) {
+            Some(pos) => {
+                self.journal.created.swap_remove(pos);
+                self.storage.big_map_remove(id)
+            }
+            None => {
+                self.journal.removed.push(id.clone());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Find the `parameter` field of a contract script.
+fn parameter_ty<'a, 'b>(script: &'b Micheline<'a>) -> Option<&'b Micheline<'a>> {
+    let fields = match script {
+        Micheline::Seq([Micheline::Seq(fields)]) | Micheline::Seq(fields) => fields,
+        _ => return None,
+    };
+    fields.iter().find_map(|field| match field {
+        Micheline::App(Prim::parameter, [ty], _) => Some(ty),
+        _ => None,
+    })
+}
+
+/// Find the path to the given entrypoint in the parameter type, as a sequence
+/// of `or` branches, `true` meaning `Right`.
+fn entrypoint_path(ty: &Micheline, entrypoint: &Entrypoint) -> Option<Vec<bool>> {
+    fn go(ty: &Micheline, entrypoint: &Entrypoint, path: &mut Vec<bool>) -> bool {
+        let Micheline::App(prim, args, anns) = ty else {
+            return false;
+        };
+        if let Ok(Some(ann)) = anns.get_single_field_ann() {
+            if Entrypoint::try_from(ann).as_ref() == Ok(entrypoint) {
+                return true;
+            }
+        }
+        if let (Prim::or, [l, r]) = (prim, args) {
+            for (is_right, branch) in [(false, l), (true, r)] {
+                path.push(is_right);
+                if go(branch, entrypoint, path) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+    let mut path = vec![];
+    if go(ty, entrypoint, &mut path) {
+        Some(path)
+    } else if entrypoint.is_default() {
+        // NB: without an explicit `%default` entrypoint, the default one is
+        // the whole parameter.
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Wrap a parameter for the entrypoint of the given address into a value of
+/// the whole parameter type of the contract.
+fn wrap_parameter<'a>(
+    micheline_code: &Micheline,
+    destination: &Address,
+    param: TypedValue<'a>,
+) -> Result<TypedValue<'a>, LedgerError<'a>> {
+    let path = parameter_ty(micheline_code)
+        .and_then(|ty| entrypoint_path(ty, &destination.entrypoint))
+        .ok_or_else(|| LedgerError::EntrypointNotFound(destination.clone()))?;
+    Ok(path.into_iter().rev().fold(param, |val, is_right| {
+        TypedValue::new_or(if is_right {
+            Or::Right(val)
+        } else {
+            Or::Left(val)
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ByteReprTrait;
+    use crate::global_constants::ScriptExprHash;
+    use crate::parser::test_helpers::{parse, parse_contract_script};
+
+    const SOURCE: &str = "tz1TSbthBCECxmnABv73icw7yyyvUWFLAoSP";
+
+    fn source() -> AddressHash {
+        SOURCE.try_into().unwrap()
+    }
+
+    fn script(src: &str) -> &'static Micheline<'static> {
+        let src: &'static str = src.to_owned().leak();
+        Box::leak(Box::new(parse_contract_script(src).unwrap()))
+    }
+
+    fn addr(hash: &AddressHash, ep: &str) -> Address {
+        Address {
+            hash: hash.clone(),
+            entrypoint: Entrypoint::try_from(ep).unwrap(),
+        }
+    }
+
+    #[test]
+    fn entrypoint_paths() {
+        let ty = parse("or (nat %a) (or (int %b) (unit %default))").unwrap();
+        let ep = |s: &str| Entrypoint::try_from(s).unwrap();
+        assert_eq!(entrypoint_path(&ty, &ep("a")), Some(vec![false]));
+        assert_eq!(entrypoint_path(&ty, &ep("b")), Some(vec![true, false]));
+        assert_eq!(
+            entrypoint_path(&ty, &Entrypoint::default()),
+            Some(vec![true, true])
+        );
+        assert_eq!(entrypoint_path(&ty, &ep("c")), None);
+        let ty = parse("or (nat %a) int").unwrap();
+        assert_eq!(entrypoint_path(&ty, &Entrypoint::default()), Some(vec![]));
+    }
+
+    #[test]
+    fn transfer_to_implicit() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        ledger.set_balance(source(), 100);
+        let dest: AddressHash = "tz1Nw5nr152qddEjKT2dKBH8XcBMDAg72iLw".try_into().unwrap();
+        assert_eq!(
+            ledger.transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&dest, ""),
+                30,
+                parse("Unit").unwrap()
+            ),
+            Ok(vec![])
+        );
+        assert_eq!(ledger.balance(&source()), 70);
+        assert_eq!(ledger.balance(&dest), 30);
+        assert_eq!(
+            ledger.transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&dest, ""),
+                71,
+                parse("Unit").unwrap()
+            ),
+            Err(LedgerError::BalanceTooLow {
+                address: source(),
+                balance: 70,
+                amount: 71
+            })
+        );
+    }
+
+    #[test]
+    fn internal_calls() {
+        // The caller forwards its parameter to the callee's `%add` entrypoint
+        // along with the received amount, and emits an event afterwards; the
+        // callee records its sender and adds to its storage.
+        let callee_code = script(
+            "parameter (or (nat %add) (unit %reset));
+             storage (pair (option address) nat);
+             code { UNPAIR; SWAP; CDR; SWAP;
+                    IF_LEFT { ADD } { DROP 2; PUSH nat 0 };
+                    SENDER; SOME; PAIR; NIL operation; PAIR }",
+        );
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let callee = ledger
+            .originate(ctx, &arena, callee_code, parse("Pair None 1").unwrap(), 0)
+            .unwrap();
+        let caller_code = script(&format!(
+            r#"parameter nat;
+               storage unit;
+               code {{ CAR;
+                       PUSH address "{callee}%add"; CONTRACT nat;
+                       IF_NONE {{ UNIT; FAILWITH }} {{}};
+                       AMOUNT; DIG 2; TRANSFER_TOKENS;
+                       PUSH string "done"; EMIT %done;
+                       NIL operation; SWAP; CONS; SWAP; CONS;
+                       UNIT; SWAP; PAIR }}"#,
+            callee = callee.to_base58_check()
+        ));
+        let caller = ledger
+            .originate(ctx, &arena, caller_code, parse("Unit").unwrap(), 0)
+            .unwrap();
+        ledger.set_balance(source(), 100);
+
+        let events = ledger
+            .transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&caller, ""),
+                10,
+                parse("5").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            ledger.storage(&callee),
+            Some(&TypedValue::new_pair(
+                TypedValue::new_option(Some(TypedValue::Address(addr(&caller, "")))),
+                TypedValue::nat(6)
+            ))
+        );
+        assert_eq!(ledger.balance(&source()), 90);
+        assert_eq!(ledger.balance(&caller), 0);
+        assert_eq!(ledger.balance(&callee), 10);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].emitter, caller);
+        assert_eq!(events[0].event.value, TypedValue::String("done".into()));
+        // context is restored afterwards
+        assert_eq!(ctx.source, Ctx::default().source);
+        assert_eq!(ctx.self_address, Ctx::default().self_address);
+    }
+
+    #[test]
+    fn rollback_on_failwith() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let failing = ledger
+            .originate(
+                ctx,
+                &arena,
+                script("parameter unit; storage unit; code { PUSH string \"nope\"; FAILWITH }"),
+                parse("Unit").unwrap(),
+                0,
+            )
+            .unwrap();
+        // Sends its whole balance to the failing contract, after creating
+        // a contract and updating its storage.
+        let caller_code = script(&format!(
+            r#"parameter unit;
+               storage nat;
+               code {{ CDR; PUSH nat 1; ADD;
+                       PUSH address "{failing}"; CONTRACT unit;
+                       IF_NONE {{ UNIT; FAILWITH }} {{}};
+                       BALANCE; UNIT; TRANSFER_TOKENS;
+                       UNIT; PUSH mutez 0; NONE key_hash;
+                       CREATE_CONTRACT {{ parameter unit; storage unit; code {{ CDR; NIL operation; PAIR }} }};
+                       SWAP; DROP;
+                       NIL operation; SWAP; CONS; SWAP; CONS; PAIR }}"#,
+            failing = failing.to_base58_check()
+        ));
+        let caller = ledger
+            .originate(ctx, &arena, caller_code, parse("0").unwrap(), 50)
+            .unwrap();
+        ledger.set_balance(source(), 100);
+        let before = ledger.clone();
+        assert_eq!(
+            ledger.transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&caller, ""),
+                10,
+                parse("Unit").unwrap()
+            ),
+            Err(LedgerError::InterpretError(InterpretError::FailedWith(
+                crate::ast::Type::String,
+                TypedValue::String("nope".into())
+            )))
+        );
+        assert_eq!(ledger.accounts, before.accounts);
+        assert_eq!(ledger.storage(&caller), Some(&TypedValue::nat(0)));
+    }
+
+    #[test]
+    fn rollback_big_maps_and_counters() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let noop = ledger
+            .originate(
+                ctx,
+                &arena,
+                script("parameter unit; storage unit; code { CDR; NIL operation; PAIR }"),
+                parse("Unit").unwrap(),
+                0,
+            )
+            .unwrap();
+        let failing = ledger
+            .originate(
+                ctx,
+                &arena,
+                script("parameter unit; storage unit; code { PUSH string \"nope\"; FAILWITH }"),
+                parse("Unit").unwrap(),
+                0,
+            )
+            .unwrap();
+        // Sets `k` to `k` in its big map, then calls `addr`.
+        let writer = ledger
+            .originate(
+                ctx,
+                &arena,
+                script(
+                    r#"parameter (pair int address);
+                       storage (big_map int int);
+                       code { UNPAIR; UNPAIR; DUP; SOME; SWAP;
+                              DIG 2; DUG 3; UPDATE; SWAP;
+                              CONTRACT unit; IF_NONE { UNIT; FAILWITH } {};
+                              PUSH mutez 0; UNIT; TRANSFER_TOKENS;
+                              NIL operation; SWAP; CONS; PAIR }"#,
+                ),
+                parse("{}").unwrap(),
+                0,
+            )
+            .unwrap();
+        let param1 = format!("Pair 1 \"{}\"", noop.to_base58_check()).leak();
+        let param2 = format!("Pair 2 \"{}\"", failing.to_base58_check()).leak();
+        let id = BigMapId(0.into());
+
+        ledger
+            .transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&writer, ""),
+                0,
+                parse(param1).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            ctx.big_map_storage
+                .big_map_get(&arena, &id, &TypedValue::int(1)),
+            Ok(Some(TypedValue::int(1)))
+        );
+        let before = ledger.clone();
+        let counters = ctx.counters();
+
+        assert!(ledger
+            .transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&writer, ""),
+                0,
+                parse(param2).unwrap(),
+            )
+            .is_err());
+        assert_eq!(ledger.accounts, before.accounts);
+        assert_eq!(ctx.counters(), counters);
+        assert_eq!(
+            ctx.big_map_storage
+                .big_map_get(&arena, &id, &TypedValue::int(1)),
+            Ok(Some(TypedValue::int(1)))
+        );
+        assert_eq!(
+            ctx.big_map_storage
+                .big_map_get(&arena, &id, &TypedValue::int(2)),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn create_contract_and_set_delegate() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let code = script(
+            r#"parameter unit;
+               storage (option address);
+               code { DROP;
+                      PUSH nat 7; PUSH mutez 5; NONE key_hash;
+                      CREATE_CONTRACT { parameter unit; storage nat; code { CDR; NIL operation; PAIR } };
+                      SWAP; SOME;
+                      PUSH key_hash "tz1Nw5nr152qddEjKT2dKBH8XcBMDAg72iLw"; SOME; SET_DELEGATE;
+                      NIL operation; SWAP; CONS; DIG 2; CONS; PAIR }"#,
+        );
+        let factory = ledger
+            .originate(ctx, &arena, code, parse("None").unwrap(), 20)
+            .unwrap();
+        ledger.set_balance(source(), 100);
+        assert_eq!(
+            ledger.transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&factory, ""),
+                0,
+                parse("Unit").unwrap()
+            ),
+            Ok(vec![])
+        );
+        let Some(TypedValue::Option(Some(child))) = ledger.storage(&factory) else {
+            panic!("expected the address of the originated contract");
+        };
+        let TypedValue::Address(child) = child.as_ref() else {
+            panic!("expected an address");
+        };
+        assert_eq!(ledger.storage(&child.hash), Some(&TypedValue::nat(7)));
+        assert_eq!(ledger.balance(&child.hash), 5);
+        assert_eq!(ledger.balance(&factory), 15);
+        assert_eq!(
+            ledger.account(&factory).unwrap().delegate,
+            Some("tz1Nw5nr152qddEjKT2dKBH8XcBMDAg72iLw".try_into().unwrap())
+        );
+    }
+
+    #[test]
+    fn originate_with_constants() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let body = parse("{ CDR ; PUSH nat 1 ; ADD ; NIL operation ; PAIR }").unwrap();
+        let hash = ScriptExprHash::hash_bytes(&body.encode());
+        ctx.register_global_constants([body.encode()]);
+        let code = script(&format!(
+            r#"parameter unit; storage nat; code (constant "{hash}")"#
+        ));
+        let address = ledger
+            .originate(ctx, &arena, code, parse("0").unwrap(), 0)
+            .unwrap();
+        ledger.set_balance(source(), 100);
+        ledger
+            .transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&address, ""),
+                0,
+                parse("Unit").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(ledger.storage(&address), Some(&TypedValue::nat(1)));
+
+        let missing = ScriptExprHash::hash_bytes(&[]);
+        let code = script(&format!(
+            r#"parameter unit; storage nat; code (constant "{missing}")"#
+        ));
+        assert_eq!(
+            ledger.originate(ctx, &arena, code, parse("0").unwrap(), 0),
+            Err(LedgerError::ConstantError(
+                ConstantError::NonexistentGlobal(missing)
+            ))
+        );
+    }
+
+    #[test]
+    fn unknown_destination() {
+        let arena = Arena::new();
+        let ctx = &mut Ctx::default();
+        let mut ledger = Ledger::new();
+        let dest: AddressHash = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi".try_into().unwrap();
+        assert_eq!(
+            ledger.transfer(
+                ctx,
+                &arena,
+                source(),
+                addr(&dest, ""),
+                0,
+                parse("Unit").unwrap()
+            ),
+            Err(LedgerError::ContractNotFound(dest))
+        );
+    }
+}
@@ -23,6 +23,38 @@ pub enum MacroArgs<'a> {
     TwoArgs(Micheline<'a>, Micheline<'a>),
 }
 
+/// A step of a `C[AD]+R`-style accessor macro.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CadrStep {
+    /// `A`, i.e. accessing the left element of a pair.
+    A,
+    /// `D`, i.e. accessing the right element of a pair.
+    D,
+}
+
+/// Structure of a `P[PAI]+R`-style pairing macro, e.g. `PAPPAIIR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairStruct {
+    /// A stack element, i.e. `A` when in the left position, or `I` when in the
+    /// right position.
+    Leaf,
+    /// A pair of two sub-structures, i.e. `P`.
+    Pair(Box<PairStruct>, Box<PairStruct>),
+}
+
+impl PairStruct {
+    fn fmt_with(&self, f: &mut std::fmt::Formatter<'_>, leaf: char) -> std::fmt::Result {
+        match self {
+            PairStruct::Leaf => write!(f, "{leaf}"),
+            PairStruct::Pair(l, r) => {
+                write!(f, "P")?;
+                l.fmt_with(f, 'A')?;
+                r.fmt_with(f, 'I')
+            }
+        }
+    }
+}
+
 /// Enum representing macro names.
 #[derive(Debug, Clone, PartialEq, Eq, Logos)]
 #[allow(non_camel_case_types, clippy::upper_case_acronyms, missing_docs)]
@@ -30,36 +62,132 @@ pub enum MacroArgs<'a> {
 pub enum Macro {
     #[token("CMPEQ")]
     CMPEQ,
+    #[token("CMPNEQ")]
+    CMPNEQ,
+    #[token("CMPLT")]
+    CMPLT,
+    #[token("CMPGT")]
+    CMPGT,
     #[token("CMPLE")]
     CMPLE,
+    #[token("CMPGE")]
+    CMPGE,
     #[token("IF_SOME")]
     IF_SOME,
+    #[token("IF_RIGHT")]
+    IF_RIGHT,
     #[token("IFCMPEQ")]
     IFCMPEQ,
+    #[token("IFCMPNEQ")]
+    IFCMPNEQ,
+    #[token("IFCMPLT")]
+    IFCMPLT,
+    #[token("IFCMPGT")]
+    IFCMPGT,
     #[token("IFCMPLE")]
     IFCMPLE,
+    #[token("IFCMPGE")]
+    IFCMPGE,
+    #[token("IFEQ")]
+    IFEQ,
+    #[token("IFNEQ")]
+    IFNEQ,
+    #[token("IFLT")]
+    IFLT,
+    #[token("IFGT")]
+    IFGT,
+    #[token("IFLE")]
+    IFLE,
+    #[token("IFGE")]
+    IFGE,
     #[token("ASSERT")]
     ASSERT,
+    #[token("ASSERT_NONE")]
+    ASSERT_NONE,
+    #[token("ASSERT_SOME")]
+    ASSERT_SOME,
+    #[token("ASSERT_LEFT")]
+    ASSERT_LEFT,
+    #[token("ASSERT_RIGHT")]
+    ASSERT_RIGHT,
+    #[token("ASSERT_EQ")]
+    ASSERT_EQ,
+    #[token("ASSERT_NEQ")]
+    ASSERT_NEQ,
+    #[token("ASSERT_LT")]
+    ASSERT_LT,
+    #[token("ASSERT_GT")]
+    ASSERT_GT,
+    #[token("ASSERT_LE")]
+    ASSERT_LE,
+    #[token("ASSERT_GE")]
+    ASSERT_GE,
     #[token("ASSERT_CMPEQ")]
     ASSERT_CMPEQ,
+    #[token("ASSERT_CMPNEQ")]
+    ASSERT_CMPNEQ,
+    #[token("ASSERT_CMPLT")]
+    ASSERT_CMPLT,
+    #[token("ASSERT_CMPGT")]
+    ASSERT_CMPGT,
     #[token("ASSERT_CMPLE")]
     ASSERT_CMPLE,
+    #[token("ASSERT_CMPGE")]
+    ASSERT_CMPGE,
     #[token("FAIL")]
     FAIL,
     /// Corresponds to `DI..IP` macro. The value carried by the variant
-    /// corresponds to the number of `I`s.
-    #[regex("DII+P", lex_diip)]
+    /// corresponds to the depth, written in roman numerals between `D` and
+    /// `P`, e.g. the number of `I`s.
+    #[regex("D[IVXLCDM]+P", lex_diip)]
     DIIP(u16),
     /// Corresponds to `DU..UP` macro. The value carried by the variant
     /// corresponds to the number of `U`s.
     #[regex("DUU+P", lex_duup)]
     DUUP(u16),
+    /// Corresponds to `C[AD]+R` macro, e.g. `CADR`.
+    #[regex("C[AD][AD]+R", |lex| lex_cadr(&lex.slice()[1..]))]
+    CADR(Vec<CadrStep>),
+    /// Corresponds to `SET_C[AD]+R` macro, e.g. `SET_CADR`.
+    #[regex("SET_C[AD]+R", |lex| lex_cadr(&lex.slice()[5..]))]
+    SET_CADR(Vec<CadrStep>),
+    /// Corresponds to `MAP_C[AD]+R` macro, e.g. `MAP_CADR`.
+    #[regex("MAP_C[AD]+R", |lex| lex_cadr(&lex.slice()[5..]))]
+    MAP_CADR(Vec<CadrStep>),
+    /// Corresponds to `P[PAI]+R` macro, e.g. `PAPAIR`.
+    #[regex("P[PAI]+R", |lex| lex_pair_struct(lex.slice()))]
+    PAPAIR(PairStruct),
+    /// Corresponds to `UNP[PAI]+R` macro, e.g. `UNPAPAIR`.
+    #[regex("UNP[PAI]+R", |lex| lex_pair_struct(&lex.slice()[2..]))]
+    UNPAPAIR(PairStruct),
 }
 
 fn lex_diip(lex: &mut Lexer<Macro>) -> Result<u16, LexerError> {
-    (lex.slice().len() - 2)
-        .try_into()
-        .map_err(|_| LexerError::UnknownToken)
+    let s = lex.slice();
+    let roman = &s[1..s.len() - 1];
+    // NB: this mirrors the reference implementation, which doesn't check
+    // that the numeral is well-formed.
+    let mut res: i64 = 0;
+    let mut last = 0;
+    for c in roman.chars().rev() {
+        let n = match c {
+            'M' => 1000,
+            'D' => 500,
+            'C' => 100,
+            'L' => 50,
+            'X' => 10,
+            'V' => 5,
+            'I' => 1,
+            _ => return Err(LexerError::UnknownToken),
+        };
+        if n < last {
+            res -= n;
+        } else {
+            res += n;
+            last = n;
+        }
+    }
+    res.try_into().map_err(|_| LexerError::UnknownToken)
 }
 
 fn lex_duup(lex: &mut Lexer<Macro>) -> Result<u16, LexerError> {
@@ -68,11 +196,69 @@ fn lex_duup(lex: &mut Lexer<Macro>) -> Result<u16, LexerError> {
         .map_err(|_| LexerError::UnknownToken)
 }
 
+/// Takes the `[AD]+R` part of an accessor macro.
+fn lex_cadr(s: &str) -> Vec<CadrStep> {
+    s[..s.len() - 1]
+        .chars()
+        .map(|c| match c {
+            'A' => CadrStep::A,
+            _ => CadrStep::D,
+        })
+        .collect()
+}
+
+/// Takes the `P[PAI]+R` part of a pairing macro.
+fn lex_pair_struct(s: &str) -> Result<PairStruct, LexerError> {
+    // `None` means the top level, `Some(true)` the left position of a pair.
+    fn go(s: &[u8], i: usize, left: Option<bool>) -> Option<(usize, PairStruct)> {
+        match s.get(i)? {
+            b'P' => {
+                let (i, l) = go(s, i + 1, Some(true))?;
+                let (i, r) = go(s, i, Some(false))?;
+                Some((i, PairStruct::Pair(Box::new(l), Box::new(r))))
+            }
+            b'A' if left == Some(true) => Some((i + 1, PairStruct::Leaf)),
+            b'I' if left != Some(true) => Some((i + 1, PairStruct::Leaf)),
+            _ => None,
+        }
+    }
+    let s = &s.as_bytes()[..s.len() - 1];
+    match go(s, 0, None) {
+        Some((last, res)) if last == s.len() => Ok(res),
+        _ => Err(LexerError::UnknownToken),
+    }
+}
+
 impl std::fmt::Display for Macro {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_cadr = |f: &mut std::fmt::Formatter<'_>, steps: &[CadrStep]| {
+            write!(f, "C")?;
+            for step in steps {
+                write!(f, "{step:?}")?;
+            }
+            write!(f, "R")
+        };
         match self {
             Macro::DIIP(c) => write!(f, "D{}P", "I".repeat(usize::from(*c))),
             Macro::DUUP(c) => write!(f, "D{}P", "U".repeat(usize::from(*c))),
+            Macro::CADR(steps) => fmt_cadr(f, steps),
+            Macro::SET_CADR(steps) => {
+                write!(f, "SET_")?;
+                fmt_cadr(f, steps)
+            }
+            Macro::MAP_CADR(steps) => {
+                write!(f, "MAP_")?;
+                fmt_cadr(f, steps)
+            }
+            Macro::PAPAIR(p) => {
+                p.fmt_with(f, 'I')?;
+                write!(f, "R")
+            }
+            Macro::UNPAPAIR(p) => {
+                write!(f, "UN")?;
+                p.fmt_with(f, 'I')?;
+                write!(f, "R")
+            }
             _ => write!(f, "{:?}", &self),
         }
     }
@@ -91,4 +277,52 @@ mod tests {
     fn test_duup_display() {
         assert_eq!(format!("{}", Macro::DUUP(5)), "DUUUUUP");
     }
+
+    #[track_caller]
+    fn lex_one(s: &str) -> Result<Macro, LexerError> {
+        let mut lexer = Macro::lexer(s);
+        let res = lexer.next().unwrap();
+        assert_eq!(lexer.next(), None);
+        res
+    }
+
+    #[test]
+    fn test_diip_roman() {
+        assert_eq!(lex_one("DIIP"), Ok(Macro::DIIP(2)));
+        assert_eq!(lex_one("DIVP"), Ok(Macro::DIIP(4)));
+        assert_eq!(lex_one("DXIIP"), Ok(Macro::DIIP(12)));
+        // ill-formed numerals are read like in the reference implementation
+        assert_eq!(lex_one("DIIXP"), Ok(Macro::DIIP(8)));
+    }
+
+    #[test]
+    fn test_cadr_roundtrip() {
+        for s in [
+            "CADR",
+            "CDDAR",
+            "SET_CAR",
+            "SET_CADDR",
+            "MAP_CDR",
+            "MAP_CAADR",
+        ] {
+            assert_eq!(lex_one(s).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_pair_struct() {
+        for s in [
+            "PAPAIR",
+            "PPAIIR",
+            "PAPPAIIR",
+            "PPAIPAIR",
+            "UNPAPAIR",
+            "UNPPAIPAIR",
+        ] {
+            assert_eq!(lex_one(s).unwrap().to_string(), s);
+        }
+        assert_eq!(lex_one("PAPIR"), Err(LexerError::UnknownToken));
+        assert_eq!(lex_one("PAPAIIR"), Err(LexerError::UnknownToken));
+        assert_eq!(lex_one("PIAR"), Err(LexerError::UnknownToken));
+    }
 }
@@ -17,18 +17,10 @@
 //! supported:
 //!
 //! - `EMPTY_MAP`
-//! - `SAPLING_EMPTY_STATE`
-//! - `SAPLING_VERIFY_UPDATE`
-//! - `OPEN_CHEST`
-//! - `VIEW`
 //!
 //! The following types are currently not supported:
 //!
-//! - `chest`
-//! - `chest_key`
 //! - `tx_rollup_l2_address`
-//! - `sapling_state`
-//! - `sapling_transaction`
 //!
 //! # Usage
 //!
@@ -52,6 +44,10 @@
 //!   Michelson script, i.e. something that defines `parameter`, `storage` and
 //!   `code` fields.
 //!
+//! Scripts referring to global constants, i.e. containing `constant "expr..."`
+//! nodes, need to have those expanded with
+//! [ast::Micheline::expand_constants] before typechecking.
+//!
 //! Any of these functions requires a reference to the external context,
 //! [context::Ctx]. Context keeps track of the used gas, and also carries
 //! information about the world outside of the interpreter. You can construct a
@@ -68,6 +64,16 @@
 //! convert [ast::TypedValue] into [ast::Micheline], at which point,
 //! [ast::Micheline::encode] can be employed to serialize the data.
 //!
+//! [ast::Micheline] can also be printed back as Michelson source, either via
+//! its `Display` implementation or with [printer::Printer] for more control,
+//! e.g. folding expanded macros. [ast::Micheline::to_json] and
+//! [ast::Micheline::from_json] convert to and from the JSON representation
+//! used by `octez-client`.
+//!
+//! To debug a script, set [context::Ctx::tracer] to a [tracer::Tracer], which
+//! is called before and after every instruction. [tracer::JsonTracer] records a
+//! trace in the format of `octez-client run script --trace-stack`.
+//!
 //! Some functions require access to a [typed_arena::Arena]. [parser::Parser]
 //! already has one, so that one can be reused. If memory consumption is a
 //! concern, and depending on the workload, it may be slightly more economical
@@ -136,13 +142,19 @@ pub mod ast;
 pub mod bls;
 pub mod context;
 pub mod gas;
+pub mod global_constants;
 pub mod interpreter;
 mod irrefutable_match;
+pub mod ledger;
 pub mod lexer;
 pub mod parser;
+pub mod printer;
+pub mod sapling;
 pub mod serializer;
 pub mod stack;
 mod syntax;
+pub mod timelock;
+pub mod tracer;
 pub mod typechecker;
 pub mod tzt;
 
@@ -1056,6 +1068,7 @@ mod tests {
         let cs = cs_mich.typecheck_script(&mut ctx).unwrap();
         let expected_op = TypedValue::new_operation(
             Operation::CreateContract(CreateContract {
+                address: "KT1CvVk9uuEpf5t88frj41xMzHc5M6FHqxZw".try_into().unwrap(),
                 delegate: None,
                 amount: 100,
                 storage: TypedValue::Unit,
@@ -11,6 +11,7 @@ use super::*;
 
 use crate::lexer::macros::*;
 use crate::lexer::Prim;
+use std::borrow::Cow;
 
 /// Errors possible during macro expansion.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
@@ -19,103 +20,436 @@ pub enum MacroError {
     /// e.g. `FAIL {}`, or `IF_SOME` without arguments.
     #[error("unexpected number of arguments for macro: {0}")]
     UnexpectedArgumentCount(Macro),
+    /// Macro doesn't accept the annotations it is given, e.g. `FAIL @x`, or
+    /// `SET_CAR %a %b`.
+    #[error("unexpected annotation on macro: {0}")]
+    UnexpectedAnnotation(Macro),
+    /// Macro expects a sequence argument, but got something else, e.g. `DIIP
+    /// 1`.
+    #[error("macro {0} expects a sequence")]
+    SequenceExpected(Macro),
+}
+
+const PATH_ANN: Annotation<'static> = Annotation::Special(Cow::Borrowed("@%"));
+const PATH_PATH_ANN: Annotation<'static> = Annotation::Special(Cow::Borrowed("@%%"));
+const FIELD_PATH_ANN: Annotation<'static> = Annotation::Special(Cow::Borrowed("%@"));
+const EMPTY_FIELD_ANN: Annotation<'static> = Annotation::Field(Cow::Borrowed(""));
+
+/// Split annotations into field annotations (i.e. those starting with `%`) and
+/// the rest.
+fn partition_field_anns(anns: Vec<Annotation>) -> (Vec<Annotation>, Vec<Annotation>) {
+    anns.into_iter()
+        .partition(|a| matches!(a, Annotation::Field(_)) || *a == FIELD_PATH_ANN)
 }
 
 /// Expand a macro in raw [Micheline]. Requires access to an [Arena] in order to
 /// allocate the new instructions the macro was expanded to.
+///
+/// Expansions follow the ones done by `octez-client`, including the placement
+/// of annotations, so that the expanded code has the same gas cost and
+/// serialization.
 pub fn expand_macro<'a>(
     arena: &'a Arena<Micheline<'a>>,
     m: &Macro,
+    anns: Vec<Annotation<'a>>,
     args: MacroArgs<'a>,
 ) -> Result<Micheline<'a>, ParserError> {
     use Macro::*;
     use MacroArgs::*;
     use MacroError::*;
     use Micheline as M;
-    use Micheline::*;
+    use Option::{None, Some};
     use Prim::*;
     let unex_arg_err: ParserError = UnexpectedArgumentCount(m.clone()).into();
+    let unex_ann_err: ParserError = UnexpectedAnnotation(m.clone()).into();
+    let app = |prim, args: Vec<Micheline<'a>>, anns: Vec<Annotation<'a>>| {
+        M::App(prim, M::alloc_iter(arena, args.into_iter()), anns.into())
+    };
+    let seq = |items: Vec<Micheline<'a>>| M::Seq(M::alloc_iter(arena, items.into_iter()));
+    let fail = || M::seq(arena, [M::prim0(UNIT), M::prim0(FAILWITH)]);
+    let may_rename = |anns: Vec<Annotation<'a>>| {
+        if anns.is_empty() {
+            M::Seq(&[])
+        } else {
+            seq(vec![app(RENAME, vec![], anns)])
+        }
+    };
+    let expect_seq = |arg: Micheline<'a>| match arg {
+        M::Seq(..) => Ok(arg),
+        _ => Err(ParserError::from(SequenceExpected(m.clone()))),
+    };
+    let comparison = |m: &Macro| match m {
+        CMPEQ | IFCMPEQ | IFEQ | ASSERT_EQ | ASSERT_CMPEQ => EQ,
+        CMPNEQ | IFCMPNEQ | IFNEQ | ASSERT_NEQ | ASSERT_CMPNEQ => NEQ,
+        CMPLT | IFCMPLT | IFLT | ASSERT_LT | ASSERT_CMPLT => LT,
+        CMPGT | IFCMPGT | IFGT | ASSERT_GT | ASSERT_CMPGT => GT,
+        CMPLE | IFCMPLE | IFLE | ASSERT_LE | ASSERT_CMPLE => LE,
+        _ => GE,
+    };
     match (m, args) {
-        (CMPEQ, NoArgs) => Ok(M::seq(arena, [M::prim0(COMPARE), M::prim0(EQ)])),
-        (CMPEQ, _) => Err(unex_arg_err),
+        (CMPEQ | CMPNEQ | CMPLT | CMPGT | CMPLE | CMPGE, NoArgs) => Ok(seq(vec![
+            M::prim0(COMPARE),
+            app(comparison(m), vec![], anns),
+        ])),
+
+        (IFCMPEQ | IFCMPNEQ | IFCMPLT | IFCMPGT | IFCMPLE | IFCMPGE, TwoArgs(ib1, ib2)) => {
+            Ok(seq(vec![
+                M::prim0(COMPARE),
+                M::prim0(comparison(m)),
+                app(IF, vec![ib1, ib2], anns),
+            ]))
+        }
+
+        (IFEQ | IFNEQ | IFLT | IFGT | IFLE | IFGE, TwoArgs(ib1, ib2)) => Ok(seq(vec![
+            M::prim0(comparison(m)),
+            app(IF, vec![ib1, ib2], anns),
+        ])),
+
+        (IF_SOME, TwoArgs(ib1, ib2)) => Ok(seq(vec![app(IF_NONE, vec![ib2, ib1], anns)])),
 
-        (CMPLE, NoArgs) => Ok(M::seq(arena, [M::prim0(COMPARE), M::prim0(LE)])),
-        (CMPLE, _) => Err(unex_arg_err),
+        (IF_RIGHT, TwoArgs(ib1, ib2)) => Ok(seq(vec![app(IF_LEFT, vec![ib2, ib1], anns)])),
 
-        (IF_SOME, TwoArgs(ib1, ib2)) => Ok(M::seq(arena, [M::prim2(arena, IF_NONE, ib2, ib1)])),
-        (IF_SOME, _) => Err(unex_arg_err),
+        (
+            IFCMPEQ | IFCMPNEQ | IFCMPLT | IFCMPGT | IFCMPLE | IFCMPGE | IFEQ | IFNEQ | IFLT | IFGT
+            | IFLE | IFGE | IF_SOME | IF_RIGHT,
+            NoArgs,
+        ) if !anns.is_empty() => Err(unex_ann_err),
 
-        (IFCMPEQ, TwoArgs(ib1, ib2)) => Ok(M::seq(
+        (ASSERT | ASSERT_NONE | FAIL, _) if !anns.is_empty() => Err(unex_ann_err),
+
+        (ASSERT, NoArgs) => Ok(seq(vec![M::prim2(
             arena,
-            [
-                M::prim0(COMPARE),
-                M::prim0(EQ),
-                M::prim2(arena, IF, ib1, ib2),
-            ],
-        )),
-        (IFCMPEQ, _) => Err(unex_arg_err),
+            IF,
+            M::Seq(&[]),
+            M::seq(arena, [fail()]),
+        )])),
 
-        (IFCMPLE, TwoArgs(ib1, ib2)) => Ok(M::seq(
+        (ASSERT_NONE, NoArgs) => Ok(seq(vec![M::prim2(
             arena,
-            [
-                M::prim0(COMPARE),
-                M::prim0(LE),
-                M::prim2(arena, IF, ib1, ib2),
-            ],
-        )),
-        (IFCMPLE, _) => Err(unex_arg_err),
+            IF_NONE,
+            M::Seq(&[]),
+            M::seq(arena, [fail()]),
+        )])),
 
-        (ASSERT, NoArgs) => Ok(M::seq(
+        (ASSERT_SOME, NoArgs) => Ok(seq(vec![M::prim2(
             arena,
-            [M::prim2(
-                arena,
-                IF,
-                Seq(&[]),
-                M::seq(arena, [expand_macro(arena, &FAIL, NoArgs)?]),
-            )],
-        )),
-        (ASSERT, _) => Err(unex_arg_err),
+            IF_NONE,
+            M::seq(arena, [fail()]),
+            may_rename(anns),
+        )])),
 
-        // The following might seem a bit less straight forward than it could be. But the reference
-        // implementation wraps the first two instructions in a seq, so we are doing the same.
-        (ASSERT_CMPEQ, NoArgs) => Ok(M::seq(
+        (ASSERT_LEFT, NoArgs) => Ok(seq(vec![M::prim2(
             arena,
-            [
-                expand_macro(arena, &CMPEQ, NoArgs)?,
-                M::prim2(
-                    arena,
-                    IF,
-                    Seq(&[]),
-                    M::seq(arena, [expand_macro(arena, &FAIL, NoArgs)?]),
-                ),
-            ],
-        )),
-        (ASSERT_CMPEQ, _) => Err(unex_arg_err),
-
-        (ASSERT_CMPLE, NoArgs) => Ok(M::seq(
+            IF_LEFT,
+            may_rename(anns),
+            M::seq(arena, [fail()]),
+        )])),
+
+        (ASSERT_RIGHT, NoArgs) => Ok(seq(vec![M::prim2(
             arena,
-            [
-                expand_macro(arena, &CMPLE, NoArgs)?,
-                M::prim2(
-                    arena,
-                    IF,
-                    Seq(&[]),
-                    M::seq(arena, [expand_macro(arena, &FAIL, NoArgs)?]),
-                ),
-            ],
-        )),
-        (ASSERT_CMPLE, _) => Err(unex_arg_err),
-
-        (FAIL, NoArgs) => Ok(M::seq(arena, [M::prim0(UNIT), M::prim0(FAILWITH)])),
-        (FAIL, _) => Err(unex_arg_err),
+            IF_LEFT,
+            M::seq(arena, [fail()]),
+            may_rename(anns),
+        )])),
+
+        (
+            ASSERT_EQ | ASSERT_NEQ | ASSERT_LT | ASSERT_GT | ASSERT_LE | ASSERT_GE | ASSERT_CMPEQ
+            | ASSERT_CMPNEQ | ASSERT_CMPLT | ASSERT_CMPGT | ASSERT_CMPLE | ASSERT_CMPGE,
+            _,
+        ) if !anns.is_empty() => Err(unex_ann_err),
+
+        (ASSERT_EQ | ASSERT_NEQ | ASSERT_LT | ASSERT_GT | ASSERT_LE | ASSERT_GE, NoArgs) => {
+            Ok(seq(vec![
+                M::prim0(comparison(m)),
+                M::prim2(arena, IF, M::Seq(&[]), M::seq(arena, [fail()])),
+            ]))
+        }
+
+        // The following might seem a bit less straight forward than it could be. But the reference
+        // implementation wraps the first two instructions in a seq, so we are doing the same.
+        (
+            ASSERT_CMPEQ | ASSERT_CMPNEQ | ASSERT_CMPLT | ASSERT_CMPGT | ASSERT_CMPLE
+            | ASSERT_CMPGE,
+            NoArgs,
+        ) => Ok(seq(vec![
+            M::seq(arena, [M::prim0(COMPARE), M::prim0(comparison(m))]),
+            M::prim2(arena, IF, M::Seq(&[]), M::seq(arena, [fail()])),
+        ])),
+
+        (FAIL, NoArgs) => Ok(fail()),
 
         // Do not wrap expansion of DII+P and DUU+P in a Seq to
         // match octez-client behavior.
-        (DIIP(c), OneArg(ib)) => Ok(M::prim2(arena, DIP, M::Int((*c).into()), ib)),
-        (DIIP(_), _) => Err(unex_arg_err),
+        (DIIP(1), OneArg(ib)) => Ok(app(DIP, vec![expect_seq(ib)?], anns)),
+        (DIIP(c), OneArg(ib)) => Ok(app(DIP, vec![M::Int((*c).into()), expect_seq(ib)?], anns)),
+
+        (DUUP(c), NoArgs) => Ok(app(DUP, vec![M::Int((*c).into())], anns)),
+
+        (CADR(steps), NoArgs) => {
+            let (path_anns, _): (Vec<_>, Vec<_>) = anns
+                .iter()
+                .cloned()
+                .partition(|a| *a == PATH_ANN || *a == PATH_PATH_ANN);
+            let last = steps.len() - 1;
+            Ok(seq(steps
+                .iter()
+                .enumerate()
+                .map(|(i, step)| {
+                    let prim = match step {
+                        CadrStep::A => CAR,
+                        CadrStep::D => CDR,
+                    };
+                    let anns = if i == last {
+                        anns.clone()
+                    } else {
+                        path_anns.clone()
+                    };
+                    app(prim, vec![], anns)
+                })
+                .collect()))
+        }
+
+        (SET_CADR(steps), NoArgs) => {
+            let (field_ann, anns) = single_field_ann(m, anns)?;
+            let (init, rest) = steps.split_last().unwrap_or_else(|| unreachable!());
+            let access_check = |prim| match &field_ann {
+                Some(f) => vec![
+                    M::prim0(DUP),
+                    app(prim, vec![], vec![f.clone()]),
+                    M::prim0(DROP),
+                ],
+                None => vec![],
+            };
+            let field = field_ann.clone().unwrap_or(EMPTY_FIELD_ANN);
+            let init = match init {
+                CadrStep::A => {
+                    let mut res = access_check(CAR);
+                    res.extend([
+                        app(CDR, vec![], vec![PATH_PATH_ANN]),
+                        M::prim0(SWAP),
+                        app(PAIR, vec![], vec![field, FIELD_PATH_ANN]),
+                    ]);
+                    res
+                }
+                CadrStep::D => {
+                    let mut res = access_check(CDR);
+                    res.extend([
+                        app(CAR, vec![], vec![PATH_PATH_ANN]),
+                        app(PAIR, vec![], vec![FIELD_PATH_ANN, field]),
+                    ]);
+                    res
+                }
+            };
+            Ok(wrap_cadr_steps(arena, rest, seq(init), anns))
+        }
+
+        (MAP_CADR(steps), OneArg(body)) => {
+            let body = expect_seq(body)?;
+            let (field_ann, anns) = single_field_ann(m, anns)?;
+            let (init, rest) = steps.split_last().unwrap_or_else(|| unreachable!());
+            let cr_anns = match &field_ann {
+                Some(f) => vec![Annotation::Variable(Cow::Owned(
+                    f.to_string()[1..].to_owned(),
+                ))],
+                None => vec![],
+            };
+            let field = field_ann.unwrap_or(EMPTY_FIELD_ANN);
+            let init = match init {
+                CadrStep::A => vec![
+                    M::prim0(DUP),
+                    app(CDR, vec![], vec![PATH_PATH_ANN]),
+                    M::prim1(arena, DIP, seq(vec![app(CAR, vec![], cr_anns), body])),
+                    M::prim0(SWAP),
+                    app(PAIR, vec![], vec![field, FIELD_PATH_ANN]),
+                ],
+                CadrStep::D => vec![
+                    M::prim0(DUP),
+                    app(CDR, vec![], cr_anns),
+                    body,
+                    M::prim0(SWAP),
+                    app(CAR, vec![], vec![PATH_PATH_ANN]),
+                    app(PAIR, vec![], vec![FIELD_PATH_ANN, field]),
+                ],
+            };
+            Ok(wrap_cadr_steps(arena, rest, seq(init), anns))
+        }
+
+        (PAPAIR(ast), NoArgs) => {
+            let (field_anns, anns) = partition_field_anns(anns);
+            let mut pair_anns = vec![];
+            distribute_pair_anns(ast, &mut field_anns.into_iter(), &mut pair_anns);
+            let mut res = vec![];
+            let mut depth = 0;
+            let mut node = 0;
+            expand_pair_struct(ast, &mut depth, &mut |depth| {
+                let mut node_anns = match pair_anns.get(node) {
+                    Some((car, cdr)) if car.is_some() || cdr.is_some() => {
+                        let mut res: Vec<_> = car.iter().cloned().collect();
+                        if res.is_empty() {
+                            res.push(EMPTY_FIELD_ANN);
+                        }
+                        res.extend(cdr.iter().cloned());
+                        res
+                    }
+                    _ => vec![],
+                };
+                if node == 0 {
+                    node_anns.extend(anns.iter().cloned());
+                }
+                node += 1;
+                res.push(wrap_dip(arena, depth, app(PAIR, vec![], node_anns)));
+            });
+            // NB: the reference implementation emits `PAIR`s in the reverse
+            // order of traversal.
+            res.reverse();
+            Ok(seq(res))
+        }
+
+        (UNPAPAIR(ast), NoArgs) => {
+            let mut res = vec![];
+            let mut depth = 0;
+            expand_pair_struct(ast, &mut depth, &mut |depth| {
+                res.push(wrap_dip(arena, depth, M::prim0(UNPAIR)))
+            });
+            Ok(seq(res))
+        }
+
+        _ => Err(unex_arg_err),
+    }
+}
+/// Expand `CAR n` and `CDR n` macros into `GET`, leaving other primitive
+/// applications as they are.
+pub fn expand_prim_app<'a>(
+    arena: &'a Arena<Micheline<'a>>,
+    prim: Prim,
+    anns: Vec<Annotation<'a>>,
+    args: Vec<Micheline<'a>>,
+) -> Micheline<'a> {
+    use Micheline as M;
+    match (prim, args.as_slice()) {
+        (Prim::CAR, [M::Int(n)]) => M::seq(
+            arena,
+            [M::App(
+                Prim::GET,
+                M::alloc_seq(arena, [M::Int(2 * n + 1)]),
+                anns.into(),
+            )],
+        ),
+        (Prim::CDR, [M::Int(n)]) => M::seq(
+            arena,
+            [M::App(
+                Prim::GET,
+                M::alloc_seq(arena, [M::Int(2 * n)]),
+                anns.into(),
+            )],
+        ),
+        _ => M::App(prim, M::alloc_iter(arena, args.into_iter()), anns.into()),
+    }
+}
 
-        (DUUP(c), NoArgs) => Ok(M::prim1(arena, DUP, M::Int((*c).into()))),
-        (DUUP(_), _) => Err(unex_arg_err),
+/// Get the single optional field annotation of `SET_C[AD]+R` and `MAP_C[AD]+R`
+/// macros, and the rest of annotations.
+#[allow(clippy::type_complexity)]
+fn single_field_ann<'a>(
+    m: &Macro,
+    anns: Vec<Annotation<'a>>,
+) -> Result<(Option<Annotation<'a>>, Vec<Annotation<'a>>), MacroError> {
+    let (field_anns, anns) = partition_field_anns(anns);
+    match <[_; 1]>::try_from(field_anns) {
+        Ok([f]) => Ok((Some(f), anns)),
+        Err(field_anns) if field_anns.is_empty() => Ok((None, anns)),
+        Err(_) => Err(MacroError::UnexpectedAnnotation(m.clone())),
+    }
+}
+
+/// Wrap the innermost step of `SET_C[AD]+R` or `MAP_C[AD]+R` expansion into
+/// the outer steps. Annotations end up on the outermost `PAIR`.
+fn wrap_cadr_steps<'a>(
+    arena: &'a Arena<Micheline<'a>>,
+    steps: &[CadrStep],
+    init: Micheline<'a>,
+    anns: Vec<Annotation<'a>>,
+) -> Micheline<'a> {
+    use Micheline as M;
+    use Prim::{CAR, CDR, DIP, DUP, PAIR, SWAP};
+    let app = |prim, anns: Vec<Annotation<'a>>| M::App(prim, &[], anns.into());
+    let mut anns = Some(anns);
+    steps.iter().enumerate().rev().fold(init, |acc, (i, step)| {
+        let mut pair_anns = vec![FIELD_PATH_ANN, FIELD_PATH_ANN];
+        if i == 0 {
+            pair_anns.extend(anns.take().unwrap_or_default());
+        }
+        let (prim_in, prim_out) = match step {
+            CadrStep::A => (CAR, CDR),
+            CadrStep::D => (CDR, CAR),
+        };
+        let dip = M::prim1(
+            arena,
+            DIP,
+            M::seq(arena, [app(prim_in, vec![PATH_PATH_ANN]), acc]),
+        );
+        let mut res = vec![M::prim0(DUP), dip, app(prim_out, vec![PATH_PATH_ANN])];
+        if let CadrStep::A = step {
+            res.push(M::prim0(SWAP));
+        }
+        res.push(app(PAIR, pair_anns));
+        M::Seq(M::alloc_iter(arena, res.into_iter()))
+    })
+}
+
+/// Assign field annotations of a `P[PAI]+R` macro to `PAIR` nodes, in
+/// pre-order. Each entry of `res` holds the left and the right field annotation
+/// of the corresponding node.
+fn distribute_pair_anns<'a>(
+    ast: &PairStruct,
+    anns: &mut impl Iterator<Item = Annotation<'a>>,
+    res: &mut Vec<(Option<Annotation<'a>>, Option<Annotation<'a>>)>,
+) {
+    if let PairStruct::Pair(l, r) = ast {
+        let node = res.len();
+        res.push((None, None));
+        for (is_left, sub) in [(true, l), (false, r)] {
+            match sub.as_ref() {
+                PairStruct::Pair(..) => distribute_pair_anns(sub, anns, res),
+                PairStruct::Leaf => match anns.next() {
+                    Some(ann) if is_left => res[node].0 = Some(ann),
+                    Some(ann) => res[node].1 = Some(ann),
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+/// Traverse a `P[PAI]+R` structure in pre-order, calling `f` with the stack
+/// depth of each pair node.
+fn expand_pair_struct(ast: &PairStruct, depth: &mut u32, f: &mut impl FnMut(u32)) {
+    match ast {
+        PairStruct::Leaf => *depth += 1,
+        PairStruct::Pair(l, r) => {
+            f(*depth);
+            expand_pair_struct(l, depth, f);
+            expand_pair_struct(r, depth, f);
+        }
+    }
+}
+
+/// Wrap an instruction into `DIP` of the given depth, unless it's zero.
+fn wrap_dip<'a>(
+    arena: &'a Arena<Micheline<'a>>,
+    depth: u32,
+    instr: Micheline<'a>,
+) -> Micheline<'a> {
+    use Micheline as M;
+    match depth {
+        0 => instr,
+        1 => M::prim1(arena, Prim::DIP, M::seq(arena, [instr])),
+        _ => M::prim2(
+            arena,
+            Prim::DIP,
+            M::Int(depth.into()),
+            M::seq(arena, [instr]),
+        ),
     }
 }
 
@@ -177,4 +511,154 @@ mod tests {
             "unexpected number of arguments for macro: FAIL"
         );
     }
+
+    #[track_caller]
+    fn assert_expands(src: &str, expected: &str) {
+        assert_eq!(parse(src).unwrap(), parse(expected).unwrap());
+    }
+
+    #[test]
+    fn test_comparison_macros() {
+        assert_expands("{ CMPNEQ @a }", "{ { COMPARE ; NEQ @a } }");
+        assert_expands("{ CMPGE }", "{ { COMPARE ; GE } }");
+        assert_expands(
+            "{ IFCMPGT { UNIT } {} }",
+            "{ { COMPARE ; GT ; IF { UNIT } {} } }",
+        );
+        assert_expands("{ IFLT { UNIT } {} }", "{ { LT ; IF { UNIT } {} } }");
+        assert_expands("{ IF_RIGHT { UNIT } {} }", "{ { IF_LEFT {} { UNIT } } }");
+        assert_eq!(
+            parse("{ IFEQ @a }").unwrap_err().to_string(),
+            "unexpected annotation on macro: IFEQ"
+        );
+    }
+
+    #[test]
+    fn test_assert_macros() {
+        assert_expands(
+            "{ ASSERT_NONE }",
+            "{ { IF_NONE {} { { UNIT ; FAILWITH } } } }",
+        );
+        assert_expands(
+            "{ ASSERT_SOME }",
+            "{ { IF_NONE { { UNIT ; FAILWITH } } {} } }",
+        );
+        assert_expands(
+            "{ ASSERT_SOME @x }",
+            "{ { IF_NONE { { UNIT ; FAILWITH } } { RENAME @x } } }",
+        );
+        assert_expands(
+            "{ ASSERT_LEFT }",
+            "{ { IF_LEFT {} { { UNIT ; FAILWITH } } } }",
+        );
+        assert_expands(
+            "{ ASSERT_RIGHT }",
+            "{ { IF_LEFT { { UNIT ; FAILWITH } } {} } }",
+        );
+        assert_expands(
+            "{ ASSERT_NEQ }",
+            "{ { NEQ ; IF {} { { UNIT ; FAILWITH } } } }",
+        );
+        assert_expands(
+            "{ ASSERT_CMPGT }",
+            "{ { { COMPARE ; GT } ; IF {} { { UNIT ; FAILWITH } } } }",
+        );
+        assert_eq!(
+            parse("{ ASSERT @x }").unwrap_err().to_string(),
+            "unexpected annotation on macro: ASSERT"
+        );
+    }
+
+    #[test]
+    fn test_cadr_macros() {
+        assert_expands("{ CADR }", "{ { CAR ; CDR } }");
+        assert_expands("{ CDAR @x }", "{ { CDR ; CAR @x } }");
+        assert_expands("{ CDDR @% }", "{ { CDR @% ; CDR @% } }");
+        assert_expands("{ CAR 0 ; CDR 0 }", "{ { GET 1 } ; { GET 0 } }");
+        assert_expands("{ CAR 1 ; CDR @x 2 }", "{ { GET 3 } ; { GET @x 4 } }");
+        assert_eq!(
+            parse("{ CADR {} }").unwrap_err().to_string(),
+            "unexpected number of arguments for macro: CADR"
+        );
+    }
+
+    #[test]
+    fn test_set_cadr_macros() {
+        assert_expands("{ SET_CAR }", "{ { CDR @%% ; SWAP ; PAIR % %@ } }");
+        assert_expands("{ SET_CDR }", "{ { CAR @%% ; PAIR %@ % } }");
+        assert_expands(
+            "{ SET_CAR %x }",
+            "{ { DUP ; CAR %x ; DROP ; CDR @%% ; SWAP ; PAIR %x %@ } }",
+        );
+        assert_expands(
+            "{ SET_CADR }",
+            "{ { DUP ; DIP { CAR @%% ; { CAR @%% ; PAIR %@ % } } ; \
+               CDR @%% ; SWAP ; PAIR %@ %@ } }",
+        );
+        assert_expands(
+            "{ SET_CDAR @s }",
+            "{ { DUP ; DIP { CDR @%% ; { CDR @%% ; SWAP ; PAIR % %@ } } ; \
+               CAR @%% ; PAIR %@ %@ @s } }",
+        );
+        assert_eq!(
+            parse("{ SET_CAR %x %y }").unwrap_err().to_string(),
+            "unexpected annotation on macro: SET_CAR"
+        );
+    }
+
+    #[test]
+    fn test_map_cadr_macros() {
+        assert_expands(
+            "{ MAP_CAR { CAR } }",
+            "{ { DUP ; CDR @%% ; DIP { CAR ; { CAR } } ; SWAP ; PAIR % %@ } }",
+        );
+        assert_expands(
+            "{ MAP_CDR %x { CAR } }",
+            "{ { DUP ; CDR @x ; { CAR } ; SWAP ; CAR @%% ; PAIR %@ %x } }",
+        );
+        assert_expands(
+            "{ MAP_CAADR { CAR } }",
+            "{ { DUP ; \
+                 DIP { CAR @%% ; \
+                       { DUP ; \
+                         DIP { CAR @%% ; \
+                               { DUP ; CDR ; { CAR } ; SWAP ; CAR @%% ; PAIR %@ % } } ; \
+                         CDR @%% ; SWAP ; PAIR %@ %@ } } ; \
+                 CDR @%% ; SWAP ; PAIR %@ %@ } }",
+        );
+        assert_eq!(
+            parse("{ MAP_CAR 1 }").unwrap_err().to_string(),
+            "macro MAP_CAR expects a sequence"
+        );
+    }
+
+    #[test]
+    fn test_pair_macros() {
+        assert_expands("{ PAPAIR }", "{ { DIP { PAIR } ; PAIR } }");
+        assert_expands("{ PAPPAIIR }", "{ { DIP { PAIR } ; DIP { PAIR } ; PAIR } }");
+        assert_expands("{ PPAIIR }", "{ { PAIR ; PAIR } }");
+        assert_expands("{ PPAIPAIR }", "{ { DIP 2 { PAIR } ; PAIR ; PAIR } }");
+        assert_expands(
+            "{ PAPAIR %a %b %c @p }",
+            "{ { DIP { PAIR %b %c } ; PAIR %a @p } }",
+        );
+        assert_expands("{ PAPAIR %a %b }", "{ { DIP { PAIR %b } ; PAIR %a } }");
+        assert_expands("{ PPAIIR %a %b %c }", "{ { PAIR %a %b ; PAIR % %c } }");
+        assert_expands("{ UNPAPAIR }", "{ { UNPAIR ; DIP { UNPAIR } } }");
+        assert_expands(
+            "{ UNPPAIPAIR }",
+            "{ { UNPAIR ; UNPAIR ; DIP 2 { UNPAIR } } }",
+        );
+    }
+
+    #[test]
+    fn test_dip_dup_macros() {
+        assert_expands("{ DIVP { UNIT } }", "{ DIP 4 { UNIT } }");
+        assert_expands("{ DIIP @x { UNIT } }", "{ DIP @x 2 { UNIT } }");
+        assert_expands("{ DUUUP @x }", "{ DUP @x 3 }");
+        assert_eq!(
+            parse("{ DIIP UNIT }").unwrap_err().to_string(),
+            "macro DIIP expects a sequence"
+        );
+    }
 }
@@ -0,0 +1,325 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Michelson pretty-printer, i.e. conversion of [Micheline] back to concrete
+//! syntax.
+//!
+//! The output follows the conventions of `octez-client`: sequence elements are
+//! separated by ` ; `, primitive applications in argument position are wrapped
+//! in parentheses when they have arguments or annotations, and nodes that
+//! don't fit into the configured width are broken over several lines.
+
+mod macros;
+
+use std::fmt;
+
+use crate::ast::{Annotation, Micheline};
+
+/// Micheline pretty-printer configuration. Use [Printer::print] to produce
+/// the concrete syntax.
+///
+/// [Micheline] also implements [fmt::Display] using [Printer::default].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Printer {
+    /// Maximum line width. Nodes that don't fit are broken over several
+    /// lines. Atoms are never broken, so lines can still exceed the width.
+    pub width: usize,
+    /// Whether to fold expanded macros back, e.g. print `{ COMPARE ; EQ }` as
+    /// `CMPEQ`. A sequence is only folded if expanding the macro produces
+    /// exactly the same [Micheline], so folding never changes the meaning or
+    /// the serialization of the code.
+    pub fold_macros: bool,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer {
+            width: 80,
+            fold_macros: false,
+        }
+    }
+}
+
+/// Intermediate representation used for layout.
+enum Doc {
+    /// Literal, printed as is.
+    Atom(String),
+    /// Primitive or macro application. The head contains the name and
+    /// annotations.
+    App {
+        head: String,
+        args: Vec<Doc>,
+        needs_parens: bool,
+    },
+    /// Braced sequence.
+    Seq(Vec<Doc>),
+}
+
+impl Printer {
+    /// Print [Micheline] as Michelson concrete syntax.
+    pub fn print(&self, m: &Micheline) -> String {
+        let mut out = String::new();
+        self.render(&self.doc_of(m, true), 0, false, &mut out);
+        out
+    }
+
+    /// Convert [Micheline] to [Doc]. `fold_root` controls whether the node
+    /// itself may be folded into a macro. Arguments of primitives are never
+    /// folded at the root, since that would drop the braces around them.
+    fn doc_of(&self, m: &Micheline, fold_root: bool) -> Doc {
+        if self.fold_macros && fold_root {
+            if let Some(folded) = macros::fold(m) {
+                return Doc::App {
+                    needs_parens: !(folded.args.is_empty() && folded.anns.is_empty()),
+                    head: head(&folded.name, &folded.anns),
+                    args: folded.args.iter().map(|a| self.doc_of(a, false)).collect(),
+                };
+            }
+        }
+        match m {
+            Micheline::Int(i) => Doc::Atom(i.to_string()),
+            Micheline::String(s) => Doc::Atom(escape_string(s)),
+            Micheline::Bytes(b) => Doc::Atom(format!("0x{}", hex::encode(b))),
+            Micheline::App(prim, args, anns) => Doc::App {
+                needs_parens: !(args.is_empty() && anns.is_empty()),
+                head: head(&prim.to_string(), anns.iter()),
+                args: args.iter().map(|a| self.doc_of(a, false)).collect(),
+            },
+            Micheline::Seq(items) => Doc::Seq(items.iter().map(|i| self.doc_of(i, true)).collect()),
+        }
+    }
+
+    /// Render `doc` starting at column `col`. `in_arg` means the node is an
+    /// argument of a primitive application.
+    fn render(&self, doc: &Doc, col: usize, in_arg: bool, out: &mut String) {
+        let mut flat = String::new();
+        render_flat(doc, in_arg, &mut flat);
+        if col + flat.len() <= self.width {
+            out.push_str(&flat);
+            return;
+        }
+        match doc {
+            Doc::Seq(items) if !items.is_empty() => {
+                out.push_str("{ ");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(" ;");
+                        newline(col + 2, out);
+                    }
+                    self.render(item, col + 2, false, out);
+                }
+                out.push_str(" }");
+            }
+            Doc::App {
+                head,
+                args,
+                needs_parens,
+            } if !args.is_empty() => {
+                let parens = in_arg && *needs_parens;
+                let col = if parens {
+                    out.push('(');
+                    col + 1
+                } else {
+                    col
+                };
+                out.push_str(head);
+                for arg in args {
+                    newline(col + 2, out);
+                    self.render(arg, col + 2, true, out);
+                }
+                if parens {
+                    out.push(')');
+                }
+            }
+            _ => out.push_str(&flat),
+        }
+    }
+}
+
+fn render_flat(doc: &Doc, in_arg: bool, out: &mut String) {
+    match doc {
+        Doc::Atom(s) => out.push_str(s),
+        Doc::App {
+            head,
+            args,
+            needs_parens,
+        } => {
+            let parens = in_arg && *needs_parens;
+            if parens {
+                out.push('(');
+            }
+            out.push_str(head);
+            for arg in args {
+                out.push(' ');
+                render_flat(arg, true, out);
+            }
+            if parens {
+                out.push(')');
+            }
+        }
+        Doc::Seq(items) if items.is_empty() => out.push_str("{}"),
+        Doc::Seq(items) => {
+            out.push_str("{ ");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" ; ");
+                }
+                render_flat(item, false, out);
+            }
+            out.push_str(" }");
+        }
+    }
+}
+
+fn newline(indent: usize, out: &mut String) {
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+}
+
+fn head<'b, 'c: 'b>(name: &str, anns: impl IntoIterator<Item = &'b Annotation<'c>>) -> String {
+    let mut res = name.to_owned();
+    for ann in anns {
+        res.push(' ');
+        res.push_str(&ann.to_string());
+    }
+    res
+}
+
+/// Quote a string, escaping characters as the lexer expects them.
+fn escape_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+impl fmt::Display for Micheline<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Printer::default().print(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::test_helpers::*;
+
+    #[track_caller]
+    fn roundtrip(src: &str) {
+        let parsed = parse(src).unwrap();
+        let printed = parsed.to_string();
+        assert_eq!(printed, src);
+        assert_eq!(parse(&printed).unwrap(), parsed);
+    }
+
+    #[test]
+    fn print_atoms() {
+        roundtrip("1");
+        roundtrip("-42");
+        roundtrip("\"foo \\\"bar\\\" \\\\ \\n\"");
+        roundtrip("0x");
+        roundtrip("0xdeadbeef");
+        roundtrip("Unit");
+    }
+
+    #[test]
+    fn print_apps() {
+        roundtrip("Pair 1 (Some \"foo\") Unit");
+        roundtrip("pair (int %a) (nat :b) unit");
+        roundtrip("DUP @x");
+        roundtrip("PUSH (option nat) None");
+        roundtrip("{}");
+        roundtrip("{ DROP ; UNIT ; FAILWITH }");
+        roundtrip("{ Elt 1 { 2 ; 3 } ; Elt 4 {} }");
+    }
+
+    #[test]
+    fn print_breaks_lines() {
+        let src = "{ parameter (or (pair %first nat nat) (pair %second string string)) ; \
+                   storage unit ; \
+                   code { CDR ; NIL operation ; PAIR } }";
+        let printed = parse(src).unwrap().to_string();
+        assert_eq!(
+            printed,
+            "{ parameter (or (pair %first nat nat) (pair %second string string)) ;\n  \
+             storage unit ;\n  code { CDR ; NIL operation ; PAIR } }"
+        );
+        assert_eq!(parse(&printed).unwrap(), parse(src).unwrap());
+    }
+
+    #[test]
+    fn print_narrow() {
+        let printer = Printer {
+            width: 10,
+            ..Printer::default()
+        };
+        assert_eq!(
+            printer.print(&parse("{ IF_LEFT { DROP } { DUP ; DROP } }").unwrap()),
+            "{ IF_LEFT\n    { DROP }\n    { DUP ;\n      DROP } }"
+        );
+    }
+
+    #[track_caller]
+    fn check_fold(src: &str, expected: &str) {
+        let printer = Printer {
+            fold_macros: true,
+            ..Printer::default()
+        };
+        let printed = printer.print(&parse(src).unwrap());
+        assert_eq!(printed, expected);
+        assert_eq!(parse(&printed).unwrap(), parse(src).unwrap());
+    }
+
+    #[test]
+    fn fold_macros() {
+        check_fold("{ CMPEQ @x ; CMPGE }", "{ CMPEQ @x ; CMPGE }");
+        check_fold("{ IFCMPLT { UNIT } {} }", "{ IFCMPLT { UNIT } {} }");
+        check_fold("{ IFNEQ { UNIT } {} }", "{ IFNEQ { UNIT } {} }");
+        check_fold("{ IF_SOME { UNIT } {} }", "{ IF_SOME { UNIT } {} }");
+        check_fold("{ IF_RIGHT { UNIT } {} }", "{ IF_RIGHT { UNIT } {} }");
+        check_fold("{ FAIL }", "{ FAIL }");
+        check_fold(
+            "{ ASSERT ; ASSERT_NONE ; ASSERT_SOME @x ; ASSERT_LEFT ; ASSERT_RIGHT @y }",
+            "{ ASSERT ; ASSERT_NONE ; ASSERT_SOME @x ; ASSERT_LEFT ; ASSERT_RIGHT @y }",
+        );
+        check_fold(
+            "{ ASSERT_EQ ; ASSERT_CMPGT }",
+            "{ ASSERT_EQ ; ASSERT_CMPGT }",
+        );
+        check_fold("{ CADR ; CDDAR @x }", "{ CADR ; CDDAR @x }");
+        check_fold("{ CAR 2 ; CDR 3 }", "{ CAR 2 ; CDR 3 }");
+        check_fold(
+            "{ SET_CAR ; SET_CDR %x ; SET_CADR @y ; SET_CDAR %z @w }",
+            "{ SET_CAR ; SET_CDR %x ; SET_CADR @y ; SET_CDAR %z @w }",
+        );
+        check_fold(
+            "{ MAP_CAR { DROP ; UNIT } ; MAP_CDDR %x @y { DROP ; UNIT } }",
+            "{ MAP_CAR { DROP ; UNIT } ; MAP_CDDR %x @y { DROP ; UNIT } }",
+        );
+        check_fold(
+            "{ PAPAIR ; PPAIIR ; PAPPAIIR %a %b %c @d ; PPAIPAIR %a %b %c %d }",
+            "{ PAPAIR ; PPAIIR ; PAPPAIIR %a %b %c @d ; PPAIPAIR %a %b %c %d }",
+        );
+        check_fold("{ UNPAPAIR ; UNPPAIPAIR }", "{ UNPAPAIR ; UNPPAIPAIR }");
+        // arguments of primitives are not folded at the root, but their
+        // elements are
+        check_fold("{ DIP { UNIT ; FAILWITH } }", "{ DIP { UNIT ; FAILWITH } }");
+        check_fold("{ DIP { { UNIT ; FAILWITH } } }", "{ DIP { FAIL } }");
+        // and neither is anything that doesn't match the expansion exactly
+        check_fold("{ COMPARE @x ; EQ }", "{ COMPARE @x ; EQ }");
+        check_fold("{ { PAIR } ; { CAR } }", "{ { PAIR } ; { CAR } }");
+    }
+}
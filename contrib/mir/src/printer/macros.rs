@@ -0,0 +1,356 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Folding of expanded macros, the inverse of [crate::parser::macros].
+//!
+//! Folding is done in two steps: first, the shape of a sequence is used to
+//! guess which macros it could be an expansion of, then each guess is expanded
+//! and compared with the original. The first guess that matches wins.
+
+use typed_arena::Arena;
+
+use crate::ast::{Annotation, Annotations, Micheline};
+use crate::lexer::macros::{CadrStep, Macro, MacroArgs, PairStruct};
+use crate::lexer::Prim;
+use crate::parser::macros::{expand_macro, expand_prim_app};
+
+/// A macro application recognized in expanded [Micheline].
+pub(super) struct Folded<'a> {
+    pub name: String,
+    pub anns: Vec<Annotation<'static>>,
+    pub args: Vec<Micheline<'a>>,
+}
+
+enum Candidate<'a> {
+    Macro(Macro, Vec<Annotation<'static>>, Vec<Micheline<'a>>),
+    /// `CAR n` or `CDR n`.
+    Prim(Prim, Vec<Annotation<'static>>, Micheline<'a>),
+}
+
+impl<'a> Candidate<'a> {
+    fn expands_to(&self, m: &Micheline<'a>) -> bool {
+        let arena = Arena::new();
+        match self {
+            Candidate::Macro(mac, anns, args) => {
+                let args = match args.as_slice() {
+                    [] => MacroArgs::NoArgs,
+                    [a] => MacroArgs::OneArg(a.clone()),
+                    [a, b] => MacroArgs::TwoArgs(a.clone(), b.clone()),
+                    _ => return false,
+                };
+                expand_macro(&arena, mac, anns.clone(), args).is_ok_and(|res| res == *m)
+            }
+            Candidate::Prim(prim, anns, arg) => {
+                expand_prim_app(&arena, *prim, anns.clone(), vec![arg.clone()]) == *m
+            }
+        }
+    }
+
+    fn into_folded(self) -> Folded<'a> {
+        match self {
+            Candidate::Macro(mac, anns, args) => Folded {
+                name: mac.to_string(),
+                anns,
+                args,
+            },
+            Candidate::Prim(prim, anns, arg) => Folded {
+                name: prim.to_string(),
+                anns,
+                args: vec![arg],
+            },
+        }
+    }
+}
+
+/// Try to fold a node into a macro application.
+pub(super) fn fold<'a>(m: &Micheline<'a>) -> Option<Folded<'a>> {
+    let Micheline::Seq(items) = m else {
+        return None;
+    };
+    candidates(items)
+        .into_iter()
+        .find(|c| c.expands_to(m))
+        .map(Candidate::into_folded)
+}
+
+fn owned(anns: &Annotations) -> Vec<Annotation<'static>> {
+    anns.iter().map(|a| a.clone().into_owned()).collect()
+}
+
+/// Annotations of `{ RENAME @x }` used in `ASSERT_SOME` and similar.
+fn rename_anns(m: &Micheline) -> Vec<Annotation<'static>> {
+    match m {
+        Micheline::Seq([Micheline::App(Prim::RENAME, [], anns)]) => owned(anns),
+        _ => vec![],
+    }
+}
+
+/// Macros corresponding to a comparison primitive, in order: `CMPxx`,
+/// `IFCMPxx`, `IFxx`, `ASSERT_xx` and `ASSERT_CMPxx`.
+fn comparison_macros(prim: Prim) -> Option<[Macro; 5]> {
+    use Macro::*;
+    Some(match prim {
+        Prim::EQ => [CMPEQ, IFCMPEQ, IFEQ, ASSERT_EQ, ASSERT_CMPEQ],
+        Prim::NEQ => [CMPNEQ, IFCMPNEQ, IFNEQ, ASSERT_NEQ, ASSERT_CMPNEQ],
+        Prim::LT => [CMPLT, IFCMPLT, IFLT, ASSERT_LT, ASSERT_CMPLT],
+        Prim::GT => [CMPGT, IFCMPGT, IFGT, ASSERT_GT, ASSERT_CMPGT],
+        Prim::LE => [CMPLE, IFCMPLE, IFLE, ASSERT_LE, ASSERT_CMPLE],
+        Prim::GE => [CMPGE, IFCMPGE, IFGE, ASSERT_GE, ASSERT_CMPGE],
+        _ => return None,
+    })
+}
+
+fn candidates<'a>(items: &'a [Micheline<'a>]) -> Vec<Candidate<'a>> {
+    use Candidate as C;
+    use Macro::*;
+    use Micheline as M;
+    let mut res = vec![];
+    match items {
+        [M::App(Prim::IF, _, _)] => res.push(C::Macro(ASSERT, vec![], vec![])),
+        [M::App(Prim::IF_NONE, [l, r], anns)] => {
+            res.push(C::Macro(ASSERT_NONE, vec![], vec![]));
+            res.push(C::Macro(ASSERT_SOME, rename_anns(r), vec![]));
+            res.push(C::Macro(IF_SOME, owned(anns), vec![r.clone(), l.clone()]));
+        }
+        [M::App(Prim::IF_LEFT, [l, r], anns)] => {
+            res.push(C::Macro(ASSERT_LEFT, rename_anns(l), vec![]));
+            res.push(C::Macro(ASSERT_RIGHT, rename_anns(r), vec![]));
+            res.push(C::Macro(IF_RIGHT, owned(anns), vec![r.clone(), l.clone()]));
+        }
+        [M::App(Prim::GET, [M::Int(n)], anns)] => {
+            let prim = if n.bit(0) { Prim::CAR } else { Prim::CDR };
+            res.push(C::Prim(prim, owned(anns), M::Int(n >> 1)));
+        }
+        [M::App(Prim::UNIT, [], _), M::App(Prim::FAILWITH, [], _)] => {
+            res.push(C::Macro(FAIL, vec![], vec![]))
+        }
+        [M::App(op, [], _), M::App(Prim::IF, args, anns)] => {
+            if let Some([_, _, ifxx, assert_xx, _]) = comparison_macros(*op) {
+                res.push(C::Macro(assert_xx, vec![], vec![]));
+                res.push(C::Macro(ifxx, owned(anns), args.to_vec()));
+            }
+        }
+        [M::Seq([_, M::App(op, [], _)]), M::App(Prim::IF, _, _)] => {
+            if let Some([.., assert_cmpxx]) = comparison_macros(*op) {
+                res.push(C::Macro(assert_cmpxx, vec![], vec![]));
+            }
+        }
+        [M::App(Prim::COMPARE, [], _), M::App(op, [], anns)] => {
+            if let Some([cmpxx, ..]) = comparison_macros(*op) {
+                res.push(C::Macro(cmpxx, owned(anns), vec![]));
+            }
+        }
+        [M::App(Prim::COMPARE, [], _), M::App(op, [], _), M::App(Prim::IF, args, anns)] => {
+            if let Some([_, ifcmpxx, ..]) = comparison_macros(*op) {
+                res.push(C::Macro(ifcmpxx, owned(anns), args.to_vec()));
+            }
+        }
+        _ => {}
+    }
+    if let Some(c) = cadr_candidate(items) {
+        res.push(c);
+    }
+    res.extend(set_map_cadr_candidates(items));
+    if let Some(c) = papair_candidate(items) {
+        res.push(c);
+    }
+    if let Some(c) = unpapair_candidate(items) {
+        res.push(c);
+    }
+    res
+}
+
+fn cadr_candidate<'a>(items: &'a [Micheline<'a>]) -> Option<Candidate<'a>> {
+    if items.len() < 2 {
+        return None;
+    }
+    let steps = items
+        .iter()
+        .map(|item| match item {
+            Micheline::App(Prim::CAR, [], _) => Some(CadrStep::A),
+            Micheline::App(Prim::CDR, [], _) => Some(CadrStep::D),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let Some(Micheline::App(_, _, anns)) = items.last() else {
+        return None;
+    };
+    Some(Candidate::Macro(Macro::CADR(steps), owned(anns), vec![]))
+}
+
+/// Peel the outer `DUP ; DIP { CAR/CDR ; ... } ; ...` layers of `SET_C[AD]+R`
+/// and `MAP_C[AD]+R` expansions. Returns the steps, the non-field annotations
+/// of the outermost `PAIR` and the innermost sequence.
+#[allow(clippy::type_complexity)]
+fn peel_cadr_layers<'a>(
+    mut items: &'a [Micheline<'a>],
+) -> (Vec<CadrStep>, Vec<Annotation<'static>>, &'a [Micheline<'a>]) {
+    use Micheline as M;
+    let mut steps = vec![];
+    let mut anns = None;
+    while let [M::App(Prim::DUP, [], _), M::App(Prim::DIP, [M::Seq([M::App(prim, [], _), M::Seq(sub)])], _), .., M::App(Prim::PAIR, [], pair_anns)] =
+        items
+    {
+        steps.push(match prim {
+            Prim::CAR => CadrStep::A,
+            Prim::CDR => CadrStep::D,
+            _ => break,
+        });
+        anns.get_or_insert_with(|| owned(pair_anns).into_iter().skip(2).collect());
+        items = sub;
+    }
+    (steps, anns.unwrap_or_default(), items)
+}
+
+fn set_map_cadr_candidates<'a>(items: &'a [Micheline<'a>]) -> Vec<Candidate<'a>> {
+    use Micheline as M;
+    let (steps, rest_anns, inner) = peel_cadr_layers(items);
+    let with_field = |field: &Annotations| {
+        let mut anns = owned(field);
+        anns.extend(rest_anns.iter().cloned());
+        anns
+    };
+    let mut res = vec![];
+    let mut push = |step, field: Option<&Annotations>, mac: fn(Vec<CadrStep>) -> Macro, args| {
+        let mut steps = steps.clone();
+        steps.push(step);
+        let field_anns = field.map_or_else(|| rest_anns.clone(), with_field);
+        res.push(Candidate::Macro(mac(steps), field_anns, args));
+    };
+    match inner {
+        // SET_C[AD]+R, with or without the access check
+        [.., M::App(Prim::SWAP, [], _), M::App(Prim::PAIR, [], pair_anns)] => {
+            let field = first_ann(pair_anns);
+            push(CadrStep::A, None, Macro::SET_CADR, vec![]);
+            push(CadrStep::A, Some(&field), Macro::SET_CADR, vec![]);
+        }
+        [.., M::App(Prim::CAR, [], _), M::App(Prim::PAIR, [], pair_anns)] => {
+            let field = second_ann(pair_anns);
+            push(CadrStep::D, None, Macro::SET_CADR, vec![]);
+            push(CadrStep::D, Some(&field), Macro::SET_CADR, vec![]);
+        }
+        _ => {}
+    }
+    match inner {
+        [M::App(Prim::DUP, [], _), M::App(Prim::CDR, [], _), M::App(Prim::DIP, [M::Seq([_, body])], _), M::App(Prim::SWAP, [], _), M::App(Prim::PAIR, [], pair_anns)] =>
+        {
+            let field = first_ann(pair_anns);
+            push(CadrStep::A, None, Macro::MAP_CADR, vec![body.clone()]);
+            push(
+                CadrStep::A,
+                Some(&field),
+                Macro::MAP_CADR,
+                vec![body.clone()],
+            );
+        }
+        [M::App(Prim::DUP, [], _), M::App(Prim::CDR, [], _), body, M::App(Prim::SWAP, [], _), M::App(Prim::CAR, [], _), M::App(Prim::PAIR, [], pair_anns)] =>
+        {
+            let field = second_ann(pair_anns);
+            push(CadrStep::D, None, Macro::MAP_CADR, vec![body.clone()]);
+            push(
+                CadrStep::D,
+                Some(&field),
+                Macro::MAP_CADR,
+                vec![body.clone()],
+            );
+        }
+        _ => {}
+    }
+    res
+}
+
+fn first_ann(anns: &Annotations) -> Annotations<'static> {
+    owned(anns).into_iter().take(1).collect()
+}
+
+fn second_ann(anns: &Annotations) -> Annotations<'static> {
+    owned(anns).into_iter().skip(1).take(1).collect()
+}
+
+/// Collect stack depths of `DIP n { instr }` items, where `instr` is
+/// `expected`. Depth 0 means `instr` is used without `DIP`.
+#[allow(clippy::type_complexity)]
+fn dip_depths<'a>(
+    items: &'a [Micheline<'a>],
+    expected: Prim,
+) -> Option<Vec<(u32, &'a Annotations<'a>)>> {
+    use Micheline as M;
+    items
+        .iter()
+        .map(|item| {
+            let (depth, instr) = match item {
+                M::App(Prim::DIP, [M::Seq([instr])], _) => (1, instr),
+                M::App(Prim::DIP, [M::Int(n), M::Seq([instr])], _) => (n.try_into().ok()?, instr),
+                instr => (0, instr),
+            };
+            match instr {
+                M::App(prim, [], anns) if *prim == expected => Some((depth, anns)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reconstruct the structure of a `P[PAI]+R` macro from the stack depths of
+/// its pair nodes, listed in pre-order.
+fn pair_struct(depths: &[u32]) -> Option<PairStruct> {
+    fn go(depths: &[u32], pos: &mut usize, depth: &mut u32) -> PairStruct {
+        if depths.get(*pos) == Some(depth) {
+            *pos += 1;
+            let l = go(depths, pos, depth);
+            let r = go(depths, pos, depth);
+            PairStruct::Pair(Box::new(l), Box::new(r))
+        } else {
+            *depth += 1;
+            PairStruct::Leaf
+        }
+    }
+    let mut pos = 0;
+    let res = go(depths, &mut pos, &mut 0);
+    (pos == depths.len() && depths.len() > 1).then_some(res)
+}
+
+fn papair_candidate<'a>(items: &'a [Micheline<'a>]) -> Option<Candidate<'a>> {
+    let mut nodes = dip_depths(items, Prim::PAIR)?;
+    nodes.reverse();
+    let ast = pair_struct(&nodes.iter().map(|(d, _)| *d).collect::<Vec<_>>())?;
+    // Field annotations are assigned to leaves in pre-order, see
+    // `distribute_pair_anns` in the parser.
+    fn collect(
+        ast: &PairStruct,
+        nodes: &[(u32, &Annotations)],
+        idx: &mut usize,
+        out: &mut Vec<Annotation<'static>>,
+    ) {
+        let PairStruct::Pair(l, r) = ast else { return };
+        let fields: Vec<_> = owned(nodes[*idx].1)
+            .into_iter()
+            .filter(|a| matches!(a, Annotation::Field(_)))
+            .collect();
+        *idx += 1;
+        for (i, sub) in [l, r].into_iter().enumerate() {
+            match sub.as_ref() {
+                PairStruct::Leaf => out.extend(fields.get(i).cloned()),
+                sub => collect(sub, nodes, idx, out),
+            }
+        }
+    }
+    let mut anns = vec![];
+    collect(&ast, &nodes, &mut 0, &mut anns);
+    anns.extend(
+        owned(nodes[0].1)
+            .into_iter()
+            .filter(|a| !matches!(a, Annotation::Field(_))),
+    );
+    Some(Candidate::Macro(Macro::PAPAIR(ast), anns, vec![]))
+}
+
+fn unpapair_candidate<'a>(items: &'a [Micheline<'a>]) -> Option<Candidate<'a>> {
+    let nodes = dip_depths(items, Prim::UNPAIR)?;
+    let ast = pair_struct(&nodes.iter().map(|(d, _)| *d).collect::<Vec<_>>())?;
+    Some(Candidate::Macro(Macro::UNPAPAIR(ast), vec![], vec![]))
+}
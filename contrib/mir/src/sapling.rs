@@ -0,0 +1,934 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Sapling shielded transactions, backing the `sapling_transaction` and
+//! `sapling_state` types and the `SAPLING_EMPTY_STATE` and
+//! `SAPLING_VERIFY_UPDATE` instructions.
+//!
+//! This follows the reference implementation in `src/lib_sapling` and
+//! `sapling_validator.ml` in the Octez repository. Everything Tezos-specific,
+//! i.e. the transaction encoding, the signature hashes, the nullifier and
+//! root checks, and the bookkeeping of the state, is done here. The
+//! zero-knowledge proofs, the signatures and the Pedersen hashes of the
+//! commitment tree are delegated to a [SaplingVerifier], whose methods map
+//! one-to-one to the verification API of `librustzcash` (vendored in
+//! `src/rust_deps/librustzcash`). With the `zcash` feature, [zcash] provides
+//! an implementation on top of the crates `librustzcash` is built on. Without
+//! it, and unless a verifier is given in [crate::context::Ctx::sapling_verifier],
+//! `SAPLING_VERIFY_UPDATE` is rejected by the typechecker.
+//!
+//! Sapling states live in the lazy storage, much like `big_map`s, see
+//! [SaplingStorage].
+
+use cryptoxide::{blake2b::Blake2b, digest::Digest};
+use num_bigint::BigInt;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Display,
+    mem,
+};
+
+use crate::ast::big_map::LazyStorageError;
+
+#[cfg(feature = "zcash")]
+pub mod zcash;
+
+/// Length of hashes, commitments, nullifiers and value commitments.
+pub const HASH_LENGTH: usize = 32;
+
+/// Length of a Groth16 proof, used for both spend and output proofs.
+pub const PROOF_LENGTH: usize = 48 + 96 + 48;
+
+/// Length of spend authorization and binding signatures.
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// Length of the `crypto_box` nonces of a ciphertext.
+pub const NONCE_LENGTH: usize = 24;
+
+/// Length of the encrypted `payload_out` of a ciphertext: ephemeral secret
+/// key, ephemeral public key and the authentication tag.
+pub const PAYLOAD_OUT_LENGTH: usize = 32 + 32 + 16;
+
+/// Size of the encrypted `payload_enc` of a ciphertext besides the memo:
+/// diversifier, amount, `rcm`, authentication tag and the length prefix of
+/// the memo.
+const PAYLOAD_ENC_OVERHEAD: usize = 11 + 8 + 32 + 16 + 4;
+
+/// Maximal number of inputs in a transaction.
+pub const MAX_INPUTS: usize = 5208;
+
+/// Maximal number of outputs in a transaction.
+pub const MAX_OUTPUTS: usize = 2019;
+
+/// Height of the commitment tree.
+pub const TREE_HEIGHT: u8 = 32;
+
+/// Number of past roots of the commitment tree a transaction can be anchored
+/// to.
+pub const ROOTS_SIZE: usize = 120;
+
+/// Value of an empty leaf of the commitment tree.
+pub const UNCOMMITTED: [u8; HASH_LENGTH] = {
+    let mut x = [0; HASH_LENGTH];
+    x[0] = 1;
+    x
+};
+
+/// Root of the empty commitment tree.
+pub const EMPTY_ROOT: [u8; HASH_LENGTH] = [
+    0xfb, 0xc2, 0xf4, 0x30, 0x0c, 0x01, 0xf0, 0xb7, 0x82, 0x0d, 0x00, 0xe3, 0x34, 0x7c, 0x8d, 0xa4,
+    0xee, 0x61, 0x46, 0x74, 0x37, 0x6c, 0xbc, 0x45, 0x35, 0x9d, 0xaa, 0x54, 0xf9, 0xb5, 0x49, 0x3e,
+];
+
+/// A spend description, i.e. an input of a [Transaction].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    /// Value commitment.
+    pub cv: [u8; HASH_LENGTH],
+    /// Nullifier of the spent note.
+    pub nf: [u8; HASH_LENGTH],
+    /// Randomized public key.
+    pub rk: [u8; HASH_LENGTH],
+    /// Spend proof.
+    pub proof: [u8; PROOF_LENGTH],
+    /// Spend authorization signature.
+    pub signature: [u8; SIGNATURE_LENGTH],
+}
+
+/// Encrypted note of an [Output].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ciphertext {
+    /// Value commitment.
+    pub cv: [u8; HASH_LENGTH],
+    /// Ephemeral public key.
+    pub epk: [u8; HASH_LENGTH],
+    /// Encrypted note, including the memo.
+    pub payload_enc: Vec<u8>,
+    /// Nonce of `payload_enc`.
+    pub nonce_enc: [u8; NONCE_LENGTH],
+    /// Encrypted data allowing the sender to recover the note.
+    pub payload_out: [u8; PAYLOAD_OUT_LENGTH],
+    /// Nonce of `payload_out`.
+    pub nonce_out: [u8; NONCE_LENGTH],
+}
+
+impl Ciphertext {
+    /// Size of the memo in the encrypted note, or [None] if the payload has an
+    /// impossible length.
+    pub fn memo_size(&self) -> Option<u16> {
+        self.payload_enc
+            .len()
+            .checked_sub(PAYLOAD_ENC_OVERHEAD)
+            .and_then(|x| x.try_into().ok())
+    }
+}
+
+/// An output description, i.e. an output of a [Transaction].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// Note commitment.
+    pub cm: [u8; HASH_LENGTH],
+    /// Output proof.
+    pub proof: [u8; PROOF_LENGTH],
+    /// Encrypted note.
+    pub ciphertext: Ciphertext,
+}
+
+/// A Sapling transaction, i.e. a value of type `sapling_transaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    inputs: Vec<Input>,
+    outputs: Vec<Output>,
+    binding_sig: [u8; SIGNATURE_LENGTH],
+    balance: i64,
+    root: [u8; HASH_LENGTH],
+    bound_data: Vec<u8>,
+}
+
+impl Transaction {
+    /// Construct a new transaction. Returns [None] if there are too many inputs
+    /// or outputs, or if the outputs have invalid or differing memo sizes.
+    pub fn new(
+        inputs: Vec<Input>,
+        outputs: Vec<Output>,
+        binding_sig: [u8; SIGNATURE_LENGTH],
+        balance: i64,
+        root: [u8; HASH_LENGTH],
+        bound_data: Vec<u8>,
+    ) -> Option<Self> {
+        if inputs.len() > MAX_INPUTS || outputs.len() > MAX_OUTPUTS {
+            return None;
+        }
+        if let Some(first) = outputs.first() {
+            let memo_size = first.ciphertext.memo_size()?;
+            if outputs
+                .iter()
+                .any(|o| o.ciphertext.memo_size() != Some(memo_size))
+            {
+                return None;
+            }
+        }
+        Some(Transaction {
+            inputs,
+            outputs,
+            binding_sig,
+            balance,
+            root,
+            bound_data,
+        })
+    }
+
+    /// Spend descriptions.
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs
+    }
+
+    /// Output descriptions.
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    /// Binding signature.
+    pub fn binding_sig(&self) -> &[u8; SIGNATURE_LENGTH] {
+        &self.binding_sig
+    }
+
+    /// Difference between the value of the inputs and the outputs, i.e. the
+    /// amount leaving the shielded pool.
+    pub fn balance(&self) -> i64 {
+        self.balance
+    }
+
+    /// Root of the commitment tree the inputs are anchored to.
+    pub fn root(&self) -> &[u8; HASH_LENGTH] {
+        &self.root
+    }
+
+    /// Arbitrary data bound to the transaction by the binding signature.
+    pub fn bound_data(&self) -> &[u8] {
+        &self.bound_data
+    }
+
+    /// Memo size of the outputs, or [None] if the transaction has no outputs.
+    pub fn memo_size(&self) -> Option<u16> {
+        self.outputs.first().and_then(|o| o.ciphertext.memo_size())
+    }
+
+    /// Hash signed by the spend authorization signature of the given input.
+    pub fn input_sighash(input: &Input, anti_replay: &[u8]) -> [u8; HASH_LENGTH] {
+        let mut hasher = Blake2b::new_keyed(HASH_LENGTH, anti_replay);
+        hasher.input(&input.cv);
+        hasher.input(&input.nf);
+        hasher.input(&input.rk);
+        hasher.input(&input.proof);
+        let mut out = [0; HASH_LENGTH];
+        hasher.result(&mut out);
+        out
+    }
+
+    /// Hash signed by the binding signature.
+    pub fn sighash(&self, anti_replay: &[u8]) -> [u8; HASH_LENGTH] {
+        let mut hasher = Blake2b::new_keyed(HASH_LENGTH, anti_replay);
+        for input in &self.inputs {
+            hasher.input(&input.encode());
+        }
+        for output in &self.outputs {
+            hasher.input(&output.encode());
+        }
+        hasher.input(&self.bound_data);
+        let mut out = [0; HASH_LENGTH];
+        hasher.result(&mut out);
+        out
+    }
+}
+
+/// Errors that can happen when verifying Sapling transactions.
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum SaplingError {
+    /// No Sapling verifier is configured, see [crate::context::Ctx::sapling_verifier].
+    #[error("sapling verification is not available")]
+    VerifierUnavailable,
+    /// The verifier failed for a reason other than the transaction being
+    /// invalid.
+    #[error("sapling verifier error: {0}")]
+    VerifierError(String),
+    /// An error occurred when working with the sapling state storage.
+    #[error("lazy storage error: {0}")]
+    LazyStorageError(#[from] LazyStorageError),
+}
+
+/// Cryptographic primitives needed to verify Sapling transactions.
+///
+/// These correspond to the `librustzcash_sapling_*` verification functions
+/// and `librustzcash_merkle_hash` of `librustzcash`.
+pub trait SaplingVerifier {
+    /// Check the zero-knowledge proofs and the signatures of a transaction.
+    /// Must return `Ok(false)` for an invalid transaction.
+    ///
+    /// This amounts to creating a verification context, running
+    /// `check_output` on every output, `check_spend` on every input, anchored
+    /// at [Transaction::root] and with the respective `input_sighashes`, and
+    /// finally `final_check` with [Transaction::balance],
+    /// [Transaction::binding_sig] and `sighash`.
+    fn verify(
+        &self,
+        transaction: &Transaction,
+        input_sighashes: &[[u8; HASH_LENGTH]],
+        sighash: &[u8; HASH_LENGTH],
+    ) -> Result<bool, SaplingError>;
+
+    /// Pedersen hash of two nodes of the commitment tree at the given height,
+    /// leaves being at height `0`.
+    fn merkle_hash(
+        &self,
+        height: u8,
+        lhs: &[u8; HASH_LENGTH],
+        rhs: &[u8; HASH_LENGTH],
+    ) -> Result<[u8; HASH_LENGTH], SaplingError>;
+
+    /// Whether the verifier is backed by actual cryptographic primitives.
+    /// If not, `SAPLING_VERIFY_UPDATE` is rejected by the typechecker.
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// The default [SaplingVerifier], used when no cryptographic backend is
+/// available. Fails on every call with [SaplingError::VerifierUnavailable],
+/// and makes the typechecker reject `SAPLING_VERIFY_UPDATE`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnavailableSaplingVerifier;
+
+impl SaplingVerifier for UnavailableSaplingVerifier {
+    fn verify(
+        &self,
+        _transaction: &Transaction,
+        _input_sighashes: &[[u8; HASH_LENGTH]],
+        _sighash: &[u8; HASH_LENGTH],
+    ) -> Result<bool, SaplingError> {
+        Err(SaplingError::VerifierUnavailable)
+    }
+
+    fn merkle_hash(
+        &self,
+        _height: u8,
+        _lhs: &[u8; HASH_LENGTH],
+        _rhs: &[u8; HASH_LENGTH],
+    ) -> Result<[u8; HASH_LENGTH], SaplingError> {
+        Err(SaplingError::VerifierUnavailable)
+    }
+
+    fn is_available(&self) -> bool {
+        false
+    }
+}
+
+/// A [SaplingVerifier] that failed to be set up, failing on every call with
+/// the reason why.
+#[cfg(feature = "zcash")]
+#[derive(Debug, Clone)]
+struct FailedSaplingVerifier(SaplingError);
+
+#[cfg(feature = "zcash")]
+impl SaplingVerifier for FailedSaplingVerifier {
+    fn verify(
+        &self,
+        _transaction: &Transaction,
+        _input_sighashes: &[[u8; HASH_LENGTH]],
+        _sighash: &[u8; HASH_LENGTH],
+    ) -> Result<bool, SaplingError> {
+        Err(self.0.clone())
+    }
+
+    fn merkle_hash(
+        &self,
+        _height: u8,
+        _lhs: &[u8; HASH_LENGTH],
+        _rhs: &[u8; HASH_LENGTH],
+    ) -> Result<[u8; HASH_LENGTH], SaplingError> {
+        Err(self.0.clone())
+    }
+}
+
+/// The [SaplingVerifier] used by default, see
+/// [crate::context::Ctx::sapling_verifier].
+pub(crate) fn default_verifier<'a>() -> Box<dyn SaplingVerifier + 'a> {
+    #[cfg(feature = "zcash")]
+    {
+        match zcash::ZcashSaplingVerifier::from_installed_params() {
+            Ok(verifier) => Box::new(verifier),
+            Err(err) => Box::new(FailedSaplingVerifier(err)),
+        }
+    }
+    #[cfg(not(feature = "zcash"))]
+    {
+        Box::new(UnavailableSaplingVerifier)
+    }
+}
+
+/// Id of a sapling state in the lazy storage.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SaplingStateId(pub BigInt);
+
+impl Display for SaplingStateId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Changes to a sapling state that are not yet applied to the lazy storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaplingDiff {
+    /// New commitments along with their ciphertexts, in insertion order.
+    pub commitments_and_ciphertexts: Vec<([u8; HASH_LENGTH], Ciphertext)>,
+    /// New nullifiers, in insertion order.
+    pub nullifiers: Vec<[u8; HASH_LENGTH]>,
+}
+
+impl SaplingDiff {
+    /// Whether the diff carries no changes.
+    pub fn is_empty(&self) -> bool {
+        self.commitments_and_ciphertexts.is_empty() && self.nullifiers.is_empty()
+    }
+}
+
+/// Represents a `sapling_state` value.
+///
+/// Like [crate::ast::BigMap], it is split into the part in the lazy storage
+/// and an in-memory diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaplingState {
+    /// Id of the state in the lazy storage, or [None] for a state created
+    /// during the execution.
+    pub id: Option<SaplingStateId>,
+    /// Changes not yet applied to the lazy storage.
+    pub diff: SaplingDiff,
+    /// Memo size of the notes in the state.
+    pub memo_size: u16,
+}
+
+impl SaplingState {
+    /// Michelson's `SAPLING_EMPTY_STATE`.
+    pub fn empty(memo_size: u16) -> Self {
+        SaplingState {
+            id: None,
+            diff: SaplingDiff::default(),
+            memo_size,
+        }
+    }
+
+    fn root_mem(
+        &self,
+        root: &[u8; HASH_LENGTH],
+        storage: &(impl SaplingStorage + ?Sized),
+    ) -> Result<bool, LazyStorageError> {
+        // NB: roots are only updated when the diff is applied, so the roots
+        // introduced by the diff can't be used yet.
+        match &self.id {
+            Some(id) => storage.sapling_root_mem(id, root),
+            None => Ok(root == &EMPTY_ROOT),
+        }
+    }
+
+    fn nullifier_mem(
+        &self,
+        nf: &[u8; HASH_LENGTH],
+        storage: &(impl SaplingStorage + ?Sized),
+    ) -> Result<bool, LazyStorageError> {
+        // to avoid a double spend we need to check both the diff and the
+        // storage
+        Ok(self.diff.nullifiers.contains(nf)
+            || match &self.id {
+                Some(id) => storage.sapling_nullifier_mem(id, nf)?,
+                None => false,
+            })
+    }
+
+    /// Michelson's `SAPLING_VERIFY_UPDATE`. Returns [None] if the transaction
+    /// is invalid for this state, otherwise the transaction balance and the
+    /// updated state.
+    ///
+    /// `anti_replay` is the string the signatures are keyed with, i.e. the
+    /// address of the contract followed by the chain id, both in base58-check.
+    pub fn verify_update(
+        mut self,
+        transaction: &Transaction,
+        anti_replay: &[u8],
+        storage: &(impl SaplingStorage + ?Sized),
+        verifier: &(impl SaplingVerifier + ?Sized),
+    ) -> Result<Option<(i64, SaplingState)>, SaplingError> {
+        if transaction
+            .outputs
+            .iter()
+            .any(|o| o.ciphertext.memo_size() != Some(self.memo_size))
+        {
+            return Ok(None);
+        }
+        if !self.root_mem(&transaction.root, storage)? {
+            return Ok(None);
+        }
+        // NB: nullifiers are added one by one, so that the same input can't
+        // be spent twice in a single transaction.
+        for input in &transaction.inputs {
+            if self.nullifier_mem(&input.nf, storage)? {
+                return Ok(None);
+            }
+            self.diff.nullifiers.push(input.nf);
+        }
+        let input_sighashes: Vec<_> = transaction
+            .inputs
+            .iter()
+            .map(|i| Transaction::input_sighash(i, anti_replay))
+            .collect();
+        let sighash = transaction.sighash(anti_replay);
+        if !verifier.verify(transaction, &input_sighashes, &sighash)? {
+            return Ok(None);
+        }
+        self.diff.commitments_and_ciphertexts.extend(
+            transaction
+                .outputs
+                .iter()
+                .map(|o| (o.cm, o.ciphertext.clone())),
+        );
+        Ok(Some((transaction.balance, self)))
+    }
+}
+
+/// All the operations for working with sapling states in the lazy storage.
+///
+/// This is the counterpart of [crate::ast::big_map::LazyStorage] for sapling
+/// states.
+pub trait SaplingStorage {
+    /// Get the memo size of the given sapling state.
+    ///
+    /// This returns None if the state with such ID is not present in the
+    /// storage.
+    fn sapling_get_memo_size(&self, id: &SaplingStateId) -> Result<Option<u16>, LazyStorageError>;
+
+    /// Check whether the given root is among the [ROOTS_SIZE] most recent
+    /// roots of the commitment tree of the given state.
+    ///
+    /// The specified id must point to a valid state in the lazy storage.
+    fn sapling_root_mem(
+        &self,
+        id: &SaplingStateId,
+        root: &[u8; HASH_LENGTH],
+    ) -> Result<bool, LazyStorageError>;
+
+    /// Check whether the given nullifier was already spent in the given state.
+    ///
+    /// The specified id must point to a valid state in the lazy storage.
+    fn sapling_nullifier_mem(
+        &self,
+        id: &SaplingStateId,
+        nf: &[u8; HASH_LENGTH],
+    ) -> Result<bool, LazyStorageError>;
+
+    /// Allocate a new empty sapling state.
+    fn sapling_new(&mut self, memo_size: u16) -> Result<SaplingStateId, LazyStorageError>;
+
+    /// Allocate a new sapling state, filling it with the contents of another
+    /// state in the lazy storage.
+    ///
+    /// The specified id must point to a valid state in the lazy storage.
+    fn sapling_copy(&mut self, id: &SaplingStateId) -> Result<SaplingStateId, LazyStorageError>;
+
+    /// Apply a diff to the given state: add the nullifiers and the
+    /// commitments, and record the new root of the commitment tree, computed
+    /// with `verifier`.
+    ///
+    /// The specified id must point to a valid state in the lazy storage.
+    fn sapling_apply_diff(
+        &mut self,
+        id: &SaplingStateId,
+        diff: SaplingDiff,
+        verifier: &dyn SaplingVerifier,
+    ) -> Result<(), SaplingError>;
+}
+
+/// A sapling state with metadata, used in [InMemorySaplingStorage].
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct StateInfo {
+    memo_size: u16,
+    commitments: Vec<[u8; HASH_LENGTH]>,
+    ciphertexts: Vec<Ciphertext>,
+    nullifiers: BTreeSet<[u8; HASH_LENGTH]>,
+    roots: VecDeque<[u8; HASH_LENGTH]>,
+}
+
+/// Simple implementation for [SaplingStorage].
+///
+/// Unlike the protocol, which keeps one root per block level, every
+/// application of a non-empty diff records a new root.
+#[derive(Clone, Debug)]
+pub struct InMemorySaplingStorage {
+    next_id: BigInt,
+    states: BTreeMap<SaplingStateId, StateInfo>,
+}
+
+impl InMemorySaplingStorage {
+    /// Construct a new, empty, in-memory storage.
+    pub fn new() -> Self {
+        InMemorySaplingStorage {
+            next_id: 0.into(),
+            states: BTreeMap::new(),
+        }
+    }
+
+    fn get_next_id(&mut self) -> SaplingStateId {
+        let id = SaplingStateId(self.next_id.clone());
+        self.next_id += 1;
+        id
+    }
+
+    fn access_state(&self, id: &SaplingStateId) -> Result<&StateInfo, LazyStorageError> {
+        self.states
+            .get(id)
+            .ok_or_else(|| panic!("Non-existent sapling state by id {id}"))
+    }
+
+    fn access_state_mut(
+        &mut self,
+        id: &SaplingStateId,
+    ) -> Result<&mut StateInfo, LazyStorageError> {
+        self.states
+            .get_mut(id)
+            .ok_or_else(|| panic!("Non-existent sapling state by id {id}"))
+    }
+}
+
+impl Default for InMemorySaplingStorage {
+    fn default() -> Self {
+        InMemorySaplingStorage::new()
+    }
+}
+
+/// Compute the root of the commitment tree with the given leaves, the rest of
+/// the tree being filled with [UNCOMMITTED].
+fn merkle_root(
+    leaves: &[[u8; HASH_LENGTH]],
+    verifier: &dyn SaplingVerifier,
+) -> Result<[u8; HASH_LENGTH], SaplingError> {
+    let mut level = leaves.to_vec();
+    let mut uncommitted = UNCOMMITTED;
+    for height in 0..TREE_HEIGHT {
+        level = level
+            .chunks(2)
+            .map(|pair| verifier.merkle_hash(height, &pair[0], pair.get(1).unwrap_or(&uncommitted)))
+            .collect::<Result<_, _>>()?;
+        uncommitted = verifier.merkle_hash(height, &uncommitted, &uncommitted)?;
+    }
+    Ok(level.first().copied().unwrap_or(uncommitted))
+}
+
+impl SaplingStorage for InMemorySaplingStorage {
+    fn sapling_get_memo_size(&self, id: &SaplingStateId) -> Result<Option<u16>, LazyStorageError> {
+        Ok(self.states.get(id).map(|info| info.memo_size))
+    }
+
+    fn sapling_root_mem(
+        &self,
+        id: &SaplingStateId,
+        root: &[u8; HASH_LENGTH],
+    ) -> Result<bool, LazyStorageError> {
+        Ok(self.access_state(id)?.roots.contains(root))
+    }
+
+    fn sapling_nullifier_mem(
+        &self,
+        id: &SaplingStateId,
+        nf: &[u8; HASH_LENGTH],
+    ) -> Result<bool, LazyStorageError> {
+        Ok(self.access_state(id)?.nullifiers.contains(nf))
+    }
+
+    fn sapling_new(&mut self, memo_size: u16) -> Result<SaplingStateId, LazyStorageError> {
+        let id = self.get_next_id();
+        self.states.insert(
+            id.clone(),
+            StateInfo {
+                memo_size,
+                commitments: Vec::new(),
+                ciphertexts: Vec::new(),
+                nullifiers: BTreeSet::new(),
+                roots: VecDeque::from([EMPTY_ROOT]),
+            },
+        );
+        Ok(id)
+    }
+
+    fn sapling_copy(
+        &mut self,
+        copied_id: &SaplingStateId,
+    ) -> Result<SaplingStateId, LazyStorageError> {
+        let id = self.get_next_id();
+        let info = self.access_state(copied_id)?.clone();
+        self.states.insert(id.clone(), info);
+        Ok(id)
+    }
+
+    fn sapling_apply_diff(
+        &mut self,
+        id: &SaplingStateId,
+        diff: SaplingDiff,
+        verifier: &dyn SaplingVerifier,
+    ) -> Result<(), SaplingError> {
+        let info = self.access_state_mut(id)?;
+        info.nullifiers.extend(diff.nullifiers);
+        if diff.commitments_and_ciphertexts.is_empty() {
+            // avoids adding duplicates to the roots
+            return Ok(());
+        }
+        for (cm, ciphertext) in diff.commitments_and_ciphertexts {
+            info.commitments.push(cm);
+            info.ciphertexts.push(ciphertext);
+        }
+        let root = merkle_root(&info.commitments, verifier)?;
+        if info.roots.len() == ROOTS_SIZE {
+            info.roots.pop_front();
+        }
+        info.roots.push_back(root);
+        Ok(())
+    }
+}
+
+/// Given sapling state IDs before contract execution and sapling states after
+/// the execution, dump all the updates to the lazy storage. This is the
+/// counterpart of [crate::ast::big_map::dump_big_map_updates].
+///
+/// After the call, [SaplingState::diff] in all provided states is guaranteed
+/// to be empty and all [SaplingState::id]s are guaranteed to be non-None. Some
+/// [SaplingState::id] fields may change to avoid duplications. Unlike big
+/// maps, sapling states are never removed from the storage.
+pub fn dump_sapling_state_updates(
+    storage: &mut (impl SaplingStorage + ?Sized),
+    verifier: &dyn SaplingVerifier,
+    finished_with_states: &mut [&mut SaplingState],
+) -> Result<(), SaplingError> {
+    let mut seen_ids = BTreeSet::new();
+    for state in finished_with_states {
+        let id = match state.id.take() {
+            // The same state in the storage is used by several values, the
+            // first one is updated in-place and the rest are copied.
+            Some(id) => match seen_ids.insert(id.clone()) {
+                true => id,
+                false => storage.sapling_copy(&id)?,
+            },
+            None => storage.sapling_new(state.memo_size)?,
+        };
+        storage.sapling_apply_diff(&id, mem::take(&mut state.diff), verifier)?;
+        state.id = Some(id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A [SaplingVerifier] accepting or rejecting every transaction, with a
+    /// stand-in Blake2b-based merkle hash.
+    pub(crate) struct MockVerifier(pub bool);
+
+    impl SaplingVerifier for MockVerifier {
+        fn verify(
+            &self,
+            _transaction: &Transaction,
+            _input_sighashes: &[[u8; HASH_LENGTH]],
+            _sighash: &[u8; HASH_LENGTH],
+        ) -> Result<bool, SaplingError> {
+            Ok(self.0)
+        }
+
+        fn merkle_hash(
+            &self,
+            height: u8,
+            lhs: &[u8; HASH_LENGTH],
+            rhs: &[u8; HASH_LENGTH],
+        ) -> Result<[u8; HASH_LENGTH], SaplingError> {
+            let mut hasher = Blake2b::new(HASH_LENGTH);
+            hasher.input(&[height]);
+            hasher.input(lhs);
+            hasher.input(rhs);
+            let mut out = [0; HASH_LENGTH];
+            hasher.result(&mut out);
+            Ok(out)
+        }
+    }
+
+    pub(crate) fn input(nf: u8) -> Input {
+        Input {
+            cv: [1; HASH_LENGTH],
+            nf: [nf; HASH_LENGTH],
+            rk: [2; HASH_LENGTH],
+            proof: [3; PROOF_LENGTH],
+            signature: [4; SIGNATURE_LENGTH],
+        }
+    }
+
+    pub(crate) fn output(cm: u8, memo_size: u16) -> Output {
+        Output {
+            cm: [cm; HASH_LENGTH],
+            proof: [5; PROOF_LENGTH],
+            ciphertext: Ciphertext {
+                cv: [6; HASH_LENGTH],
+                epk: [7; HASH_LENGTH],
+                payload_enc: vec![8; PAYLOAD_ENC_OVERHEAD + memo_size as usize],
+                nonce_enc: [9; NONCE_LENGTH],
+                payload_out: [10; PAYLOAD_OUT_LENGTH],
+                nonce_out: [11; NONCE_LENGTH],
+            },
+        }
+    }
+
+    pub(crate) fn transaction(
+        inputs: Vec<Input>,
+        outputs: Vec<Output>,
+        balance: i64,
+        root: [u8; HASH_LENGTH],
+    ) -> Transaction {
+        Transaction::new(
+            inputs,
+            outputs,
+            [12; SIGNATURE_LENGTH],
+            balance,
+            root,
+            b"bound".to_vec(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn empty_root() {
+        let mut root = UNCOMMITTED;
+        // NB: the stand-in hash doesn't give the real empty root, but the
+        // shape of the computation must match that of the protocol
+        let verifier = MockVerifier(true);
+        for height in 0..TREE_HEIGHT {
+            root = verifier.merkle_hash(height, &root, &root).unwrap();
+        }
+        assert_eq!(merkle_root(&[], &verifier), Ok(root));
+        assert_eq!(merkle_root(&[UNCOMMITTED], &verifier), Ok(root));
+    }
+
+    #[test]
+    fn memo_size() {
+        assert_eq!(output(0, 8).ciphertext.memo_size(), Some(8));
+        let mut short = output(0, 0).ciphertext;
+        short.payload_enc.pop();
+        assert_eq!(short.memo_size(), None);
+        assert!(Transaction::new(
+            vec![],
+            vec![output(0, 8), output(1, 16)],
+            [0; 64],
+            0,
+            EMPTY_ROOT,
+            vec![]
+        )
+        .is_none());
+        assert_eq!(transaction(vec![], vec![], 0, EMPTY_ROOT).memo_size(), None);
+    }
+
+    #[test]
+    fn sighashes_depend_on_anti_replay() {
+        let tx = transaction(vec![input(1)], vec![output(1, 8)], 0, EMPTY_ROOT);
+        assert_ne!(tx.sighash(b"a"), tx.sighash(b"b"));
+        assert_ne!(
+            Transaction::input_sighash(&tx.inputs()[0], b"a"),
+            Transaction::input_sighash(&tx.inputs()[0], b"b")
+        );
+    }
+
+    #[test]
+    fn verify_update() {
+        let storage = InMemorySaplingStorage::new();
+        let verifier = MockVerifier(true);
+        let tx = transaction(vec![input(1)], vec![output(1, 8)], -5, EMPTY_ROOT);
+        let (balance, state) = SaplingState::empty(8)
+            .verify_update(&tx, b"key", &storage, &verifier)
+            .unwrap()
+            .unwrap();
+        assert_eq!(balance, -5);
+        assert_eq!(state.diff.nullifiers, vec![[1; HASH_LENGTH]]);
+        assert_eq!(
+            state.diff.commitments_and_ciphertexts,
+            vec![([1; HASH_LENGTH], output(1, 8).ciphertext)]
+        );
+        // double spend
+        assert_eq!(
+            state.verify_update(&tx, b"key", &storage, &verifier),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn verify_update_rejects() {
+        let storage = InMemorySaplingStorage::new();
+        let check = |tx: &Transaction, verifier: &dyn SaplingVerifier| {
+            SaplingState::empty(8).verify_update(tx, b"key", &storage, verifier)
+        };
+        let tx = transaction(vec![], vec![output(1, 16)], 0, EMPTY_ROOT);
+        assert_eq!(check(&tx, &MockVerifier(true)), Ok(None));
+        let tx = transaction(vec![], vec![output(1, 8)], 0, [0; HASH_LENGTH]);
+        assert_eq!(check(&tx, &MockVerifier(true)), Ok(None));
+        let tx = transaction(vec![input(1), input(1)], vec![], 0, EMPTY_ROOT);
+        assert_eq!(check(&tx, &MockVerifier(true)), Ok(None));
+        let tx = transaction(vec![input(1)], vec![output(1, 8)], 0, EMPTY_ROOT);
+        assert_eq!(check(&tx, &MockVerifier(false)), Ok(None));
+        assert_eq!(
+            check(&tx, &UnavailableSaplingVerifier),
+            Err(SaplingError::VerifierUnavailable)
+        );
+    }
+
+    #[test]
+    fn dump_to_storage() {
+        let mut storage = InMemorySaplingStorage::new();
+        let verifier = MockVerifier(true);
+        let tx = transaction(vec![input(1)], vec![output(1, 8)], 0, EMPTY_ROOT);
+        let (_, mut state) = SaplingState::empty(8)
+            .verify_update(&tx, b"key", &storage, &verifier)
+            .unwrap()
+            .unwrap();
+        let mut copy = state.clone();
+        dump_sapling_state_updates(&mut storage, &verifier, &mut [&mut state]).unwrap();
+        let id = state.id.clone().unwrap();
+        assert!(state.diff.is_empty());
+        assert_eq!(storage.sapling_get_memo_size(&id), Ok(Some(8)));
+        assert_eq!(
+            storage.sapling_nullifier_mem(&id, &[1; HASH_LENGTH]),
+            Ok(true)
+        );
+        assert_eq!(storage.sapling_root_mem(&id, &EMPTY_ROOT), Ok(true));
+        let root = merkle_root(&[[1; HASH_LENGTH]], &verifier).unwrap();
+        assert_eq!(storage.sapling_root_mem(&id, &root), Ok(true));
+
+        // the nullifier is now spent in the storage
+        assert_eq!(
+            state
+                .clone()
+                .verify_update(&tx, b"key", &storage, &verifier),
+            Ok(None)
+        );
+
+        // a transaction anchored to the new root is accepted
+        let tx = transaction(vec![input(2)], vec![], 0, root);
+        assert!(state
+            .clone()
+            .verify_update(&tx, b"key", &storage, &verifier)
+            .unwrap()
+            .is_some());
+
+        // duplicate ids are copied
+        copy.id = Some(id.clone());
+        copy.diff = SaplingDiff::default();
+        let mut same = state.clone();
+        dump_sapling_state_updates(&mut storage, &verifier, &mut [&mut same, &mut copy]).unwrap();
+        assert_eq!(same.id, Some(id.clone()));
+        assert_ne!(copy.id, Some(id));
+    }
+}
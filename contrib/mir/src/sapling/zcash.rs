@@ -0,0 +1,436 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! A [SaplingVerifier] backed by `zcash_proofs` and `zcash_primitives`, the
+//! crates `librustzcash` (vendored in `src/rust_deps/librustzcash`) builds its
+//! Sapling verification functions on, in the same versions.
+//!
+//! As in Octez, ZIP 216 is not enforced for signatures.
+//!
+//! Checking the zero-knowledge proofs requires the Groth16 verifying keys of
+//! the Sapling spend and output circuits. These are read from the
+//! `sapling-spend.params` and `sapling-output.params` files, see
+//! [ZcashSaplingVerifier::from_installed_params].
+
+use bellman::groth16::{self, PreparedVerifyingKey, Proof, VerifyingKey};
+use bls12_381::Bls12;
+use group::GroupEncoding;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+use zcash_primitives::sapling::{merkle_hash, redjubjub};
+use zcash_primitives::transaction::components::Amount;
+use zcash_proofs::sapling::SaplingVerificationContext;
+
+use super::{SaplingError, SaplingVerifier, Transaction, HASH_LENGTH};
+
+/// Name of the file holding the parameters of the spend circuit.
+pub const SPEND_PARAMS_FILE: &str = "sapling-spend.params";
+
+/// Name of the file holding the parameters of the output circuit.
+pub const OUTPUT_PARAMS_FILE: &str = "sapling-output.params";
+
+/// Read the Groth16 verifying key at the start of a Sapling parameters
+/// file.
+pub fn read_verifying_key(reader: impl Read) -> io::Result<VerifyingKey<Bls12>> {
+    VerifyingKey::read(reader)
+}
+
+/// A [SaplingVerifier] implementing the Sapling protocol.
+///
+/// A verifier without the spend (resp. output) verifying key can still
+/// compute merkle hashes and verify transactions without inputs (resp.
+/// outputs), but fails with [SaplingError::VerifierError] otherwise.
+#[derive(Clone, Default)]
+pub struct ZcashSaplingVerifier {
+    spend_vk: Option<Arc<PreparedVerifyingKey<Bls12>>>,
+    output_vk: Option<Arc<PreparedVerifyingKey<Bls12>>>,
+}
+
+impl std::fmt::Debug for ZcashSaplingVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZcashSaplingVerifier")
+            .field("spend_vk", &self.spend_vk.is_some())
+            .field("output_vk", &self.output_vk.is_some())
+            .finish()
+    }
+}
+
+impl ZcashSaplingVerifier {
+    /// Construct a verifier from the verifying keys of the spend and output
+    /// circuits.
+    pub fn new(
+        spend_vk: Option<&VerifyingKey<Bls12>>,
+        output_vk: Option<&VerifyingKey<Bls12>>,
+    ) -> Self {
+        let prepare = |vk| Arc::new(groth16::prepare_verifying_key(vk));
+        ZcashSaplingVerifier {
+            spend_vk: spend_vk.map(prepare),
+            output_vk: output_vk.map(prepare),
+        }
+    }
+
+    /// Construct a verifier from the spend and output parameters files.
+    pub fn from_params_files(
+        spend: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let read = |path: &Path| read_verifying_key(BufReader::new(File::open(path)?));
+        Ok(Self::new(
+            Some(&read(spend.as_ref())?),
+            Some(&read(output.as_ref())?),
+        ))
+    }
+
+    /// Construct a verifier from the parameters files installed on the
+    /// system, looked up in the same directories as Octez does, e.g.
+    /// `$HOME/.zcash-params` or `/usr/share/zcash-params`.
+    ///
+    /// The files are only looked up and read once per process: if they can't
+    /// be found or read, the error is kept and returned by every later call.
+    pub fn from_installed_params() -> Result<Self, SaplingError> {
+        static INSTALLED: OnceLock<Result<ZcashSaplingVerifier, SaplingError>> = OnceLock::new();
+        INSTALLED
+            .get_or_init(|| {
+                let dir = find_params_dir().ok_or_else(|| {
+                    SaplingError::VerifierError(format!(
+                        "could not find {SPEND_PARAMS_FILE} and {OUTPUT_PARAMS_FILE}"
+                    ))
+                })?;
+                Self::from_params_files(dir.join(SPEND_PARAMS_FILE), dir.join(OUTPUT_PARAMS_FILE))
+                    .map_err(|err| {
+                        SaplingError::VerifierError(format!(
+                            "could not read the sapling parameters in {}: {err}",
+                            dir.display()
+                        ))
+                    })
+            })
+            .clone()
+    }
+}
+
+fn missing_key(circuit: &str) -> SaplingError {
+    SaplingError::VerifierError(format!("the {circuit} verifying key is not available"))
+}
+
+fn read_point(bytes: &[u8; HASH_LENGTH]) -> Option<jubjub::ExtendedPoint> {
+    Option::from(jubjub::ExtendedPoint::from_bytes(bytes))
+}
+
+fn read_scalar(bytes: &[u8; HASH_LENGTH]) -> Option<bls12_381::Scalar> {
+    Option::from(bls12_381::Scalar::from_bytes(bytes))
+}
+
+/// Check an output description, as `librustzcash_sapling_check_output` does.
+fn check_output(
+    ctx: &mut SaplingVerificationContext,
+    vk: &PreparedVerifyingKey<Bls12>,
+    output: &super::Output,
+) -> bool {
+    let (Some(cv), Some(cmu), Some(epk), Ok(proof)) = (
+        read_point(&output.ciphertext.cv),
+        read_scalar(&output.cm),
+        read_point(&output.ciphertext.epk),
+        Proof::read(&output.proof[..]),
+    ) else {
+        return false;
+    };
+    ctx.check_output(cv, cmu, epk, proof, vk)
+}
+
+/// Check a spend description, as `librustzcash_sapling_check_spend` does.
+fn check_spend(
+    ctx: &mut SaplingVerificationContext,
+    vk: &PreparedVerifyingKey<Bls12>,
+    input: &super::Input,
+    anchor: bls12_381::Scalar,
+    sighash: &[u8; HASH_LENGTH],
+) -> bool {
+    let (Some(cv), Ok(rk), Ok(signature), Ok(proof)) = (
+        read_point(&input.cv),
+        redjubjub::PublicKey::read(&input.rk[..]),
+        redjubjub::Signature::read(&input.signature[..]),
+        Proof::read(&input.proof[..]),
+    ) else {
+        return false;
+    };
+    ctx.check_spend(cv, anchor, &input.nf, rk, sighash, signature, proof, vk)
+}
+
+impl SaplingVerifier for ZcashSaplingVerifier {
+    fn verify(
+        &self,
+        transaction: &Transaction,
+        input_sighashes: &[[u8; HASH_LENGTH]],
+        sighash: &[u8; HASH_LENGTH],
+    ) -> Result<bool, SaplingError> {
+        let mut ctx = SaplingVerificationContext::new(false);
+        if !transaction.outputs().is_empty() {
+            let vk = self
+                .output_vk
+                .as_deref()
+                .ok_or_else(|| missing_key("output"))?;
+            for output in transaction.outputs() {
+                if !check_output(&mut ctx, vk, output) {
+                    return Ok(false);
+                }
+            }
+        }
+        if !transaction.inputs().is_empty() {
+            let vk = self
+                .spend_vk
+                .as_deref()
+                .ok_or_else(|| missing_key("spend"))?;
+            let Some(anchor) = read_scalar(transaction.root()) else {
+                return Ok(false);
+            };
+            for (input, input_sighash) in transaction.inputs().iter().zip(input_sighashes) {
+                if !check_spend(&mut ctx, vk, input, anchor, input_sighash) {
+                    return Ok(false);
+                }
+            }
+        }
+        let (Ok(balance), Ok(binding_sig)) = (
+            Amount::from_i64(transaction.balance()),
+            redjubjub::Signature::read(&transaction.binding_sig()[..]),
+        ) else {
+            return Ok(false);
+        };
+        Ok(ctx.final_check(balance, sighash, binding_sig))
+    }
+
+    fn merkle_hash(
+        &self,
+        height: u8,
+        lhs: &[u8; HASH_LENGTH],
+        rhs: &[u8; HASH_LENGTH],
+    ) -> Result<[u8; HASH_LENGTH], SaplingError> {
+        Ok(merkle_hash(height.into(), lhs, rhs))
+    }
+}
+
+/// Directory containing both parameters files, looked up in the same order
+/// as `find_params` in `src/lib_sapling/rustzcash.ml` of Octez.
+fn find_params_dir() -> Option<PathBuf> {
+    let env = |var| std::env::var_os(var).map(PathBuf::from);
+    let mut candidates = vec![];
+    if let Some(dir) = env("XDG_DATA_HOME") {
+        candidates.push(dir.join(".local/share/zcash-params"));
+    }
+    if let Some(dirs) = std::env::var_os("XDG_DATA_DIRS") {
+        candidates.extend(std::env::split_paths(&dirs).map(|dir| dir.join("zcash-params")));
+    }
+    if let Some(dir) = env("OPAM_SWITCH_PREFIX") {
+        candidates.push(dir.join("share/zcash-params"));
+    }
+    if let Some(dir) = env("PWD") {
+        candidates.push(dir.join("_opam/share/zcash-params"));
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        candidates.push(dir.join("_opam/share/zcash-params"));
+    }
+    if let Some(dir) = env("HOME") {
+        candidates.push(dir.join(".zcash-params"));
+        candidates.push(dir.join(".local/share/zcash-params"));
+    }
+    if let Some(dir) = env("HOMEBREW_PREFIX") {
+        candidates.push(dir.join("share/zcash-params"));
+    }
+    candidates.push("/usr/local/share/zcash-params".into());
+    candidates.push("/usr/share/zcash-params".into());
+    candidates
+        .into_iter()
+        .find(|dir| dir.join(SPEND_PARAMS_FILE).is_file() && dir.join(OUTPUT_PARAMS_FILE).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sapling::{
+        Ciphertext, Output, EMPTY_ROOT, NONCE_LENGTH, PAYLOAD_OUT_LENGTH, PROOF_LENGTH,
+        SIGNATURE_LENGTH, TREE_HEIGHT, UNCOMMITTED,
+    };
+    use zcash_primitives::constants::{
+        SPENDING_KEY_GENERATOR, VALUE_COMMITMENT_RANDOMNESS_GENERATOR,
+    };
+
+    /// Parse a hash given in big-endian, as in the Octez test vectors.
+    fn hash_be(s: &str) -> [u8; HASH_LENGTH] {
+        let mut bytes: [u8; HASH_LENGTH] = hex::decode(s).unwrap().try_into().unwrap();
+        bytes.reverse();
+        bytes
+    }
+
+    fn hash(s: &str) -> [u8; HASH_LENGTH] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+
+    // Test vectors from `src/lib_sapling/test/test_merkle.ml` in Octez.
+
+    #[test]
+    fn merkle_hash_vector() {
+        let verifier = ZcashSaplingVerifier::default();
+        let a = hash_be("87a086ae7d2252d58729b30263fb7b66308bf94ef59a76c9c86e7ea016536505");
+        let b = hash_be("a75b84a125b2353da7e8d96ee2a15efe4de23df9601b9d9564ba59de57130406");
+        assert_eq!(
+            verifier.merkle_hash(25, &a, &b),
+            Ok(hash_be(
+                "5bf43b5736c19b714d1f462c9d22ba3492c36e3d9bbd7ca24d94b440550aa561"
+            ))
+        );
+    }
+
+    #[test]
+    fn empty_root() {
+        let verifier = ZcashSaplingVerifier::default();
+        let mut root = UNCOMMITTED;
+        for height in 0..TREE_HEIGHT {
+            root = verifier.merkle_hash(height, &root, &root).unwrap();
+        }
+        assert_eq!(root, EMPTY_ROOT);
+    }
+
+    #[test]
+    fn merkle_roots() {
+        let verifier = ZcashSaplingVerifier::default();
+        let commitments = [
+            "556f3af94225d46b1ef652abc9005dee873b2e245eef07fd5be587e0f21023b0",
+            "5814b127a6c6b8f07ed03f0f6e2843ff04c9851ff824a4e5b4dad5b5f3475722",
+            "6c030e6d7460f91668cc842ceb78cdb54470469e78cd59cf903d3a6e1aa03e7c",
+            "30a0d08406b9e3693ee4c062bd1e6816f95bf14f5a13aafa1d57942c6c1d4250",
+            "12fc3e7298eb327a88abcc406fbe595e45dddd9b4209803b2e0baa3a8663ecaa",
+        ]
+        .map(hash_be);
+        let roots = [
+            "8c3daa300c9710bf24d2595536e7c80ff8d147faca726636d28e8683a0c27703",
+            "8611f17378eb55e8c3c3f0a5f002e2b0a7ca39442fc928322b8072d1079c213d",
+            "3db73b998d536be0e1c2ec124df8e0f383ae7b602968ff6a5276ca0695023c46",
+            "7ac2e6442fec5970e116dfa4f2ee606f395366cafb1fa7dfd6c3de3ce18c4363",
+            "6a8f11ab2a11c262e39ed4ea3825ae6c94739ccf94479cb69402c5722b034532",
+        ]
+        .map(hash);
+        // the vectors are for a tree of height 4
+        for (n, root) in roots.iter().enumerate() {
+            let mut level = commitments[..=n].to_vec();
+            let mut uncommitted = UNCOMMITTED;
+            for height in 0..4 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| {
+                        verifier
+                            .merkle_hash(height, &pair[0], pair.get(1).unwrap_or(&uncommitted))
+                            .unwrap()
+                    })
+                    .collect();
+                uncommitted = verifier
+                    .merkle_hash(height, &uncommitted, &uncommitted)
+                    .unwrap();
+            }
+            assert_eq!(&level[0], root);
+        }
+    }
+
+    /// Binding signature of a transaction without inputs nor outputs and with
+    /// a zero balance, whose binding verifying key is the identity, hence
+    /// signed with the zero signing key.
+    fn zero_key_binding_sig() -> [u8; SIGNATURE_LENGTH] {
+        let r = jubjub::Fr::from(42);
+        let rbar = (VALUE_COMMITMENT_RANDOMNESS_GENERATOR * r).to_bytes();
+        let mut sig = [0; SIGNATURE_LENGTH];
+        sig[..HASH_LENGTH].copy_from_slice(&rbar);
+        // s = r + c * bsk = r, whatever the message
+        sig[HASH_LENGTH..].copy_from_slice(&r.to_bytes());
+        sig
+    }
+
+    fn transaction(
+        outputs: Vec<Output>,
+        binding_sig: [u8; SIGNATURE_LENGTH],
+        balance: i64,
+    ) -> Transaction {
+        Transaction::new(vec![], outputs, binding_sig, balance, EMPTY_ROOT, vec![]).unwrap()
+    }
+
+    #[test]
+    fn binding_signature() {
+        let verifier = ZcashSaplingVerifier::default();
+        let sighash = [7; HASH_LENGTH];
+        let sig = zero_key_binding_sig();
+        assert_eq!(
+            verifier.verify(&transaction(vec![], sig, 0), &[], &sighash),
+            Ok(true)
+        );
+        // a non-zero balance changes the binding verifying key
+        assert_eq!(
+            verifier.verify(&transaction(vec![], sig, 1), &[], &sighash),
+            Ok(false)
+        );
+        // out of range balance
+        assert_eq!(
+            verifier.verify(&transaction(vec![], sig, i64::MIN), &[], &sighash),
+            Ok(false)
+        );
+        let mut bad_sig = sig;
+        bad_sig[HASH_LENGTH] ^= 1;
+        assert_eq!(
+            verifier.verify(&transaction(vec![], bad_sig, 0), &[], &sighash),
+            Ok(false)
+        );
+    }
+
+    fn output(proof: [u8; PROOF_LENGTH]) -> Output {
+        let point = SPENDING_KEY_GENERATOR.to_bytes();
+        Output {
+            cm: UNCOMMITTED,
+            proof,
+            ciphertext: Ciphertext {
+                cv: point,
+                epk: point,
+                payload_enc: vec![0; 11 + 8 + 32 + 16 + 4 + 8],
+                nonce_enc: [0; NONCE_LENGTH],
+                payload_out: [0; PAYLOAD_OUT_LENGTH],
+                nonce_out: [0; NONCE_LENGTH],
+            },
+        }
+    }
+
+    #[test]
+    fn missing_verifying_key() {
+        let verifier = ZcashSaplingVerifier::default();
+        let sighash = [0; HASH_LENGTH];
+        let tx = transaction(vec![output([0; PROOF_LENGTH])], [0; SIGNATURE_LENGTH], 0);
+        assert_eq!(
+            verifier.verify(&tx, &[], &sighash),
+            Err(missing_key("output"))
+        );
+    }
+
+    #[test]
+    fn invalid_output_proof() {
+        let params = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../images/ci/zcash-params/",
+            "sapling-output.params"
+        );
+        let vk = read_verifying_key(BufReader::new(File::open(params).unwrap())).unwrap();
+        let verifier = ZcashSaplingVerifier::new(None, Some(&vk));
+        let sighash = [0; HASH_LENGTH];
+        let mut proof = [0; PROOF_LENGTH];
+        proof[..48].copy_from_slice(&bls12_381::G1Affine::generator().to_compressed());
+        proof[48..144].copy_from_slice(&bls12_381::G2Affine::generator().to_compressed());
+        proof[144..].copy_from_slice(&bls12_381::G1Affine::generator().to_compressed());
+        // well-formed, but wrong, proof
+        let tx = transaction(vec![output(proof)], [0; SIGNATURE_LENGTH], 0);
+        assert_eq!(verifier.verify(&tx, &[], &sighash), Ok(false));
+        // malformed proof
+        let tx = transaction(vec![output([0; PROOF_LENGTH])], [0; SIGNATURE_LENGTH], 0);
+        assert_eq!(verifier.verify(&tx, &[], &sighash), Ok(false));
+    }
+}
@@ -10,7 +10,7 @@
 
 use super::constants::*;
 use bitvec::{order::Lsb0, vec::BitVec, view::BitView};
-use num_bigint::{BigInt, Sign};
+use num_bigint::{BigInt, BigUint, Sign};
 use smallvec::{smallvec, SmallVec};
 use strum::EnumCount;
 use typed_arena::Arena;
@@ -21,10 +21,12 @@ use crate::{
         Annotation, Micheline,
     },
     lexer::{try_ann_from_str, Prim},
+    sapling::{Ciphertext, Input, Output, Transaction},
+    timelock::{Chest, ChestKey, NONCE_LENGTH},
 };
 
 /// Errors that can happen during deserialization.
-#[derive(PartialEq, Debug, Clone, Copy, thiserror::Error)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, thiserror::Error)]
 pub enum DecodeError {
     /// Trailing bytes present after decoding a value.
     #[error("trailing bytes after decoding the value")]
@@ -47,6 +49,18 @@ pub enum DecodeError {
     /// Failed to deserialize an annotation.
     #[error("could not decode annotation")]
     BadAnnotation,
+    /// A natural number encoding ends with a redundant zero byte.
+    #[error("trailing zero byte in a natural number")]
+    TrailingZero,
+    /// The decoded data doesn't form a valid `chest`.
+    #[error("invalid time-lock chest")]
+    InvalidChest,
+    /// The decoded data doesn't form a valid `chest_key`.
+    #[error("invalid time-lock chest key")]
+    InvalidChestKey,
+    /// The decoded data doesn't form a valid `sapling_transaction`.
+    #[error("invalid sapling transaction")]
+    InvalidSaplingTransaction,
 }
 
 /// If the number of arguments is small, an allocation-avoiding optimization is
@@ -92,6 +106,100 @@ impl<'a> Micheline<'a> {
     }
 }
 
+impl Chest {
+    /// Decode a `chest` from its binary representation, i.e. the contents of
+    /// the `bytes` literal representing it in Michelson.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut it = bytes.into();
+        let locked_value = decode_n(&mut it)?;
+        let nonce = *it
+            .take_const::<NONCE_LENGTH>()
+            .ok_or(DecodeError::UnexpectedEOF)?;
+        let payload = get_bytes(&mut it)?.to_vec();
+        if it.peek().is_some() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Chest::new(locked_value, nonce, payload).ok_or(DecodeError::InvalidChest)
+    }
+}
+
+impl ChestKey {
+    /// Decode a `chest_key` from its binary representation, i.e. the contents
+    /// of the `bytes` literal representing it in Michelson.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut it = bytes.into();
+        let locked_value = decode_n(&mut it)?;
+        let unlocked_value = decode_n(&mut it)?;
+        let vdf_proof = decode_n(&mut it)?;
+        let nonce = decode_n(&mut it)?;
+        if it.peek().is_some() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        ChestKey::new(locked_value, unlocked_value, vdf_proof, nonce)
+            .ok_or(DecodeError::InvalidChestKey)
+    }
+}
+
+impl Transaction {
+    /// Decode a `sapling_transaction` from its binary representation, i.e. the
+    /// contents of the `bytes` literal representing it in Michelson.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut it = bytes.into();
+        let mut inputs_it: BytesIt = get_bytes(&mut it)?.into();
+        let mut inputs = Vec::new();
+        while inputs_it.peek().is_some() {
+            inputs.push(decode_sapling_input(&mut inputs_it)?);
+        }
+        let mut outputs_it: BytesIt = get_bytes(&mut it)?.into();
+        let mut outputs = Vec::new();
+        while outputs_it.peek().is_some() {
+            outputs.push(decode_sapling_output(&mut outputs_it)?);
+        }
+        let binding_sig = *it.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+        let balance = i64::from_be_bytes(*it.take_const().ok_or(DecodeError::UnexpectedEOF)?);
+        let root = *it.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+        let bound_data = get_bytes(&mut it)?.to_vec();
+        if it.peek().is_some() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Transaction::new(inputs, outputs, binding_sig, balance, root, bound_data)
+            .ok_or(DecodeError::InvalidSaplingTransaction)
+    }
+}
+
+fn decode_sapling_input(bytes: &mut BytesIt) -> Result<Input, DecodeError> {
+    Ok(Input {
+        cv: *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?,
+        nf: *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?,
+        rk: *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?,
+        proof: *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?,
+        signature: *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?,
+    })
+}
+
+fn decode_sapling_output(bytes: &mut BytesIt) -> Result<Output, DecodeError> {
+    let cm = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let proof = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let cv = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let epk = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let payload_enc = get_bytes(bytes)?.to_vec();
+    let nonce_enc = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let payload_out = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    let nonce_out = *bytes.take_const().ok_or(DecodeError::UnexpectedEOF)?;
+    Ok(Output {
+        cm,
+        proof,
+        ciphertext: Ciphertext {
+            cv,
+            epk,
+            payload_enc,
+            nonce_enc,
+            payload_out,
+            nonce_out,
+        },
+    })
+}
+
 struct BytesIt<'a>(&'a [u8]);
 
 impl<'a> BytesIt<'a> {
@@ -196,6 +304,27 @@ fn decode_int(bytes: &mut BytesIt) -> Result<Micheline<'static>, DecodeError> {
     )));
 }
 
+/// Decode a natural number in Data_encoding's `n` format, i.e. 7 bits per
+/// byte, least significant group first, with the high bit set on all bytes but
+/// the last.
+fn decode_n(bytes: &mut BytesIt) -> Result<BigUint, DecodeError> {
+    let mut bitvec: BitVec<u8, Lsb0> = BitVec::new();
+    let mut first = true;
+    loop {
+        let byte = *bytes.next_ref().ok_or(DecodeError::UnexpectedEOF)?;
+        if byte == 0 && !first {
+            return Err(DecodeError::TrailingZero);
+        }
+        first = false;
+        bitvec.extend_from_bitslice(&byte.view_bits::<Lsb0>()[..7]);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    bitvec.set_uninitialized(false);
+    Ok(BigUint::from_bytes_le(&bitvec.into_vec()))
+}
+
 fn get_bytes<'a>(bytes: &mut BytesIt<'a>) -> Result<&'a [u8], DecodeError> {
     let len = get_len(bytes)? as usize;
     bytes.take(len).ok_or(DecodeError::UnexpectedEOF)
@@ -538,4 +667,105 @@ mod test {
             check_err("0x045b000000026161", DecodeError::BadAnnotation);
         }
     }
+
+    mod timelock {
+        use super::*;
+
+        fn decode_key(hex_bytes: &str) -> Result<ChestKey, DecodeError> {
+            ChestKey::decode(&hex::decode(hex_bytes).unwrap())
+        }
+
+        #[test]
+        fn natural_numbers() {
+            let key = decode_key("02ac0280800101").unwrap();
+            assert_eq!(key.locked_value(), &BigUint::from(2u32));
+            assert_eq!(key.unlocked_value(), &BigUint::from(300u32));
+            assert_eq!(key.vdf_proof(), &BigUint::from(1u32 << 14));
+            assert_eq!(key.nonce(), &BigUint::from(1u32));
+        }
+
+        #[test]
+        fn chest_key_errors() {
+            assert_eq!(decode_key("020101"), Err(DecodeError::UnexpectedEOF));
+            assert_eq!(decode_key("0201018100"), Err(DecodeError::TrailingZero));
+            assert_eq!(decode_key("0201010101"), Err(DecodeError::TrailingBytes));
+            assert_eq!(decode_key("01010101"), Err(DecodeError::InvalidChestKey));
+            assert_eq!(decode_key("02010100"), Err(DecodeError::InvalidChestKey));
+        }
+
+        #[test]
+        fn chest_errors() {
+            let decode = |hex_bytes: String| Chest::decode(&hex::decode(hex_bytes).unwrap());
+            let nonce = "00".repeat(NONCE_LENGTH);
+            let tag = "00".repeat(16);
+            assert!(decode(format!("02{nonce}00000011{tag}00")).is_ok());
+            assert_eq!(
+                decode(format!("02{}", &nonce[2..])),
+                Err(DecodeError::UnexpectedEOF)
+            );
+            assert_eq!(
+                decode(format!("02{nonce}00000012{tag}00")),
+                Err(DecodeError::UnexpectedEOF)
+            );
+            assert_eq!(
+                decode(format!("02{nonce}00000010{tag}00")),
+                Err(DecodeError::TrailingBytes)
+            );
+            assert_eq!(
+                decode(format!("02{nonce}00000010{tag}")),
+                Err(DecodeError::InvalidChest)
+            );
+            assert_eq!(
+                decode(format!("01{nonce}00000011{tag}00")),
+                Err(DecodeError::InvalidChest)
+            );
+        }
+    }
+
+    mod sapling {
+        use super::*;
+        use crate::sapling::tests::{input, output, transaction};
+        use crate::sapling::EMPTY_ROOT;
+
+        #[test]
+        fn roundtrip() {
+            let tx = transaction(
+                vec![input(1), input(2)],
+                vec![output(3, 8)],
+                -15,
+                EMPTY_ROOT,
+            );
+            assert_eq!(Transaction::decode(&tx.encode()), Ok(tx));
+            let empty = transaction(vec![], vec![], 0, EMPTY_ROOT);
+            assert_eq!(Transaction::decode(&empty.encode()), Ok(empty));
+        }
+
+        #[test]
+        fn errors() {
+            let bytes = transaction(vec![input(1)], vec![output(3, 8)], 0, EMPTY_ROOT).encode();
+            assert_eq!(
+                Transaction::decode(&bytes[..bytes.len() - 1]),
+                Err(DecodeError::UnexpectedEOF)
+            );
+            assert_eq!(
+                Transaction::decode(&[bytes.as_slice(), &[0]].concat()),
+                Err(DecodeError::TrailingBytes)
+            );
+            // outputs with different memo sizes
+            let mut outputs = transaction(vec![], vec![output(3, 8)], 0, EMPTY_ROOT).encode();
+            let mut other = transaction(vec![], vec![output(4, 9)], 0, EMPTY_ROOT).encode();
+            // splice the second output into the first transaction's outputs list
+            let out_len = u32::from_be_bytes(outputs[4..8].try_into().unwrap()) as usize;
+            let other_len = u32::from_be_bytes(other[4..8].try_into().unwrap()) as usize;
+            let mut spliced = vec![0, 0, 0, 0];
+            spliced.extend(((out_len + other_len) as u32).to_be_bytes());
+            spliced.extend(&outputs[8..8 + out_len]);
+            spliced.extend(other.drain(8..8 + other_len));
+            spliced.extend(outputs.drain(8 + out_len..));
+            assert_eq!(
+                Transaction::decode(&spliced),
+                Err(DecodeError::InvalidSaplingTransaction)
+            );
+        }
+    }
 }
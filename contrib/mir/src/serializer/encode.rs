@@ -7,6 +7,7 @@
 
 //! Micheline serialization.
 
+use num_bigint::BigUint;
 use std::mem::size_of;
 use tezos_data_encoding::{enc::BinWriter, types::Zarith};
 
@@ -14,6 +15,8 @@ use super::constants::*;
 use crate::{
     ast::{Annotation, Annotations, Micheline},
     lexer::Prim,
+    sapling::{Input, Output, Transaction},
+    timelock::{Chest, ChestKey},
 };
 
 trait AppEncoder<'a>: IntoIterator<Item = &'a Micheline<'a>> + Sized {
@@ -227,6 +230,96 @@ impl<'a> BinWriter for Micheline<'a> {
     }
 }
 
+/// Put a natural number in Data_encoding's `n` format, i.e. 7 bits per byte,
+/// least significant group first, with the high bit set on all bytes but the
+/// last.
+fn put_n(n: &BigUint, out: &mut Vec<u8>) {
+    let mut groups = n.to_radix_le(128);
+    let last = groups.len() - 1;
+    for group in &mut groups[..last] {
+        *group |= 0x80;
+    }
+    out.extend_from_slice(&groups)
+}
+
+impl Chest {
+    /// Serialize a `chest` to its binary representation. Inverse of
+    /// [Chest::decode].
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_n(self.locked_value(), &mut out);
+        out.extend_from_slice(self.nonce());
+        put_len(self.payload().len() as Len, &mut out);
+        out.extend_from_slice(self.payload());
+        out
+    }
+}
+
+impl ChestKey {
+    /// Serialize a `chest_key` to its binary representation. Inverse of
+    /// [ChestKey::decode].
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_n(self.locked_value(), &mut out);
+        put_n(self.unlocked_value(), &mut out);
+        put_n(self.vdf_proof(), &mut out);
+        put_n(self.nonce(), &mut out);
+        out
+    }
+}
+
+impl Input {
+    /// Serialize a Sapling spend description to its binary representation.
+    pub fn encode(&self) -> Vec<u8> {
+        [
+            &self.cv[..],
+            &self.nf,
+            &self.rk,
+            &self.proof,
+            &self.signature,
+        ]
+        .concat()
+    }
+}
+
+impl Output {
+    /// Serialize a Sapling output description to its binary representation.
+    pub fn encode(&self) -> Vec<u8> {
+        let ct = &self.ciphertext;
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.cm);
+        out.extend_from_slice(&self.proof);
+        out.extend_from_slice(&ct.cv);
+        out.extend_from_slice(&ct.epk);
+        put_len(ct.payload_enc.len() as Len, &mut out);
+        out.extend_from_slice(&ct.payload_enc);
+        out.extend_from_slice(&ct.nonce_enc);
+        out.extend_from_slice(&ct.payload_out);
+        out.extend_from_slice(&ct.nonce_out);
+        out
+    }
+}
+
+impl Transaction {
+    /// Serialize a `sapling_transaction` to its binary representation. Inverse
+    /// of [Transaction::decode].
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let inputs: Vec<u8> = self.inputs().iter().flat_map(Input::encode).collect();
+        put_len(inputs.len() as Len, &mut out);
+        out.extend_from_slice(&inputs);
+        let outputs: Vec<u8> = self.outputs().iter().flat_map(Output::encode).collect();
+        put_len(outputs.len() as Len, &mut out);
+        out.extend_from_slice(&outputs);
+        out.extend_from_slice(self.binding_sig());
+        out.extend_from_slice(&self.balance().to_be_bytes());
+        out.extend_from_slice(self.root());
+        put_len(self.bound_data().len() as Len, &mut out);
+        out.extend_from_slice(self.bound_data());
+        out
+    }
+}
+
 #[cfg(test)]
 mod test_encoding {
     use super::*;
@@ -0,0 +1,208 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Conversion between [Micheline] and its JSON representation, as used by
+//! `octez-client` and the node RPCs.
+
+use num_bigint::BigInt;
+use serde_json::{Map, Value};
+use typed_arena::Arena;
+
+use crate::{
+    ast::{Annotation, Annotations, Micheline},
+    lexer::{try_ann_from_str, Prim, PrimError},
+};
+
+/// Errors that can happen when converting JSON to [Micheline].
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum JsonError {
+    /// JSON value doesn't represent any Micheline node.
+    #[error("unexpected JSON value: {0}")]
+    UnexpectedValue(String),
+    /// Value of the `int` field is not a decimal integer.
+    #[error("invalid integer: {0}")]
+    InvalidInt(String),
+    /// Value of the `bytes` field is not a hexadecimal string.
+    #[error("invalid bytes: {0}")]
+    InvalidBytes(String),
+    /// Unknown primitive name.
+    #[error(transparent)]
+    UnknownPrim(#[from] PrimError),
+    /// Invalid annotation in `annots`.
+    #[error("invalid annotation: {0}")]
+    InvalidAnnotation(String),
+}
+
+impl<'a> Micheline<'a> {
+    /// Convert [Micheline] to JSON. Empty `args` and `annots` fields are
+    /// omitted, like `octez-client` does.
+    pub fn to_json(&self) -> Value {
+        let obj = |key: &str, val: Value| Value::Object(Map::from_iter([(key.to_owned(), val)]));
+        match self {
+            Micheline::Int(i) => obj("int", Value::String(i.to_string())),
+            Micheline::String(s) => obj("string", Value::String(s.clone())),
+            Micheline::Bytes(b) => obj("bytes", Value::String(hex::encode(b))),
+            Micheline::App(prim, args, anns) => {
+                let mut res = Map::new();
+                res.insert("prim".to_owned(), Value::String(prim.to_string()));
+                if !args.is_empty() {
+                    res.insert(
+                        "args".to_owned(),
+                        Value::Array(args.iter().map(Micheline::to_json).collect()),
+                    );
+                }
+                if !anns.is_empty() {
+                    res.insert(
+                        "annots".to_owned(),
+                        Value::Array(anns.iter().map(|a| Value::String(a.to_string())).collect()),
+                    );
+                }
+                Value::Object(res)
+            }
+            Micheline::Seq(items) => Value::Array(items.iter().map(Micheline::to_json).collect()),
+        }
+    }
+
+    /// Convert JSON to [Micheline]. Requires access to an [Arena] to allocate
+    /// the nested nodes.
+    pub fn from_json(arena: &'a Arena<Micheline<'a>>, json: &Value) -> Result<Self, JsonError> {
+        let unexpected = || JsonError::UnexpectedValue(json.to_string());
+        let str_field = |obj: &Map<String, Value>, key: &str| -> Result<String, JsonError> {
+            match obj.get(key) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                _ => Err(unexpected()),
+            }
+        };
+        let from_array = |items: &Vec<Value>| -> Result<&'a [Micheline<'a>], JsonError> {
+            let items = items
+                .iter()
+                .map(|item| Micheline::from_json(arena, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Micheline::alloc_iter(arena, items.into_iter()))
+        };
+        match json {
+            Value::Array(items) => Ok(Micheline::Seq(from_array(items)?)),
+            Value::Object(obj) if obj.len() == 1 && obj.contains_key("int") => {
+                let s = str_field(obj, "int")?;
+                s.parse::<BigInt>()
+                    .map(Micheline::Int)
+                    .map_err(|_| JsonError::InvalidInt(s))
+            }
+            Value::Object(obj) if obj.len() == 1 && obj.contains_key("string") => {
+                Ok(Micheline::String(str_field(obj, "string")?))
+            }
+            Value::Object(obj) if obj.len() == 1 && obj.contains_key("bytes") => {
+                let s = str_field(obj, "bytes")?;
+                hex::decode(&s)
+                    .map(Micheline::Bytes)
+                    .map_err(|_| JsonError::InvalidBytes(s))
+            }
+            Value::Object(obj)
+                if obj.contains_key("prim")
+                    && obj
+                        .keys()
+                        .all(|k| matches!(k.as_str(), "prim" | "args" | "annots")) =>
+            {
+                let prim: Prim = str_field(obj, "prim")?.parse()?;
+                let args = match obj.get("args") {
+                    None => &[],
+                    Some(Value::Array(args)) => from_array(args)?,
+                    Some(_) => return Err(unexpected()),
+                };
+                let anns = match obj.get("annots") {
+                    None => Annotations::new(),
+                    Some(Value::Array(anns)) => {
+                        anns.iter()
+                            .map(ann_from_json)
+                            .collect::<Result<Annotations, _>>()?
+                    }
+                    Some(_) => return Err(unexpected()),
+                };
+                Ok(Micheline::App(prim, args, anns))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+}
+
+fn ann_from_json(json: &Value) -> Result<Annotation<'static>, JsonError> {
+    match json {
+        Value::String(s) if !s.is_empty() => try_ann_from_str(s)
+            .map(Annotation::into_owned)
+            .ok_or_else(|| JsonError::InvalidAnnotation(s.clone())),
+        _ => Err(JsonError::InvalidAnnotation(json.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::test_helpers::parse;
+    use serde_json::json;
+
+    #[track_caller]
+    fn check(src: &str, expected: Value) {
+        let m = parse(src).unwrap();
+        assert_eq!(m.to_json(), expected);
+        assert_eq!(Micheline::from_json(&Arena::new(), &expected), Ok(m));
+    }
+
+    #[test]
+    fn roundtrip() {
+        check("-42", json!({"int": "-42"}));
+        check("\"foo\"", json!({"string": "foo"}));
+        check("0xdead", json!({"bytes": "dead"}));
+        check("{}", json!([]));
+        check("Unit", json!({"prim": "Unit"}));
+        check(
+            "pair (int %a) (nat :b @c)",
+            json!({"prim": "pair", "args": [
+                {"prim": "int", "annots": ["%a"]},
+                {"prim": "nat", "annots": [":b", "@c"]},
+            ]}),
+        );
+        check(
+            "{ DUP @% ; PUSH nat 1 ; PAIR %@ % }",
+            json!([
+                {"prim": "DUP", "annots": ["@%"]},
+                {"prim": "PUSH", "args": [{"prim": "nat"}, {"int": "1"}]},
+                {"prim": "PAIR", "annots": ["%@", "%"]},
+            ]),
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let arena = Arena::new();
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"int": "1a"})),
+            Err(JsonError::InvalidInt("1a".to_owned()))
+        );
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"bytes": "xyz"})),
+            Err(JsonError::InvalidBytes("xyz".to_owned()))
+        );
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"prim": "FOO"})),
+            Err(JsonError::UnknownPrim(PrimError("FOO".to_owned())))
+        );
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"prim": "DUP", "annots": ["x"]})),
+            Err(JsonError::InvalidAnnotation("x".to_owned()))
+        );
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"int": 1})),
+            Err(JsonError::UnexpectedValue("{\"int\":1}".to_owned()))
+        );
+        assert_eq!(
+            Micheline::from_json(&arena, &json!({"prim": "DUP", "foo": []})),
+            Err(JsonError::UnexpectedValue(
+                "{\"foo\":[],\"prim\":\"DUP\"}".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,423 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Timelock encryption data types and operations, backing the `chest` and
+//! `chest_key` types and the `OPEN_CHEST` instruction.
+//!
+//! This follows the reference implementation in `src/lib_crypto/timelock.ml`
+//! in the Octez repository: a Wesolowski VDF over the RSA-2048 group is used to
+//! prove that the chest was unlocked, and the payload itself is encrypted with
+//! NaCl's `secretbox`.
+
+use cryptoxide::{
+    blake2b::Blake2b,
+    mac::{Mac, MacResult},
+    poly1305::Poly1305,
+    salsa20::Salsa20,
+};
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use std::sync::OnceLock;
+
+/// Length of the `secretbox` nonce.
+pub const NONCE_LENGTH: usize = 24;
+
+/// Length of the `secretbox` authentication tag.
+pub const TAG_LENGTH: usize = 16;
+
+/// The RSA-2048 challenge modulus, see
+/// <https://en.wikipedia.org/wiki/RSA_numbers#RSA-2048>.
+const RSA2048: &str = "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357";
+
+fn rsa2048() -> &'static BigUint {
+    static MODULUS: OnceLock<BigUint> = OnceLock::new();
+    MODULUS.get_or_init(|| RSA2048.parse().unwrap())
+}
+
+/// A timelocked chest, i.e. a value of type `chest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chest {
+    locked_value: BigUint,
+    nonce: [u8; NONCE_LENGTH],
+    payload: Vec<u8>,
+}
+
+impl Chest {
+    /// Construct a new chest from its components. `payload` is the
+    /// authentication tag followed by the encrypted data. Returns [None] if the
+    /// components do not form a valid chest.
+    pub fn new(locked_value: BigUint, nonce: [u8; NONCE_LENGTH], payload: Vec<u8>) -> Option<Self> {
+        if locked_value <= BigUint::one() || payload.len() <= TAG_LENGTH {
+            return None;
+        }
+        Some(Chest {
+            locked_value,
+            nonce,
+            payload,
+        })
+    }
+
+    /// The locked value of the chest.
+    pub fn locked_value(&self) -> &BigUint {
+        &self.locked_value
+    }
+
+    /// The `secretbox` nonce.
+    pub fn nonce(&self) -> &[u8; NONCE_LENGTH] {
+        &self.nonce
+    }
+
+    /// The authentication tag followed by the encrypted data.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Size of the encrypted data, i.e. of the plaintext, in bytes.
+    pub fn plaintext_size(&self) -> usize {
+        self.payload.len() - TAG_LENGTH
+    }
+}
+
+/// A key for a timelocked chest, i.e. a value of type `chest_key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChestKey {
+    locked_value: BigUint,
+    unlocked_value: BigUint,
+    vdf_proof: BigUint,
+    nonce: BigUint,
+}
+
+impl ChestKey {
+    /// Construct a new chest key from its components. Returns [None] if the
+    /// components do not form a valid chest key.
+    pub fn new(
+        locked_value: BigUint,
+        unlocked_value: BigUint,
+        vdf_proof: BigUint,
+        nonce: BigUint,
+    ) -> Option<Self> {
+        let modulus = rsa2048();
+        if locked_value <= BigUint::one()
+            || &locked_value >= modulus
+            || &unlocked_value >= modulus
+            || &vdf_proof >= modulus
+            || nonce.is_zero()
+        {
+            return None;
+        }
+        Some(ChestKey {
+            locked_value,
+            unlocked_value,
+            vdf_proof,
+            nonce,
+        })
+    }
+
+    /// The locked value of the VDF tuple.
+    pub fn locked_value(&self) -> &BigUint {
+        &self.locked_value
+    }
+
+    /// The unlocked value of the VDF tuple.
+    pub fn unlocked_value(&self) -> &BigUint {
+        &self.unlocked_value
+    }
+
+    /// The Wesolowski proof of the VDF tuple.
+    pub fn vdf_proof(&self) -> &BigUint {
+        &self.vdf_proof
+    }
+
+    /// The randomizing nonce.
+    pub fn nonce(&self) -> &BigUint {
+        &self.nonce
+    }
+}
+
+/// Open `chest` with `chest_key`, assuming it was locked for `time` steps.
+/// Returns [None] if the key does not correspond to the chest. If the key is
+/// correct, but the payload can't be decrypted, returns an empty plaintext, as
+/// the reference implementation does.
+///
+/// NB: the reference implementation raises an exception when `time` is `0`;
+/// here it's treated as a bogus opening instead.
+pub fn open_chest(chest: &Chest, chest_key: &ChestKey, time: u64) -> Option<Vec<u8>> {
+    if time == 0 || !verify(time, chest, chest_key) {
+        return None;
+    }
+    let key = symmetric_key(chest_key);
+    Some(secretbox_open(&key, &chest.nonce, &chest.payload).unwrap_or_default())
+}
+
+/// Converts a `nat` to the time parameter of [open_chest]. Returns [None] if
+/// it doesn't fit into OCaml's native integer, in which case no proof can be
+/// correct.
+pub fn time_of_nat(time: &BigUint) -> Option<u64> {
+    time.to_u64().filter(|t| *t <= (i64::MAX >> 1) as u64)
+}
+
+fn verify(time: u64, chest: &Chest, chest_key: &ChestKey) -> bool {
+    let modulus = rsa2048();
+    // link between the precomputed tuple and the chest
+    let randomized_challenge = chest_key.locked_value.modpow(&chest_key.nonce, modulus);
+    randomized_challenge == chest.locked_value && verify_wesolowski(time, chest_key)
+}
+
+fn verify_wesolowski(time: u64, chest_key: &ChestKey) -> bool {
+    let modulus = rsa2048();
+    let l = hash_to_prime(time, &chest_key.locked_value, &chest_key.unlocked_value);
+    let r = BigUint::from(2u32).modpow(&BigUint::from(time), &l);
+    let rhs = chest_key.vdf_proof.modpow(&l, modulus) * chest_key.locked_value.modpow(&r, modulus)
+        % modulus;
+    chest_key.unlocked_value == rhs
+}
+
+fn hash_to_prime(time: u64, value: &BigUint, key: &BigUint) -> BigUint {
+    const SEPARATOR: &[u8] = b"\xff\x00\xff\x00\xff\x00\xff\x00";
+    let mut to_hash = time.to_string().into_bytes();
+    for x in [rsa2048(), value, key] {
+        to_hash.extend_from_slice(SEPARATOR);
+        to_hash.extend_from_slice(&to_bits(x));
+    }
+    let mut hash = [0u8; 32];
+    Blake2b::blake2b(&mut hash, &to_hash, b"\x20");
+    next_prime(BigUint::from_bytes_le(&hash))
+}
+
+/// Little-endian representation padded to a whole number of 64-bit limbs,
+/// mirroring `Z.to_bits`.
+fn to_bits(x: &BigUint) -> Vec<u8> {
+    if x.is_zero() {
+        return Vec::new();
+    }
+    let mut res = x.to_bytes_le();
+    res.resize(res.len().div_ceil(8) * 8, 0);
+    res
+}
+
+fn symmetric_key(chest_key: &ChestKey) -> [u8; 32] {
+    let updated = chest_key.unlocked_value.modpow(&chest_key.nonce, rsa2048());
+    let mut key = [0u8; 32];
+    Blake2b::blake2b(
+        &mut key,
+        updated.to_string().as_bytes(),
+        b"Tezoskdftimelockv1",
+    );
+    key
+}
+
+/// NaCl's `crypto_secretbox_open_easy`, i.e. XSalsa20-Poly1305 decryption of
+/// the tag followed by the ciphertext.
+fn secretbox_open(key: &[u8; 32], nonce: &[u8; NONCE_LENGTH], payload: &[u8]) -> Option<Vec<u8>> {
+    let (tag, ciphertext) = payload.split_at(TAG_LENGTH);
+    let mut cipher = Salsa20::new_xsalsa20(key, nonce);
+    let mut mac_key = [0u8; 32];
+    cipher.process(&[0u8; 32], &mut mac_key);
+    let mut mac = Poly1305::new(&mac_key);
+    mac.input(ciphertext);
+    if mac.result() != MacResult::new(tag) {
+        return None;
+    }
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    cipher.process(ciphertext, &mut plaintext);
+    Some(plaintext)
+}
+
+/// Smallest prime strictly greater than `n`.
+fn next_prime(n: BigUint) -> BigUint {
+    let mut candidate = n + 1u32;
+    if candidate <= BigUint::from(2u32) {
+        return BigUint::from(2u32);
+    }
+    if candidate.is_even() {
+        candidate += 1u32;
+    }
+    while !is_probable_prime(&candidate) {
+        candidate += 2u32;
+    }
+    candidate
+}
+
+const SMALL_PRIMES: [u32; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// Miller-Rabin primality test with the first 25 primes as witnesses. This is
+/// at least as strong as the test GMP's `mpz_nextprime` runs.
+fn is_probable_prime(n: &BigUint) -> bool {
+    for p in SMALL_PRIMES {
+        if n == &BigUint::from(p) {
+            return true;
+        }
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+    let one = BigUint::one();
+    let n_minus_one = n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+    'witness: for p in SMALL_PRIMES {
+        let mut x = BigUint::from(p).modpow(&d, n);
+        if x == one || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&BigUint::from(2u32), n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A chest locked for 10 steps, containing `"hello"`.
+    pub(crate) const CHEST: &str = concat!(
+        "f7b8ec97f0b193fbb485acd2b3cd87a692dfd68fb5d990eeb4decc90e9d6d98390e7a2f8d7c3ddfae1d7f6f8",
+        "aad99ed3a8e9f5acb6d7dcc1b098c6d6cb94decdb3fbb0c1c4a1bda9c4e9c1f9ffa6b294cec8c595e8b880a5",
+        "8c8087f681ebf2d89d9596aeccf7b5b0efb0cce9f8dcdf99a1ccbbbd98feaecba6ede8ccc7a785c8d1ccabb0",
+        "e7eac4f69ead83c4a7a2b7bccbaadef98f8aec90cd89d6c1befcd09acdfda28bccf8d3e1b1818686ceb3e292",
+        "fafe94d2ca94e68190a6918ab8dfecd5a0cd878dacb9c6acbdf1878e88a784a486e8829bc5d1c9d78fe8d8eb",
+        "d8a8a0c28ec980b9baee9fa9f0d7fbcbf3f3afe5e6cb82a9c2e9b4c8ebc0998ecaadb1bac8becce6a1d6f1c9",
+        "b1e38aaca2e8d7caad95e29093b186b7bcfdce8cb7ebcdcb87ad91b50570eb940bd5335f973daad8619b91ff",
+        "c911f57cced458bbbf0000001560811db16fd27861d6d859421c04be8397f45fdb95",
+    );
+
+    /// Key for [CHEST].
+    pub(crate) const CHEST_KEY: &str = concat!(
+        "f7e39693a289d6db91bfab8b8f9b8c9fe19a93deebf0f0e8c4cfc0e0c386d3a089d7bff1b1afb5eafe88bdf3",
+        "acf8a8ec91e68583d19cde8dcc9d9bb5e9cecbb0968ca7cfdca5e6dfb598bab980c3a096e4f9a1bd9ce881f3",
+        "ece09297bb96fdea8d85aa9ee6d0f4e4eeecb988b8b3e1e4a486e78ac78cb1d3859482c0a5c388d9adca9390",
+        "b7a88f97c4fce3b68bb78dbfc49ac7c982d2cee0d4abdd94aaa8fff89f9eaed191e7fce1e6a7c698948ab7df",
+        "d48f8cb7eeb6c1dc9e879ae8fd9492d39e84e8d4f8a7d0ad8294d1e086beb6c598c3bfcee9b2d8fce7fabefd",
+        "ede1dc9bc298ccc0df9ef587ecc0e78b9df7e19bef8ef0c5abe2e1fec881e3f988bde0e1a0cde580f7fdcfb6",
+        "f0a9b4eceecfa29a889be6e1b6f58ec0d2ffe1fa9dc2c69dbde497ea0ac9e7f7f7d9a4acfab3b5b4cfb1d4ed",
+        "b1d7a4b485f5e5deade2ce8c9f9ac1c48cbad8c195ff918bd4c7a4eda7c7e8e4cfc587a68b90d4d09df1f4e8",
+        "c0fec894d58599aafef6a1cf9f8aa0969294d4f189a3c6db8fffb4ab9eeeaaa2cd84edcdc988e6c2d7e5ae82",
+        "f2d998e5ed9ffbffe8c2ec91e1da82a19ee5dfbca6d0b1d4c298fe9586eaeccafa8eb4d7c3bff6cab1c4dded",
+        "f087b1afa791b1f3bfd9fbcca5cfe4ef8e82e4c8c2dff1ffdcc9ad9dbcf7acbe81c098b284c9c8e6c69de0c0",
+        "bdfce6fdb681fc9381edf6b38c93a08bece68dc2fa959ccea4fde5a89cd0c0d7ee8fbcb5cefbdc989fdfa08a",
+        "f9e385fce0ae81c8e1e790b7a4cbc0b598b6dfa1daddababd39cf6ed9b9198ceed98c3ffa696d6bab793c9aa",
+        "d4ddb6ebd4fdf8bcfefeca9d4901b960",
+    );
+
+    /// A different chest locked for 10 steps.
+    pub(crate) const OTHER_CHEST: &str = concat!(
+        "f5d2faa59ff3aefadcf7c9a6aa9ef2d7dbb2ffb4a2cfc3e999e4dde8bdc5ee8ab786b9e3c5a4d0f1d589c1c8",
+        "b485dec3e8f8fe9782e3f3c7f3e6adc5be9ca5edf88f88f784ebf0b3c0dca0f9b1b3b7fcd3ecc4def5eaa6ba",
+        "e4cba48095b9b2caf5cc98f39af0f1c5a8eac8ecfeffe4aec0dcbddcadeee8a480fd929bfda8efe6daa3ddc8",
+        "9ba3bd9cdceffae3eadfa19fd284eaa7b9e1fcf5d7fefc8afce5d3c5929aabfcaedafdf0f1deedbb88d0b3b6",
+        "ab899defd8d6b993d6e4a4d0c480bfefc5c09ab890978398ddc0a29af0aebabf8dddcbf1eeb0d4d5cfc4a1ff",
+        "8695d2b0a689c7e3d68a94a6e4e7b890a59cf4dd978db6cebec38abc9bfbbdfc978fdb96f8aacae3b3dcb184",
+        "bbe0c0dce1c0d3c2bddad489c9e7948fd0e0b1919ad3bf91d491e99408b85de4d4bab5b9e452ccec7ffa8eff",
+        "b5e8ecb3e9f971a65500000015ccb36bd1c6f97bbf45b840f2374de1d1a9f291be12",
+    );
+
+    /// Key for [OTHER_CHEST].
+    pub(crate) const OTHER_CHEST_KEY: &str = concat!(
+        "f5d2faa59ff3aefadcf7c9a6aa9ef2d7dbb2ffb4a2cfc3e999e4dde8bdc5ee8ab786b9e3c5a4d0f1d589c1c8",
+        "b485dec3e8f8fe9782e3f3c7f3e6adc5be9ca5edf88f88f784ebf0b3c0dca0f9b1b3b7fcd3ecc4def5eaa6ba",
+        "e4cba48095b9b2caf5cc98f39af0f1c5a8eac8ecfeffe4aec0dcbddcadeee8a480fd929bfda8efe6daa3ddc8",
+        "9ba3bd9cdceffae3eadfa19fd284eaa7b9e1fcf5d7fefc8afce5d3c5929aabfcaedafdf0f1deedbb88d0b3b6",
+        "ab899defd8d6b993d6e4a4d0c480bfefc5c09ab890978398ddc0a29af0aebabf8dddcbf1eeb0d4d5cfc4a1ff",
+        "8695d2b0a689c7e3d68a94a6e4e7b890a59cf4dd978db6cebec38abc9bfbbdfc978fdb96f8aacae3b3dcb184",
+        "bbe0c0dce1c0d3c2bddad489c9e7948fd0e0b1919ad3bf91d491e99408efdffba6cff8d0cee38284bbe9c190",
+        "a09abeb6a3a4abffd8acd8b0fdfccbe68fe9e0bf879cc186ba988695f7c1a482c6cbb1f0bfffb8b4baddbdd6",
+        "efb0f8b3dbfaceb0eee9b5e6f9bd98b2f891a2c4f0a3c4c9a6d39cdbabefc6f2a3c1b09fbef78af9a7a8a68e",
+        "d1e98ffa98c5f9d0e199cde4a1f9c4bfa1b4afa2b3d3e4ecd7dce9fbe8a192adaea7c0bd94d4ce93befdb2ef",
+        "83d8d2c9e5fbd9a5dfa48ffbdca0b1f7c19cc58ce6bac0fe878aa481a9cdbfa88cc6c6e6a99e998bc2d1ea95",
+        "e59b97cdc5e69f91e6e1c388ffd59feb92dbdcd3d2cd8bdafe82d4e99c9ba2b6db9cd4a4bef5f3caa0eada80",
+        "c88ffb919db59bc197e3a9f4f4c1d88d8aadf5d2fef4bfcfe48ba3e6f5ef8bf1d9e8f0be88f2f1bbea88b9e3",
+        "f0bff9b09f8496e6c3a79dd690050101",
+    );
+
+    pub(crate) fn chest(hex: &str) -> Chest {
+        Chest::decode(&hex::decode(hex).unwrap()).unwrap()
+    }
+
+    pub(crate) fn chest_key(hex: &str) -> ChestKey {
+        ChestKey::decode(&hex::decode(hex).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn open_correct_key() {
+        assert_eq!(
+            open_chest(&chest(CHEST), &chest_key(CHEST_KEY), 10),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(
+            open_chest(&chest(OTHER_CHEST), &chest_key(OTHER_CHEST_KEY), 10),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[test]
+    fn open_wrong_key() {
+        assert_eq!(
+            open_chest(&chest(CHEST), &chest_key(OTHER_CHEST_KEY), 10),
+            None
+        );
+        assert_eq!(
+            open_chest(&chest(OTHER_CHEST), &chest_key(CHEST_KEY), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn open_wrong_time() {
+        assert_eq!(open_chest(&chest(CHEST), &chest_key(CHEST_KEY), 9), None);
+        assert_eq!(open_chest(&chest(CHEST), &chest_key(CHEST_KEY), 11), None);
+        assert_eq!(open_chest(&chest(CHEST), &chest_key(CHEST_KEY), 0), None);
+    }
+
+    #[test]
+    fn open_bad_payload() {
+        // the key is correct, but the ciphertext was tampered with, so the
+        // result is an empty plaintext
+        let mut bytes = hex::decode(CHEST).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        let chest = Chest::decode(&bytes).unwrap();
+        assert_eq!(open_chest(&chest, &chest_key(CHEST_KEY), 10), Some(vec![]));
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        assert_eq!(hex::encode(chest(CHEST).encode()), CHEST);
+        assert_eq!(hex::encode(chest_key(CHEST_KEY).encode()), CHEST_KEY);
+    }
+
+    #[test]
+    fn next_prime_samples() {
+        let next = |n: u32| next_prime(BigUint::from(n));
+        assert_eq!(next(0), BigUint::from(2u32));
+        assert_eq!(next(2), BigUint::from(3u32));
+        assert_eq!(next(97), BigUint::from(101u32));
+        assert_eq!(next(7919), BigUint::from(7927u32));
+        // 2^64 + 13 is the smallest prime above 2^64
+        assert_eq!(
+            next_prime(BigUint::from(u64::MAX)),
+            BigUint::from(u64::MAX) + 14u32
+        );
+    }
+
+    #[test]
+    fn time_of_nat_bounds() {
+        assert_eq!(time_of_nat(&BigUint::from(10u32)), Some(10));
+        assert_eq!(
+            time_of_nat(&BigUint::from((1u64 << 62) - 1)),
+            Some((1u64 << 62) - 1)
+        );
+        assert_eq!(time_of_nat(&BigUint::from(1u64 << 62)), None);
+    }
+}
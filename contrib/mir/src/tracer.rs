@@ -0,0 +1,386 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Step-by-step tracing of the interpreter.
+//!
+//! Set [Ctx::tracer](crate::context::Ctx::tracer) to an implementation of
+//! [Tracer] to be notified before and after every executed instruction.
+//! [JsonTracer] is a ready-made implementation producing the same trace format
+//! as the `trace_code` RPC and `octez-client run script --trace-stack`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde_json::{Map, Value};
+use typed_arena::Arena;
+
+use crate::ast::{ContractScript, Instruction, IntoMicheline, Lambda, Micheline};
+use crate::gas::Gas;
+use crate::interpreter::InterpretError;
+use crate::lexer::Prim;
+use crate::stack::IStack;
+
+/// Hooks called by the interpreter around every instruction it executes,
+/// including the nested ones, e.g. bodies of `DIP`, `LOOP` or lambdas.
+/// Instruction sequences are reported as well, as [Instruction::Seq].
+///
+/// Both methods do nothing by default.
+pub trait Tracer<'a> {
+    /// Called before `instr` is executed on `stack`, with the gas remaining
+    /// at that point.
+    fn before_instruction(&mut self, _instr: &Instruction<'a>, _stack: &IStack<'a>, _gas: &Gas) {}
+
+    /// Called after `instr` is executed, with the resulting `stack` and the
+    /// gas remaining. If the instruction failed, `result` contains the error,
+    /// and the stack is in an unspecified state. Note that if the error is
+    /// [InterpretError::OutOfGas], the gas is exhausted, see
+    /// [Gas::is_exhausted].
+    fn after_instruction(
+        &mut self,
+        _instr: &Instruction<'a>,
+        _stack: &IStack<'a>,
+        _gas: &Gas,
+        _result: Result<(), &InterpretError<'a>>,
+    ) {
+    }
+}
+
+/// Allows keeping access to the tracer after passing it to
+/// [Ctx](crate::context::Ctx), which owns its tracer.
+impl<'a, T: Tracer<'a>> Tracer<'a> for Rc<RefCell<T>> {
+    fn before_instruction(&mut self, instr: &Instruction<'a>, stack: &IStack<'a>, gas: &Gas) {
+        self.borrow_mut().before_instruction(instr, stack, gas)
+    }
+
+    fn after_instruction(
+        &mut self,
+        instr: &Instruction<'a>,
+        stack: &IStack<'a>,
+        gas: &Gas,
+        result: Result<(), &InterpretError<'a>>,
+    ) {
+        self.borrow_mut()
+            .after_instruction(instr, stack, gas, result)
+    }
+}
+
+/// [Tracer] recording the stack and the remaining gas after every successfully
+/// executed instruction, in the format used by the `trace_code` RPC, i.e. as a
+/// JSON array of objects
+///
+/// ```json
+/// { "location": 7, "gas": "1039993105", "stack": [ { "int": "1" } ] }
+/// ```
+///
+/// where `gas` is the remaining milligas and `stack` lists the stack elements
+/// starting from the top. Sequences are not recorded, as the protocol doesn't
+/// have a separate instruction for them.
+///
+/// Typechecked instructions don't carry their source locations, so those have
+/// to be registered with [JsonTracer::locate_script] or
+/// [JsonTracer::locate_instruction] before interpreting. The `location` field
+/// is omitted for instructions with unknown location.
+///
+/// To get the trace back after the interpretation, wrap the tracer in
+/// [`Rc<RefCell<_>>`](RefCell) before passing it to the context:
+///
+/// ```
+/// use mir::ast::{Micheline, TypedValue};
+/// use mir::{context::Ctx, lexer::Prim, parser::Parser, stk, tracer::JsonTracer};
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// let parser = Parser::new();
+/// let code = parser.parse("{ PUSH nat 1 ; ADD }").unwrap();
+/// let mut ctx = Ctx::default();
+/// let instr = code
+///     .typecheck_instruction(&mut ctx, None, &[Micheline::prim0(Prim::nat)])
+///     .unwrap();
+///
+/// let tracer = Rc::new(RefCell::new(JsonTracer::new()));
+/// tracer.borrow_mut().locate_instruction(&code, 0, &instr);
+/// ctx.tracer = Some(Box::new(tracer.clone()));
+/// instr
+///     .interpret(&mut ctx, &parser.arena, &mut stk![TypedValue::nat(2)])
+///     .unwrap();
+///
+/// let trace = tracer.borrow().to_json();
+/// assert_eq!(trace[0]["location"], 1);
+/// assert_eq!(trace[1]["location"], 4);
+/// assert_eq!(trace[1]["stack"], serde_json::json!([{ "int": "3" }]));
+/// ```
+#[derive(Debug, Default)]
+pub struct JsonTracer {
+    /// Known locations, keyed by instruction addresses. The addresses are
+    /// never dereferenced, they only serve for identification.
+    locations: HashMap<*const Instruction<'static>, usize>,
+    entries: Vec<Value>,
+}
+
+impl JsonTracer {
+    /// Construct a new [JsonTracer] without any known locations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register locations for the code and the views of `contract`, which
+    /// must be the result of typechecking `script`. Locations are numbered in
+    /// prefix order starting from the root of `script`, as in the protocol.
+    ///
+    /// Locations are identified by addresses of the instructions, so
+    /// `contract` must not be moved or modified afterwards, or the locations
+    /// will be lost.
+    pub fn locate_script(&mut self, script: &Micheline, contract: &ContractScript) {
+        let (mut loc, items) = match script {
+            // top-level allows one level of nesting
+            Micheline::Seq([Micheline::Seq(items)]) => (2, items),
+            Micheline::Seq(items) => (1, items),
+            _ => return,
+        };
+        for item in items.iter() {
+            match item {
+                Micheline::App(Prim::code, [code], _) => {
+                    self.locate_instruction(code, loc + 1, &contract.code)
+                }
+                Micheline::App(Prim::view, [Micheline::String(name), header @ .., code], _) => {
+                    if let Some(view) = contract.views.get(name) {
+                        // the name, input and output types precede the code
+                        let code_loc = loc + 2 + node_count(header);
+                        self.locate_block(code, code_loc, &view.code);
+                    }
+                }
+                _ => {}
+            }
+            loc += node_count(std::slice::from_ref(item));
+        }
+    }
+
+    /// Register locations for `instr`, which must be the result of
+    /// typechecking `code`, the node at location `loc`. Locations of nested
+    /// instructions are numbered in prefix order.
+    ///
+    /// The same caveat as for [JsonTracer::locate_script] applies: `instr`
+    /// must not be moved or modified afterwards.
+    pub fn locate_instruction(&mut self, code: &Micheline, loc: usize, instr: &Instruction) {
+        self.locations.insert(key(instr), loc);
+        match instr {
+            Instruction::Seq(block) => self.locate_block(code, loc, block),
+            _ => {
+                let mut blocks = nested_blocks(instr).into_iter();
+                let mut arg_loc = loc + 1;
+                for arg in children(code) {
+                    if let Micheline::Seq(_) = arg {
+                        match blocks.next() {
+                            Some(block) => self.locate_block(arg, arg_loc, block),
+                            None => break,
+                        }
+                    }
+                    arg_loc += node_count(std::slice::from_ref(arg));
+                }
+            }
+        }
+    }
+
+    fn locate_block(&mut self, code: &Micheline, loc: usize, block: &[Instruction]) {
+        match code {
+            Micheline::Seq(items) if items.len() == block.len() => {
+                let mut item_loc = loc + 1;
+                for (item, instr) in items.iter().zip(block) {
+                    self.locate_instruction(item, item_loc, instr);
+                    item_loc += node_count(std::slice::from_ref(item));
+                }
+            }
+            // the code doesn't match the typechecked instructions, so the
+            // locations are unknown
+            _ => {}
+        }
+    }
+
+    /// Get the recorded trace as a JSON array.
+    pub fn to_json(&self) -> Value {
+        Value::Array(self.entries.clone())
+    }
+
+    /// Write the recorded trace as JSON to `writer`.
+    pub fn write(&self, writer: impl std::io::Write) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.entries)
+    }
+}
+
+impl<'a> Tracer<'a> for JsonTracer {
+    fn after_instruction(
+        &mut self,
+        instr: &Instruction<'a>,
+        stack: &IStack<'a>,
+        gas: &Gas,
+        result: Result<(), &InterpretError<'a>>,
+    ) {
+        if result.is_err() || matches!(instr, Instruction::Seq(_)) {
+            return;
+        }
+        let arena = Arena::new();
+        let mut entry = Map::new();
+        if let Some(loc) = self.locations.get(&key(instr)) {
+            entry.insert("location".to_owned(), Value::from(*loc));
+        }
+        entry.insert("gas".to_owned(), Value::String(gas.milligas().to_string()));
+        entry.insert(
+            "stack".to_owned(),
+            stack
+                .iter()
+                .map(|v| v.clone().into_micheline_optimized_legacy(&arena).to_json())
+                .collect(),
+        );
+        self.entries.push(Value::Object(entry));
+    }
+}
+
+fn key(instr: &Instruction) -> *const Instruction<'static> {
+    (instr as *const Instruction).cast()
+}
+
+/// Number of nodes in `nodes`, including all nested ones.
+fn node_count(nodes: &[Micheline]) -> usize {
+    nodes.iter().map(|n| 1 + node_count(children(n))).sum()
+}
+
+/// Direct children of a node.
+fn children<'b, 'a>(m: &'b Micheline<'a>) -> &'b [Micheline<'a>] {
+    match m {
+        Micheline::App(_, args, _) => args,
+        Micheline::Seq(items) => items,
+        _ => &[],
+    }
+}
+
+/// Nested instruction blocks, in the order they appear in the source.
+fn nested_blocks<'b, 'a>(instr: &'b Instruction<'a>) -> Vec<&'b [Instruction<'a>]> {
+    use Instruction as I;
+    match instr {
+        I::Dip(_, b) | I::Loop(b) | I::LoopLeft(b) | I::Iter(_, b) | I::Map(_, b) => vec![b],
+        I::If(b1, b2) | I::IfNone(b1, b2) | I::IfCons(b1, b2) | I::IfLeft(b1, b2) => {
+            vec![b1, b2]
+        }
+        I::Lambda(Lambda::Lambda { code, .. } | Lambda::LambdaRec { code, .. }) => vec![code],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::context::Ctx;
+    use crate::parser::test_helpers::parse;
+    use crate::stk;
+
+    fn run_traced(script: &str, parameter: &str, storage: &str) -> (Value, Result<(), ()>) {
+        let arena = Arena::new();
+        let script = parse(script).unwrap();
+        let mut ctx = Ctx::default();
+        let contract = script.typecheck_script(&mut ctx).unwrap();
+        let tracer = Rc::new(RefCell::new(JsonTracer::new()));
+        tracer.borrow_mut().locate_script(&script, &contract);
+        ctx.tracer = Some(Box::new(tracer.clone()));
+        let res = contract.interpret(
+            &mut ctx,
+            &arena,
+            parse(parameter).unwrap(),
+            parse(storage).unwrap(),
+        );
+        let trace = tracer.borrow().to_json();
+        (trace, res.map(|_| ()).map_err(|_| ()))
+    }
+
+    fn locations_and_stacks(trace: &Value) -> Vec<(Value, Value)> {
+        trace
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["location"].clone(), e["stack"].clone()))
+            .collect()
+    }
+
+    #[test]
+    fn trace_script() {
+        let (trace, res) = run_traced(
+            "{ parameter nat ; storage nat ; code { UNPAIR ; ADD ; NIL operation ; PAIR } }",
+            "1",
+            "2",
+        );
+        assert_eq!(res, Ok(()));
+        let gas = |i: usize| trace[i]["gas"].as_str().unwrap().parse::<u32>().unwrap();
+        assert!((1..4).all(|i| gas(i) < gas(i - 1)));
+        assert_eq!(
+            locations_and_stacks(&trace),
+            vec![
+                (json!(7), json!([{"int": "1"}, {"int": "2"}])),
+                (json!(8), json!([{"int": "3"}])),
+                (json!(9), json!([[], {"int": "3"}])),
+                (
+                    json!(11),
+                    json!([{"prim": "Pair", "args": [[], {"int": "3"}]}])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_nested() {
+        let (trace, res) = run_traced(
+            "{ parameter unit ; storage int ; \
+               code { CDR ; DIP { UNIT ; DROP } ; LAMBDA int int { PUSH int 1 ; ADD } ; \
+                      SWAP ; EXEC ; NIL operation ; PAIR } }",
+            "Unit",
+            "5",
+        );
+        assert_eq!(res, Ok(()));
+        let locations: Vec<_> = locations_and_stacks(&trace)
+            .into_iter()
+            .map(|(loc, _)| loc)
+            .collect();
+        assert_eq!(
+            locations,
+            [7, 10, 11, 8, 12, 20, 16, 19, 21, 22, 24].map(|l| json!(l))
+        );
+    }
+
+    #[test]
+    fn trace_failure() {
+        let (trace, res) = run_traced(
+            "{ parameter unit ; storage unit ; code { CDR ; FAILWITH } }",
+            "Unit",
+            "Unit",
+        );
+        assert_eq!(res, Err(()));
+        assert_eq!(trace.as_array().unwrap().len(), 1);
+        assert_eq!(trace[0]["location"], json!(7));
+    }
+
+    #[test]
+    fn trace_instruction_without_locations() {
+        let arena = Arena::new();
+        let mut ctx = Ctx::default();
+        let instr = parse("{ DROP ; UNIT }")
+            .unwrap()
+            .typecheck_instruction(&mut ctx, None, &[Micheline::prim0(Prim::unit)])
+            .unwrap();
+        let tracer = Rc::new(RefCell::new(JsonTracer::new()));
+        ctx.tracer = Some(Box::new(tracer.clone()));
+        ctx.gas = Gas::new(1000);
+        let mut stack = stk![crate::ast::TypedValue::Unit];
+        instr.interpret(&mut ctx, &arena, &mut stack).unwrap();
+        assert_eq!(
+            tracer.borrow().to_json(),
+            json!([
+                {"gas": "990", "stack": []},
+                {"gas": "980", "stack": [{"prim": "Unit"}]},
+            ])
+        );
+    }
+}
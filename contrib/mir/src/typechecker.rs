@@ -13,7 +13,7 @@ use chrono::prelude::DateTime;
 use num_bigint::{BigInt, BigUint, TryFromBigIntError};
 use num_traits::{Signed, Zero};
 use std::collections::hash_map::Entry;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::rc::Rc;
 use tezos_crypto_rs::{base58::FromBase58CheckError, hash::FromBytesError};
 
@@ -33,7 +33,9 @@ use crate::gas::OutOfGas;
 use crate::gas::{self, tc_cost, Gas};
 use crate::irrefutable_match::irrefutable_match;
 use crate::lexer::Prim;
+use crate::sapling::{SaplingState, SaplingStateId, Transaction};
 use crate::stack::*;
+use crate::timelock::{Chest, ChestKey};
 use crate::{ast::*, bls};
 
 /// Typechecker error type.
@@ -154,12 +156,38 @@ pub enum TcError {
     /// An error occurred when working with `big_map` storage.
     #[error("lazy storage error: {0:?}")]
     LazyStorageError(LazyStorageError),
+    /// `sapling_state` with the supplied identifier not found in the storage.
+    #[error("sapling state with ID {0} not found in the lazy storage")]
+    SaplingStateNotFound(BigInt),
+    /// Sapling types and `SAPLING_EMPTY_STATE` accept a memo size that must be
+    /// a natural between 0 and 65535 inclusive. Found an integer outside this
+    /// bounds instead.
+    #[error("expected a memo size between 0 and 65535, but got {0}")]
+    InvalidMemoSize(BigInt),
+    /// Memo sizes of sapling values didn't match.
+    #[error("sapling memo sizes do not match: {0} != {1}")]
+    MemoSizeMismatch(u16, u16),
+    /// `SAPLING_VERIFY_UPDATE` can't be used without a Sapling verifier, see
+    /// [crate::context::Ctx::sapling_verifier].
+    #[error("SAPLING_VERIFY_UPDATE is not supported: no sapling verifier is available, enable the `zcash` feature or provide one")]
+    SaplingVerifierUnavailable,
     /// Output stack after `MAP` instruction's code block is empty.
     #[error("MAP block returned an empty stack")]
     MapBlockEmptyStack,
     /// All branches of a `MAP` instruction's code block are failing.
     #[error("all branches of a MAP block use FAILWITH, its type cannot be inferred")]
     MapBlockFail,
+    /// View name is either too long or contains forbidden characters.
+    #[error("invalid view name: {0}")]
+    InvalidViewName(String),
+    /// When typechecking a complete script, encountered two views with the
+    /// same name.
+    #[error("duplicate view name: {0}")]
+    DuplicateViewName(String),
+    /// Encountered an instruction which is forbidden in view code, viz.
+    /// `CREATE_CONTRACT`, `SET_DELEGATE` or `TRANSFER_TOKENS`.
+    #[error("instruction {0} is forbidden in views")]
+    ForbiddenInView(Prim),
 }
 
 /// Errors happening when typechecking a value of type `chain_id`.
@@ -288,16 +316,95 @@ impl<'a> Micheline<'a> {
     /// Typecheck the contract script. Validates the script's types, then
     /// typechecks the code and checks the result stack is as expected. Returns
     /// typechecked script.
+    ///
+    /// Global constants are not supported here, expand them first with
+    /// [Micheline::expand_constants].
     pub fn typecheck_script(&self, ctx: &mut Ctx) -> Result<ContractScript<'a>, TcError> {
+        let ScriptFields {
+            parameter: parameter_ty,
+            storage: storage_ty,
+            code,
+            views,
+        } = self.script_fields()?;
+        let (entrypoints, parameter) = parse_parameter_ty_with_entrypoints(
+            ctx,
+            parameter_ty.ok_or(TcError::MissingTopLevelElt(Prim::parameter))?,
+        )?;
+        let storage = storage_ty
+            .ok_or(TcError::MissingTopLevelElt(Prim::storage))?
+            .parse_ty(ctx)?;
+        parameter.ensure_prop(&mut ctx.gas, TypeProperty::Passable)?;
+        storage.ensure_prop(&mut ctx.gas, TypeProperty::Storable)?;
+        let mut stack = tc_stk![Type::new_pair(parameter.clone(), storage.clone())];
+        let code = typecheck_instruction(
+            code.ok_or(TcError::MissingTopLevelElt(Prim::code))?,
+            ctx,
+            Some(&entrypoints),
+            &mut stack,
+        )?;
+        unify_stacks(
+            ctx,
+            &mut tc_stk![Type::new_pair(
+                Type::new_list(Type::Operation),
+                storage.clone()
+            )],
+            stack,
+        )?;
+        let mut typechecked_views = HashMap::new();
+        for (name, input, output, code) in views {
+            validate_view_name(name)?;
+            if typechecked_views.contains_key(name) {
+                return Err(TcError::DuplicateViewName(name.clone()));
+            }
+            let view = typecheck_view(ctx, &storage, input, output, code)?;
+            typechecked_views.insert(name.clone(), view);
+        }
+        Ok(ContractScript {
+            code,
+            parameter,
+            storage,
+            views: typechecked_views,
+        })
+    }
+
+    /// Look up the on-chain view `name` in the contract script and typecheck
+    /// it, as done by the `VIEW` instruction. Like in the Tezos protocol, the
+    /// lookup is charged as a map access over the views of the script, and the
+    /// view is typechecked anew. Returns [None] if the script has no such
+    /// view.
+    pub(crate) fn typecheck_view_by_name(
+        &self,
+        ctx: &mut Ctx,
+        name: &str,
+    ) -> Result<Option<View<'a>>, TcError> {
+        let ScriptFields { storage, views, .. } = self.script_fields()?;
+        ctx.gas
+            .consume(gas::interpret_cost::view_get(name, views.len())?)?;
+        let Some((_, input, output, code)) = views.into_iter().find(|view| view.0 == name) else {
+            return Ok(None);
+        };
+        let storage = storage
+            .ok_or(TcError::MissingTopLevelElt(Prim::storage))?
+            .parse_ty(ctx)?;
+        typecheck_view(ctx, &storage, input, output, code).map(Some)
+    }
+
+    /// Split a contract script into its top-level fields, checking that there
+    /// are no unexpected or duplicate fields. Views are returned as
+    /// `(name, input, output, code)`.
+    fn script_fields(&self) -> Result<ScriptFields<'_, 'a>, TcError> {
         let seq = match self {
             // top-level allows one level of nesting
             Micheline::Seq([Micheline::Seq(seq)]) => seq,
             Micheline::Seq(seq) => seq,
             x => return Err(TcError::UnexpectedMicheline(format!("{x:?}"))),
         };
-        let mut parameter_ty = None;
-        let mut storage_ty = None;
-        let mut code = None;
+        let mut fields = ScriptFields {
+            parameter: None,
+            storage: None,
+            code: None,
+            views: Vec::new(),
+        };
         fn set_if_none<T>(elt: Prim, var: &mut Option<T>, value: T) -> Result<(), TcError> {
             if var.is_none() {
                 *var = Some(value);
@@ -309,14 +416,19 @@ impl<'a> Micheline<'a> {
         for elt in seq.iter() {
             match elt {
                 Micheline::App(Prim::code, [content], anns) if anns.is_empty() => {
-                    set_if_none(Prim::code, &mut code, content)?
+                    set_if_none(Prim::code, &mut fields.code, content)?
                 }
                 Micheline::App(Prim::parameter, [content], anns) if anns.is_empty() => {
-                    set_if_none(Prim::parameter, &mut parameter_ty, content)?
+                    set_if_none(Prim::parameter, &mut fields.parameter, content)?
                 }
                 Micheline::App(Prim::storage, [content], anns) if anns.is_empty() => {
-                    set_if_none(Prim::storage, &mut storage_ty, content)?
+                    set_if_none(Prim::storage, &mut fields.storage, content)?
                 }
+                Micheline::App(
+                    Prim::view,
+                    [Micheline::String(name), input, output, code],
+                    anns,
+                ) if anns.is_empty() => fields.views.push((name, input, output, code)),
                 Micheline::Seq(..)
                 | micheline_instructions!()
                 | micheline_literals!()
@@ -327,35 +439,131 @@ impl<'a> Micheline<'a> {
                 }
             }
         }
-        let (entrypoints, parameter) = parse_parameter_ty_with_entrypoints(
-            ctx,
-            parameter_ty.ok_or(TcError::MissingTopLevelElt(Prim::parameter))?,
-        )?;
-        let storage = storage_ty
-            .ok_or(TcError::MissingTopLevelElt(Prim::storage))?
-            .parse_ty(ctx)?;
-        parameter.ensure_prop(&mut ctx.gas, TypeProperty::Passable)?;
-        storage.ensure_prop(&mut ctx.gas, TypeProperty::Storable)?;
-        let mut stack = tc_stk![Type::new_pair(parameter.clone(), storage.clone())];
-        let code = typecheck_instruction(
-            code.ok_or(TcError::MissingTopLevelElt(Prim::code))?,
-            ctx,
-            Some(&entrypoints),
-            &mut stack,
-        )?;
-        unify_stacks(
-            ctx,
-            &mut tc_stk![Type::new_pair(
-                Type::new_list(Type::Operation),
-                storage.clone()
-            )],
-            stack,
-        )?;
-        Ok(ContractScript {
-            code,
-            parameter,
-            storage,
-        })
+        Ok(fields)
+    }
+}
+
+/// Top-level fields of a contract script, see [Micheline::script_fields].
+struct ScriptFields<'m, 'a> {
+    parameter: Option<&'m Micheline<'a>>,
+    storage: Option<&'m Micheline<'a>>,
+    code: Option<&'m Micheline<'a>>,
+    views: Vec<UntypedView<'m, 'a>>,
+}
+
+/// A `view` field of a contract script, as `(name, input, output, code)`.
+type UntypedView<'m, 'a> = (
+    &'m String,
+    &'m Micheline<'a>,
+    &'m Micheline<'a>,
+    &'m Micheline<'a>,
+);
+
+/// Views names must be at most 31 characters long, and consist of
+/// alphanumeric characters, `_`, `.`, `%` or `@`.
+fn validate_view_name(name: &str) -> Result<(), TcError> {
+    const MAX_VIEW_NAME_LEN: usize = 31;
+    if name.len() <= MAX_VIEW_NAME_LEN
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'%' | b'@'))
+    {
+        Ok(())
+    } else {
+        Err(TcError::InvalidViewName(name.to_owned()))
+    }
+}
+
+/// Typecheck a single on-chain view of a contract with the given storage type.
+/// View code runs on a stack containing `pair input storage` and must leave
+/// exactly one element of the output type. `SELF` is forbidden in views, as
+/// well as instructions emitting operations, see [ensure_allowed_in_view].
+fn typecheck_view<'a>(
+    ctx: &mut Ctx,
+    storage: &Type,
+    input: &Micheline,
+    output: &Micheline,
+    code: &Micheline<'a>,
+) -> Result<View<'a>, TcError> {
+    let input_type = parse_ty(ctx, input)?;
+    input_type.ensure_prop(&mut ctx.gas, TypeProperty::Packable)?;
+    let output_type = parse_ty(ctx, output)?;
+    output_type.ensure_prop(&mut ctx.gas, TypeProperty::Packable)?;
+    let mut stack = tc_stk![Type::new_pair(input_type.clone(), storage.clone())];
+    let code = match code {
+        Micheline::Seq(instrs) => typecheck(instrs, ctx, None, &mut stack)?,
+        instr => vec![typecheck_instruction(instr, ctx, None, &mut stack)?],
+    };
+    unify_stacks(ctx, &mut tc_stk![output_type.clone()], stack)?;
+    ensure_allowed_in_view(&code)?;
+    Ok(View {
+        input_type,
+        output_type,
+        code: code.into(),
+    })
+}
+
+/// Check that view code doesn't use `CREATE_CONTRACT`, `SET_DELEGATE` or
+/// `TRANSFER_TOKENS`. Bodies of lambdas defined or pushed by the view are
+/// inspected too, as they are forbidden there as well.
+fn ensure_allowed_in_view(code: &[Instruction]) -> Result<(), TcError> {
+    use Instruction as I;
+    code.iter().try_for_each(|i| match i {
+        I::CreateContract(..) => Err(TcError::ForbiddenInView(Prim::CREATE_CONTRACT)),
+        I::SetDelegate => Err(TcError::ForbiddenInView(Prim::SET_DELEGATE)),
+        I::TransferTokens => Err(TcError::ForbiddenInView(Prim::TRANSFER_TOKENS)),
+        I::Dip(_, nested)
+        | I::Loop(nested)
+        | I::LoopLeft(nested)
+        | I::Iter(_, nested)
+        | I::Map(_, nested)
+        | I::Seq(nested) => ensure_allowed_in_view(nested),
+        I::If(l, r) | I::IfNone(l, r) | I::IfCons(l, r) | I::IfLeft(l, r) => {
+            ensure_allowed_in_view(l)?;
+            ensure_allowed_in_view(r)
+        }
+        I::Lambda(lam) => ensure_lambda_allowed_in_view(lam),
+        I::Push(val) => ensure_value_allowed_in_view(val),
+        _ => Ok(()),
+    })
+}
+
+fn ensure_lambda_allowed_in_view(lam: &Lambda) -> Result<(), TcError> {
+    match lam {
+        Lambda::Lambda { code, .. } | Lambda::LambdaRec { code, .. } => {
+            ensure_allowed_in_view(code)
+        }
+    }
+}
+
+fn ensure_closure_allowed_in_view(closure: &Closure) -> Result<(), TcError> {
+    match closure {
+        Closure::Lambda(lam) => ensure_lambda_allowed_in_view(lam),
+        Closure::Apply {
+            arg_val, closure, ..
+        } => {
+            ensure_value_allowed_in_view(arg_val)?;
+            ensure_closure_allowed_in_view(closure)
+        }
+    }
+}
+
+/// Check the lambdas contained in a pushed value, see [ensure_allowed_in_view].
+fn ensure_value_allowed_in_view(val: &TypedValue) -> Result<(), TcError> {
+    use TypedValue as V;
+    match val {
+        V::Lambda(closure) => ensure_closure_allowed_in_view(closure),
+        V::Pair(p) => {
+            ensure_value_allowed_in_view(&p.0)?;
+            ensure_value_allowed_in_view(&p.1)
+        }
+        V::Or(or) => match or.as_ref() {
+            Or::Left(v) | Or::Right(v) => ensure_value_allowed_in_view(v),
+        },
+        V::Option(Some(v)) => ensure_value_allowed_in_view(v),
+        V::List(vs) => vs.iter().try_for_each(ensure_value_allowed_in_view),
+        V::Map(m) => m.values().try_for_each(ensure_value_allowed_in_view),
+        _ => Ok(()),
     }
 }
 
@@ -502,6 +710,20 @@ fn parse_ty_with_entrypoints(
         App(bls12_381_g2, [], _) => Type::Bls12381G2,
         App(bls12_381_g2, ..) => unexpected()?,
 
+        App(chest, [], _) => Type::Chest,
+        App(chest, ..) => unexpected()?,
+
+        App(chest_key, [], _) => Type::ChestKey,
+        App(chest_key, ..) => unexpected()?,
+
+        App(sapling_state, [Micheline::Int(ms)], _) => Type::SaplingState(validate_memo_size(ms)?),
+        App(sapling_state, ..) => unexpected()?,
+
+        App(sapling_transaction, [Micheline::Int(ms)], _) => {
+            Type::SaplingTransaction(validate_memo_size(ms)?)
+        }
+        App(sapling_transaction, ..) => unexpected()?,
+
         Seq(..)
         | micheline_fields!()
         | micheline_instructions!()
@@ -1981,6 +2203,22 @@ pub(crate) fn typecheck_instruction<'a>(
         (App(CONTRACT, [_], _), []) => no_overload!(CONTRACT, len 1),
         (App(CONTRACT, expect_args!(1), _), _) => unexpected_micheline!(),
 
+        (App(VIEW, [String(name), t], _), [.., T::Address, _]) => {
+            validate_view_name(name)?;
+            let output_ty = parse_ty(ctx, t)?;
+            output_ty.ensure_prop(&mut ctx.gas, TypeProperty::Packable)?;
+            let input_ty = pop!();
+            stack[0] = T::new_option(output_ty.clone());
+            I::View {
+                name: name.clone(),
+                input_ty,
+                output_ty,
+            }
+        }
+        (App(VIEW, [String(_), _], _), [.., _, _]) => no_overload!(VIEW),
+        (App(VIEW, [String(_), _], _), [] | [_]) => no_overload!(VIEW, len 2),
+        (App(VIEW, _, _), _) => unexpected_micheline!(),
+
         (App(LEVEL, [], _), ..) => {
             stack.push(T::Nat);
             I::Level
@@ -2095,6 +2333,42 @@ pub(crate) fn typecheck_instruction<'a>(
         (App(PAIRING_CHECK, [], _), []) => no_overload!(PAIRING_CHECK, len 1),
         (App(PAIRING_CHECK, expect_args!(0), _), _) => unexpected_micheline!(),
 
+        (App(OPEN_CHEST, [], _), [.., T::Nat, T::Chest, T::ChestKey]) => {
+            stack.drop_top(2);
+            stack[0] = T::new_option(T::Bytes);
+            I::OpenChest
+        }
+        (App(OPEN_CHEST, [], _), [.., _, _, _]) => no_overload!(OPEN_CHEST),
+        (App(OPEN_CHEST, [], _), [] | [_] | [_, _]) => no_overload!(OPEN_CHEST, len 3),
+        (App(OPEN_CHEST, expect_args!(0), _), _) => unexpected_micheline!(),
+
+        (App(SAPLING_EMPTY_STATE, [Micheline::Int(ms)], _), _) => {
+            let ms = validate_memo_size(ms)?;
+            stack.push(T::SaplingState(ms));
+            I::SaplingEmptyState(ms)
+        }
+        (App(SAPLING_EMPTY_STATE, [_], _), _) => unexpected_micheline!(),
+        (App(SAPLING_EMPTY_STATE, expect_args!(1), _), _) => unexpected_micheline!(),
+
+        (
+            App(SAPLING_VERIFY_UPDATE, [], _),
+            [.., T::SaplingState(state_ms), T::SaplingTransaction(tx_ms)],
+        ) => {
+            if state_ms != tx_ms {
+                return Err(TcError::MemoSizeMismatch(*state_ms, *tx_ms));
+            }
+            if !ctx.sapling_verifier.is_available() {
+                return Err(TcError::SaplingVerifierUnavailable);
+            }
+            let state = T::SaplingState(*state_ms);
+            stack.pop();
+            stack[0] = T::new_option(T::new_pair(T::Bytes, T::new_pair(T::Int, state)));
+            I::SaplingVerifyUpdate
+        }
+        (App(SAPLING_VERIFY_UPDATE, [], _), [.., _, _]) => no_overload!(SAPLING_VERIFY_UPDATE),
+        (App(SAPLING_VERIFY_UPDATE, [], _), [] | [_]) => no_overload!(SAPLING_VERIFY_UPDATE, len 2),
+        (App(SAPLING_VERIFY_UPDATE, expect_args!(0), _), _) => unexpected_micheline!(),
+
         (App(CREATE_CONTRACT, [cs], _), [.., new_storage, T::Mutez, T::Option(opt_keyhash)])
             if matches!(opt_keyhash.as_ref(), Type::KeyHash) =>
         {
@@ -2273,8 +2547,8 @@ pub(crate) fn typecheck_value<'a>(
                     .map_err(TcError::LazyStorageError)?
                     .ok_or(TcError::BigMapNotFound(id))?;
 
-                ensure_ty_eq(&mut ctx.gas, key_type, tk)?;
-                ensure_ty_eq(&mut ctx.gas, value_type, tv)?;
+                ensure_ty_eq(&mut ctx.gas, &key_type, tk)?;
+                ensure_ty_eq(&mut ctx.gas, &value_type, tv)?;
                 Some(big_map_id)
             } else {
                 None
@@ -2407,6 +2681,40 @@ pub(crate) fn typecheck_value<'a>(
             ctx.gas.consume(gas::tc_cost::BLS_G2)?;
             TV::new_bls12381_g2(bls::G2::from_bytes(bs).ok_or_else(|| invalid_value_for_type!())?)
         }
+        (T::Chest, V::Bytes(bs)) => {
+            ctx.gas.consume(gas::tc_cost::chest(bs.len())?)?;
+            TV::new_chest(Chest::decode(bs).map_err(|_| invalid_value_for_type!())?)
+        }
+        (T::ChestKey, V::Bytes(bs)) => {
+            ctx.gas.consume(gas::tc_cost::CHEST_KEY)?;
+            TV::new_chest_key(ChestKey::decode(bs).map_err(|_| invalid_value_for_type!())?)
+        }
+        (T::SaplingTransaction(ms), V::Bytes(bs)) => {
+            let tx = Transaction::decode(bs).map_err(|_| invalid_value_for_type!())?;
+            // NB: a transaction without outputs has no memo size to check.
+            if let Some(tx_ms) = tx.memo_size() {
+                if tx_ms != *ms {
+                    return Err(TcError::MemoSizeMismatch(*ms, tx_ms));
+                }
+            }
+            TV::new_sapling_transaction(tx)
+        }
+        (T::SaplingState(ms), V::Int(id)) => {
+            let state_id = SaplingStateId(id.clone());
+            let state_ms = ctx
+                .sapling_storage
+                .sapling_get_memo_size(&state_id)
+                .map_err(TcError::LazyStorageError)?
+                .ok_or_else(|| TcError::SaplingStateNotFound(id.clone()))?;
+            if state_ms != *ms {
+                return Err(TcError::MemoSizeMismatch(*ms, state_ms));
+            }
+            TV::SaplingState(SaplingState {
+                id: Some(state_id),
+                ..SaplingState::empty(*ms)
+            })
+        }
+        (T::SaplingState(ms), V::Seq([])) => TV::SaplingState(SaplingState::empty(*ms)),
         (_, _) => return Err(invalid_value_for_type!()),
     })
 }
@@ -2483,6 +2791,10 @@ fn validate_u10(n: &BigInt) -> Result<u16, TcError> {
     Ok(res)
 }
 
+fn validate_memo_size(n: &BigInt) -> Result<u16, TcError> {
+    u16::try_from(n).map_err(|_| TcError::InvalidMemoSize(n.clone()))
+}
+
 /// An iterator that ensures the keys to be in strictly ascending order.
 /// (where you specify a getter to obtain the key from an element).
 ///
@@ -6014,7 +6326,8 @@ mod typecheck_tests {
             Ok(ContractScript {
                 parameter: Type::new_contract(Type::Unit),
                 storage: Type::Unit,
-                code: Seq(vec![Drop(None), Unit, Failwith(Type::Unit)])
+                code: Seq(vec![Drop(None), Unit, Failwith(Type::Unit)]),
+                views: HashMap::new(),
             })
         );
     }
@@ -6443,7 +6756,8 @@ mod typecheck_tests {
                     ISelf("foo".try_into().unwrap()),
                     Unit,
                     Failwith(Type::Unit)
-                ])
+                ]),
+                views: HashMap::new(),
             })
         );
     }
@@ -6487,7 +6801,8 @@ mod typecheck_tests {
                     ISelf("default".try_into().unwrap()),
                     Unit,
                     Failwith(Type::Unit)
-                ])
+                ]),
+                views: HashMap::new(),
             })
         );
     }
@@ -6777,6 +7092,283 @@ mod typecheck_tests {
         too_short_test(&app!(PAIRING_CHECK), Prim::PAIRING_CHECK, 1)
     }
 
+    #[test]
+    fn open_chest() {
+        let mut stack = tc_stk![Type::Nat, Type::Chest, Type::ChestKey];
+        assert_eq!(
+            typecheck_instruction(
+                &parse("OPEN_CHEST").unwrap(),
+                &mut Ctx::default(),
+                &mut stack
+            ),
+            Ok(OpenChest)
+        );
+        assert_eq!(stack, tc_stk![Type::new_option(Type::Bytes)]);
+    }
+
+    #[test]
+    fn open_chest_wrong_type() {
+        let mut stack = tc_stk![Type::Nat, Type::ChestKey, Type::Chest];
+        assert_eq!(
+            typecheck_instruction(
+                &parse("OPEN_CHEST").unwrap(),
+                &mut Ctx::default(),
+                &mut stack
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::OPEN_CHEST,
+                stack: stk![Type::Nat, Type::ChestKey, Type::Chest],
+                reason: None,
+            })
+        );
+    }
+
+    #[test]
+    fn open_chest_too_short() {
+        too_short_test(&app!(OPEN_CHEST), Prim::OPEN_CHEST, 3)
+    }
+
+    #[test]
+    fn push_chest() {
+        use crate::timelock::tests::{chest, chest_key, CHEST, CHEST_KEY};
+        let mut stack = tc_stk![];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(&format!("PUSH chest 0x{CHEST}")).unwrap(),
+                &mut Ctx::default(),
+                &mut stack
+            ),
+            Ok(Push(TypedValue::new_chest(chest(CHEST))))
+        );
+        assert_eq!(stack, tc_stk![Type::Chest]);
+        let mut stack = tc_stk![];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(&format!("PUSH chest_key 0x{CHEST_KEY}")).unwrap(),
+                &mut Ctx::default(),
+                &mut stack
+            ),
+            Ok(Push(TypedValue::new_chest_key(chest_key(CHEST_KEY))))
+        );
+        assert_eq!(stack, tc_stk![Type::ChestKey]);
+    }
+
+    #[test]
+    fn push_chest_invalid() {
+        // the payload is shorter than the authentication tag
+        let hex_val = format!("02{}00000010{}", "00".repeat(24), "00".repeat(16));
+        assert_eq!(
+            typecheck_instruction(
+                &parse(&format!("PUSH chest 0x{hex_val}")).unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::InvalidValueForType(
+                format!("{:?}", Micheline::Bytes(hex::decode(&hex_val).unwrap())),
+                Type::Chest,
+            ))
+        );
+        // the nonce is zero
+        assert_eq!(
+            typecheck_instruction(
+                &parse("PUSH chest_key 0x02010100").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::InvalidValueForType(
+                "Bytes([2, 1, 1, 0])".into(),
+                Type::ChestKey,
+            ))
+        );
+    }
+
+    #[test]
+    fn chest_not_comparable() {
+        assert_eq!(
+            typecheck_instruction(
+                &parse("COMPARE").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![Type::Chest, Type::Chest]
+            ),
+            Err(TcError::InvalidTypeProperty(
+                TypeProperty::Comparable,
+                Type::Chest
+            ))
+        );
+    }
+
+    #[test]
+    fn sapling_types() {
+        let ctx = &mut Ctx::default();
+        assert_eq!(
+            parse("sapling_state 8").unwrap().parse_ty(ctx),
+            Ok(Type::SaplingState(8))
+        );
+        assert_eq!(
+            parse("sapling_transaction 0").unwrap().parse_ty(ctx),
+            Ok(Type::SaplingTransaction(0))
+        );
+        assert_eq!(
+            parse("sapling_state 65536").unwrap().parse_ty(ctx),
+            Err(TcError::InvalidMemoSize(65536.into()))
+        );
+        assert_eq!(
+            parse("sapling_transaction -1").unwrap().parse_ty(ctx),
+            Err(TcError::InvalidMemoSize((-1).into()))
+        );
+        assert_eq!(
+            parse("sapling_transaction_deprecated 8")
+                .unwrap()
+                .parse_ty(ctx),
+            Err(TcError::TodoType(Prim::sapling_transaction_deprecated))
+        );
+    }
+
+    #[test]
+    fn sapling_empty_state() {
+        let mut stack = tc_stk![];
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_EMPTY_STATE 8").unwrap(),
+                &mut Ctx::default(),
+                &mut stack
+            ),
+            Ok(SaplingEmptyState(8))
+        );
+        assert_eq!(stack, tc_stk![Type::SaplingState(8)]);
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_EMPTY_STATE 65536").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::InvalidMemoSize(65536.into()))
+        );
+    }
+
+    #[test]
+    fn sapling_verify_update() {
+        use crate::sapling::tests::MockVerifier;
+        let mut ctx = Ctx::default();
+        ctx.sapling_verifier = Box::new(MockVerifier(true));
+        let mut stack = tc_stk![Type::SaplingState(8), Type::SaplingTransaction(8)];
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_VERIFY_UPDATE").unwrap(),
+                &mut ctx,
+                &mut stack
+            ),
+            Ok(SaplingVerifyUpdate)
+        );
+        assert_eq!(
+            stack,
+            tc_stk![Type::new_option(Type::new_pair(
+                Type::Bytes,
+                Type::new_pair(Type::Int, Type::SaplingState(8))
+            ))]
+        );
+    }
+
+    #[test]
+    fn sapling_verify_update_wrong_type() {
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_VERIFY_UPDATE").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![Type::SaplingState(8), Type::SaplingTransaction(4)]
+            ),
+            Err(TcError::MemoSizeMismatch(8, 4))
+        );
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_VERIFY_UPDATE").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![Type::SaplingTransaction(8), Type::SaplingState(8)]
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::SAPLING_VERIFY_UPDATE,
+                stack: stk![Type::SaplingTransaction(8), Type::SaplingState(8)],
+                reason: None,
+            })
+        );
+    }
+
+    #[test]
+    fn sapling_verify_update_unavailable() {
+        use crate::sapling::UnavailableSaplingVerifier;
+        let mut ctx = Ctx::default();
+        ctx.sapling_verifier = Box::new(UnavailableSaplingVerifier);
+        assert_eq!(
+            typecheck_instruction(
+                &parse("SAPLING_VERIFY_UPDATE").unwrap(),
+                &mut ctx,
+                &mut tc_stk![Type::SaplingState(8), Type::SaplingTransaction(8)]
+            ),
+            Err(TcError::SaplingVerifierUnavailable)
+        );
+    }
+
+    #[test]
+    fn sapling_verify_update_too_short() {
+        too_short_test(&app!(SAPLING_VERIFY_UPDATE), Prim::SAPLING_VERIFY_UPDATE, 2)
+    }
+
+    #[test]
+    fn sapling_values() {
+        use crate::sapling::{tests::*, SaplingState, EMPTY_ROOT};
+        let mut ctx = Ctx::default();
+        let id = ctx.sapling_storage.sapling_new(8).unwrap();
+        assert_eq!(
+            typecheck_value(
+                &Micheline::Int(id.0.clone()),
+                &mut ctx,
+                &Type::SaplingState(8)
+            ),
+            Ok(TypedValue::SaplingState(SaplingState {
+                id: Some(id.clone()),
+                ..SaplingState::empty(8)
+            }))
+        );
+        assert_eq!(
+            typecheck_value(&Micheline::Int(id.0), &mut ctx, &Type::SaplingState(4)),
+            Err(TcError::MemoSizeMismatch(4, 8))
+        );
+        assert_eq!(
+            typecheck_value(&Micheline::Int(5.into()), &mut ctx, &Type::SaplingState(8)),
+            Err(TcError::SaplingStateNotFound(5.into()))
+        );
+        assert_eq!(
+            typecheck_value(&Micheline::Seq(&[]), &mut ctx, &Type::SaplingState(8)),
+            Ok(TypedValue::SaplingState(SaplingState::empty(8)))
+        );
+
+        let tx = transaction(vec![input(1)], vec![output(2, 8)], 0, EMPTY_ROOT);
+        let bytes = Micheline::Bytes(tx.encode());
+        assert_eq!(
+            typecheck_value(&bytes, &mut ctx, &Type::SaplingTransaction(8)),
+            Ok(TypedValue::new_sapling_transaction(tx))
+        );
+        assert_eq!(
+            typecheck_value(&bytes, &mut ctx, &Type::SaplingTransaction(4)),
+            Err(TcError::MemoSizeMismatch(4, 8))
+        );
+    }
+
+    #[test]
+    fn sapling_state_not_pushable() {
+        assert_eq!(
+            typecheck_instruction(
+                &parse("PUSH (sapling_state 8) {}").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::InvalidTypeProperty(
+                TypeProperty::Pushable,
+                Type::SaplingState(8)
+            ))
+        );
+    }
+
     mod mul {
         use super::*;
         use Type as T;
@@ -8033,4 +8625,186 @@ mod typecheck_tests {
             })
         );
     }
+
+    #[test]
+    fn script_with_views() {
+        let mut ctx = Ctx::default();
+        assert_eq!(
+            parse_contract_script(concat!(
+                "parameter unit;",
+                "storage nat;",
+                "code { CDR; NIL operation; PAIR };",
+                r#"view "add" nat nat { UNPAIR; ADD };"#,
+                r#"view "get" unit nat { CDR };"#,
+            ))
+            .unwrap()
+            .typecheck_script(&mut ctx),
+            Ok(ContractScript {
+                parameter: Type::Unit,
+                storage: Type::Nat,
+                code: Seq(vec![Cdr, Nil, Pair]),
+                views: HashMap::from([
+                    (
+                        "add".to_owned(),
+                        crate::ast::View {
+                            input_type: Type::Nat,
+                            output_type: Type::Nat,
+                            code: vec![Unpair, Add(overloads::Add::NatNat)].into(),
+                        }
+                    ),
+                    (
+                        "get".to_owned(),
+                        crate::ast::View {
+                            input_type: Type::Unit,
+                            output_type: Type::Nat,
+                            code: vec![Cdr].into(),
+                        }
+                    ),
+                ]),
+            })
+        );
+    }
+
+    #[test]
+    fn script_with_views_errors() {
+        #[track_caller]
+        fn check(view: &str, err: TcError) {
+            let src =
+                format!("parameter unit; storage nat; code {{ CDR; NIL operation; PAIR }}; {view}");
+            assert_eq!(
+                parse_contract_script(&src)
+                    .unwrap()
+                    .typecheck_script(&mut Ctx::default()),
+                Err(err)
+            );
+        }
+        check(
+            r#"view "a" unit nat { CDR }; view "a" unit nat { CDR }"#,
+            TcError::DuplicateViewName("a".to_owned()),
+        );
+        check(
+            r#"view "a-b" unit nat { CDR }"#,
+            TcError::InvalidViewName("a-b".to_owned()),
+        );
+        check(
+            r#"view "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" unit nat { CDR }"#,
+            TcError::InvalidViewName("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_owned()),
+        );
+        check(
+            r#"view "a" unit int { CDR }"#,
+            TcError::StacksNotEqual(
+                stk![Type::Int],
+                stk![Type::Nat],
+                TypesNotEqual(Type::Int, Type::Nat).into(),
+            ),
+        );
+        check(
+            r#"view "a" unit nat { DROP; SELF; DROP; PUSH nat 1 }"#,
+            TcError::SelfForbidden,
+        );
+        check(
+            r#"view "a" unit nat { CDR; DIP { NONE key_hash; SET_DELEGATE; DROP } }"#,
+            TcError::ForbiddenInView(Prim::SET_DELEGATE),
+        );
+        check(
+            r#"view "a" unit nat { CDR; NONE key_hash; SET_DELEGATE; DROP }"#,
+            TcError::ForbiddenInView(Prim::SET_DELEGATE),
+        );
+        check(
+            r#"view "a" unit nat { CDR; LAMBDA unit operation { DROP; NONE key_hash; SET_DELEGATE }; DROP }"#,
+            TcError::ForbiddenInView(Prim::SET_DELEGATE),
+        );
+        check(
+            r#"view "a" unit nat { CDR; LAMBDA_REC unit operation { DROP; DROP; NONE key_hash; SET_DELEGATE }; DROP }"#,
+            TcError::ForbiddenInView(Prim::SET_DELEGATE),
+        );
+        check(
+            r#"view "a" unit nat { CDR; PUSH (pair nat (list (lambda unit operation))) (Pair 1 { { DROP; NONE key_hash; SET_DELEGATE } }); DROP }"#,
+            TcError::ForbiddenInView(Prim::SET_DELEGATE),
+        );
+        check(
+            r#"view "a" unit (big_map nat nat) { DROP; EMPTY_BIG_MAP nat nat }"#,
+            TcError::InvalidTypeProperty(
+                TypeProperty::Packable,
+                Type::new_big_map(Type::Nat, Type::Nat),
+            ),
+        );
+    }
+
+    #[test]
+    fn view_instr() {
+        let stk = &mut tc_stk![Type::Address, Type::Unit];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(r#"VIEW "get" nat"#).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Ok(Instruction::View {
+                name: "get".to_owned(),
+                input_ty: Type::Unit,
+                output_ty: Type::Nat,
+            })
+        );
+        assert_eq!(stk, &tc_stk![Type::new_option(Type::Nat)]);
+    }
+
+    #[test]
+    fn view_instr_errors() {
+        let stk = &mut tc_stk![Type::Nat, Type::Unit];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(r#"VIEW "get" nat"#).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::VIEW,
+                stack: stk![Type::Nat, Type::Unit],
+                reason: None,
+            })
+        );
+        let stk = &mut tc_stk![Type::Unit];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(r#"VIEW "get" nat"#).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::VIEW,
+                stack: stk![Type::Unit],
+                reason: Some(NoMatchingOverloadReason::StackTooShort { expected: 2 }),
+            })
+        );
+        let stk = &mut tc_stk![Type::Address, Type::Unit];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(r#"VIEW "g-t" nat"#).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Err(TcError::InvalidViewName("g-t".to_owned()))
+        );
+        let stk = &mut tc_stk![Type::Address, Type::Unit];
+        assert_eq!(
+            typecheck_instruction(
+                &parse(r#"VIEW "get" operation"#).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Err(TcError::InvalidTypeProperty(
+                TypeProperty::Packable,
+                Type::Operation
+            ))
+        );
+        let stk = &mut tc_stk![Type::Address, Type::Unit];
+        assert_eq!(
+            typecheck_instruction(&parse("VIEW 1 nat").unwrap(), &mut Ctx::default(), stk),
+            Err(TcError::UnexpectedMicheline(format!(
+                "{:?}",
+                parse("VIEW 1 nat").unwrap()
+            )))
+        );
+    }
 }
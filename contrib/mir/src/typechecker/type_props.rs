@@ -61,7 +61,7 @@ impl Type {
                 | TypeProperty::Packable => return invalid_type_prop(),
                 TypeProperty::Passable | TypeProperty::Storable | TypeProperty::BigMapValue => (),
             },
-            Bls12381Fr | Bls12381G1 | Bls12381G2 => match prop {
+            Bls12381Fr | Bls12381G1 | Bls12381G2 | Chest | ChestKey => match prop {
                 TypeProperty::Comparable => return invalid_type_prop(),
                 TypeProperty::Passable
                 | TypeProperty::Storable
@@ -111,6 +111,22 @@ impl Type {
                 | TypeProperty::BigMapValue
                 | TypeProperty::Duplicable => p.1.ensure_prop(gas, prop)?,
             },
+            SaplingState(_) => match prop {
+                TypeProperty::Comparable
+                | TypeProperty::BigMapValue
+                | TypeProperty::Packable
+                | TypeProperty::Pushable => return invalid_type_prop(),
+                TypeProperty::Passable | TypeProperty::Storable | TypeProperty::Duplicable => (),
+            },
+            SaplingTransaction(_) => match prop {
+                TypeProperty::Comparable => return invalid_type_prop(),
+                TypeProperty::Passable
+                | TypeProperty::Storable
+                | TypeProperty::Pushable
+                | TypeProperty::Packable
+                | TypeProperty::BigMapValue
+                | TypeProperty::Duplicable => (),
+            },
             BigMap(p) => match prop {
                 TypeProperty::Comparable
                 | TypeProperty::BigMapValue
@@ -142,10 +142,13 @@ fn typecheck_stack<'a>(
 }
 
 impl<'a> Parser<'a> {
-    /// Parse top-level definition of a TZT test.
-    pub fn parse_tzt_test(&'a self, src: &'a str) -> Result<TztTest, Box<dyn Error + '_>> {
+    /// Parse top-level definition of a TZT test. Syntax errors render the
+    /// offending token, so the returned error doesn't borrow from `src` and
+    /// can be downcast, e.g. to [TcError].
+    pub fn parse_tzt_test(&'a self, src: &'a str) -> Result<TztTest, Box<dyn Error>> {
         tztTestEntitiesParser::new()
-            .parse(&self.arena, spanned_lexer(src))?
+            .parse(&self.arena, spanned_lexer(src))
+            .map_err(|e| e.map_token(|tok| tok.to_string()))?
             .try_into()
     }
 }
@@ -410,6 +413,16 @@ pub fn run_tzt_test<'a>(
     test: TztTest<'a>,
     arena: &'a Arena<Micheline<'a>>,
 ) -> Result<(), TztTestError<'a>> {
+    run_tzt_test_measuring_gas(test, arena).0
+}
+
+/// Same as [run_tzt_test], but also returns the amount of milligas consumed by
+/// typechecking and interpreting the test code. The amount is [None] if gas
+/// was exhausted.
+pub fn run_tzt_test_measuring_gas<'a>(
+    test: TztTest<'a>,
+    arena: &'a Arena<Micheline<'a>>,
+) -> (Result<(), TztTestError<'a>>, Option<u32>) {
     // Here we compare the outcome of the interpreting with the
     // expectation from the test, and declare the result of the test
     // accordingly.
@@ -431,5 +444,10 @@ pub fn run_tzt_test<'a>(
 
     let execution_result =
         execute_tzt_test_code(test.code, &mut ctx, arena, test.parameter, test.input);
-    check_expectation(&mut ctx, test.output, execution_result)
+    let gas_used = (!ctx.gas.is_exhausted())
+        .then(|| crate::gas::Gas::default().milligas() - ctx.gas.milligas());
+    (
+        check_expectation(&mut ctx, test.output, execution_result),
+        gas_used,
+    )
 }
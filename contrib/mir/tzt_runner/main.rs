@@ -5,44 +5,489 @@
 /*                                                                            */
 /******************************************************************************/
 
-use std::env;
-use std::fs::read_to_string;
+//! Runner for TZT tests, see
+//! <https://tezos.gitlab.io/active/michelson.html#tzt-a-syntax-extension-for-writing-unit-tests>.
+//! Run with `--help` for usage.
+
+mod report;
+
+use std::collections::HashSet;
+use std::fs::{self, read_to_string, File};
+use std::io::BufWriter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use std::{env, thread};
 
 use mir::parser::Parser;
+use mir::typechecker::TcError;
 use mir::tzt::*;
 use typed_arena::Arena;
 
-fn run_test(file: &str) -> Result<(), String> {
-    let contents = read_to_string(file).map_err(|e| e.to_string())?;
-    let parser = Parser::new();
-    let tzt_test = parser
-        .parse_tzt_test(&contents)
-        .map_err(|e| e.to_string())?;
+use report::{Outcome, Summary, TestReport};
+
+const USAGE: &str = "\
+Usage: tzt_runner [OPTIONS] PATH...
+
+Runs TZT tests. Each PATH is either a test file or a directory, which is
+searched recursively for `*.tzt` files.
+
+Options:
+  --glob PATTERN          Only run test files whose path matches PATTERN. `*`
+                          and `?` don't match `/`, `**` matches anything. Can
+                          be repeated.
+  --filter SUBSTRING      Only run tests whose name contains SUBSTRING. Can be
+                          repeated.
+  -j, --jobs N            Number of tests to run in parallel. Defaults to the
+                          number of available CPUs.
+  --known-failures FILE   File with names of tests that are expected to fail,
+                          one per line. Empty lines and lines starting with `#`
+                          are ignored.
+  --junit FILE            Write a JUnit XML report to FILE.
+  --json FILE             Write a JSON report to FILE.
+  -h, --help              Print this message.
+
+A test name is the path of the test file relative to the directory it was
+found in, without the `.tzt` extension, e.g. `abs_00`. Tests using TZT fields
+that MIR doesn't support are skipped, as are tests using `other_contracts` that
+fail because an input contract is unknown.";
+
+/// TZT fields that `mir::tzt` can't parse yet. Tests using them are skipped.
+const UNSUPPORTED_FIELDS: &[&str] = &["big_maps", "now", "sender", "source"];
+
+/// TZT fields that `mir::tzt` supports only partially: contracts listed in
+/// them aren't known yet when the input stack is typechecked. Tests using them
+/// are skipped only when they fail on that, other failures are reported.
+const PARTIALLY_SUPPORTED_FIELDS: &[&str] = &["other_contracts"];
+
+/// Command line options.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    globs: Vec<String>,
+    filters: Vec<String>,
+    jobs: Option<usize>,
+    known_failures: Option<PathBuf>,
+    junit: Option<PathBuf>,
+    json: Option<PathBuf>,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--glob" => opts.globs.push(value()?),
+            "--filter" => opts.filters.push(value()?),
+            "-j" | "--jobs" => {
+                let jobs = value()?;
+                opts.jobs = match jobs.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("invalid number of jobs: {jobs}")),
+                }
+            }
+            "--known-failures" => opts.known_failures = Some(value()?.into()),
+            "--junit" => opts.junit = Some(value()?.into()),
+            "--json" => opts.json = Some(value()?.into()),
+            "-h" | "--help" => opts.help = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => opts.paths.push(arg.into()),
+        }
+    }
+    Ok(opts)
+}
+
+/// A test file to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TestFile {
+    name: String,
+    path: PathBuf,
+}
+
+/// Collect test files from the given paths, searching directories
+/// recursively. The result is sorted by path.
+fn collect_tests(paths: &[PathBuf]) -> Result<Vec<TestFile>, String> {
+    fn walk(root: &Path, dir: &Path, out: &mut Vec<TestFile>) -> Result<(), String> {
+        let entries = fs::read_dir(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        for entry in entries {
+            let path = entry.map_err(|e| format!("{}: {e}", dir.display()))?.path();
+            if path.is_dir() {
+                walk(root, &path, out)?;
+            } else if path.extension().is_some_and(|ext| ext == "tzt") {
+                let rel = path.strip_prefix(root).unwrap_or(&path);
+                out.push(TestFile {
+                    name: test_name(rel),
+                    path,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut res = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, path, &mut res)?;
+        } else {
+            let file_name = path.file_name().map_or(path.as_path(), Path::new);
+            res.push(TestFile {
+                name: test_name(file_name),
+                path: path.clone(),
+            });
+        }
+    }
+    res.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(res)
+}
 
+fn test_name(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    match path.strip_suffix(".tzt") {
+        Some(name) => name.to_owned(),
+        None => path,
+    }
+}
+
+/// Match `s` against a glob pattern. `*` matches any sequence of characters
+/// except `/`, `**` matches any sequence of characters, `?` matches any
+/// character except `/`.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    fn go(p: &[char], s: &[char]) -> bool {
+        match p {
+            [] => s.is_empty(),
+            ['*', '*', rest @ ..] => (0..=s.len()).any(|i| go(rest, &s[i..])),
+            ['*', rest @ ..] => {
+                let max = s.iter().position(|c| *c == '/').unwrap_or(s.len());
+                (0..=max).any(|i| go(rest, &s[i..]))
+            }
+            ['?', rest @ ..] => matches!(s, [c, ..] if *c != '/') && go(rest, &s[1..]),
+            [c, rest @ ..] => s.first() == Some(c) && go(rest, &s[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    go(&p, &s)
+}
+
+fn is_selected(test: &TestFile, opts: &Options) -> bool {
+    let path = test.path.to_string_lossy().replace('\\', "/");
+    (opts.globs.is_empty() || opts.globs.iter().any(|g| glob_match(g, &path)))
+        && (opts.filters.is_empty() || opts.filters.iter().any(|f| test.name.contains(f)))
+}
+
+fn parse_known_failures(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Names of the top-level fields of a TZT test, e.g. `code`, `input`,
+/// `output`. This doesn't validate the test, so it works for fields the
+/// parser doesn't know about.
+fn tzt_fields(src: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut depth = 0usize;
+    let mut expect_field = true;
+    let mut chars = src.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '{' | '(' => {
+                depth += 1;
+                expect_field = false;
+            }
+            '}' | ')' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => expect_field = true,
+            c if c.is_whitespace() => {}
+            c if expect_field && depth == 0 && (c.is_ascii_alphabetic() || c == '_') => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || *c == '_') {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                res.push(&src[i..end]);
+                expect_field = false;
+            }
+            _ => expect_field = false,
+        }
+    }
+    res
+}
+
+/// Run a test, returning the outcome and the consumed milligas. Known
+/// failures are not taken into account here.
+fn run_test(file: &Path) -> (Outcome, Option<u32>) {
+    let contents = match read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) => return (Outcome::Failed(e.to_string()), None),
+    };
+    let fields = tzt_fields(&contents);
+    if let Some(field) = fields.iter().find(|f| UNSUPPORTED_FIELDS.contains(f)) {
+        return (
+            Outcome::Skipped(format!("field `{field}` is not supported")),
+            None,
+        );
+    }
+    let partially_supported = fields
+        .iter()
+        .find(|f| PARTIALLY_SUPPORTED_FIELDS.contains(f));
+
+    let parser = Parser::new();
     let arena = Arena::new();
-    run_tzt_test(tzt_test, &arena).map_err(|e| format!("{}", e))
+    let (outcome, milligas_used) = match parser.parse_tzt_test(&contents) {
+        Ok(tzt_test) => match run_tzt_test_measuring_gas(tzt_test, &arena) {
+            (Ok(()), milligas_used) => (Outcome::Passed, milligas_used),
+            (Err(e), milligas_used) => (Outcome::Failed(e.to_string()), milligas_used),
+        },
+        Err(e) => match partially_supported {
+            Some(field) if matches!(e.downcast_ref(), Some(TcError::NoSuchContract)) => (
+                Outcome::Skipped(format!("field `{field}` is only partially supported: {e}")),
+                None,
+            ),
+            _ => (Outcome::Failed(e.to_string()), None),
+        },
+    };
+    (outcome, milligas_used)
+}
+
+fn run_and_report(test: &TestFile, known_failures: &HashSet<String>) -> TestReport {
+    let start = Instant::now();
+    let (outcome, milligas_used) = panic::catch_unwind(AssertUnwindSafe(|| run_test(&test.path)))
+        .unwrap_or_else(|e| {
+            let msg = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            (Outcome::Failed(format!("panicked: {msg}")), None)
+        });
+    let outcome = match (outcome, known_failures.contains(&test.name)) {
+        (Outcome::Failed(e), true) => Outcome::KnownFailure(e),
+        (Outcome::Passed, true) => Outcome::UnexpectedPass,
+        (outcome, _) => outcome,
+    };
+    TestReport {
+        name: test.name.clone(),
+        file: test.path.display().to_string(),
+        outcome,
+        milligas_used,
+        time: start.elapsed(),
+    }
+}
+
+/// Run tests on `jobs` threads, printing results as they come. Reports are
+/// returned in the same order as tests.
+fn run_tests(tests: &[TestFile], jobs: usize, known_failures: &HashSet<String>) -> Vec<TestReport> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![None; tests.len()]);
+    thread::scope(|s| {
+        for _ in 0..jobs.min(tests.len()) {
+            thread::Builder::new()
+                // deeply nested tests need as much stack as the main thread has
+                .stack_size(8 * 1024 * 1024)
+                .spawn_scoped(s, || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(test) = tests.get(i) else { break };
+                    let report = run_and_report(test, known_failures);
+                    println!("Running {} : {}", report.file, report.outcome);
+                    reports.lock().unwrap()[i] = Some(report);
+                })
+                .expect("failed to spawn a test thread");
+        }
+    });
+    reports
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("all tests were run"))
+        .collect()
 }
 
 fn main() {
-    // Read the cmd line arguments as a list of Strings.
-    // First one is the name of the file being executed
-    // and the rest are the actual arguments, so drop the first one.
-    let test_files = &env::args().collect::<Vec<String>>()[1..];
-
-    // Walk through all the test paths and execute each of them.
-    // Print the result for each run.
-    let mut exit_code = 0;
-    for test in test_files {
-        print!("Running {} : ", test);
-        match run_test(test) {
-            Ok(_) => println!("Ok"),
-            Err(e) => {
-                exit_code = 1;
-                println!("{}", e);
-            }
+    // Skip the first argument, which is the name of the executable.
+    let opts = match parse_args(env::args().skip(1)) {
+        Ok(opts) if opts.help => {
+            println!("{USAGE}");
+            return;
+        }
+        Ok(opts) if !opts.paths.is_empty() => opts,
+        Ok(_) => {
+            eprintln!("{USAGE}");
+            std::process::exit(2)
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2)
         }
+    };
+    if let Err(e) = run(&opts) {
+        eprintln!("{e}");
+        std::process::exit(2)
+    }
+}
+
+fn run(opts: &Options) -> Result<(), String> {
+    let known_failures = match &opts.known_failures {
+        Some(path) => parse_known_failures(
+            &read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        ),
+        None => HashSet::new(),
+    };
+    let tests: Vec<TestFile> = collect_tests(&opts.paths)?
+        .into_iter()
+        .filter(|t| is_selected(t, opts))
+        .collect();
+    let jobs = opts.jobs.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+
+    let reports = run_tests(&tests, jobs, &known_failures);
+
+    let summary = Summary::new(&reports);
+    println!("{summary}");
+    if let Some(path) = &opts.junit {
+        let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        report::write_junit(&reports, BufWriter::new(file))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    if let Some(path) = &opts.json {
+        let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report::to_json(&reports))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    if reports.iter().any(|r| r.outcome.is_failure()) {
+        std::process::exit(1)
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = |s: &str| parse_args(s.split_whitespace().map(str::to_owned));
+        assert_eq!(
+            args("--glob *.tzt -j 4 suite --filter abs --known-failures kf --junit r.xml --json r.json"),
+            Ok(Options {
+                paths: vec!["suite".into()],
+                globs: vec!["*.tzt".to_owned()],
+                filters: vec!["abs".to_owned()],
+                jobs: Some(4),
+                known_failures: Some("kf".into()),
+                junit: Some("r.xml".into()),
+                json: Some("r.json".into()),
+                help: false,
+            })
+        );
+        assert_eq!(
+            args("--jobs 0"),
+            Err("invalid number of jobs: 0".to_owned())
+        );
+        assert_eq!(args("--glob"), Err("missing value for --glob".to_owned()));
+        assert_eq!(args("--foo"), Err("unknown option --foo".to_owned()));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.tzt", "abs_00.tzt"));
+        assert!(!glob_match("*.tzt", "legacy/abs_00.tzt"));
+        assert!(glob_match("**.tzt", "legacy/abs_00.tzt"));
+        assert!(glob_match("suite/**/add_??.tzt", "suite/legacy/add_00.tzt"));
+        assert!(!glob_match(
+            "suite/**/add_??.tzt",
+            "suite/legacy/add_000.tzt"
+        ));
+        assert!(glob_match("suite/*_0?.tzt", "suite/mul_nat-mutez_01.tzt"));
+        assert!(!glob_match("suite/?", "suite//"));
+    }
+
+    #[test]
+    fn test_selection() {
+        let test = TestFile {
+            name: "legacy/sub_00".to_owned(),
+            path: "suite/legacy/sub_00.tzt".into(),
+        };
+        let select = |globs: &[&str], filters: &[&str]| {
+            is_selected(
+                &test,
+                &Options {
+                    globs: globs.iter().map(|s| s.to_string()).collect(),
+                    filters: filters.iter().map(|s| s.to_string()).collect(),
+                    ..Options::default()
+                },
+            )
+        };
+        assert!(select(&[], &[]));
+        assert!(select(&["suite/legacy/*"], &["sub", "add"]));
+        assert!(!select(&["suite/*.tzt"], &[]));
+        assert!(!select(&[], &["add"]));
+    }
+
+    #[test]
+    fn test_known_failures() {
+        assert_eq!(
+            parse_known_failures("# comment\nabs_00\n\n  legacy/sub_00  \n"),
+            HashSet::from(["abs_00".to_owned(), "legacy/sub_00".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_tzt_fields() {
+        assert_eq!(
+            tzt_fields(
+                r#"# big_maps in a comment
+                code { MEM ; /* now ; */ DROP } ;
+                input { Stack_elt string "; sender" ; Stack_elt (big_map nat nat) 10 } ;
+                output (Failed "x") ;
+                big_maps { Big_map 10 nat nat { Elt 0 1 } } ;"#
+            ),
+            vec!["code", "input", "output", "big_maps"]
+        );
     }
-    std::process::exit(exit_code)
 }
 
 #[cfg(test)]
@@ -0,0 +1,289 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Test outcomes and machine-readable reports.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+/// Outcome of running a single test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The test passed.
+    Passed,
+    /// The test failed with the given message.
+    Failed(String),
+    /// The test failed with the given message, but it is listed as a known
+    /// failure.
+    KnownFailure(String),
+    /// The test passed, but it is listed as a known failure.
+    UnexpectedPass,
+    /// The test wasn't run, or its failure is ignored, for the given reason.
+    Skipped(String),
+}
+
+impl Outcome {
+    /// Whether the outcome should fail the whole run.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Outcome::Failed(_) | Outcome::UnexpectedPass)
+    }
+
+    fn status(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "passed",
+            Outcome::Failed(_) => "failed",
+            Outcome::KnownFailure(_) => "known_failure",
+            Outcome::UnexpectedPass => "unexpected_pass",
+            Outcome::Skipped(_) => "skipped",
+        }
+    }
+
+    fn message(&self) -> Option<&str> {
+        match self {
+            Outcome::Passed => None,
+            Outcome::Failed(msg) | Outcome::KnownFailure(msg) | Outcome::Skipped(msg) => Some(msg),
+            Outcome::UnexpectedPass => Some("the test is a known failure, but it passed"),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "Ok"),
+            Outcome::Failed(msg) => write!(f, "{}", msg),
+            Outcome::KnownFailure(msg) => write!(f, "Known failure: {}", msg),
+            Outcome::UnexpectedPass => write!(f, "Passed, but listed as a known failure"),
+            Outcome::Skipped(msg) => write!(f, "Skipped: {}", msg),
+        }
+    }
+}
+
+/// Result of running a single test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    /// Test name, used for filtering and in the known failures list.
+    pub name: String,
+    /// Path to the test file.
+    pub file: String,
+    /// Test outcome.
+    pub outcome: Outcome,
+    /// Milligas consumed by typechecking and interpreting the test code, if
+    /// the code was run and didn't exhaust gas.
+    pub milligas_used: Option<u32>,
+    /// Time spent running the test.
+    pub time: Duration,
+}
+
+/// Number of tests per outcome.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub known_failures: usize,
+    pub unexpected_passes: usize,
+    pub skipped: usize,
+}
+
+impl Summary {
+    pub fn new(reports: &[TestReport]) -> Self {
+        let mut res = Summary::default();
+        for r in reports {
+            let counter = match r.outcome {
+                Outcome::Passed => &mut res.passed,
+                Outcome::Failed(_) => &mut res.failed,
+                Outcome::KnownFailure(_) => &mut res.known_failures,
+                Outcome::UnexpectedPass => &mut res.unexpected_passes,
+                Outcome::Skipped(_) => &mut res.skipped,
+            };
+            *counter += 1;
+        }
+        res
+    }
+
+    pub fn total(&self) -> usize {
+        self.passed + self.failed + self.known_failures + self.unexpected_passes + self.skipped
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} tests: {} passed, {} failed, {} known failures, {} unexpected passes, {} skipped",
+            self.total(),
+            self.passed,
+            self.failed,
+            self.known_failures,
+            self.unexpected_passes,
+            self.skipped
+        )
+    }
+}
+
+/// Convert test reports to JSON.
+pub fn to_json(reports: &[TestReport]) -> Value {
+    let summary = Summary::new(reports);
+    let tests: Vec<Value> = reports
+        .iter()
+        .map(|r| {
+            json!({
+                "name": r.name,
+                "file": r.file,
+                "status": r.outcome.status(),
+                "message": r.outcome.message(),
+                "milligas_used": r.milligas_used,
+                "time": r.time.as_secs_f64(),
+            })
+        })
+        .collect();
+    json!({
+        "summary": {
+            "total": summary.total(),
+            "passed": summary.passed,
+            "failed": summary.failed,
+            "known_failures": summary.known_failures,
+            "unexpected_passes": summary.unexpected_passes,
+            "skipped": summary.skipped,
+        },
+        "tests": tests,
+    })
+}
+
+/// Write test reports in the JUnit XML format. Known failures are reported
+/// as skipped, unexpected passes as failures.
+pub fn write_junit(reports: &[TestReport], mut out: impl Write) -> io::Result<()> {
+    let summary = Summary::new(reports);
+    let failures = summary.failed + summary.unexpected_passes;
+    let skipped = summary.skipped + summary.known_failures;
+    let time: f64 = reports.iter().map(|r| r.time.as_secs_f64()).sum();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites tests="{}" failures="{failures}" skipped="{skipped}" time="{time:.3}">"#,
+        summary.total()
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="tzt" tests="{}" failures="{failures}" errors="0" skipped="{skipped}" time="{time:.3}">"#,
+        summary.total()
+    )?;
+    for r in reports {
+        writeln!(
+            out,
+            r#"    <testcase name="{}" classname="tzt" file="{}" time="{:.3}">"#,
+            escape_xml(&r.name),
+            escape_xml(&r.file),
+            r.time.as_secs_f64()
+        )?;
+        if let Some(milligas) = r.milligas_used {
+            writeln!(out, "      <properties>")?;
+            writeln!(
+                out,
+                r#"        <property name="milligas_used" value="{milligas}"/>"#
+            )?;
+            writeln!(out, "      </properties>")?;
+        }
+        let tag = match r.outcome {
+            Outcome::Passed => None,
+            Outcome::Failed(_) | Outcome::UnexpectedPass => Some("failure"),
+            Outcome::KnownFailure(_) | Outcome::Skipped(_) => Some("skipped"),
+        };
+        if let Some(tag) = tag {
+            let message = match &r.outcome {
+                Outcome::KnownFailure(msg) => format!("known failure: {msg}"),
+                outcome => outcome.message().unwrap_or_default().to_owned(),
+            };
+            writeln!(out, r#"      <{tag} message="{}"/>"#, escape_xml(&message))?;
+        }
+        writeln!(out, "    </testcase>")?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\n' => res.push_str("&#10;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reports() -> Vec<TestReport> {
+        let report = |name: &str, outcome, milligas_used| TestReport {
+            name: name.to_owned(),
+            file: format!("suite/{name}.tzt"),
+            outcome,
+            milligas_used,
+            time: Duration::from_millis(2),
+        };
+        vec![
+            report("add_00", Outcome::Passed, Some(1410)),
+            report("add_01", Outcome::Failed("a < b".to_owned()), Some(100)),
+            report("add_02", Outcome::KnownFailure("\"x\"".to_owned()), None),
+            report("add_03", Outcome::UnexpectedPass, Some(0)),
+            report("now_00", Outcome::Skipped("uses now".to_owned()), None),
+        ]
+    }
+
+    #[test]
+    fn summary() {
+        assert_eq!(
+            Summary::new(&reports()).to_string(),
+            "5 tests: 1 passed, 1 failed, 1 known failures, 1 unexpected passes, 1 skipped"
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        let json = to_json(&reports());
+        assert_eq!(json["summary"]["total"], 5);
+        assert_eq!(
+            json["tests"][0],
+            json!({
+                "name": "add_00",
+                "file": "suite/add_00.tzt",
+                "status": "passed",
+                "message": null,
+                "milligas_used": 1410,
+                "time": 0.002,
+            })
+        );
+        assert_eq!(json["tests"][2]["status"], "known_failure");
+        assert_eq!(json["tests"][3]["status"], "unexpected_pass");
+    }
+
+    #[test]
+    fn junit_report() {
+        let mut out = Vec::new();
+        write_junit(&reports(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(
+            r#"<testsuite name="tzt" tests="5" failures="2" errors="0" skipped="2" time="0.010">"#
+        ));
+        assert!(out.contains(r#"<property name="milligas_used" value="1410"/>"#));
+        assert!(out.contains(r#"<failure message="a &lt; b"/>"#));
+        assert!(out.contains(r#"<skipped message="known failure: &quot;x&quot;"/>"#));
+        assert!(out.contains(r#"<skipped message="uses now"/>"#));
+    }
+}
@@ -39,6 +39,18 @@ pub const SBI_TEZOS_ED25519_SIGN: u64 = 0x06;
 /// Function ID for `sbi_tezos_blake2b_hash256`
 pub const SBI_TEZOS_BLAKE2B_HASH256: u64 = 0x07;
 
+/// Function ID for `sbi_tezos_reveal_preimage`
+pub const SBI_TEZOS_REVEAL_PREIMAGE: u64 = 0x09;
+
+/// Function ID for `sbi_tezos_reveal_dal_page`
+pub const SBI_TEZOS_REVEAL_DAL_PAGE: u64 = 0x0A;
+
+/// Function ID for `sbi_tezos_outbox_write`
+pub const SBI_TEZOS_OUTBOX_WRITE: u64 = 0x0B;
+
+/// Maximum number of bytes written in response to a reveal request
+pub const MAX_REVEAL_SIZE: usize = 4096;
+
 /// Standard SBI errors
 #[derive(Debug, Copy, Clone)]
 #[repr(i64)]
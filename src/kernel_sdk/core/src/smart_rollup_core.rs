@@ -75,6 +75,24 @@ extern "C" {
     /// Get the number of subkeys of the prefix given by `path`.
     pub fn store_list_size(path: *const u8, path_len: usize) -> i64;
 
+    /// Write the name of the `index`-th subkey of the prefix given by `path` to
+    /// memory, and return its size.
+    ///
+    /// The name is trimmed if it is longer than `max_size`. The value at `path`
+    /// itself, if any, is counted as a subkey with an empty name.
+    ///
+    /// Returns [`STORE_INVALID_SUBKEY_INDEX`] if `index` is not smaller than
+    /// [`store_list_size`].
+    ///
+    /// [`STORE_INVALID_SUBKEY_INDEX`]: crate::STORE_INVALID_SUBKEY_INDEX
+    pub fn store_get_nth_key(
+        path: *const u8,
+        path_len: usize,
+        index: i64,
+        dst: *mut u8,
+        max_size: usize,
+    ) -> i32;
+
     /// Moves the value and/or subkeys of `from_path` to `to_path`.
     ///
     /// Overwrites the destination, if it already exists.
@@ -256,6 +274,21 @@ pub unsafe trait SmartRollupCore {
     /// - `path_len` must be the length of that slice.
     unsafe fn store_list_size(&self, path: *const u8, path_len: usize) -> i64;
 
+    /// See [store_get_nth_key].
+    ///
+    /// # Safety
+    /// - `path` must be a ptr to a correctly path-encoded slice of bytes.
+    /// - `path_len` must be the length of that slice.
+    /// - `dst` must point to a mutable slice of bytes with `capacity >= max_size`.
+    unsafe fn store_get_nth_key(
+        &self,
+        path: *const u8,
+        path_len: usize,
+        index: i64,
+        dst: *mut u8,
+        max_size: usize,
+    ) -> i32;
+
     /// See [store_move] above.
     ///
     /// # Safety
@@ -352,14 +385,27 @@ mod riscv64_hermit {
     };
     use tezos_smart_rollup_constants::{
         core::{
-            GENERIC_INVALID_ACCESS, MEMORY_INVALID_ACCESS, METADATA_LENGTH,
-            ORIGINATION_LEVEL_LENGTH, ROLLUP_ADDRESS_LENGTH,
+            FULL_OUTBOX, GENERIC_INVALID_ACCESS, INPUT_OUTPUT_TOO_LARGE,
+            MEMORY_INVALID_ACCESS, METADATA_LENGTH, ORIGINATION_LEVEL_LENGTH,
+            PREIMAGE_HASH_SIZE, ROLLUP_ADDRESS_LENGTH,
         },
         riscv::{
-            SbiError, SBI_FIRMWARE_TEZOS, SBI_TEZOS_INBOX_NEXT, SBI_TEZOS_METADATA_REVEAL,
+            SbiError, SBI_FIRMWARE_TEZOS, SBI_TEZOS_INBOX_NEXT,
+            SBI_TEZOS_METADATA_REVEAL, SBI_TEZOS_OUTBOX_WRITE, SBI_TEZOS_REVEAL_DAL_PAGE,
+            SBI_TEZOS_REVEAL_PREIMAGE,
         },
     };
 
+    /// Tag of raw data reveals in the protocol's encoding of reveal requests
+    const REVEAL_RAW_DATA_TAG: u8 = 0;
+
+    /// Tag of DAL page reveals in the protocol's encoding of reveal requests
+    const REQUEST_DAL_PAGE_TAG: u8 = 2;
+
+    /// Size of a DAL page request: published level (4), slot index (1) and
+    /// page index (2)
+    const DAL_PAGE_REQUEST_SIZE: usize = 7;
+
     /// Check the SBI return value for errors.
     fn check_sbi_result(result: isize) -> Result<usize, i32> {
         match SbiError::from_result(result) {
@@ -404,8 +450,33 @@ mod riscv64_hermit {
         }
     }
 
-    pub unsafe fn write_output(_src: *const u8, _num_bytes: usize) -> i32 {
-        unimplemented!()
+    pub unsafe fn write_output(src: *const u8, num_bytes: usize) -> i32 {
+        let result: isize;
+
+        // SBI call
+        //   extension = SBI_FIRMWARE_TEZOS
+        //   function = SBI_TEZOS_OUTBOX_WRITE
+        core::arch::asm!(
+            "ecall",
+            in("a0") src,
+            in("a1") num_bytes,
+            in("a6") SBI_TEZOS_OUTBOX_WRITE,
+            in("a7") SBI_FIRMWARE_TEZOS,
+            lateout("a0") result,
+        );
+
+        match SbiError::from_result(result) {
+            // The message exceeds the maximum output size.
+            Some(SbiError::InvalidParam) => INPUT_OUTPUT_TOO_LARGE,
+
+            // The outbox for the current level is full.
+            Some(SbiError::Denied) => FULL_OUTBOX,
+
+            _ => match check_sbi_result(result) {
+                Ok(_) => 0,
+                Err(err) => err,
+            },
+        }
     }
 
     pub unsafe fn write_debug(src: *const u8, num_bytes: usize) {
@@ -451,6 +522,16 @@ mod riscv64_hermit {
         unimplemented!()
     }
 
+    pub unsafe fn store_get_nth_key(
+        _path: *const u8,
+        _path_len: usize,
+        _index: i64,
+        _dst: *mut u8,
+        _max_size: usize,
+    ) -> i32 {
+        unimplemented!()
+    }
+
     pub unsafe fn store_move(
         _from_path: *const u8,
         _from_path_len: usize,
@@ -470,21 +551,94 @@ mod riscv64_hermit {
     }
 
     pub unsafe fn reveal_preimage(
-        _hash_addr: *const u8,
-        _hash_len: usize,
-        _destination_addr: *mut u8,
-        _max_bytes: usize,
+        hash_addr: *const u8,
+        hash_len: usize,
+        destination_addr: *mut u8,
+        max_bytes: usize,
     ) -> i32 {
-        unimplemented!()
+        let result: isize;
+
+        // SBI call
+        //   extension = SBI_FIRMWARE_TEZOS
+        //   function = SBI_TEZOS_REVEAL_PREIMAGE
+        core::arch::asm!(
+            "ecall",
+            in("a0") hash_addr,
+            in("a1") hash_len,
+            in("a2") destination_addr,
+            in("a3") max_bytes,
+            in("a6") SBI_TEZOS_REVEAL_PREIMAGE,
+            in("a7") SBI_FIRMWARE_TEZOS,
+            lateout("a0") result,
+        );
+
+        match check_sbi_result(result) {
+            Ok(result) => result as i32,
+            Err(err) => err,
+        }
+    }
+
+    unsafe fn reveal_dal_page(
+        published_level: i32,
+        slot_index: u8,
+        page_index: i16,
+        destination_addr: *mut u8,
+        max_bytes: usize,
+    ) -> i32 {
+        let result: isize;
+
+        // SBI call
+        //   extension = SBI_FIRMWARE_TEZOS
+        //   function = SBI_TEZOS_REVEAL_DAL_PAGE
+        core::arch::asm!(
+            "ecall",
+            in("a0") published_level as isize,
+            in("a1") slot_index as usize,
+            in("a2") page_index as isize,
+            in("a3") destination_addr,
+            in("a4") max_bytes,
+            in("a6") SBI_TEZOS_REVEAL_DAL_PAGE,
+            in("a7") SBI_FIRMWARE_TEZOS,
+            lateout("a0") result,
+        );
+
+        match check_sbi_result(result) {
+            Ok(result) => result as i32,
+            Err(err) => err,
+        }
     }
 
     pub unsafe fn reveal(
-        _payload_addr: *const u8,
-        _payload_len: usize,
-        _destination_addr: *mut u8,
-        _max_bytes: usize,
+        payload_addr: *const u8,
+        payload_len: usize,
+        destination_addr: *mut u8,
+        max_bytes: usize,
     ) -> i32 {
-        unimplemented!()
+        let payload = unsafe { from_raw_parts(payload_addr, payload_len) };
+
+        match payload.split_first() {
+            Some((&REVEAL_RAW_DATA_TAG, hash)) if hash.len() == PREIMAGE_HASH_SIZE => {
+                reveal_preimage(hash.as_ptr(), hash.len(), destination_addr, max_bytes)
+            }
+
+            Some((&REQUEST_DAL_PAGE_TAG, page))
+                if page.len() == DAL_PAGE_REQUEST_SIZE =>
+            {
+                let published_level =
+                    i32::from_be_bytes([page[0], page[1], page[2], page[3]]);
+                let page_index = i16::from_be_bytes([page[5], page[6]]);
+                reveal_dal_page(
+                    published_level,
+                    page[4],
+                    page_index,
+                    destination_addr,
+                    max_bytes,
+                )
+            }
+
+            // Other reveals are not supported on RISC-V yet.
+            _ => GENERIC_INVALID_ACCESS,
+        }
     }
 
     pub unsafe fn store_value_size(_path: *const u8, _path_len: usize) -> i32 {
@@ -27,6 +27,8 @@ use tezos_data_encoding::enc::BinWriter;
 use tezos_data_encoding::encoding::HasEncoding;
 use tezos_data_encoding::nom::NomReader;
 
+pub mod signed;
+
 #[derive(Debug, PartialEq, Eq, NomReader, HasEncoding, BinWriter)]
 enum InboxMessageRepr<Expr: Michelson> {
     #[encoding(tag = 0)]
@@ -0,0 +1,869 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Signed external messages.
+//!
+//! A [`SignedBatch`] is a list of [`Operation`]s, each carrying the public key of its
+//! [`Signer`], a nonce and an opaque payload interpreted by the kernel. It is sent as the
+//! contents of an [`ExternalMessageFrame::Targetted`] message.
+//!
+//! Operations are either signed individually - with *ed25519*, *secp256k1*, *p256* or
+//! *BLS* keys - or, when every signer uses a BLS key, with a single aggregated signature.
+//!
+//! What is signed is the *blake2b* digest of the address of the targetted rollup, followed
+//! by the binary encoding of the operation. Operations can therefore not be replayed on
+//! another rollup, and [`NonceStore`] rejects operations replayed on the same rollup.
+//!
+//! Batches are built off-chain with [`SignedBatchBuilder`].
+//!
+//! ```
+//! use tezos_crypto_rs::hash::{HashTrait, SeedEd25519};
+//! use tezos_smart_rollup_encoding::inbox::signed::*;
+//! use tezos_smart_rollup_encoding::inbox::ExternalMessageFrame;
+//! use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+//! use tezos_data_encoding::enc::BinWriter;
+//!
+//! let rollup =
+//!     SmartRollupAddress::from_b58check("sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa").unwrap();
+//! let (pk, sk) = SeedEd25519::try_from_bytes(&[7; 32]).unwrap().keypair().unwrap();
+//!
+//! // Client side
+//! let message = SignedBatchBuilder::new(rollup.clone())
+//!     .push_ed25519(pk, &sk, 0, b"hello".to_vec())
+//!     .unwrap()
+//!     .build();
+//! let mut bytes = Vec::new();
+//! message.bin_write(&mut bytes).unwrap();
+//!
+//! // Kernel side
+//! let ExternalMessageFrame::Targetted { address, contents } =
+//!     ExternalMessageFrame::parse(&bytes).unwrap();
+//! assert_eq!(address, rollup);
+//!
+//! let batch = SignedBatch::parse(contents).unwrap();
+//! batch.verify(&rollup).unwrap();
+//! assert_eq!(batch.operations[0].payload, b"hello");
+//! ```
+//!
+//! [`ExternalMessageFrame::Targetted`]: super::ExternalMessageFrame::Targetted
+
+use super::ExternalMessageFrame;
+use crate::smart_rollup::SmartRollupAddress;
+use crypto::base58::FromBase58CheckError;
+use crypto::hash::HashTrait;
+use crypto::hash::{
+    BlsSignature, Ed25519Signature, P256Signature, PublicKeyBls, PublicKeyEd25519,
+    PublicKeyP256, PublicKeySecp256k1, Secp256k1Signature, SecretKeyEd25519,
+};
+use crypto::{CryptoError, PublicKeySignatureVerifier};
+use nom::combinator::all_consuming;
+use nom::Finish;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::encoding::HasEncoding;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_host::path::{concat, OwnedPath, Path, PathError};
+use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError};
+use thiserror::Error;
+
+/// Public key of the signer of an [`Operation`].
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub enum Signer {
+    /// Signer with an ed25519 key.
+    #[encoding(tag = 0)]
+    Ed25519(PublicKeyEd25519),
+    /// Signer with a secp256k1 key.
+    #[encoding(tag = 1)]
+    Secp256k1(PublicKeySecp256k1),
+    /// Signer with a p256 key.
+    #[encoding(tag = 2)]
+    P256(PublicKeyP256),
+    /// Signer with a BLS key.
+    #[encoding(tag = 3)]
+    Bls(PublicKeyBls),
+}
+
+impl Signer {
+    /// Parse a signer from its base58 encoded public key - `edpk`, `sppk`, `p2pk` or
+    /// `BLpk`.
+    pub fn from_b58check(data: &str) -> Result<Self, FromBase58CheckError> {
+        if data.starts_with("edpk") {
+            PublicKeyEd25519::from_b58check(data).map(Self::Ed25519)
+        } else if data.starts_with("sppk") {
+            PublicKeySecp256k1::from_b58check(data).map(Self::Secp256k1)
+        } else if data.starts_with("p2pk") {
+            PublicKeyP256::from_b58check(data).map(Self::P256)
+        } else if data.starts_with("BLpk") {
+            PublicKeyBls::from_b58check(data).map(Self::Bls)
+        } else {
+            Err(FromBase58CheckError::InvalidBase58)
+        }
+    }
+
+    /// Base58 encoding of the public key of the signer.
+    pub fn to_b58check(&self) -> String {
+        match self {
+            Self::Ed25519(pk) => pk.to_b58check(),
+            Self::Secp256k1(pk) => pk.to_b58check(),
+            Self::P256(pk) => pk.to_b58check(),
+            Self::Bls(pk) => pk.to_b58check(),
+        }
+    }
+
+    /// Verify that `signature` is a signature of `message` by this signer.
+    ///
+    /// Signatures using a different scheme to the key of the signer, or which are
+    /// malformed, are invalid.
+    pub fn verify(
+        &self,
+        signature: &OperationSignature,
+        message: &[u8],
+    ) -> Result<bool, SignatureError> {
+        // ed25519 verification returns an error, rather than `false`, for
+        // invalid signatures.
+        let valid = |result: Result<bool, CryptoError>| result.unwrap_or(false);
+
+        match (self, signature) {
+            (Self::Ed25519(pk), OperationSignature::Ed25519(sig)) => {
+                Ok(valid(pk.verify_signature(sig, message)))
+            }
+            (Self::Secp256k1(pk), OperationSignature::Secp256k1(sig)) => {
+                Ok(valid(pk.verify_signature(sig, message)))
+            }
+            (Self::P256(pk), OperationSignature::P256(sig)) => {
+                Ok(valid(pk.verify_signature(sig, message)))
+            }
+            (Self::Bls(pk), OperationSignature::Bls(sig)) => {
+                verify_aggregated(sig, &[(message.to_vec(), pk)])
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Signature of a single [`Operation`], using the scheme of its [`Signer`].
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub enum OperationSignature {
+    /// An ed25519 signature.
+    #[encoding(tag = 0)]
+    Ed25519(Ed25519Signature),
+    /// A secp256k1 signature.
+    #[encoding(tag = 1)]
+    Secp256k1(Secp256k1Signature),
+    /// A p256 signature.
+    #[encoding(tag = 2)]
+    P256(P256Signature),
+    /// A BLS signature.
+    #[encoding(tag = 3)]
+    Bls(BlsSignature),
+}
+
+impl OperationSignature {
+    /// Parse a base58 encoded signature - `edsig`, `spsig1`, `p2sig` or `BLsig`.
+    pub fn from_b58check(data: &str) -> Result<Self, FromBase58CheckError> {
+        if data.starts_with("edsig") {
+            Ed25519Signature::from_b58check(data).map(Self::Ed25519)
+        } else if data.starts_with("spsig1") {
+            Secp256k1Signature::from_b58check(data).map(Self::Secp256k1)
+        } else if data.starts_with("p2sig") {
+            P256Signature::from_b58check(data).map(Self::P256)
+        } else if data.starts_with("BLsig") {
+            BlsSignature::from_b58check(data).map(Self::Bls)
+        } else {
+            Err(FromBase58CheckError::InvalidBase58)
+        }
+    }
+
+    /// Base58 encoding of the signature.
+    pub fn to_b58check(&self) -> String {
+        match self {
+            Self::Ed25519(sig) => sig.to_b58check(),
+            Self::Secp256k1(sig) => sig.to_b58check(),
+            Self::P256(sig) => sig.to_b58check(),
+            Self::Bls(sig) => sig.to_b58check(),
+        }
+    }
+}
+
+/// An operation, sent by a [`Signer`] to the kernel.
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct Operation {
+    /// The public key of the signer.
+    pub signer: Signer,
+    /// The nonce of the operation, see [`NonceStore`].
+    pub nonce: i64,
+    /// Contents of the operation, interpreted by the kernel.
+    #[encoding(dynamic, list)]
+    pub payload: Vec<u8>,
+}
+
+impl Operation {
+    /// The digest signed by the signer of the operation, when sent to `rollup`.
+    pub fn signing_digest(
+        &self,
+        rollup: &SmartRollupAddress,
+    ) -> Result<Vec<u8>, SignatureError> {
+        let mut bytes = Vec::new();
+        rollup
+            .bin_write(&mut bytes)
+            .and_then(|()| self.bin_write(&mut bytes))
+            .map_err(|_| SignatureError::Encoding)?;
+
+        Ok(crypto::blake2b::digest_256(&bytes))
+    }
+}
+
+/// One signature per operation of a [`SignedBatch`], see [`BatchSignature::Individual`].
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct IndividualSignatures {
+    /// The signature of each operation, in the same order.
+    #[encoding(dynamic, list)]
+    pub signatures: Vec<OperationSignature>,
+}
+
+/// Signatures of the operations of a [`SignedBatch`].
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub enum BatchSignature {
+    /// One signature per operation, in the same order.
+    #[encoding(tag = 0)]
+    Individual(IndividualSignatures),
+    /// A single signature, aggregating the signatures of every operation. All signers
+    /// must use BLS keys.
+    #[encoding(tag = 1)]
+    Aggregated(BlsSignature),
+}
+
+/// A batch of signed operations, sent as an external message.
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct SignedBatch {
+    /// The operations of the batch.
+    #[encoding(dynamic, list)]
+    pub operations: Vec<Operation>,
+    /// The signatures of the operations.
+    pub signature: BatchSignature,
+}
+
+/// Errors that may occur when signing or verifying a [`SignedBatch`].
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    /// The number of signatures differs from the number of operations.
+    #[error("Batch has {operations} operations, but {signatures} signatures")]
+    SignatureCount {
+        /// The number of operations in the batch.
+        operations: usize,
+        /// The number of individual signatures in the batch.
+        signatures: usize,
+    },
+    /// An aggregated signature was used for an operation whose signer has no BLS key.
+    #[error("Signer of operation {0} cannot take part in an aggregated signature")]
+    NotAggregatable(usize),
+    /// The signature of an operation is invalid.
+    #[error("Invalid signature for operation {0}")]
+    InvalidSignature(usize),
+    /// The aggregated signature is invalid.
+    #[error("Invalid aggregated signature")]
+    InvalidAggregatedSignature,
+    /// BLS signatures are only supported with the `bls` feature.
+    #[error("BLS signatures are not supported")]
+    BlsUnsupported,
+    /// An operation could not be encoded.
+    #[error("Unable to encode operation")]
+    Encoding,
+    /// Cryptographic primitives returned an error.
+    #[error("Error propagated by cryptographic primitives: {0}")]
+    Crypto(CryptoError),
+}
+
+impl SignedBatch {
+    /// Parse a batch from the contents of an [`ExternalMessageFrame`].
+    ///
+    /// The whole input must be consumed.
+    pub fn parse(input: &[u8]) -> Result<Self, tezos_data_encoding::nom::NomError> {
+        let (_remaining, batch) = all_consuming(Self::nom_read)(input).finish()?;
+        Ok(batch)
+    }
+
+    /// Verify the signatures of every operation in the batch, as sent to `rollup`.
+    ///
+    /// This does **not** check the nonces of the operations, see [`NonceStore`].
+    pub fn verify(&self, rollup: &SmartRollupAddress) -> Result<(), SignatureError> {
+        match &self.signature {
+            BatchSignature::Individual(IndividualSignatures { signatures }) => {
+                if signatures.len() != self.operations.len() {
+                    return Err(SignatureError::SignatureCount {
+                        operations: self.operations.len(),
+                        signatures: signatures.len(),
+                    });
+                }
+
+                for (index, (operation, signature)) in
+                    self.operations.iter().zip(signatures).enumerate()
+                {
+                    let digest = operation.signing_digest(rollup)?;
+                    if !operation.signer.verify(signature, &digest)? {
+                        return Err(SignatureError::InvalidSignature(index));
+                    }
+                }
+
+                Ok(())
+            }
+            BatchSignature::Aggregated(signature) => {
+                let mut signed = Vec::with_capacity(self.operations.len());
+                for (index, operation) in self.operations.iter().enumerate() {
+                    let Signer::Bls(pk) = &operation.signer else {
+                        return Err(SignatureError::NotAggregatable(index));
+                    };
+                    signed.push((operation.signing_digest(rollup)?, pk));
+                }
+
+                if verify_aggregated(signature, &signed)? {
+                    Ok(())
+                } else {
+                    Err(SignatureError::InvalidAggregatedSignature)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bls")]
+fn verify_aggregated(
+    signature: &BlsSignature,
+    signed: &[(Vec<u8>, &PublicKeyBls)],
+) -> Result<bool, SignatureError> {
+    signature
+        .aggregate_verify(&mut signed.iter().map(|(digest, pk)| (digest.as_slice(), *pk)))
+        .map_err(SignatureError::Crypto)
+}
+
+#[cfg(not(feature = "bls"))]
+fn verify_aggregated(
+    _signature: &BlsSignature,
+    _signed: &[(Vec<u8>, &PublicKeyBls)],
+) -> Result<bool, SignatureError> {
+    Err(SignatureError::BlsUnsupported)
+}
+
+/// Client-side builder of signed external messages.
+///
+/// Operations are signed as they are pushed to the batch.
+#[derive(Debug, Clone)]
+pub struct SignedBatchBuilder {
+    rollup: SmartRollupAddress,
+    operations: Vec<Operation>,
+    signatures: Vec<OperationSignature>,
+}
+
+impl SignedBatchBuilder {
+    /// Start building a batch, to be sent to `rollup`.
+    pub fn new(rollup: SmartRollupAddress) -> Self {
+        Self {
+            rollup,
+            operations: Vec::new(),
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Push an operation, signed by `sign`.
+    ///
+    /// `sign` is given the digest to sign, see [`Operation::signing_digest`]. This allows
+    /// keys held elsewhere - such as in a wallet or a remote signer - to be used.
+    pub fn push_with(
+        mut self,
+        signer: Signer,
+        nonce: i64,
+        payload: Vec<u8>,
+        sign: impl FnOnce(&[u8]) -> Result<OperationSignature, CryptoError>,
+    ) -> Result<Self, SignatureError> {
+        let operation = Operation {
+            signer,
+            nonce,
+            payload,
+        };
+
+        let digest = operation.signing_digest(&self.rollup)?;
+        let signature = sign(&digest).map_err(SignatureError::Crypto)?;
+
+        self.operations.push(operation);
+        self.signatures.push(signature);
+        Ok(self)
+    }
+
+    /// Push an operation, signed with an ed25519 secret key.
+    pub fn push_ed25519(
+        self,
+        pk: PublicKeyEd25519,
+        sk: &SecretKeyEd25519,
+        nonce: i64,
+        payload: Vec<u8>,
+    ) -> Result<Self, SignatureError> {
+        self.push_with(Signer::Ed25519(pk), nonce, payload, |digest| {
+            let signature = sk.sign(digest)?;
+            Ed25519Signature::try_from(signature.as_ref())
+                .map(OperationSignature::Ed25519)
+                .map_err(|_| CryptoError::InvalidSignature)
+        })
+    }
+
+    /// Build the batch, with one signature per operation.
+    pub fn build(self) -> ExternalMessageFrame<Vec<u8>> {
+        let batch = SignedBatch {
+            operations: self.operations,
+            signature: BatchSignature::Individual(IndividualSignatures {
+                signatures: self.signatures,
+            }),
+        };
+
+        Self::frame(self.rollup, batch)
+    }
+
+    /// Build the batch, aggregating the signatures of every operation into one.
+    ///
+    /// Every operation must have been signed with a BLS key.
+    #[cfg(feature = "bls")]
+    pub fn build_aggregated(
+        self,
+    ) -> Result<ExternalMessageFrame<Vec<u8>>, SignatureError> {
+        let signatures = self
+            .signatures
+            .iter()
+            .enumerate()
+            .map(|(index, signature)| match signature {
+                OperationSignature::Bls(signature) => Ok(signature),
+                _ => Err(SignatureError::NotAggregatable(index)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let signature =
+            BlsSignature::aggregate_sigs(&signatures).map_err(SignatureError::Crypto)?;
+
+        let batch = SignedBatch {
+            operations: self.operations,
+            signature: BatchSignature::Aggregated(signature),
+        };
+
+        Ok(Self::frame(self.rollup, batch))
+    }
+
+    fn frame(
+        address: SmartRollupAddress,
+        batch: SignedBatch,
+    ) -> ExternalMessageFrame<Vec<u8>> {
+        let mut contents = Vec::new();
+        batch
+            .bin_write(&mut contents)
+            .expect("signed batches are always serializable");
+
+        ExternalMessageFrame::Targetted { address, contents }
+    }
+}
+
+/// Errors that may occur when checking the nonce of an [`Operation`].
+#[derive(Debug, Error)]
+pub enum NonceError {
+    /// The nonce is not the one expected for its signer.
+    #[error("Expected nonce {expected}, got {actual}")]
+    Invalid {
+        /// The next nonce expected for the signer.
+        expected: i64,
+        /// The nonce of the operation.
+        actual: i64,
+    },
+    /// Could not compute the storage path of the signer.
+    #[error("Invalid nonce path: {0}")]
+    Path(PathError),
+    /// Error when accessing the durable storage.
+    #[error("Error accessing nonces in storage: {0}")]
+    Storage(RuntimeError),
+}
+
+/// Per-signer nonces, kept in durable storage, protecting against replayed operations.
+///
+/// Every signer starts with a nonce of `0`, and each accepted operation must use the
+/// next nonce.
+#[derive(Debug)]
+pub struct NonceStore<'a, P: Path> {
+    root: &'a P,
+}
+
+impl<'a, P: Path> NonceStore<'a, P> {
+    /// Keep the nonces of signers under `root`.
+    pub const fn new(root: &'a P) -> Self {
+        Self { root }
+    }
+
+    /// The nonce expected for the next operation of `signer`.
+    ///
+    /// Only a signer without a stored nonce starts at `0`: a stored value which is not
+    /// a nonce is an error, rather than allowing previous operations to be replayed.
+    pub fn expected(
+        &self,
+        host: &impl Runtime,
+        signer: &Signer,
+    ) -> Result<i64, NonceError> {
+        let path = self.signer_path(signer)?;
+
+        match host.store_read_all(&path) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map(i64::from_le_bytes)
+                .map_err(|_| NonceError::Storage(RuntimeError::DecodingError)),
+            Err(RuntimeError::PathNotFound) => Ok(0),
+            Err(error) => Err(NonceError::Storage(error)),
+        }
+    }
+
+    /// Check that `operation` uses the nonce expected for its signer, and increment it.
+    ///
+    /// This should only be called once the signature of the operation has been verified.
+    pub fn check_and_increment(
+        &self,
+        host: &mut impl Runtime,
+        operation: &Operation,
+    ) -> Result<(), NonceError> {
+        let expected = self.expected(host, &operation.signer)?;
+
+        if operation.nonce != expected {
+            return Err(NonceError::Invalid {
+                expected,
+                actual: operation.nonce,
+            });
+        }
+
+        let path = self.signer_path(&operation.signer)?;
+        let next = expected.saturating_add(1);
+        host.store_write_all(&path, &next.to_le_bytes())
+            .map_err(NonceError::Storage)
+    }
+
+    fn signer_path(&self, signer: &Signer) -> Result<OwnedPath, NonceError> {
+        let mut bytes = Vec::new();
+        signer
+            .bin_write(&mut bytes)
+            .expect("signers are always serializable");
+
+        let signer = OwnedPath::try_from(format!("/{}", hex::encode(bytes)))
+            .map_err(NonceError::Path)?;
+
+        concat(self.root, &signer).map_err(NonceError::Path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crypto::hash::{SecretKeyBls, SeedEd25519};
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn rollup() -> SmartRollupAddress {
+        SmartRollupAddress::from_b58check("sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa").unwrap()
+    }
+
+    fn keypair(seed: u8) -> (PublicKeyEd25519, SecretKeyEd25519) {
+        SeedEd25519::try_from_bytes(&[seed; 32])
+            .unwrap()
+            .keypair()
+            .unwrap()
+    }
+
+    fn bls_keypair(seed: u8) -> (PublicKeyBls, SecretKeyBls) {
+        let (sk, pk) = crypto::bls::keypair_from_ikm([seed; 32]).unwrap();
+        (pk, sk)
+    }
+
+    fn push_bls(
+        builder: SignedBatchBuilder,
+        (pk, sk): (PublicKeyBls, SecretKeyBls),
+        nonce: i64,
+        payload: Vec<u8>,
+    ) -> SignedBatchBuilder {
+        builder
+            .push_with(Signer::Bls(pk), nonce, payload, |digest| {
+                sk.sign(digest).map(OperationSignature::Bls)
+            })
+            .unwrap()
+    }
+
+    fn signed_batch() -> SignedBatch {
+        let (pk1, sk1) = keypair(1);
+        let (pk2, sk2) = keypair(2);
+
+        let ExternalMessageFrame::Targetted { address, contents } =
+            SignedBatchBuilder::new(rollup())
+                .push_ed25519(pk1, &sk1, 0, vec![1, 2, 3])
+                .unwrap()
+                .push_ed25519(pk2, &sk2, 5, vec![4])
+                .unwrap()
+                .build();
+
+        assert_eq!(address, rollup());
+        SignedBatch::parse(&contents).unwrap()
+    }
+
+    #[test]
+    fn encode_decode_signed_batch() {
+        let batch = signed_batch();
+
+        let mut bytes = Vec::new();
+        batch.bin_write(&mut bytes).unwrap();
+
+        assert_eq!(batch, SignedBatch::parse(&bytes).unwrap());
+
+        bytes.push(0);
+        assert!(SignedBatch::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn verify_signed_batch() {
+        let batch = signed_batch();
+        assert!(batch.verify(&rollup()).is_ok());
+
+        let mut tampered = batch.clone();
+        tampered.operations[1].payload = vec![5];
+        assert!(matches!(
+            tampered.verify(&rollup()),
+            Err(SignatureError::InvalidSignature(1))
+        ));
+
+        let mut truncated = batch.clone();
+        truncated.operations.pop();
+        assert!(matches!(
+            truncated.verify(&rollup()),
+            Err(SignatureError::SignatureCount {
+                operations: 1,
+                signatures: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn signed_batch_not_valid_for_other_rollup() {
+        let batch = signed_batch();
+        let other =
+            SmartRollupAddress::from_b58check("sr1RYurGZtN8KNSpkMcCt9CgWeUaNkzsAfXf")
+                .unwrap();
+
+        assert!(matches!(
+            batch.verify(&other),
+            Err(SignatureError::InvalidSignature(0))
+        ));
+    }
+
+    #[test]
+    fn signer_b58check_roundtrip() {
+        let (pk, sk) = keypair(3);
+        let signer = Signer::from_b58check(&pk.to_b58check()).unwrap();
+        assert_eq!(signer, Signer::Ed25519(pk));
+
+        let signature = sk.sign(b"message").unwrap();
+        let signature = OperationSignature::Ed25519(
+            Ed25519Signature::try_from(signature.as_ref()).unwrap(),
+        );
+        let encoded = signature.to_b58check();
+        assert_eq!(
+            signature,
+            OperationSignature::from_b58check(&encoded).unwrap()
+        );
+
+        assert!(signer.verify(&signature, b"message").unwrap());
+        assert!(!signer.verify(&signature, b"other").unwrap());
+        assert!(Signer::from_b58check(&encoded).is_err());
+    }
+
+    #[test]
+    fn verify_secp256k1_signature() {
+        let signer = Signer::from_b58check(
+            "sppk7a2WEfU54QzcQZ2EMjihtcxLeRtNTVxHw4FW2e8W5kEJ8ZargSb",
+        )
+        .unwrap();
+        let signature = OperationSignature::from_b58check(
+            "spsig1QLf7cczTbt4UHFGQKUrB2pS3ZTu9wdXR29zKxVPQkhBaiLez6hRcM142ms7HagQa3vuPstvMtYq44y4x4RPcrLu76ZuQ7",
+        )
+        .unwrap();
+
+        assert!(matches!(signer, Signer::Secp256k1(_)));
+        assert!(signer.verify(&signature, b"hello, test").unwrap());
+        assert!(!signer.verify(&signature, b"hello, other").unwrap());
+    }
+
+    #[test]
+    fn verify_p256_signature() {
+        let signer = Signer::from_b58check(
+            "p2pk65p7HKSGvkMdeK5yckM2nmi59oGNw4ksqdcvwxxF3AV3hopkfGS",
+        )
+        .unwrap();
+        let signature = OperationSignature::from_b58check(
+            "p2sigefoF8vJvSshWmLL6NyX6QnQUyUhq76r3F3ST6mTNqeCFzosDQyaRanoZpm14eeakZhAJ3LdGHFE4z9cPv9yTWFqWM4j9A",
+        )
+        .unwrap();
+
+        assert!(matches!(signer, Signer::P256(_)));
+        assert!(signer.verify(&signature, b"hello, message").unwrap());
+        assert!(!signer.verify(&signature, b"hello, other").unwrap());
+    }
+
+    #[test]
+    fn verify_bls_signature() {
+        let (pk, sk) = bls_keypair(1);
+        let signer = Signer::Bls(pk);
+        let signature = OperationSignature::Bls(sk.sign(b"message").unwrap());
+
+        assert!(signer.verify(&signature, b"message").unwrap());
+        assert!(!signer.verify(&signature, b"other").unwrap());
+
+        let (other, _) = bls_keypair(2);
+        assert!(!Signer::Bls(other).verify(&signature, b"message").unwrap());
+    }
+
+    #[test]
+    fn signature_scheme_must_match_signer() {
+        let (pk, sk) = keypair(1);
+        let (bls_pk, _) = bls_keypair(1);
+        let signature = sk.sign(b"message").unwrap();
+        let signature = OperationSignature::Ed25519(
+            Ed25519Signature::try_from(signature.as_ref()).unwrap(),
+        );
+
+        assert!(Signer::Ed25519(pk).verify(&signature, b"message").unwrap());
+        assert!(!Signer::Bls(bls_pk).verify(&signature, b"message").unwrap());
+    }
+
+    #[test]
+    fn verify_aggregated_batch() {
+        let builder = push_bls(
+            SignedBatchBuilder::new(rollup()),
+            bls_keypair(1),
+            0,
+            vec![1],
+        );
+        let builder = push_bls(builder, bls_keypair(2), 3, vec![2, 3]);
+
+        let ExternalMessageFrame::Targetted { address, contents } =
+            builder.build_aggregated().unwrap();
+        assert_eq!(address, rollup());
+
+        let batch = SignedBatch::parse(&contents).unwrap();
+        assert!(matches!(batch.signature, BatchSignature::Aggregated(_)));
+        assert!(batch.verify(&rollup()).is_ok());
+
+        let mut tampered = batch.clone();
+        tampered.operations[0].nonce = 1;
+        assert!(matches!(
+            tampered.verify(&rollup()),
+            Err(SignatureError::InvalidAggregatedSignature)
+        ));
+
+        let mut truncated = batch;
+        truncated.operations.pop();
+        assert!(matches!(
+            truncated.verify(&rollup()),
+            Err(SignatureError::InvalidAggregatedSignature)
+        ));
+    }
+
+    #[test]
+    fn aggregated_batch_requires_bls_signers() {
+        let (pk, sk) = keypair(1);
+        let builder = push_bls(
+            SignedBatchBuilder::new(rollup()),
+            bls_keypair(1),
+            0,
+            vec![1],
+        );
+        let builder = builder.push_ed25519(pk, &sk, 0, vec![2]).unwrap();
+
+        assert!(matches!(
+            builder.build_aggregated(),
+            Err(SignatureError::NotAggregatable(1))
+        ));
+
+        let ExternalMessageFrame::Targetted { contents, .. } = push_bls(
+            SignedBatchBuilder::new(rollup()),
+            bls_keypair(1),
+            0,
+            vec![1],
+        )
+        .build_aggregated()
+        .unwrap();
+        let mut batch = SignedBatch::parse(&contents).unwrap();
+        batch
+            .operations
+            .insert(0, signed_batch().operations.remove(0));
+
+        assert!(matches!(
+            batch.verify(&rollup()),
+            Err(SignatureError::NotAggregatable(0))
+        ));
+    }
+
+    const NONCES: RefPath = RefPath::assert_from(b"/nonces");
+
+    #[test]
+    fn nonce_store_rejects_replayed_operations() {
+        let mut host = MockHost::default();
+        let store = NonceStore::new(&NONCES);
+        let batch = signed_batch();
+        let mut operation = batch.operations[0].clone();
+        let other = &batch.operations[1].signer;
+
+        assert_eq!(store.expected(&host, &operation.signer).unwrap(), 0);
+        store.check_and_increment(&mut host, &operation).unwrap();
+        assert_eq!(store.expected(&host, &operation.signer).unwrap(), 1);
+
+        // Replaying the operation is rejected, and doesn't change the nonce.
+        assert!(matches!(
+            store.check_and_increment(&mut host, &operation),
+            Err(NonceError::Invalid {
+                expected: 1,
+                actual: 0
+            })
+        ));
+        assert_eq!(store.expected(&host, &operation.signer).unwrap(), 1);
+
+        // Nonces can't be skipped either.
+        operation.nonce = 2;
+        assert!(matches!(
+            store.check_and_increment(&mut host, &operation),
+            Err(NonceError::Invalid {
+                expected: 1,
+                actual: 2
+            })
+        ));
+
+        operation.nonce = 1;
+        store.check_and_increment(&mut host, &operation).unwrap();
+        assert_eq!(store.expected(&host, &operation.signer).unwrap(), 2);
+
+        // Nonces are kept per signer.
+        assert_eq!(store.expected(&host, other).unwrap(), 0);
+    }
+
+    #[test]
+    fn nonce_store_rejects_invalid_stored_nonces() {
+        let mut host = MockHost::default();
+        let store = NonceStore::new(&NONCES);
+        let operation = signed_batch().operations.remove(0);
+        let path = store.signer_path(&operation.signer).unwrap();
+
+        host.store_write_all(&path, &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            store.expected(&host, &operation.signer),
+            Err(NonceError::Storage(RuntimeError::DecodingError))
+        ));
+        assert!(matches!(
+            store.check_and_increment(&mut host, &operation),
+            Err(NonceError::Storage(_))
+        ));
+
+        host.store_delete(&path).unwrap();
+        let child = concat(&path, &RefPath::assert_from(b"/child")).unwrap();
+        host.store_write_all(&child, &[0]).unwrap();
+        assert!(matches!(
+            store.expected(&host, &operation.signer),
+            Err(NonceError::Storage(RuntimeError::HostErr(
+                host::Error::StoreNotAValue
+            )))
+        ));
+    }
+}
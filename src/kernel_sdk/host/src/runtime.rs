@@ -19,7 +19,7 @@ use crate::dal_parameters::RollupDalParameters;
 use crate::input::Message;
 use crate::metadata::RollupMetadata;
 #[cfg(feature = "alloc")]
-use crate::path::{Path, RefPath};
+use crate::path::{concat, OwnedPath, Path, RefPath, PATH_MAX_SIZE, PATH_SEPARATOR};
 #[cfg(not(feature = "alloc"))]
 use crate::path::{Path, RefPath};
 use crate::DAL_PARAMETERS_SIZE;
@@ -161,6 +161,56 @@ pub trait Runtime {
     /// See [SmartRollupCore::store_list_size].
     fn store_count_subkeys<T: Path>(&self, prefix: &T) -> Result<u64, RuntimeError>;
 
+    /// Path of the subkey of `prefix` at `index`, with `index` smaller than
+    /// the [`store_count_subkeys`] of `prefix`.
+    ///
+    /// The value stored at `prefix` itself, if any, is counted as a subkey:
+    /// `None` is returned when `index` designates it.
+    ///
+    /// See [SmartRollupCore::store_get_nth_key].
+    ///
+    /// [`store_count_subkeys`]: Runtime::store_count_subkeys
+    #[cfg(feature = "alloc")]
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError>;
+
+    /// Iterate over the paths of the subkeys of `prefix`.
+    ///
+    /// The value stored at `prefix` itself is skipped. A `prefix` with no
+    /// subkeys yields nothing.
+    #[cfg(feature = "alloc")]
+    fn store_subkeys<'a, T: Path>(
+        &'a self,
+        prefix: &'a T,
+    ) -> Result<Subkeys<'a, Self, T>, RuntimeError> {
+        let count = match self.store_count_subkeys(prefix) {
+            Ok(count) => count,
+            Err(RuntimeError::HostErr(Error::StoreNotANode)) => 0,
+            Err(e) => return Err(e),
+        };
+
+        Ok(Subkeys {
+            host: self,
+            prefix,
+            index: 0,
+            count,
+        })
+    }
+
+    /// List the paths of the subkeys of `prefix`.
+    ///
+    /// See [`Runtime::store_subkeys`].
+    #[cfg(feature = "alloc")]
+    fn store_list_subkeys<T: Path>(
+        &self,
+        prefix: &T,
+    ) -> Result<Vec<OwnedPath>, RuntimeError> {
+        self.store_subkeys(prefix)?.collect()
+    }
+
     /// Move one part of durable storage to a different location
     ///
     /// See [SmartRollupCore::store_move].
@@ -244,6 +294,39 @@ pub trait Runtime {
     fn runtime_version(&self) -> Result<String, RuntimeError>;
 }
 
+/// Iterator over the paths of the subkeys of a prefix, returned by
+/// [`Runtime::store_subkeys`].
+#[cfg(feature = "alloc")]
+pub struct Subkeys<'a, Host: ?Sized, T> {
+    host: &'a Host,
+    prefix: &'a T,
+    index: u64,
+    count: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Host: Runtime + ?Sized, T: Path> Iterator for Subkeys<'a, Host, T> {
+    type Item = Result<OwnedPath, RuntimeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.count {
+            let index = self.index;
+            self.index += 1;
+
+            match self.host.store_get_subkey(self.prefix, index) {
+                Ok(None) => continue,
+                Ok(Some(path)) => return Some(Ok(path)),
+                Err(e) => {
+                    // Stop iterating after the first error.
+                    self.index = self.count;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
 const REBOOT_PATH: RefPath = RefPath::assert_from(b"/kernel/env/reboot");
 
 impl<Host> Runtime for Host
@@ -515,6 +598,47 @@ where
         }
     }
 
+    #[cfg(feature = "alloc")]
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError> {
+        let index =
+            i64::try_from(index).map_err(|_| RuntimeError::StoreListIndexOutOfBounds)?;
+
+        // The subkey name is written after a separator, to form a path step.
+        let mut buffer = [PATH_SEPARATOR; PATH_MAX_SIZE + 1];
+        let res = unsafe {
+            SmartRollupCore::store_get_nth_key(
+                self,
+                prefix.as_ptr(),
+                prefix.size(),
+                index,
+                buffer[1..].as_mut_ptr(),
+                PATH_MAX_SIZE,
+            )
+        };
+
+        match Error::wrap(res) {
+            // The value at `prefix` has an empty name.
+            Ok(0) => Ok(None),
+            Ok(size) => {
+                // SAFETY: the host only returns names of existing subkeys,
+                // which are valid path steps.
+                let step =
+                    unsafe { OwnedPath::from_bytes_unchecked(buffer[..=size].to_vec()) };
+                concat(prefix, &step)
+                    .map(Some)
+                    .map_err(|_| RuntimeError::DecodingError)
+            }
+            Err(Error::StoreInvalidSubkeyIndex) => {
+                Err(RuntimeError::StoreListIndexOutOfBounds)
+            }
+            Err(e) => Err(RuntimeError::HostErr(e)),
+        }
+    }
+
     fn store_move(
         &mut self,
         from_path: &impl Path,
@@ -1263,6 +1387,47 @@ mod tests {
         assert_eq!(Ok(subkey_count.try_into().unwrap()), result);
     }
 
+    #[test]
+    fn store_get_subkey() {
+        // Arrange
+        const PATH: RefPath<'static> = RefPath::assert_from(b"/prefix");
+        const SUBKEYS: [&str; 3] = ["a", "", "b.c"];
+
+        let mut mock = MockSmartRollupCore::new();
+
+        mock.expect_store_get_nth_key()
+            .withf(|ptr, size, _index, _dst, max_size| {
+                let slice = unsafe { from_raw_parts(*ptr, *size) };
+
+                PATH.as_bytes() == slice && *max_size > 0
+            })
+            .returning(|_, _, index, dst, _| match SUBKEYS.get(index as usize) {
+                Some(name) => {
+                    let buffer = unsafe { from_raw_parts_mut(dst, name.len()) };
+                    buffer.copy_from_slice(name.as_bytes());
+                    name.len() as i32
+                }
+                None => Error::StoreInvalidSubkeyIndex.code(),
+            });
+        mock.expect_store_list_size()
+            .return_const(SUBKEYS.len() as i64);
+
+        // Act
+        let first = mock.store_get_subkey(&PATH, 0);
+        let value = mock.store_get_subkey(&PATH, 1);
+        let out_of_bounds = mock.store_get_subkey(&PATH, 3);
+        let subkeys = mock.store_list_subkeys(&PATH);
+
+        // Assert
+        let expected_a = OwnedPath::try_from("/prefix/a".to_string()).unwrap();
+        let expected_bc = OwnedPath::try_from("/prefix/b.c".to_string()).unwrap();
+
+        assert_eq!(Ok(Some(expected_a.clone())), first);
+        assert_eq!(Ok(None), value);
+        assert_eq!(Err(RuntimeError::StoreListIndexOutOfBounds), out_of_bounds);
+        assert_eq!(Ok(vec![expected_a, expected_bc]), subkeys);
+    }
+
     #[test]
     fn reveal_preimage_ok() {
         let mut mock = MockSmartRollupCore::new();
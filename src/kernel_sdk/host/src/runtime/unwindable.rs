@@ -9,6 +9,8 @@
 use super::{Runtime, RuntimeError, ValueType};
 #[cfg(feature = "alloc")]
 use crate::input::Message;
+#[cfg(feature = "alloc")]
+use crate::path::OwnedPath;
 use crate::{dal_parameters::RollupDalParameters, metadata::RollupMetadata, path::Path};
 use alloc::rc::Rc;
 use core::panic::{RefUnwindSafe, UnwindSafe};
@@ -148,6 +150,15 @@ impl<R: Runtime> Runtime for UnwindableRuntime<R> {
         self.runtime.read().unwrap().store_count_subkeys(prefix)
     }
 
+    #[cfg(feature = "alloc")]
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError> {
+        self.runtime.read().unwrap().store_get_subkey(prefix, index)
+    }
+
     fn store_move(
         &mut self,
         from_path: &impl Path,
@@ -6,6 +6,7 @@ use std::process::Command;
 
 fn main() {
     println!("cargo:rerun-if-changed=./installer.wasm");
+    println!("cargo:rerun-if-changed=./authenticated-installer.wasm");
 
     Command::new("sh")
         .arg("-c")
@@ -0,0 +1,157 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Signing of upgrades, for installers governed by an upgrade authority.
+
+use tezos_crypto_rs::hash::{HashTrait, SeedEd25519};
+use tezos_crypto_rs::CryptoError;
+use tezos_data_encoding::enc::BinError;
+use tezos_smart_rollup_encoding::inbox::signed::{OperationSignature, Signer};
+use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+use tezos_smart_rollup_host::path::OwnedPath;
+use tezos_smart_rollup_installer_config::binary::auth::{
+    Committee, CommitteeSignature, UpgradeAuthority, UPGRADE_AUTHORITY_PATH,
+};
+use tezos_smart_rollup_installer_config::binary::owned::{
+    OwnedBytes, OwnedConfigInstruction, OwnedConfigProgram,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Invalid public key: {0}.")]
+    PublicKey(String),
+    #[error("Invalid committee threshold: {0}.")]
+    Threshold(String),
+    #[error("Invalid signature: {0}.")]
+    Signature(String),
+    #[error("Invalid rollup address: {0}.")]
+    RollupAddress(String),
+    #[error("Invalid secret key, expected an `edsk` ed25519 seed.")]
+    SecretKey,
+    #[error("Unable to encode config program: {0}.")]
+    Encoding(BinError),
+    #[error("Unable to sign upgrade: {0}.")]
+    Signing(CryptoError),
+}
+
+/// Signature of an upgrade, by the upgrade authority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeSignature {
+    /// Signature by the key of the authority.
+    Key(OperationSignature),
+    /// Signatures by members of the committee of the authority.
+    Committee(Vec<CommitteeSignature>),
+}
+
+/// Parse an upgrade authority: either a single public key, or a committee
+/// given as `THRESHOLD:PK1,PK2,...`.
+pub fn parse_authority(authority: &str) -> Result<UpgradeAuthority, AuthError> {
+    let parse_signer = |pk: &str| {
+        Signer::from_b58check(pk.trim()).map_err(|_| AuthError::PublicKey(pk.into()))
+    };
+
+    match authority.split_once(':') {
+        None => parse_signer(authority).map(UpgradeAuthority::Key),
+        Some((threshold, members)) => {
+            let threshold = threshold
+                .parse()
+                .map_err(|_| AuthError::Threshold(threshold.into()))?;
+            let members = members
+                .split(',')
+                .map(parse_signer)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(UpgradeAuthority::Committee(Committee {
+                threshold,
+                members,
+            }))
+        }
+    }
+}
+
+/// Parse a signature of an upgrade.
+pub fn parse_signature(signature: &str) -> Result<OperationSignature, AuthError> {
+    OperationSignature::from_b58check(signature)
+        .map_err(|_| AuthError::Signature(signature.into()))
+}
+
+/// Parse the `sr1` address of the rollup an upgrade is signed for.
+pub fn parse_rollup_address(address: &str) -> Result<SmartRollupAddress, AuthError> {
+    SmartRollupAddress::from_b58check(address)
+        .map_err(|_| AuthError::RollupAddress(address.into()))
+}
+
+/// Parse the signature of an upgrade by a committee member, given as
+/// `INDEX:SIGNATURE`.
+pub fn parse_committee_signature(
+    signature: &str,
+) -> Result<CommitteeSignature, AuthError> {
+    let (member, member_signature) = signature
+        .split_once(':')
+        .ok_or_else(|| AuthError::Signature(signature.into()))?;
+    let member = member
+        .parse()
+        .map_err(|_| AuthError::Signature(signature.into()))?;
+    Ok(CommitteeSignature {
+        member,
+        signature: parse_signature(member_signature)?,
+    })
+}
+
+/// Append the instruction setting the upgrade authority to the config program.
+///
+/// Subsequent upgrades must then be signed by `authority`.
+pub fn with_upgrade_authority(
+    mut config: OwnedConfigProgram,
+    authority: &UpgradeAuthority,
+) -> Result<OwnedConfigProgram, AuthError> {
+    let authority = authority.to_bytes().map_err(AuthError::Encoding)?;
+    config.0.push(OwnedConfigInstruction::set_instr(
+        OwnedBytes(authority),
+        OwnedPath::from(UPGRADE_AUTHORITY_PATH),
+    ));
+    Ok(config)
+}
+
+/// Sign the config program with an ed25519 secret key, given as an `edsk` seed, to
+/// upgrade the rollup at `rollup_address` with the given `nonce`.
+pub fn sign_upgrade(
+    config: &OwnedConfigProgram,
+    rollup_address: &SmartRollupAddress,
+    nonce: u64,
+    secret_key: &str,
+) -> Result<OperationSignature, AuthError> {
+    let (_, sk) = SeedEd25519::from_b58check(secret_key)
+        .map_err(|_| AuthError::SecretKey)?
+        .keypair()
+        .map_err(|_| AuthError::SecretKey)?;
+    let digest = config
+        .upgrade_digest(rollup_address, nonce)
+        .map_err(AuthError::Encoding)?;
+    sk.sign(digest)
+        .map(OperationSignature::Ed25519)
+        .map_err(AuthError::Signing)
+}
+
+/// Prepend the verification instruction for `signature`, made with the given `nonce`,
+/// to the config program.
+pub fn authenticate(
+    config: OwnedConfigProgram,
+    nonce: u64,
+    signature: &UpgradeSignature,
+) -> Result<OwnedConfigProgram, AuthError> {
+    let verify = match signature {
+        UpgradeSignature::Key(signature) => {
+            OwnedConfigInstruction::verify_signature_instr(nonce, signature)
+        }
+        UpgradeSignature::Committee(signatures) => {
+            OwnedConfigInstruction::verify_multisig_instr(nonce, signatures)
+        }
+    }
+    .map_err(AuthError::Encoding)?;
+
+    let mut instructions = vec![verify];
+    instructions.extend(config.0);
+    Ok(OwnedConfigProgram(instructions))
+}
@@ -4,6 +4,14 @@
 
 use clap::{Parser, Subcommand};
 use std::ffi::OsString;
+use tezos_smart_rollup_encoding::inbox::signed::OperationSignature;
+use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+use tezos_smart_rollup_installer::auth::{
+    parse_authority, parse_committee_signature, parse_rollup_address, parse_signature,
+};
+use tezos_smart_rollup_installer_config::binary::auth::{
+    CommitteeSignature, UpgradeAuthority,
+};
 
 #[derive(Parser)]
 #[command(long_about = None)]
@@ -29,5 +37,123 @@ pub enum Commands {
 
         #[arg(short, long, value_name = "DISPLAY_ROOT_HASH")]
         display_root_hash: bool,
+
+        /// Set the authority that must sign subsequent upgrades: either a public key,
+        /// or a committee given as `THRESHOLD:PK1,PK2,...`.
+        #[arg(
+            short = 'A',
+            long,
+            value_name = "UPGRADE_AUTHORITY",
+            value_parser = parse_authority
+        )]
+        authority: Option<UpgradeAuthority>,
+
+        /// Signature of the upgrade by the current upgrade authority.
+        #[arg(
+            long,
+            value_name = "SIGNATURE",
+            value_parser = parse_signature,
+            conflicts_with = "committee_signature",
+            requires = "nonce"
+        )]
+        signature: Option<OperationSignature>,
+
+        /// Signature of the upgrade by a member of the current upgrade committee,
+        /// given as `INDEX:SIGNATURE`.
+        #[arg(
+            long,
+            value_name = "COMMITTEE_SIGNATURE",
+            value_parser = parse_committee_signature,
+            requires = "nonce"
+        )]
+        committee_signature: Vec<CommitteeSignature>,
+
+        /// Nonce the upgrade was signed with.
+        #[arg(long, value_name = "NONCE")]
+        nonce: Option<u64>,
+    },
+    /// Decode the config of an installer kernel, or a setup file, into readable
+    /// instructions.
+    Decode {
+        #[arg(
+            short,
+            long,
+            value_name = "INSTALLER",
+            required_unless_present = "setup_file",
+            conflicts_with = "setup_file"
+        )]
+        installer: Option<OsString>,
+
+        #[arg(short = 'S', long, value_name = "INSTALLER_SETUP_CONFIG")]
+        setup_file: Option<OsString>,
+    },
+    /// Check that the preimages revealed by an installer, or from root hashes, are
+    /// present in the preimages dir and hash correctly.
+    Verify {
+        #[arg(
+            short,
+            long,
+            value_name = "INSTALLER",
+            required_unless_present = "root_hash"
+        )]
+        installer: Option<OsString>,
+
+        #[arg(short, long, value_name = "ROOT_HASH")]
+        root_hash: Vec<String>,
+
+        #[arg(short = 'P', long, value_name = "PREIMAGES_DIR")]
+        preimages_dir: OsString,
+    },
+    /// Run an installer against a mock host, and display the resulting durable
+    /// storage.
+    Simulate {
+        #[arg(short, long, value_name = "INSTALLER")]
+        installer: OsString,
+
+        #[arg(short = 'P', long, value_name = "PREIMAGES_DIR")]
+        preimages_dir: Option<OsString>,
+
+        /// Start from a `MockHost` snapshot of the durable storage, rather than
+        /// an empty one.
+        #[arg(long, value_name = "SNAPSHOT")]
+        snapshot: Option<OsString>,
+    },
+    /// Sign an upgrade, for an installer governed by an upgrade authority.
+    SignUpgrade {
+        #[arg(short, long, value_name = "UPGRADE_TO_KERNEL")]
+        upgrade_to: OsString,
+
+        #[arg(short = 'P', long, value_name = "PREIMAGES_OUTPUT_DIR")]
+        preimages_dir: OsString,
+
+        #[arg(short = 'S', long, value_name = "INSTALLER_SETUP_CONFIG")]
+        setup_file: Option<OsString>,
+
+        /// The new upgrade authority, if the upgrade changes it.
+        #[arg(
+            short = 'A',
+            long,
+            value_name = "UPGRADE_AUTHORITY",
+            value_parser = parse_authority
+        )]
+        authority: Option<UpgradeAuthority>,
+
+        /// The `sr1` address of the rollup to upgrade.
+        #[arg(
+            short,
+            long,
+            value_name = "ROLLUP_ADDRESS",
+            value_parser = parse_rollup_address
+        )]
+        rollup_address: SmartRollupAddress,
+
+        /// Nonce of the upgrade, greater than the nonce of any previous upgrade of
+        /// the rollup.
+        #[arg(short, long, value_name = "NONCE")]
+        nonce: u64,
+
+        /// The `edsk` ed25519 seed of the signer.
+        #[arg(short = 'k', long, value_name = "SECRET_KEY")]
+        secret_key: String,
     },
 }
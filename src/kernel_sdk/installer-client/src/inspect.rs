@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Inspection of installer kernels and setup files.
+
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::path::Path;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_encoding::dac::pages::prepare_preimages;
+use tezos_smart_rollup_encoding::inbox::signed::OperationSignature;
+use tezos_smart_rollup_host::path::{OwnedPath, Path as _};
+use tezos_smart_rollup_installer_config::binary::auth::CommitteeSignature;
+use tezos_smart_rollup_installer_config::binary::owned::{
+    OwnedConfigInstruction, OwnedConfigProgram,
+};
+use tezos_smart_rollup_installer_config::binary::{
+    ConfigInstruction, MoveInstruction, RevealInstruction, SetInstruction,
+    VerifyMultisigInstruction, VerifySignatureInstruction,
+};
+use tezos_smart_rollup_installer_config::yaml::{ConfigConversionError, YamlConfig};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InspectError {
+    #[error("Unable to read file: {0}.")]
+    File(std::io::Error),
+    #[error("Unable to decode hex installer: {0}.")]
+    Hex(hex::FromHexError),
+    #[error("Unable to decode installer config: {0}.")]
+    Config(&'static str),
+    #[error("Unable to parse setup file: {0}.")]
+    Setup(serde_yaml::Error),
+    #[error("Unable to convert setup file to a config program: {0}.")]
+    Conversion(#[from] ConfigConversionError),
+}
+
+/// Read an installer kernel, either as `wasm`, or hex-encoded.
+pub fn read_installer(installer: &Path) -> Result<Vec<u8>, InspectError> {
+    let kernel = fs::read(installer).map_err(InspectError::File)?;
+
+    if installer.extension() == Some(OsStr::new("wasm")) {
+        return Ok(kernel);
+    }
+
+    let kernel = String::from_utf8_lossy(&kernel);
+    hex::decode(kernel.trim()).map_err(InspectError::Hex)
+}
+
+/// Decode the config program of an installer kernel.
+pub fn installer_config(installer: &Path) -> Result<OwnedConfigProgram, InspectError> {
+    let kernel = read_installer(installer)?;
+    OwnedConfigProgram::decode(&kernel).map_err(InspectError::Config)
+}
+
+/// Convert a setup file to the config program it is installed with.
+///
+/// Values too large to be set directly are revealed instead: their root hash is
+/// computed, but no preimages are saved.
+pub fn setup_file_config(setup_file: &Path) -> Result<OwnedConfigProgram, InspectError> {
+    let setup_file = File::open(setup_file).map_err(InspectError::File)?;
+    let yaml_config = YamlConfig::from_reader(setup_file).map_err(InspectError::Setup)?;
+
+    let content_to_preimages =
+        |content: Vec<u8>| prepare_preimages(&content, |_, _| {}).ok();
+    Ok(yaml_config.to_config_program(content_to_preimages)?)
+}
+
+/// Human readable description of a config instruction.
+pub fn describe_instruction(instr: &OwnedConfigInstruction) -> String {
+    let path = |path: &OwnedPath| String::from_utf8_lossy(path.as_bytes()).into_owned();
+
+    match instr {
+        ConfigInstruction::Reveal(RevealInstruction { hash, to }) => {
+            format!("reveal {} to {}", hex::encode(&hash.0), path(to))
+        }
+        ConfigInstruction::Move(MoveInstruction { from, to }) => {
+            format!("move {} to {}", path(from), path(to))
+        }
+        ConfigInstruction::Set(SetInstruction { value, to }) => {
+            format!("set {} at {}", hex::encode(&value.0), path(to))
+        }
+        ConfigInstruction::VerifySignature(VerifySignatureInstruction {
+            nonce,
+            signature,
+        }) => match OperationSignature::nom_read(&signature.0) {
+            Ok(([], signature)) => {
+                format!(
+                    "verify signature {} (nonce {})",
+                    signature.to_b58check(),
+                    nonce
+                )
+            }
+            _ => format!(
+                "verify signature <invalid {}> (nonce {})",
+                hex::encode(&signature.0),
+                nonce
+            ),
+        },
+        ConfigInstruction::VerifyMultisig(VerifyMultisigInstruction {
+            nonce,
+            signatures,
+        }) => {
+            let mut input = signatures.0.as_slice();
+            let mut described = vec![];
+            while !input.is_empty() {
+                match CommitteeSignature::nom_read(input) {
+                    Ok((rest, CommitteeSignature { member, signature })) => {
+                        described.push(format!("{}:{}", member, signature.to_b58check()));
+                        input = rest;
+                    }
+                    Err(_) => {
+                        described.push(format!("<invalid {}>", hex::encode(input)));
+                        break;
+                    }
+                }
+            }
+            format!("verify multisig {} (nonce {})", described.join(" "), nonce)
+        }
+    }
+}
@@ -8,6 +8,10 @@ use wasm_gen::write_custom_section;
 
 const INSTALLER_KERNEL: &[u8] = include_bytes!("../installer.wasm");
 
+// Installer built with support for verification instructions.
+const AUTHENTICATED_INSTALLER_KERNEL: &[u8] =
+    include_bytes!("../authenticated-installer.wasm");
+
 /// Set the installer config for the reveal installer.
 ///
 /// This is set as a custom section of the installer binary.
@@ -20,9 +24,16 @@ const INSTALLER_KERNEL: &[u8] = include_bytes!("../installer.wasm");
 /// a root hash, using the _reveal data channel_, is possible with this approach.
 ///
 /// For more information about which instructions config might contain,
-/// see in `installer_kernel/src/instr.rs`.
+/// see in `installer-config/src/binary/instr.rs`.
+///
+/// Configs containing verification instructions use the larger installer
+/// built with the `authenticated-upgrades` feature.
 pub fn with_config_program(config_programm: OwnedConfigProgram) -> Vec<u8> {
-    let mut installer = INSTALLER_KERNEL.to_vec();
+    let mut installer = if config_programm.0.iter().any(|i| i.is_verification()) {
+        AUTHENTICATED_INSTALLER_KERNEL.to_vec()
+    } else {
+        INSTALLER_KERNEL.to_vec()
+    };
 
     let mut config_programm_encoded = vec![];
     config_programm
@@ -33,3 +44,11 @@ pub fn with_config_program(config_programm: OwnedConfigProgram) -> Vec<u8> {
 
     installer
 }
+
+/// Whether `installer` was built with support for verification instructions.
+///
+/// Installers produced by [`with_config_program`] start with the kernel they
+/// were built from, followed by the config as a custom section.
+pub fn supports_verification(installer: &[u8]) -> bool {
+    installer.starts_with(AUTHENTICATED_INSTALLER_KERNEL)
+}
@@ -2,9 +2,12 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod auth;
 pub mod config;
+pub mod inspect;
 pub mod installer;
 pub mod preimages;
+pub mod simulate;
 
 use tezos_smart_rollup_host::path::RefPath;
 
@@ -3,15 +3,28 @@
 // SPDX-License-Identifier: MIT
 
 mod commands;
-mod installer;
 mod output;
-mod preimages;
 
 use clap::Parser;
 use commands::Cli;
 use commands::Commands;
+use std::ffi::OsString;
 use std::path::Path;
+use tezos_smart_rollup_encoding::dac::PreimageHash;
+use tezos_smart_rollup_installer::auth::{
+    authenticate, sign_upgrade, with_upgrade_authority, AuthError, UpgradeSignature,
+};
 use tezos_smart_rollup_installer::config::{create_installer_config, ConfigurationError};
+use tezos_smart_rollup_installer::inspect::{
+    describe_instruction, installer_config, read_installer, setup_file_config,
+    InspectError,
+};
+use tezos_smart_rollup_installer::installer;
+use tezos_smart_rollup_installer::preimages;
+use tezos_smart_rollup_installer::simulate::{simulate, SimulateError};
+use tezos_smart_rollup_installer_config::binary::auth::UpgradeAuthority;
+use tezos_smart_rollup_installer_config::binary::owned::OwnedConfigProgram;
+use tezos_smart_rollup_installer_config::binary::ConfigInstruction;
 use thiserror::Error;
 
 fn main() -> Result<(), ClientError> {
@@ -22,19 +35,31 @@ fn main() -> Result<(), ClientError> {
             preimages_dir,
             setup_file,
             display_root_hash,
+            authority,
+            signature,
+            committee_signature,
+            nonce,
         } => {
-            let upgrade_to = Path::new(&upgrade_to);
             let output = Path::new(&output);
-            let preimages_dir = Path::new(&preimages_dir);
 
-            let kernel =
-                std::fs::read(upgrade_to).map_err(preimages::Error::ContentFile)?;
-
-            let root_hash = preimages::content_to_preimages(kernel, preimages_dir)?;
+            let (root_hash, config) =
+                upgrade_config(&upgrade_to, &preimages_dir, setup_file, authority)?;
             let root_hash_hex = hex::encode(root_hash.as_ref());
 
-            let config =
-                create_installer_config(root_hash, setup_file, Some(preimages_dir))?;
+            let signature = match signature {
+                Some(signature) => Some(UpgradeSignature::Key(signature)),
+                None if !committee_signature.is_empty() => {
+                    Some(UpgradeSignature::Committee(committee_signature))
+                }
+                None => None,
+            };
+            let config = match (signature, nonce) {
+                (Some(signature), Some(nonce)) => {
+                    authenticate(config, nonce, &signature)?
+                }
+                (Some(_), None) => unreachable!("Required by the command line parser"),
+                (None, _) => config,
+            };
             let kernel = installer::with_config_program(config);
 
             output::save_kernel(output, &kernel).map_err(ClientError::SaveInstaller)?;
@@ -43,11 +68,121 @@ fn main() -> Result<(), ClientError> {
                 println!("ROOT_HASH: {}", root_hash_hex);
             };
         }
+        Commands::Decode {
+            installer,
+            setup_file,
+        } => {
+            let config = match (installer, setup_file) {
+                (Some(installer), _) => installer_config(Path::new(&installer))?,
+                (None, Some(setup_file)) => setup_file_config(Path::new(&setup_file))?,
+                (None, None) => unreachable!("Required by the command line parser"),
+            };
+
+            for (i, instr) in config.0.iter().enumerate() {
+                println!("{}: {}", i, describe_instruction(instr));
+            }
+        }
+        Commands::Verify {
+            installer,
+            root_hash,
+            preimages_dir,
+        } => {
+            let mut root_hashes = vec![];
+            if let Some(installer) = installer {
+                for instr in installer_config(Path::new(&installer))?.0 {
+                    if let ConfigInstruction::Reveal(reveal) = instr {
+                        root_hashes.push(reveal.hash.0);
+                    }
+                }
+            }
+            for hash in root_hash {
+                root_hashes.push(hex::decode(&hash).map_err(InspectError::Hex)?);
+            }
+            let root_hashes = root_hashes
+                .into_iter()
+                .map(|hash| {
+                    let hex = hex::encode(&hash);
+                    hash.try_into()
+                        .map_err(|_| ClientError::InvalidRootHash(hex))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match preimages::verify_preimages(root_hashes, Path::new(&preimages_dir)) {
+                Ok(checked) => println!("{} preimages verified.", checked),
+                Err(issues) => {
+                    for issue in issues.iter() {
+                        eprintln!("{}", issue);
+                    }
+                    return Err(ClientError::InvalidPreimages(issues.len()));
+                }
+            }
+        }
+        Commands::Simulate {
+            installer,
+            preimages_dir,
+            snapshot,
+        } => {
+            let kernel = read_installer(Path::new(&installer))?;
+            let simulation = simulate(
+                &kernel,
+                preimages_dir.as_ref().map(Path::new),
+                snapshot.as_ref().map(Path::new),
+            )?;
+
+            for (path, value) in simulation.durable.iter() {
+                if value.len() <= 32 {
+                    println!("{} ({} bytes): {}", path, value.len(), hex::encode(value));
+                } else {
+                    println!("{} ({} bytes)", path, value.len());
+                }
+            }
+
+            simulation.result.map_err(ClientError::InstallationFailed)?;
+        }
+        Commands::SignUpgrade {
+            upgrade_to,
+            preimages_dir,
+            setup_file,
+            authority,
+            rollup_address,
+            nonce,
+            secret_key,
+        } => {
+            let (_, config) =
+                upgrade_config(&upgrade_to, &preimages_dir, setup_file, authority)?;
+            let signature = sign_upgrade(&config, &rollup_address, nonce, &secret_key)?;
+
+            println!("SIGNATURE: {}", signature.to_b58check());
+        }
     }
 
     Ok(())
 }
 
+/// The config program upgrading to `upgrade_to`, as signed by the upgrade authority.
+fn upgrade_config(
+    upgrade_to: &OsString,
+    preimages_dir: &OsString,
+    setup_file: Option<OsString>,
+    authority: Option<UpgradeAuthority>,
+) -> Result<(PreimageHash, OwnedConfigProgram), ClientError> {
+    let upgrade_to = Path::new(upgrade_to);
+    let preimages_dir = Path::new(preimages_dir);
+
+    let kernel = std::fs::read(upgrade_to).map_err(preimages::Error::ContentFile)?;
+
+    let root_hash = preimages::content_to_preimages(kernel, preimages_dir)?;
+
+    let config =
+        create_installer_config(root_hash.clone(), setup_file, Some(preimages_dir))?;
+    let config = match authority {
+        Some(authority) => with_upgrade_authority(config, &authority)?,
+        None => config,
+    };
+
+    Ok((root_hash, config))
+}
+
 #[derive(Debug, Error)]
 enum ClientError {
     #[error("Error preimaging kernel: {0}")]
@@ -56,4 +191,16 @@ enum ClientError {
     ConfigError(#[from] ConfigurationError),
     #[error("Unable to save installer kernel: {0}")]
     SaveInstaller(std::io::Error),
+    #[error("Error authenticating upgrade: {0}")]
+    AuthError(#[from] AuthError),
+    #[error("Error inspecting installer: {0}")]
+    InspectError(#[from] InspectError),
+    #[error("Error simulating installer: {0}")]
+    SimulateError(#[from] SimulateError),
+    #[error("Invalid root hash: {0}")]
+    InvalidRootHash(String),
+    #[error("{0} invalid preimages")]
+    InvalidPreimages(usize),
+    #[error("Installation failed: {0}")]
+    InstallationFailed(&'static str),
 }
@@ -2,9 +2,13 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::collections::BTreeSet;
 use std::fs;
 use std::path::Path;
-use tezos_smart_rollup_encoding::dac::pages::prepare_preimages;
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
+use tezos_smart_rollup_encoding::dac::pages::{
+    make_preimage_hash, prepare_preimages, SlicePage,
+};
 use tezos_smart_rollup_encoding::dac::PreimageHash;
 use thiserror::Error;
 
@@ -38,3 +42,56 @@ pub fn content_to_preimages(
     prepare_preimages(content.as_ref(), save_preimages)
         .map_err(|e| Error::Preimage(e.to_string()))
 }
+
+/// A problem with a preimage, found by [`verify_preimages`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PreimageIssue {
+    #[error("Preimage {0} is missing.")]
+    Missing(String),
+    #[error("Preimage {0} doesn't match its hash.")]
+    HashMismatch(String),
+    #[error("Preimage {0} is not a valid page.")]
+    InvalidPage(String),
+}
+
+/// Check that the preimages of the pages revealed from `root_hashes` are all in
+/// `preimage_dir`, and hash correctly.
+///
+/// Returns the number of preimages checked, or the problems found.
+pub fn verify_preimages(
+    root_hashes: impl IntoIterator<Item = [u8; PREIMAGE_HASH_SIZE]>,
+    preimage_dir: &Path,
+) -> Result<usize, Vec<PreimageIssue>> {
+    let mut to_check: Vec<_> = root_hashes.into_iter().collect();
+    let mut checked = BTreeSet::new();
+    let mut issues = vec![];
+
+    while let Some(hash) = to_check.pop() {
+        if !checked.insert(hash) {
+            continue;
+        }
+
+        let name = hex::encode(hash);
+        let Ok(preimage) = fs::read(preimage_dir.join(&name)) else {
+            issues.push(PreimageIssue::Missing(name));
+            continue;
+        };
+
+        if make_preimage_hash(&preimage).ok() != Some(hash) {
+            issues.push(PreimageIssue::HashMismatch(name));
+            continue;
+        }
+
+        match SlicePage::try_from(preimage.as_slice()) {
+            Ok(SlicePage::V0HashPage(page)) => to_check.extend(page.hashes().copied()),
+            Ok(SlicePage::V0ContentPage(_)) => (),
+            Err(_) => issues.push(PreimageIssue::InvalidPage(name)),
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(checked.len())
+    } else {
+        Err(issues)
+    }
+}
@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Dry-run of an installer kernel against a [`MockHost`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tezos_smart_rollup_host::runtime::Runtime;
+use tezos_smart_rollup_installer_config::binary::install::install_kernel;
+use tezos_smart_rollup_installer_config::binary::owned::OwnedConfigProgram;
+use tezos_smart_rollup_mock::MockHost;
+use thiserror::Error;
+
+use crate::installer::supports_verification;
+use crate::KERNEL_BOOT_PATH;
+
+#[derive(Debug, Error)]
+pub enum SimulateError {
+    #[error("Unable to load durable storage snapshot: {0}.")]
+    Snapshot(std::io::Error),
+    #[error("Unable to read preimages dir: {0}.")]
+    PreimagesDir(std::io::Error),
+    #[error("Unable to write installer to the boot path.")]
+    BootPath,
+    #[error("Unable to decode installer config: {0}.")]
+    Config(&'static str),
+    #[error("The installer config contains verification instructions, but the installer kernel does not support them.")]
+    VerificationUnsupported,
+}
+
+/// Outcome of running an installer.
+#[derive(Debug)]
+pub struct Simulation {
+    /// Result of the installation, as reported by the installer kernel.
+    pub result: Result<(), &'static str>,
+    /// Values of the durable storage once the program has run, by path.
+    pub durable: BTreeMap<String, Vec<u8>>,
+}
+
+/// Run the installer kernel on `installer`, as if it had just been installed at
+/// the kernel boot path.
+///
+/// The installer kernel is run natively - it reads and evaluates the config
+/// program from the boot path, just as the `installer.wasm` and
+/// `authenticated-installer.wasm` kernels do. Verification instructions are only
+/// evaluated if `installer` is built from `authenticated-installer.wasm`: a config
+/// containing them is rejected otherwise, as the installer would fail to upgrade.
+///
+/// The host starts from an empty durable storage, or from `snapshot` if given.
+/// Every file in `preimages_dir` is made available to the *reveal data* channel.
+pub fn simulate(
+    installer: &[u8],
+    preimages_dir: Option<&Path>,
+    snapshot: Option<&Path>,
+) -> Result<Simulation, SimulateError> {
+    let authenticated_upgrades = supports_verification(installer);
+    let config = OwnedConfigProgram::decode(installer).map_err(SimulateError::Config)?;
+    if !authenticated_upgrades && config.0.iter().any(|i| i.is_verification()) {
+        return Err(SimulateError::VerificationUnsupported);
+    }
+
+    let mut host = match snapshot {
+        Some(snapshot) => {
+            MockHost::load_snapshot(snapshot).map_err(SimulateError::Snapshot)?
+        }
+        None => MockHost::default(),
+    };
+
+    if let Some(preimages_dir) = preimages_dir {
+        for entry in fs::read_dir(preimages_dir).map_err(SimulateError::PreimagesDir)? {
+            let path = entry.map_err(SimulateError::PreimagesDir)?.path();
+            if path.is_file() {
+                let preimage = fs::read(path).map_err(SimulateError::PreimagesDir)?;
+                host.set_preimage(preimage);
+            }
+        }
+    }
+
+    host.store_write_all(&KERNEL_BOOT_PATH, installer)
+        .map_err(|_| SimulateError::BootPath)?;
+
+    let result = install_kernel(&mut host, KERNEL_BOOT_PATH, authenticated_upgrades);
+
+    let durable = host
+        .snapshot()
+        .durable
+        .into_iter()
+        .map(|(path, value)| {
+            let value = hex::decode(value).expect("Snapshot values are hex-encoded");
+            (path, value)
+        })
+        .collect();
+
+    Ok(Simulation { result, durable })
+}
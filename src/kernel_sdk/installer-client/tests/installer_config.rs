@@ -6,19 +6,33 @@
 use std::ffi::OsString;
 use std::fs;
 use std::path::Path;
+use tezos_crypto_rs::hash::{HashTrait, SeedEd25519};
+use tezos_data_encoding::enc::BinWriter;
 use tezos_smart_rollup::core_unsafe::MAX_FILE_CHUNK_SIZE;
 use tezos_smart_rollup::dac::pages::prepare_preimages;
 use tezos_smart_rollup::dac::PreimageHash;
 use tezos_smart_rollup::host::Runtime;
+use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
 use tezos_smart_rollup_host::path::{OwnedPath, RefPath};
 use tezos_smart_rollup_host::runtime::RuntimeError;
+use tezos_smart_rollup_installer::auth::{
+    authenticate, parse_authority, sign_upgrade, with_upgrade_authority, UpgradeSignature,
+};
 use tezos_smart_rollup_installer::config::create_installer_config;
 use tezos_smart_rollup_installer::installer::with_config_program;
+use tezos_smart_rollup_installer::preimages::{
+    content_to_preimages, verify_preimages, PreimageIssue,
+};
+use tezos_smart_rollup_installer::simulate::{simulate, SimulateError};
 use tezos_smart_rollup_installer::KERNEL_BOOT_PATH;
+use tezos_smart_rollup_installer_config::binary::auth::{
+    CommitteeSignature, UPGRADE_AUTHORITY_PATH,
+};
 use tezos_smart_rollup_installer_config::binary::owned::{
     OwnedBytes, OwnedConfigInstruction, OwnedConfigProgram,
 };
 use tezos_smart_rollup_mock::MockHost;
+use wasm_gen::write_custom_section;
 
 fn write_kernel_to_boot_path(host: &mut MockHost, kernel: Vec<u8>) {
     host.store_write_all(&KERNEL_BOOT_PATH, &kernel)
@@ -178,3 +192,141 @@ fn yaml_config_set_execute() {
 
     assert_eq!(expected, actual)
 }
+
+#[test]
+fn committee_signed_upgrade() {
+    let mut host = MockHost::default();
+
+    let seeds: Vec<SeedEd25519> = (1..=3)
+        .map(|i| SeedEd25519::try_from_bytes(&[i; 32]).unwrap())
+        .collect();
+    let public_keys: Vec<String> = seeds
+        .iter()
+        .map(|seed| seed.clone().keypair().unwrap().0.to_b58check())
+        .collect();
+    let authority = parse_authority(&format!("2:{}", public_keys.join(","))).unwrap();
+
+    // The first installer sets the upgrade authority
+    let config = with_upgrade_authority(OwnedConfigProgram(vec![]), &authority).unwrap();
+    write_kernel_to_boot_path(&mut host, with_config_program(config));
+    installer_kernel::installer(&mut host);
+    assert_eq!(
+        authority.to_bytes().unwrap(),
+        host.store_read_all(&UPGRADE_AUTHORITY_PATH).unwrap()
+    );
+
+    let original_kernel = fs::read("tests/resources/single_page_kernel.wasm").unwrap();
+    let root_hash = prepare_preimages(&original_kernel, |_hash, preimage| {
+        host.set_preimage(preimage);
+    })
+    .unwrap();
+    let upgrade = || create_installer_config(root_hash.clone(), None, None).unwrap();
+    let rollup_address = SmartRollupAddress::new(host.reveal_metadata().address());
+    let signature = |member: u8| CommitteeSignature {
+        member,
+        signature: sign_upgrade(
+            &upgrade(),
+            &rollup_address,
+            1,
+            &seeds[member as usize].to_b58check(),
+        )
+        .unwrap(),
+    };
+
+    // Upgrades without enough signatures are rejected
+    let unsigned = with_config_program(upgrade());
+    write_kernel_to_boot_path(&mut host, unsigned.clone());
+    installer_kernel::installer(&mut host);
+    assert_eq!(unsigned, host.store_read_all(&KERNEL_BOOT_PATH).unwrap());
+
+    let signatures = UpgradeSignature::Committee(vec![signature(1)]);
+    let config = authenticate(upgrade(), 1, &signatures).unwrap();
+    write_kernel_to_boot_path(&mut host, with_config_program(config));
+    installer_kernel::installer(&mut host);
+    assert_ne!(
+        original_kernel,
+        host.store_read_all(&KERNEL_BOOT_PATH).unwrap()
+    );
+
+    // A threshold of the committee can upgrade the kernel
+    let signatures = UpgradeSignature::Committee(vec![signature(2), signature(0)]);
+    let config = authenticate(upgrade(), 1, &signatures).unwrap();
+    let signed = with_config_program(config);
+    write_kernel_to_boot_path(&mut host, signed.clone());
+    installer_kernel::installer(&mut host);
+    assert_eq!(
+        original_kernel,
+        host.store_read_all(&KERNEL_BOOT_PATH).unwrap()
+    );
+
+    // Signed upgrades can't be replayed
+    write_kernel_to_boot_path(&mut host, signed.clone());
+    installer_kernel::installer(&mut host);
+    assert_eq!(signed, host.store_read_all(&KERNEL_BOOT_PATH).unwrap());
+}
+
+#[test]
+fn verify_preimages_dir() {
+    let preimages_dir = std::env::temp_dir().join("installer-client-verify-preimages");
+    let _ = fs::remove_dir_all(&preimages_dir);
+
+    let kernel = fs::read("tests/resources/single_page_kernel.wasm").unwrap();
+    let root_hash = content_to_preimages(kernel, &preimages_dir).unwrap();
+    let root_hash: [u8; 33] = *root_hash.as_ref();
+
+    assert_eq!(Ok(1), verify_preimages([root_hash], &preimages_dir));
+
+    let name = hex::encode(root_hash);
+    fs::write(preimages_dir.join(&name), b"tampered").unwrap();
+    assert_eq!(
+        Err(vec![PreimageIssue::HashMismatch(name.clone())]),
+        verify_preimages([root_hash], &preimages_dir)
+    );
+
+    fs::remove_dir_all(&preimages_dir).unwrap();
+    assert_eq!(
+        Err(vec![PreimageIssue::Missing(name)]),
+        verify_preimages([root_hash], &preimages_dir)
+    );
+}
+
+#[test]
+fn simulate_installer() {
+    let to = OwnedPath::try_from(String::from("/foo/tmp")).unwrap();
+    let config = OwnedConfigProgram(vec![
+        OwnedConfigInstruction::set_instr(OwnedBytes(b"GADT".to_vec()), to.clone()),
+        OwnedConfigInstruction::move_instr(
+            to,
+            OwnedPath::try_from(String::from("/bar")).unwrap(),
+        ),
+    ]);
+    let kernel = with_config_program(config);
+
+    let simulation = simulate(&kernel, None, None).unwrap();
+
+    assert_eq!(Ok(()), simulation.result);
+    assert_eq!(Some(&b"GADT".to_vec()), simulation.durable.get("/bar"));
+    assert_eq!(None, simulation.durable.get("/foo/tmp"));
+    assert_eq!(Some(&kernel), simulation.durable.get("/kernel/boot.wasm"));
+
+    // Verification instructions are checked by the installer kernel.
+    let seed = SeedEd25519::try_from_bytes(&[1; 32]).unwrap();
+    let upgrade = OwnedConfigProgram(vec![]);
+    let rollup_address =
+        SmartRollupAddress::new(MockHost::default().reveal_metadata().address());
+    let signature =
+        sign_upgrade(&upgrade, &rollup_address, 1, &seed.to_b58check()).unwrap();
+    let config = authenticate(upgrade, 1, &UpgradeSignature::Key(signature)).unwrap();
+    let mut config_encoded = vec![];
+    config.bin_write(&mut config_encoded).unwrap();
+    let simulation = simulate(&with_config_program(config), None, None).unwrap();
+    assert_eq!(Err("Couldn't read upgrade authority"), simulation.result);
+
+    // Installers without support for verification instructions are reported.
+    let mut kernel = with_config_program(OwnedConfigProgram(vec![]));
+    write_custom_section(&mut kernel, "config", &config_encoded);
+    assert!(matches!(
+        simulate(&kernel, None, None),
+        Err(SimulateError::VerificationUnsupported)
+    ));
+}
@@ -0,0 +1,448 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Authentication of upgrades by an upgrade authority.
+//!
+//! Once an [`UpgradeAuthority`] has been set at [`UPGRADE_AUTHORITY_PATH`] - usually by
+//! a `set` instruction of the installer that originated the rollup - the instructions of
+//! any later config program must follow a verification instruction. It carries either
+//! the signature of the authority's key, or the signatures of a threshold of the members
+//! of its committee.
+//!
+//! What is signed is the *blake2b* digest of the address of the rollup, the nonce of the
+//! verification instruction, and the encoding of the instructions following it - see
+//! [`instructions_digest`]. Nonces must increase from one upgrade to the next, the last
+//! one being stored at [`UPGRADE_NONCE_PATH`], so that a signed upgrade can't be
+//! replayed - on the same rollup or on another one.
+
+use super::evaluation::eval_config_instr;
+use super::ConfigInstruction;
+use tezos_smart_rollup_host::path::{Path, RefPath};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+#[cfg(feature = "alloc")]
+use {
+    super::{
+        VerifyMultisigInstruction, VerifySignatureInstruction, MAX_MULTISIG_SIGNATURES,
+    },
+    nom::{combinator::all_consuming, multi::many0},
+    tezos_data_encoding::{
+        enc::{BinError, BinWriter},
+        encoding::HasEncoding,
+        nom::NomReader,
+    },
+    tezos_smart_rollup_encoding::inbox::signed::{OperationSignature, Signer},
+};
+
+/// Path of the upgrade authority in the durable storage.
+pub const UPGRADE_AUTHORITY_PATH: RefPath =
+    RefPath::assert_from(b"/__installer_kernel/upgrade/authority");
+
+/// Path of the nonce of the last authenticated upgrade in the durable storage.
+pub const UPGRADE_NONCE_PATH: RefPath =
+    RefPath::assert_from(b"/__installer_kernel/upgrade/nonce");
+
+/// Error returned when instructions are not authenticated, while an upgrade authority
+/// is set.
+pub const UNAUTHENTICATED_UPGRADE: &str =
+    "Config instruction not authenticated by the upgrade authority";
+
+/// Whether an upgrade authority is set, and config instructions must be authenticated.
+pub fn authentication_required(host: &impl Runtime) -> Result<bool, &'static str> {
+    host.store_has(&UPGRADE_AUTHORITY_PATH)
+        .map(|value| value.is_some())
+        .map_err(|_| "Couldn't read upgrade authority")
+}
+
+/// Authentication of the instructions of a config program, as they are evaluated.
+///
+/// Shared by [`OwnedConfigProgram::evaluate`] and [`install_kernel`], which differ only
+/// in how they get at the instructions following a verification instruction.
+///
+/// [`OwnedConfigProgram::evaluate`]: crate::binary::owned::OwnedConfigProgram::evaluate
+/// [`install_kernel`]: crate::binary::install::install_kernel
+pub struct ProgramAuthentication {
+    required: bool,
+    authenticated: bool,
+}
+
+impl ProgramAuthentication {
+    /// Start the evaluation of a config program.
+    pub fn new(host: &impl Runtime) -> Result<Self, &'static str> {
+        Ok(Self {
+            required: authentication_required(host)?,
+            authenticated: false,
+        })
+    }
+
+    /// Evaluate the next instruction of the program.
+    ///
+    /// A verification instruction is checked with `verify` - against the instructions
+    /// following it - and authenticates them. Any other instruction is evaluated, as
+    /// long as it is authenticated while an upgrade authority is set.
+    pub fn eval_next<Host: Runtime, P: Path, B: AsRef<[u8]>>(
+        &mut self,
+        host: &mut Host,
+        instr: &ConfigInstruction<P, B>,
+        verify: impl FnOnce(&mut Host, &ConfigInstruction<P, B>) -> Result<(), &'static str>,
+    ) -> Result<(), &'static str> {
+        if instr.is_verification() {
+            verify(host, instr)?;
+            self.authenticated = true;
+            Ok(())
+        } else if self.required && !self.authenticated {
+            Err(UNAUTHENTICATED_UPGRADE)
+        } else {
+            eval_config_instr(host, instr)
+        }
+    }
+}
+
+/// Check that `nonce` is greater than the nonce of the last authenticated upgrade, and
+/// store it in its place.
+pub fn update_upgrade_nonce(
+    host: &mut impl Runtime,
+    nonce: u64,
+) -> Result<(), &'static str> {
+    let stored = host
+        .store_has(&UPGRADE_NONCE_PATH)
+        .map_err(|_| "Couldn't read upgrade nonce")?;
+    if stored.is_some() {
+        let mut last = [0; 8];
+        let size = host
+            .store_read_slice(&UPGRADE_NONCE_PATH, 0, &mut last)
+            .map_err(|_| "Couldn't read upgrade nonce")?;
+        if size != last.len() {
+            return Err("Couldn't decode upgrade nonce");
+        }
+        if nonce <= u64::from_be_bytes(last) {
+            return Err("Upgrade nonce already used");
+        }
+    }
+    host.store_write(&UPGRADE_NONCE_PATH, &nonce.to_be_bytes(), 0)
+        .map_err(|_| "Couldn't write upgrade nonce")
+}
+
+/// Keys allowed to authenticate upgrades.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub enum UpgradeAuthority {
+    /// Upgrades are signed by a single key.
+    #[encoding(tag = 0)]
+    Key(Signer),
+    /// Upgrades are signed by a committee.
+    #[encoding(tag = 1)]
+    Committee(Committee),
+}
+
+/// Committee of keys, a threshold of which must sign an upgrade.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct Committee {
+    /// Number of distinct members required to sign an upgrade.
+    pub threshold: u8,
+    /// Members of the committee, referred to by their index.
+    #[encoding(dynamic, list)]
+    pub members: Vec<Signer>,
+}
+
+/// Signature of an upgrade by a member of a [`Committee`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct CommitteeSignature {
+    /// Index of the member in the committee.
+    pub member: u8,
+    /// Signature of the [`instructions_digest`] by the member.
+    pub signature: OperationSignature,
+}
+
+#[cfg(feature = "alloc")]
+impl UpgradeAuthority {
+    /// Read the upgrade authority from the durable storage.
+    pub fn read(host: &impl Runtime) -> Result<Self, &'static str> {
+        let bytes = host
+            .store_read_all(&UPGRADE_AUTHORITY_PATH)
+            .map_err(|_| "Couldn't read upgrade authority")?;
+        let (_, authority) = all_consuming(UpgradeAuthority::nom_read)(bytes.as_slice())
+            .map_err(|_| "Couldn't decode upgrade authority")?;
+        Ok(authority)
+    }
+
+    /// Binary encoding of the authority, as stored at [`UPGRADE_AUTHORITY_PATH`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinError> {
+        let mut bytes = vec![];
+        self.bin_write(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Committee {
+    /// Check that a threshold of distinct members signed `digest`.
+    pub fn verify(
+        &self,
+        signatures: &[CommitteeSignature],
+        digest: &[u8],
+    ) -> Result<(), &'static str> {
+        if self.threshold == 0 || self.threshold as usize > self.members.len() {
+            return Err("Invalid upgrade committee threshold");
+        }
+        for (i, member) in self.members.iter().enumerate() {
+            if self.members[..i].contains(member) {
+                return Err("Duplicate upgrade committee member");
+            }
+        }
+        if signatures.len() > MAX_MULTISIG_SIGNATURES {
+            return Err("Too many upgrade committee signatures");
+        }
+
+        let mut signed = vec![false; self.members.len()];
+        for CommitteeSignature { member, signature } in signatures {
+            let index = *member as usize;
+            let signer = self
+                .members
+                .get(index)
+                .ok_or("Unknown upgrade committee member")?;
+            if signed[index] {
+                return Err("Duplicate upgrade committee signature");
+            }
+            if !signer.verify(signature, digest).unwrap_or(false) {
+                return Err("Invalid upgrade committee signature");
+            }
+            signed[index] = true;
+        }
+
+        if signed.iter().filter(|signed| **signed).count() < self.threshold as usize {
+            return Err("Not enough upgrade committee signatures");
+        }
+        Ok(())
+    }
+}
+
+/// The digest signed by the upgrade authority, to upgrade the rollup at
+/// `raw_rollup_address` with the encoded instructions following a verification
+/// instruction of the given `nonce`.
+///
+/// Each instruction is encoded prepended with the size of its encoding, as they appear
+/// in the config program.
+#[cfg(feature = "alloc")]
+pub fn instructions_digest(
+    raw_rollup_address: &[u8],
+    nonce: u64,
+    encoded_instructions: &[u8],
+) -> Vec<u8> {
+    let mut signed = raw_rollup_address.to_vec();
+    signed.extend_from_slice(&nonce.to_be_bytes());
+    signed.extend_from_slice(encoded_instructions);
+    tezos_crypto_rs::blake2b::digest_256(&signed)
+}
+
+/// Verification instruction evaluation
+///
+/// Check that the signatures of a verification instruction were made by the upgrade
+/// authority, over the [`instructions_digest`] of `encoded_instructions` - the
+/// instructions following it - and that its nonce wasn't used yet.
+#[cfg(feature = "alloc")]
+pub fn eval_verify_instr<P, Bytes: AsRef<[u8]>>(
+    host: &mut impl Runtime,
+    config_instr: &ConfigInstruction<P, Bytes>,
+    encoded_instructions: &[u8],
+) -> Result<(), &'static str> {
+    let authority = UpgradeAuthority::read(host)?;
+    let raw_rollup_address = host.reveal_metadata().raw_rollup_address;
+    let digest =
+        |nonce| instructions_digest(&raw_rollup_address, nonce, encoded_instructions);
+
+    let nonce = match (config_instr, authority) {
+        (
+            ConfigInstruction::VerifySignature(VerifySignatureInstruction {
+                nonce,
+                signature,
+            }),
+            UpgradeAuthority::Key(signer),
+        ) => {
+            let (_, signature) =
+                all_consuming(OperationSignature::nom_read)(signature.as_ref())
+                    .map_err(|_| "Couldn't decode upgrade signature")?;
+            if !signer.verify(&signature, &digest(*nonce)).unwrap_or(false) {
+                return Err("Invalid upgrade signature");
+            }
+            *nonce
+        }
+        (
+            ConfigInstruction::VerifyMultisig(VerifyMultisigInstruction {
+                nonce,
+                signatures,
+            }),
+            UpgradeAuthority::Committee(committee),
+        ) => {
+            let (_, signatures) =
+                all_consuming(many0(CommitteeSignature::nom_read))(signatures.as_ref())
+                    .map_err(|_| "Couldn't decode upgrade committee signatures")?;
+            committee.verify(&signatures, &digest(*nonce))?;
+            *nonce
+        }
+        (ConfigInstruction::VerifySignature(_), _)
+        | (ConfigInstruction::VerifyMultisig(_), _) => {
+            return Err("Verification instruction doesn't match the upgrade authority")
+        }
+        _ => return Err("Not a verification instruction"),
+    };
+
+    update_upgrade_nonce(host, nonce)
+}
+
+#[cfg(feature = "alloc")]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary::owned::{OwnedBytes, OwnedConfigInstruction, OwnedConfigProgram};
+    use tezos_crypto_rs::hash::SmartRollupHash;
+    use tezos_crypto_rs::hash::{
+        HashTrait, PublicKeyEd25519, SecretKeyEd25519, SeedEd25519,
+    };
+    use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+    use tezos_smart_rollup_host::path::OwnedPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn rollup_address(byte: u8) -> SmartRollupAddress {
+        SmartRollupAddress::new(SmartRollupHash::try_from_bytes(&[byte; 20]).unwrap())
+    }
+
+    fn keypair(seed: u8) -> (PublicKeyEd25519, SecretKeyEd25519) {
+        SeedEd25519::try_from_bytes(&[seed; 32])
+            .unwrap()
+            .keypair()
+            .unwrap()
+    }
+
+    fn sign(sk: &SecretKeyEd25519, digest: &[u8]) -> OperationSignature {
+        OperationSignature::Ed25519(sk.sign(digest).unwrap())
+    }
+
+    fn set_authority(host: &mut MockHost, authority: UpgradeAuthority) {
+        host.store_write_all(&UPGRADE_AUTHORITY_PATH, &authority.to_bytes().unwrap())
+            .unwrap();
+    }
+
+    fn upgrade() -> OwnedConfigProgram {
+        OwnedConfigProgram(vec![OwnedConfigInstruction::set_instr(
+            OwnedBytes(b"upgraded".to_vec()),
+            OwnedPath::try_from(String::from("/upgraded")).unwrap(),
+        )])
+    }
+
+    fn with_verification(
+        verify: OwnedConfigInstruction,
+        program: OwnedConfigProgram,
+    ) -> OwnedConfigProgram {
+        let mut instructions = vec![verify];
+        instructions.extend(program.0);
+        OwnedConfigProgram(instructions)
+    }
+
+    #[test]
+    fn upgrade_signed_by_key() {
+        let mut host = MockHost::with_address(&rollup_address(1));
+        let (pk, sk) = keypair(1);
+        let (_, other_sk) = keypair(2);
+        set_authority(&mut host, UpgradeAuthority::Key(Signer::Ed25519(pk)));
+
+        assert_eq!(Err(UNAUTHENTICATED_UPGRADE), upgrade().evaluate(&mut host));
+
+        let signed_by = |sk: &SecretKeyEd25519, address: u8, nonce: u64| {
+            let digest = upgrade()
+                .upgrade_digest(&rollup_address(address), nonce)
+                .unwrap();
+            let instr =
+                OwnedConfigInstruction::verify_signature_instr(nonce, &sign(sk, &digest))
+                    .unwrap();
+            with_verification(instr, upgrade())
+        };
+
+        assert_eq!(
+            Err("Invalid upgrade signature"),
+            signed_by(&other_sk, 1, 5).evaluate(&mut host)
+        );
+        // Signed for another rollup.
+        assert_eq!(
+            Err("Invalid upgrade signature"),
+            signed_by(&sk, 2, 5).evaluate(&mut host)
+        );
+
+        assert_eq!(Ok(()), signed_by(&sk, 1, 5).evaluate(&mut host));
+        assert_eq!(
+            b"upgraded".to_vec(),
+            host.store_read_all(&OwnedPath::try_from(String::from("/upgraded")).unwrap())
+                .unwrap()
+        );
+
+        // Signed upgrades can't be replayed, nor use an older nonce.
+        assert_eq!(
+            Err("Upgrade nonce already used"),
+            signed_by(&sk, 1, 5).evaluate(&mut host)
+        );
+        assert_eq!(
+            Err("Upgrade nonce already used"),
+            signed_by(&sk, 1, 4).evaluate(&mut host)
+        );
+        assert_eq!(Ok(()), signed_by(&sk, 1, 6).evaluate(&mut host));
+    }
+
+    #[test]
+    fn upgrade_signed_by_committee() {
+        let mut host = MockHost::default();
+        let keys: Vec<_> = (1..=3).map(keypair).collect();
+        set_authority(
+            &mut host,
+            UpgradeAuthority::Committee(Committee {
+                threshold: 2,
+                members: keys
+                    .iter()
+                    .map(|(pk, _)| Signer::Ed25519(pk.clone()))
+                    .collect(),
+            }),
+        );
+
+        let digest = upgrade().upgrade_digest(&rollup_address(0), 1).unwrap();
+        let signature = |member: u8| CommitteeSignature {
+            member,
+            signature: sign(&keys[member as usize].1, &digest),
+        };
+        let verify = |signatures: &[CommitteeSignature]| {
+            let instr =
+                OwnedConfigInstruction::verify_multisig_instr(1, signatures).unwrap();
+            with_verification(instr, upgrade()).evaluate(&mut MockHost::default())
+        };
+        let mut evaluate = |signatures: &[CommitteeSignature]| {
+            let instr =
+                OwnedConfigInstruction::verify_multisig_instr(1, signatures).unwrap();
+            with_verification(instr, upgrade()).evaluate(&mut host)
+        };
+
+        assert_eq!(
+            Err("Not enough upgrade committee signatures"),
+            evaluate(&[signature(0)])
+        );
+        assert_eq!(
+            Err("Duplicate upgrade committee signature"),
+            evaluate(&[signature(0), signature(0)])
+        );
+        let unknown = CommitteeSignature {
+            member: 3,
+            ..signature(0)
+        };
+        assert_eq!(
+            Err("Unknown upgrade committee member"),
+            evaluate(&[signature(1), unknown])
+        );
+        assert_eq!(Ok(()), evaluate(&[signature(2), signature(0)]));
+
+        // Without an authority, verification instructions can't be checked.
+        assert_eq!(
+            Err("Couldn't read upgrade authority"),
+            verify(&[signature(0), signature(1)])
+        );
+    }
+}
@@ -8,11 +8,13 @@ use tezos_data_encoding::enc::{
 };
 use tezos_smart_rollup_host::path::Path;
 
-use crate::binary::SetInstruction;
+use crate::binary::{
+    SetInstruction, VerifyMultisigInstruction, VerifySignatureInstruction,
+};
 
 use super::{
     instr::{ConfigInstruction, MoveInstruction, RefBytes, RevealInstruction},
-    owned::OwnedConfigProgram,
+    owned::{OwnedConfigInstruction, OwnedConfigProgram},
 };
 
 fn put_le_size(size: usize, out: &mut Vec<u8>) -> BinResult {
@@ -83,6 +85,38 @@ impl<P: Path, B: AsRef<[u8]>> BinWriter for SetInstruction<P, B> {
     }
 }
 
+impl<B: AsRef<[u8]>> BinWriter for VerifySignatureInstruction<B> {
+    fn bin_write(&self, out: &mut Vec<u8>) -> tezos_data_encoding::enc::BinResult {
+        (|data: &Self, out: &mut Vec<u8>| {
+            field(
+                "VerifySignatureInstruction::nonce",
+                tezos_data_encoding::enc::u64,
+            )(&data.nonce, out)?;
+            field("VerifySignatureInstruction::signature", bytes_dynamic)(
+                &data.signature,
+                out,
+            )?;
+            Ok(())
+        })(self, out)
+    }
+}
+
+impl<B: AsRef<[u8]>> BinWriter for VerifyMultisigInstruction<B> {
+    fn bin_write(&self, out: &mut Vec<u8>) -> tezos_data_encoding::enc::BinResult {
+        (|data: &Self, out: &mut Vec<u8>| {
+            field(
+                "VerifyMultisigInstruction::nonce",
+                tezos_data_encoding::enc::u64,
+            )(&data.nonce, out)?;
+            field("VerifyMultisigInstruction::signatures", bytes_dynamic)(
+                &data.signatures,
+                out,
+            )?;
+            Ok(())
+        })(self, out)
+    }
+}
+
 impl<P: Path, B: AsRef<[u8]>> BinWriter for ConfigInstruction<P, B> {
     fn bin_write(&self, out: &mut Vec<u8>) -> tezos_data_encoding::enc::BinResult {
         use tezos_data_encoding::enc::{u8, variant_with_field};
@@ -104,24 +138,43 @@ impl<P: Path, B: AsRef<[u8]>> BinWriter for ConfigInstruction<P, B> {
                 u8,
                 <SetInstruction<P, B> as BinWriter>::bin_write,
             )(&2, inner, out),
+            ConfigInstruction::VerifySignature(inner) => variant_with_field(
+                "ConfigInstruction::VerifySignature",
+                u8,
+                <VerifySignatureInstruction<B> as BinWriter>::bin_write,
+            )(&3, inner, out),
+            ConfigInstruction::VerifyMultisig(inner) => variant_with_field(
+                "ConfigInstruction::VerifyMultisig",
+                u8,
+                <VerifyMultisigInstruction<B> as BinWriter>::bin_write,
+            )(&4, inner, out),
         }
     }
 }
 
+/// Encode instructions, each prepended with the size of its encoding.
+pub(crate) fn encode_instructions(
+    instructions: &[OwnedConfigInstruction],
+    output: &mut Vec<u8>,
+) -> BinResult {
+    for instruction in instructions {
+        let mut current_instr = vec![];
+        instruction.bin_write(&mut current_instr)?;
+        // Put size of the instruction encoding first,
+        // in order to make a decoding easier
+        put_le_size(current_instr.len(), output)?;
+        output.extend_from_slice(&current_instr);
+    }
+    Ok(())
+}
+
 // Encode all commands with appended number of commands at the end.
 // It makes possible for the installer_kernel to
 // parse commands at the end of the kernel binary.
 impl BinWriter for OwnedConfigProgram {
     fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
         let initial_size = output.len();
-        for i in 0..self.0.len() {
-            let mut current_instr = vec![];
-            self.0[i].bin_write(&mut current_instr)?;
-            // Put size of the instruction encoding first,
-            // in order to make a decoding easier
-            put_le_size(current_instr.len(), output)?;
-            output.extend_from_slice(&current_instr);
-        }
+        encode_instructions(&self.0, output)?;
         put_le_size(output.len() - initial_size, output)?;
         Ok(())
     }
@@ -158,6 +211,7 @@ mod test {
 
         use crate::binary::instr::{
             ConfigInstruction, MoveInstruction, RefBytes, RevealInstruction,
+            VerifyMultisigInstruction,
         };
         roundtrip(&RefBytes("hello".as_bytes()), &mut vec![]);
 
@@ -184,5 +238,36 @@ mod test {
             }),
             &mut vec![],
         );
+
+        roundtrip(
+            &ConfigInstruction::<RefPath, _>::VerifyMultisig(VerifyMultisigInstruction {
+                nonce: 7,
+                signatures: RefBytes("committee signatures".as_bytes()),
+            }),
+            &mut vec![],
+        );
+    }
+
+    #[test]
+    fn roundtrip_config_program() {
+        use tezos_smart_rollup_host::path::OwnedPath;
+
+        use crate::binary::owned::{
+            OwnedBytes, OwnedConfigInstruction, OwnedConfigProgram,
+        };
+
+        let path = |p: &str| OwnedPath::try_from(p.to_string()).unwrap();
+        let program = OwnedConfigProgram(vec![
+            OwnedConfigInstruction::reveal_instr([3; 33].to_vec().into(), path("/a")),
+            OwnedConfigInstruction::move_instr(path("/a"), path("/b")),
+            OwnedConfigInstruction::set_instr(OwnedBytes(vec![1, 2]), path("/c")),
+        ]);
+
+        // The program is decoded from the end of the kernel it is appended to.
+        let mut kernel = b"installer kernel".to_vec();
+        program.bin_write(&mut kernel).unwrap();
+
+        assert_eq!(program, OwnedConfigProgram::decode(&kernel).unwrap());
+        assert!(OwnedConfigProgram::decode(&kernel[..kernel.len() - 1]).is_err());
     }
 }
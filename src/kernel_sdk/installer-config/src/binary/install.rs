@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: 2023 TriliTech <contact@trili.tech>
+// SPDX-FileCopyrightText: 2023 Functori <contact@functori.com>
+// SPDX-FileCopyrightText: 2023 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Evaluation of the config program at the end of an installer kernel.
+//!
+//! This is the main loop of the installer kernel, shared with the installer client
+//! which runs it natively to simulate installers.
+
+use super::auth::ProgramAuthentication;
+use super::{completed, read_size, EncodingSize, NomReader, RefConfigInstruction};
+use tezos_smart_rollup_host::path::{Path, RefPath};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+// Installer kernel will copy to this path before execution of config.
+// This is done in order avoid rewriting kernel during config execution.
+const AUXILIARY_CONFIG_INTERPRETATION_PATH: RefPath =
+    RefPath::assert_from(b"/__installer_kernel/auxiliary/kernel/boot.wasm");
+
+const VERIFICATION_UNSUPPORTED: &str =
+    "Authenticated upgrades are not supported by this installer";
+
+/// Kernel installer function.
+///
+/// Non-trivial preliminary steps needs to be processed before calling
+/// this function:
+///     - Prepare preimages of the targeted kernel.
+///     - Create a config program consisting of `reveal` instruction followed by a `move` one.
+///     - Serialise the program and write the output to durable storage.
+///     - Finally execute the config program by calling `install_kernel`, with the path the config
+///       was written to.
+///
+/// Once an upgrade authority has been set, the config instructions must follow a
+/// verification instruction signed by the authority. Verification instructions are
+/// rejected unless `authenticated_upgrades` is set, which requires the `alloc` feature.
+pub fn install_kernel(
+    host: &mut impl Runtime,
+    config_interpretation_path: RefPath,
+    authenticated_upgrades: bool,
+) -> Result<(), &'static str> {
+    if let Ok(config_program_size) =
+        read_config_program_size(host, &config_interpretation_path)
+    {
+        let mut config_instruction_buffer = [0; RefConfigInstruction::MAX_SIZE];
+
+        let kernel_size = host
+            .store_value_size(&config_interpretation_path)
+            .map_err(|_| "Failed to read kernel boot path size")?;
+
+        host.store_copy(
+            &config_interpretation_path,
+            &AUXILIARY_CONFIG_INTERPRETATION_PATH,
+        )
+        .map_err(|_| "Failed to copy kernel boot before config execution")?;
+
+        let mut authentication = ProgramAuthentication::new(host)?;
+
+        let end_offset = kernel_size - 4;
+        let mut instr_offset = end_offset - (config_program_size as usize);
+        while instr_offset < end_offset {
+            let instr_size = read_size(
+                host,
+                &AUXILIARY_CONFIG_INTERPRETATION_PATH,
+                &mut instr_offset,
+            )? as usize;
+            read_instruction_bytes(
+                host,
+                &AUXILIARY_CONFIG_INTERPRETATION_PATH,
+                &mut instr_offset,
+                &mut config_instruction_buffer[..instr_size],
+            )?;
+            let instr =
+                RefConfigInstruction::nom_read(&config_instruction_buffer[..instr_size])
+                    .map_err(|_| "Couldn't decode config instruction")
+                    .and_then(completed)?;
+
+            authentication.eval_next(host, &instr, |host, instr| {
+                if !authenticated_upgrades {
+                    return Err(VERIFICATION_UNSUPPORTED);
+                }
+                verify_remaining_instructions(host, instr, instr_offset, end_offset)
+            })?;
+        }
+
+        host.store_delete(&AUXILIARY_CONFIG_INTERPRETATION_PATH)
+            .map_err(|_| {
+                "Failed to delete auxiliary kernel boot after config execution"
+            })?;
+
+        Ok(())
+    } else {
+        Err("Failed to read size of config program")
+    }
+}
+
+/// Check a verification instruction against the encoded instructions following it,
+/// between `offset` and `end_offset` of the auxiliary config path.
+#[cfg(feature = "alloc")]
+fn verify_remaining_instructions(
+    host: &mut impl Runtime,
+    instr: &RefConfigInstruction,
+    mut offset: usize,
+    end_offset: usize,
+) -> Result<(), &'static str> {
+    use super::auth::eval_verify_instr;
+
+    let mut remaining = vec![0; end_offset - offset];
+    read_instruction_bytes(
+        host,
+        &AUXILIARY_CONFIG_INTERPRETATION_PATH,
+        &mut offset,
+        &mut remaining,
+    )?;
+    eval_verify_instr(host, instr, &remaining)
+}
+
+#[cfg(not(feature = "alloc"))]
+fn verify_remaining_instructions(
+    _host: &mut impl Runtime,
+    _instr: &RefConfigInstruction,
+    _offset: usize,
+    _end_offset: usize,
+) -> Result<(), &'static str> {
+    Err(VERIFICATION_UNSUPPORTED)
+}
+
+fn read_config_program_size(
+    host: &impl Runtime,
+    config_interpretation_path: &RefPath,
+) -> Result<u32, &'static str> {
+    let kernel_size = host
+        .store_value_size(config_interpretation_path)
+        .map_err(|_| "Couldn't read kernel boot path size")?;
+    let mut config_program_size_start = kernel_size - 4;
+
+    read_size(
+        host,
+        config_interpretation_path,
+        &mut config_program_size_start,
+    )
+}
+
+fn read_instruction_bytes(
+    host: &impl Runtime,
+    path: &impl Path,
+    offset: &mut usize,
+    mut buffer: &mut [u8],
+) -> Result<(), &'static str> {
+    while !buffer.is_empty() {
+        let read_size = Runtime::store_read_slice(host, path, *offset, buffer)
+            .map_err(|_| "Failed to read kernel boot path in read_instruction")?;
+        *offset += read_size;
+        buffer = &mut buffer[read_size..];
+    }
+    Ok(())
+}
@@ -43,19 +43,63 @@ pub struct SetInstruction<Path, Bytes> {
     pub to: Path,
 }
 
+// Verification instructions start here
+
+/// Signature, by the upgrade authority, of the instructions following it.
+///
+/// `nonce` must be greater than the nonce of any previous upgrade of the rollup.
+/// `signature` is the binary encoding of an
+/// [`OperationSignature`](tezos_smart_rollup_encoding::inbox::signed::OperationSignature).
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifySignatureInstruction<Bytes> {
+    pub nonce: u64,
+    pub signature: Bytes,
+}
+
+/// Signatures, by members of the upgrade committee, of the instructions following it.
+///
+/// `nonce` must be greater than the nonce of any previous upgrade of the rollup.
+/// `signatures` is the concatenated binary encoding of a list of
+/// [`CommitteeSignature`](crate::binary::auth::CommitteeSignature).
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifyMultisigInstruction<Bytes> {
+    pub nonce: u64,
+    pub signatures: Bytes,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum ConfigInstruction<Path, Bytes> {
     Reveal(RevealInstruction<Path, Bytes>),
     Move(MoveInstruction<Path>),
     Set(SetInstruction<Path, Bytes>),
+    VerifySignature(VerifySignatureInstruction<Bytes>),
+    VerifyMultisig(VerifyMultisigInstruction<Bytes>),
+}
+
+impl<Path, Bytes> ConfigInstruction<Path, Bytes> {
+    /// Whether the instruction authenticates the instructions following it,
+    /// rather than acting on the durable storage.
+    pub fn is_verification(&self) -> bool {
+        matches!(
+            self,
+            ConfigInstruction::VerifySignature(_) | ConfigInstruction::VerifyMultisig(_)
+        )
+    }
 }
 
 pub type RefConfigInstruction<'a> = ConfigInstruction<RefPath<'a>, RefBytes<'a>>;
 
 #[cfg(feature = "alloc")]
 pub mod owned {
-    use crate::binary::evaluation::eval_config_instr;
+    use crate::binary::auth::{
+        eval_verify_instr, instructions_digest, CommitteeSignature, ProgramAuthentication,
+    };
+    use crate::binary::bin::encode_instructions;
+    use crate::binary::{completed, size, NomReader};
+    use tezos_data_encoding::enc::{BinError, BinWriter};
     use tezos_smart_rollup_encoding::dac::PreimageHash;
+    use tezos_smart_rollup_encoding::inbox::signed::OperationSignature;
+    use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
     use tezos_smart_rollup_host::{
         path::{OwnedPath, PathError},
         runtime::Runtime,
@@ -79,12 +123,124 @@ pub mod owned {
     pub struct OwnedConfigProgram(pub Vec<OwnedConfigInstruction>);
 
     impl OwnedConfigProgram {
+        /// Evaluate the program.
+        ///
+        /// Once an upgrade authority has been set, every instruction must follow a
+        /// verification instruction, signed by the authority.
         pub fn evaluate(&self, host: &mut impl Runtime) -> Result<(), &'static str> {
-            for instruction in self.0.iter() {
-                eval_config_instr(host, instruction)?
+            let mut authentication = ProgramAuthentication::new(host)?;
+
+            for (i, instruction) in self.0.iter().enumerate() {
+                authentication.eval_next(host, instruction, |host, instruction| {
+                    let mut encoded = vec![];
+                    encode_instructions(&self.0[i + 1..], &mut encoded)
+                        .map_err(|_| "Couldn't encode config program")?;
+                    eval_verify_instr(host, instruction, &encoded)
+                })?;
             }
             Ok(())
         }
+
+        /// The digest of the program, signed by the upgrade authority of the rollup
+        /// at `rollup_address`, to upgrade it with the given `nonce`.
+        ///
+        /// It covers the encoding of the instructions of the program, and so the root
+        /// hash of any revealed kernel - see [`instructions_digest`].
+        pub fn upgrade_digest(
+            &self,
+            rollup_address: &SmartRollupAddress,
+            nonce: u64,
+        ) -> Result<Vec<u8>, BinError> {
+            let mut encoded = vec![];
+            encode_instructions(&self.0, &mut encoded)?;
+            Ok(instructions_digest(
+                rollup_address.hash().as_ref(),
+                nonce,
+                &encoded,
+            ))
+        }
+    }
+
+    impl<'a> From<RefBytes<'a>> for OwnedBytes {
+        fn from(bytes: RefBytes<'a>) -> Self {
+            OwnedBytes(bytes.0.to_vec())
+        }
+    }
+
+    impl<'a> From<RefConfigInstruction<'a>> for OwnedConfigInstruction {
+        fn from(instr: RefConfigInstruction<'a>) -> Self {
+            match instr {
+                ConfigInstruction::Reveal(RevealInstruction { hash, to }) => {
+                    ConfigInstruction::Reveal(RevealInstruction {
+                        hash: hash.into(),
+                        to: to.into(),
+                    })
+                }
+                ConfigInstruction::Move(MoveInstruction { from, to }) => {
+                    ConfigInstruction::Move(MoveInstruction {
+                        from: from.into(),
+                        to: to.into(),
+                    })
+                }
+                ConfigInstruction::Set(SetInstruction { value, to }) => {
+                    ConfigInstruction::Set(SetInstruction {
+                        value: value.into(),
+                        to: to.into(),
+                    })
+                }
+                ConfigInstruction::VerifySignature(VerifySignatureInstruction {
+                    nonce,
+                    signature,
+                }) => ConfigInstruction::VerifySignature(VerifySignatureInstruction {
+                    nonce,
+                    signature: signature.into(),
+                }),
+                ConfigInstruction::VerifyMultisig(VerifyMultisigInstruction {
+                    nonce,
+                    signatures,
+                }) => ConfigInstruction::VerifyMultisig(VerifyMultisigInstruction {
+                    nonce,
+                    signatures: signatures.into(),
+                }),
+            }
+        }
+    }
+
+    impl OwnedConfigProgram {
+        /// Decode the config program at the end of `bytes`.
+        ///
+        /// This is the inverse of the binary encoding of the program, which is
+        /// followed by its size - so that the program can be decoded from the end
+        /// of an installer kernel, as the installer itself does.
+        pub fn decode(bytes: &[u8]) -> Result<Self, &'static str> {
+            let end_offset = bytes
+                .len()
+                .checked_sub(4)
+                .ok_or("Couldn't read size of config program")?;
+            let (_, program_size) = size(&bytes[end_offset..])
+                .map_err(|_| "Couldn't read size of config program")?;
+            let mut input = end_offset
+                .checked_sub(program_size as usize)
+                .map(|start| &bytes[start..end_offset])
+                .ok_or("Invalid size of config program")?;
+
+            let mut instructions = vec![];
+            while !input.is_empty() {
+                let (rest, instr_size) = size(input)
+                    .map_err(|_| "Couldn't read size of config instruction")?;
+                if rest.len() < instr_size as usize {
+                    return Err("Invalid size of config instruction");
+                }
+                let (instr, rest) = rest.split_at(instr_size as usize);
+                let instr = RefConfigInstruction::nom_read(instr)
+                    .map_err(|_| "Couldn't decode config instruction")
+                    .and_then(completed)?;
+                instructions.push(instr.into());
+                input = rest;
+            }
+
+            Ok(OwnedConfigProgram(instructions))
+        }
     }
 
     #[derive(Debug, Error, PartialEq)]
@@ -110,6 +266,36 @@ pub mod owned {
         pub fn set_instr(value: OwnedBytes, to: OwnedPath) -> Self {
             OwnedConfigInstruction::Set(SetInstruction { value, to })
         }
+
+        pub fn verify_signature_instr(
+            nonce: u64,
+            signature: &OperationSignature,
+        ) -> Result<Self, BinError> {
+            let mut encoded = vec![];
+            signature.bin_write(&mut encoded)?;
+            Ok(OwnedConfigInstruction::VerifySignature(
+                VerifySignatureInstruction {
+                    nonce,
+                    signature: OwnedBytes(encoded),
+                },
+            ))
+        }
+
+        pub fn verify_multisig_instr(
+            nonce: u64,
+            signatures: &[CommitteeSignature],
+        ) -> Result<Self, BinError> {
+            let mut encoded = vec![];
+            for signature in signatures {
+                signature.bin_write(&mut encoded)?;
+            }
+            Ok(OwnedConfigInstruction::VerifyMultisig(
+                VerifyMultisigInstruction {
+                    nonce,
+                    signatures: OwnedBytes(encoded),
+                },
+            ))
+        }
     }
 }
 
@@ -168,6 +354,10 @@ pub mod evaluation {
                 Runtime::store_write(host, to, value.as_ref(), 0)
                     .map_err(|_| "Couldn't set key during config application")
             }
+            ConfigInstruction::VerifySignature(_)
+            | ConfigInstruction::VerifyMultisig(_) => {
+                Err("Verification instructions must be evaluated with the rest of the program")
+            }
         }
     }
 }
@@ -3,8 +3,10 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod auth;
 #[cfg(feature = "alloc")]
 mod bin;
+pub mod install;
 mod instr;
 mod nom;
 mod preimage;
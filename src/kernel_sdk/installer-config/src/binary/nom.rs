@@ -14,6 +14,7 @@ use tezos_smart_rollup_host::runtime::Runtime;
 
 use super::{
     ConfigInstruction, MoveInstruction, RefBytes, RevealInstruction, SetInstruction,
+    VerifyMultisigInstruction, VerifySignatureInstruction,
 };
 
 // Those types and helpers copy paseted from tezos_data_encoding.
@@ -137,6 +138,30 @@ impl<'a> NomReader<'a> for SetInstruction<RefPath<'a>, RefBytes<'a>> {
     }
 }
 
+impl<'a> NomReader<'a> for VerifySignatureInstruction<RefBytes<'a>> {
+    fn nom_read(bytes: &'a [u8]) -> NomResult<Self> {
+        map(
+            nom::sequence::tuple((
+                nom::number::complete::be_u64,
+                <RefBytes<'a> as NomReader>::nom_read,
+            )),
+            |(nonce, signature)| VerifySignatureInstruction { nonce, signature },
+        )(bytes)
+    }
+}
+
+impl<'a> NomReader<'a> for VerifyMultisigInstruction<RefBytes<'a>> {
+    fn nom_read(bytes: &'a [u8]) -> NomResult<Self> {
+        map(
+            nom::sequence::tuple((
+                nom::number::complete::be_u64,
+                <RefBytes<'a> as NomReader>::nom_read,
+            )),
+            |(nonce, signatures)| VerifyMultisigInstruction { nonce, signatures },
+        )(bytes)
+    }
+}
+
 impl<'a> NomReader<'a> for ConfigInstruction<RefPath<'a>, RefBytes<'a>> {
     fn nom_read(bytes: &'a [u8]) -> NomResult<Self> {
         let (input, tag) = nom::number::complete::u8(bytes)?;
@@ -153,6 +178,14 @@ impl<'a> NomReader<'a> for ConfigInstruction<RefPath<'a>, RefBytes<'a>> {
                 <SetInstruction<RefPath<'a>, RefBytes<'a>> as NomReader>::nom_read,
                 ConfigInstruction::Set,
             ))(input)?,
+            3 => (map(
+                <VerifySignatureInstruction<RefBytes<'a>> as NomReader>::nom_read,
+                ConfigInstruction::VerifySignature,
+            ))(input)?,
+            4 => (map(
+                <VerifyMultisigInstruction<RefBytes<'a>> as NomReader>::nom_read,
+                ConfigInstruction::VerifyMultisig,
+            ))(input)?,
             _ => {
                 return Err(nom::Err::Error(nom::error::Error {
                     input,
@@ -5,7 +5,10 @@
 use tezos_smart_rollup_core::{MAX_FILE_CHUNK_SIZE, PREIMAGE_HASH_SIZE};
 use tezos_smart_rollup_host::path::PATH_MAX_SIZE;
 
-use super::{ConfigInstruction, MoveInstruction, RefBytes, RevealInstruction};
+use super::{
+    ConfigInstruction, MoveInstruction, RefBytes, RevealInstruction,
+    VerifyMultisigInstruction, VerifySignatureInstruction,
+};
 
 // https://stackoverflow.com/questions/53619695/calculating-maximum-value-of-a-set-of-constant-expressions-at-compile-time
 const fn max(a: usize, b: usize) -> usize {
@@ -35,9 +38,30 @@ impl<Path, Bytes> EncodingSize for RevealInstruction<Path, Bytes> {
     const MAX_SIZE: usize = PREIMAGE_HASH_SIZE + MAX_SIZE_REF_PATH;
 }
 
+/// Maximum size of an encoded signature: a tag, followed by at most a BLS signature.
+pub const MAX_SIGNATURE_SIZE: usize = 1 + 96;
+
+/// Maximum number of signatures of a multisig verification instruction.
+pub const MAX_MULTISIG_SIGNATURES: usize = 16;
+
+impl<Bytes> EncodingSize for VerifySignatureInstruction<Bytes> {
+    const MAX_SIZE: usize = 8 + 4 + MAX_SIGNATURE_SIZE;
+}
+
+impl<Bytes> EncodingSize for VerifyMultisigInstruction<Bytes> {
+    // Each signature is preceded by the index of the committee member
+    const MAX_SIZE: usize = 8 + 4 + MAX_MULTISIG_SIGNATURES * (1 + MAX_SIGNATURE_SIZE);
+}
+
 impl<Path, Bytes> EncodingSize for ConfigInstruction<Path, Bytes> {
     const MAX_SIZE: usize = 1 + max(
-        MoveInstruction::<Path>::MAX_SIZE,
-        RevealInstruction::<Path, Bytes>::MAX_SIZE,
+        max(
+            MoveInstruction::<Path>::MAX_SIZE,
+            RevealInstruction::<Path, Bytes>::MAX_SIZE,
+        ),
+        max(
+            VerifySignatureInstruction::<Bytes>::MAX_SIZE,
+            VerifyMultisigInstruction::<Bytes>::MAX_SIZE,
+        ),
     );
 }
@@ -18,28 +18,15 @@
 #![cfg_attr(all(target_arch = "wasm32", not(feature = "std")), no_std)]
 #![forbid(unsafe_code)]
 
-mod instr;
-
-use crate::instr::read_instruction_bytes;
 use core::panic::PanicInfo;
-use instr::read_config_program_size;
 #[cfg(feature = "entrypoint")]
 use tezos_smart_rollup::entrypoint;
 use tezos_smart_rollup::host::Runtime;
 use tezos_smart_rollup::storage::path::RefPath;
-use tezos_smart_rollup_installer_config::binary::evaluation::eval_config_instr;
-use tezos_smart_rollup_installer_config::binary::{
-    completed, read_size, EncodingSize, NomReader, RefConfigInstruction,
-};
 
 // Path of currently running kernel.
 const KERNEL_BOOT_PATH: RefPath = RefPath::assert_from(b"/kernel/boot.wasm");
 
-// Installer kernel will copy to this path before execution of config.
-// This is done in order avoid rewriting kernel during config execution.
-const AUXILIARY_CONFIG_INTERPRETATION_PATH: RefPath =
-    RefPath::assert_from(b"/__installer_kernel/auxiliary/kernel/boot.wasm");
-
 /// Installer.
 #[cfg_attr(feature = "entrypoint", entrypoint::main)]
 pub fn installer<Host: Runtime>(host: &mut Host) {
@@ -69,54 +56,18 @@ fn panic(_info: &PanicInfo) -> ! {
 ///     - Serialise the program and write the output to durable storage.
 ///     - Finally execute the config program by calling `install_kernel`, with the path the config
 ///       was written to.
+///
+/// Once an upgrade authority has been set, the config instructions must follow a
+/// verification instruction signed by the authority. Verification instructions are
+/// only supported with the `authenticated-upgrades` feature.
 // TODO: provide a concrete example (see https://gitlab.com/tezos/tezos/-/issues/5855)
 pub fn install_kernel(
     host: &mut impl Runtime,
     config_interpretation_path: RefPath,
 ) -> Result<(), &'static str> {
-    if let Ok(config_program_size) =
-        read_config_program_size(host, &config_interpretation_path)
-    {
-        let mut config_instruction_buffer = [0; RefConfigInstruction::MAX_SIZE];
-
-        let kernel_size = host
-            .store_value_size(&config_interpretation_path)
-            .map_err(|_| "Failed to read kernel boot path size")?;
-
-        host.store_copy(
-            &config_interpretation_path,
-            &AUXILIARY_CONFIG_INTERPRETATION_PATH,
-        )
-        .map_err(|_| "Failed to copy kernel boot before config execution")?;
-
-        let end_offset = kernel_size - 4;
-        let mut instr_offset = end_offset - (config_program_size as usize);
-        while instr_offset < end_offset {
-            let instr_size = read_size(
-                host,
-                &AUXILIARY_CONFIG_INTERPRETATION_PATH,
-                &mut instr_offset,
-            )? as usize;
-            read_instruction_bytes(
-                host,
-                &AUXILIARY_CONFIG_INTERPRETATION_PATH,
-                &mut instr_offset,
-                &mut config_instruction_buffer[..instr_size],
-            )?;
-            let instr =
-                RefConfigInstruction::nom_read(&config_instruction_buffer[..instr_size])
-                    .map_err(|_| "Couldn't decode config instruction")
-                    .and_then(completed)?;
-            eval_config_instr(host, &instr)?;
-        }
-
-        host.store_delete(&AUXILIARY_CONFIG_INTERPRETATION_PATH)
-            .map_err(|_| {
-                "Failed to delete auxiliary kernel boot after config execution"
-            })?;
-
-        Ok(())
-    } else {
-        Err("Failed to read size of config program")
-    }
+    tezos_smart_rollup_installer_config::binary::install::install_kernel(
+        host,
+        config_interpretation_path,
+        cfg!(feature = "authenticated-upgrades"),
+    )
 }
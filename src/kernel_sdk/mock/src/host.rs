@@ -9,6 +9,7 @@
 //! _not_ compiling to **wasm**.
 
 use crate::state::{HostState, NextInput};
+use crate::ticks;
 use crate::MockHost;
 use core::{
     cell::RefCell,
@@ -34,6 +35,12 @@ impl From<HostState> for MockHost {
     }
 }
 
+impl MockHost {
+    fn consume_ticks(&self, ticks: u64) {
+        self.state.borrow_mut().consume_ticks(ticks);
+    }
+}
+
 impl AsMut<HostState> for MockHost {
     fn as_mut(&mut self) -> &mut HostState {
         self.state.get_mut()
@@ -81,7 +88,7 @@ unsafe impl SmartRollupCore for MockHost {
         dst: *mut u8,
         max_bytes: usize,
     ) -> i32 {
-        if let Some(NextInput { level, id, payload }) =
+        let result = if let Some(NextInput { level, id, payload }) =
             self.state.borrow_mut().handle_read_input(max_bytes)
         {
             let input_message_info = ReadInputMessageInfo {
@@ -97,7 +104,10 @@ unsafe impl SmartRollupCore for MockHost {
             payload.len().try_into().unwrap()
         } else {
             0_i32
-        }
+        };
+
+        self.consume_ticks(ticks::read_input(result));
+        result
     }
 
     unsafe fn write_debug(&self, src: *const u8, num_bytes: usize) {
@@ -109,15 +119,22 @@ unsafe impl SmartRollupCore for MockHost {
     unsafe fn write_output(&self, src: *const u8, num_bytes: usize) -> i32 {
         let output = from_raw_parts(src, num_bytes).to_vec();
 
-        self.state
+        let result = self
+            .state
             .borrow_mut()
             .handle_write_output(output)
             .map(|_| 0)
-            .unwrap_or_else(Error::code)
+            .unwrap_or_else(Error::code);
+
+        self.consume_ticks(ticks::write_output(num_bytes, result));
+        result
     }
 
     unsafe fn store_has(&self, path: *const u8, len: usize) -> i32 {
-        self.state.borrow().store.store_has(path, len)
+        let result = self.state.borrow().store.store_has(path, len);
+
+        self.consume_ticks(ticks::store_access(len, result.into()));
+        result
     }
 
     unsafe fn store_read(
@@ -128,10 +145,15 @@ unsafe impl SmartRollupCore for MockHost {
         dst: *mut u8,
         max_bytes: usize,
     ) -> i32 {
-        self.state
-            .borrow()
-            .store
-            .store_read(path, len, offset, dst, max_bytes)
+        let state = self.state.borrow();
+        let result = match state.check_read_size(max_bytes) {
+            Ok(()) => state.store.store_read(path, len, offset, dst, max_bytes),
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_read(len, result));
+        result
     }
 
     unsafe fn store_write(
@@ -142,22 +164,67 @@ unsafe impl SmartRollupCore for MockHost {
         src: *const u8,
         num_bytes: usize,
     ) -> i32 {
-        self.state
-            .borrow_mut()
-            .store
-            .store_write(path, len, offset, src, num_bytes)
+        let mut state = self.state.borrow_mut();
+        let result = match state
+            .check_writeable(from_raw_parts(path, len))
+            .and_then(|()| state.check_value_size(offset, num_bytes))
+        {
+            Ok(()) => state.store.store_write(path, len, offset, src, num_bytes),
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_write(len, num_bytes, result));
+        result
     }
 
     unsafe fn store_delete(&self, path: *const u8, len: usize) -> i32 {
-        self.state.borrow_mut().store.store_delete(path, len)
+        let mut state = self.state.borrow_mut();
+        let result = match state.check_writeable(from_raw_parts(path, len)) {
+            Ok(()) => state.store.store_delete(path, len),
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_delete(len, result));
+        result
     }
 
     unsafe fn store_delete_value(&self, path: *const u8, len: usize) -> i32 {
-        self.state.borrow_mut().store.store_delete_value(path, len)
+        let mut state = self.state.borrow_mut();
+        let result = match state.check_writeable(from_raw_parts(path, len)) {
+            Ok(()) => state.store.store_delete_value(path, len),
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_delete(len, result));
+        result
     }
 
     unsafe fn store_list_size(&self, path: *const u8, len: usize) -> i64 {
-        self.state.borrow().store.store_list_size(path, len)
+        let result = self.state.borrow().store.store_list_size(path, len);
+
+        self.consume_ticks(ticks::store_access(len, result));
+        result
+    }
+
+    unsafe fn store_get_nth_key(
+        &self,
+        path: *const u8,
+        len: usize,
+        index: i64,
+        dst: *mut u8,
+        max_size: usize,
+    ) -> i32 {
+        let result = self
+            .state
+            .borrow()
+            .store
+            .store_get_nth_key(path, len, index, dst, max_size);
+
+        self.consume_ticks(ticks::store_access(len, result.into()));
+        result
     }
 
     unsafe fn store_move(
@@ -167,12 +234,22 @@ unsafe impl SmartRollupCore for MockHost {
         to_path: *const u8,
         to_path_len: usize,
     ) -> i32 {
-        self.state.borrow_mut().store.store_move(
-            from_path,
-            from_path_len,
-            to_path,
-            to_path_len,
-        )
+        let mut state = self.state.borrow_mut();
+        let result = match state
+            .check_writeable(from_raw_parts(from_path, from_path_len))
+            .and_then(|()| state.check_writeable(from_raw_parts(to_path, to_path_len)))
+        {
+            Ok(()) => {
+                state
+                    .store
+                    .store_move(from_path, from_path_len, to_path, to_path_len)
+            }
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_copy(from_path_len, to_path_len, result));
+        result
     }
 
     unsafe fn store_copy(
@@ -182,12 +259,19 @@ unsafe impl SmartRollupCore for MockHost {
         to_path: *const u8,
         to_path_len: usize,
     ) -> i32 {
-        self.state.borrow_mut().store.store_copy(
-            from_path,
-            from_path_len,
-            to_path,
-            to_path_len,
-        )
+        let mut state = self.state.borrow_mut();
+        let result = match state.check_writeable(from_raw_parts(to_path, to_path_len)) {
+            Ok(()) => {
+                state
+                    .store
+                    .store_copy(from_path, from_path_len, to_path, to_path_len)
+            }
+            Err(e) => e.code(),
+        };
+        drop(state);
+
+        self.consume_ticks(ticks::store_copy(from_path_len, to_path_len, result));
+        result
     }
 
     unsafe fn reveal_preimage(
@@ -213,11 +297,16 @@ unsafe impl SmartRollupCore for MockHost {
         let slice = from_raw_parts_mut(destination_addr, bytes.len());
         slice.copy_from_slice(bytes.as_slice());
 
-        bytes.len().try_into().unwrap()
+        let result = bytes.len().try_into().unwrap();
+        self.consume_ticks(ticks::reveal(result));
+        result
     }
 
     unsafe fn store_value_size(&self, path: *const u8, path_len: usize) -> i32 {
-        self.state.borrow().store.store_value_size(path, path_len)
+        let result = self.state.borrow().store.store_value_size(path, path_len);
+
+        self.consume_ticks(ticks::store_access(path_len, result.into()));
+        result
     }
 
     unsafe fn reveal_metadata(&self, destination_addr: *mut u8, max_bytes: usize) -> i32 {
@@ -226,6 +315,8 @@ unsafe impl SmartRollupCore for MockHost {
         let len = min(max_bytes, metadata.len());
         let slice = from_raw_parts_mut(destination_addr, len);
         slice.copy_from_slice(&metadata[..len]);
+
+        self.consume_ticks(ticks::reveal(len as i32));
         len as i32
     }
 
@@ -273,18 +364,22 @@ unsafe impl SmartRollupCore for MockHost {
                 let slot_index = slot_index[0];
                 let page_index = i16::from_be_bytes(page_index.try_into().unwrap());
 
-                reveal_dal_page(
+                let result = reveal_dal_page(
                     self,
                     published_level,
                     slot_index,
                     page_index,
                     destination_addr,
                     max_bytes,
-                )
+                );
+                self.consume_ticks(ticks::reveal(result));
+                result
             }
             3 => {
                 // Reveal_dal_parameters
-                reveal_dal_parameters(self, destination_addr, max_bytes)
+                let result = reveal_dal_parameters(self, destination_addr, max_bytes);
+                self.consume_ticks(ticks::reveal(result));
+                result
             }
             tag => unimplemented!(
                 "The `reveal` host function is not yet mocked for tag {}.",
@@ -300,12 +395,13 @@ mod tests {
     use super::MockHost;
 
     use crate::state::HostState;
+    use crate::ticks::MAX_TICKS_PER_REBOOT;
     use tezos_smart_rollup_core::{MAX_FILE_CHUNK_SIZE, MAX_INPUT_MESSAGE_SIZE};
     use tezos_smart_rollup_host::input::Message;
     use tezos_smart_rollup_host::{
         metadata::RollupMetadata,
-        path::RefPath,
-        runtime::{Runtime, RuntimeError},
+        path::{OwnedPath, Path, RefPath},
+        runtime::{Runtime, RuntimeError, ValueType},
     };
 
     #[test]
@@ -486,4 +582,203 @@ mod tests {
         assert_ne!(new_value_in_store, initial_value_in_store);
         assert_eq!(new_value_in_store, smaller_value);
     }
+
+    #[test]
+    fn store_list_subkeys() {
+        let mut mock = MockHost::default();
+        const PREFIX: RefPath<'static> = RefPath::assert_from(b"/dir");
+
+        for path in ["/dir", "/dir/b", "/dir/a/x", "/dir/a/y", "/other"] {
+            let path = OwnedPath::try_from(path.to_string()).unwrap();
+            mock.store_write_all(&path, b"value").unwrap();
+        }
+
+        // The value at the prefix is counted, but not listed.
+        assert_eq!(Ok(3), mock.store_count_subkeys(&PREFIX));
+        assert_eq!(
+            Ok(vec!["/dir/a".to_string(), "/dir/b".to_string()]),
+            mock.store_list_subkeys(&PREFIX)
+                .map(|paths| paths.iter().map(|p| p.to_string()).collect())
+        );
+        assert_eq!(
+            Err(RuntimeError::StoreListIndexOutOfBounds),
+            mock.store_get_subkey(&PREFIX, 3)
+        );
+
+        // Walk the tree under the prefix.
+        let mut values = vec![];
+        let mut to_visit = vec![OwnedPath::from(PREFIX)];
+        while let Some(path) = to_visit.pop() {
+            if mock.store_has(&path).unwrap() != Some(ValueType::Subtree) {
+                values.push(path.to_string());
+            }
+            for subkey in mock.store_subkeys(&path).unwrap() {
+                to_visit.push(subkey.unwrap());
+            }
+        }
+        values.sort();
+        assert_eq!(vec!["/dir", "/dir/a/x", "/dir/a/y", "/dir/b"], values);
+
+        assert_eq!(
+            0,
+            mock.store_subkeys(&RefPath::assert_from(b"/none"))
+                .unwrap()
+                .count()
+        );
+    }
+
+    #[test]
+    fn strict_mode_readonly_paths() {
+        // `RefPath` refuses `/readonly` paths, which kernels aren't meant to write to.
+        #[derive(Debug)]
+        struct ReadonlyPath;
+
+        impl core::fmt::Display for ReadonlyPath {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "/readonly/kernel/env/reboot_counter")
+            }
+        }
+
+        unsafe impl Path for ReadonlyPath {
+            fn as_bytes(&self) -> &[u8] {
+                b"/readonly/kernel/env/reboot_counter"
+            }
+        }
+
+        let mut mock = MockHost::default();
+        const PATH: ReadonlyPath = ReadonlyPath;
+
+        assert_eq!(
+            mock.store_write(&PATH, &[1, 2, 3], 0),
+            Err(RuntimeError::HostErr(
+                tezos_smart_rollup_host::Error::StoreInvalidKey
+            ))
+        );
+
+        mock.strict_mode(true);
+        assert_eq!(
+            mock.store_write(&PATH, &[1, 2, 3], 0),
+            Err(RuntimeError::HostErr(
+                tezos_smart_rollup_host::Error::StoreReadonlyValue
+            ))
+        );
+        mock.store_write(&RefPath::assert_from(b"/a"), &[1, 2, 3], 0)
+            .unwrap();
+        assert_eq!(
+            mock.store_copy(&RefPath::assert_from(b"/a"), &PATH),
+            Err(RuntimeError::HostErr(
+                tezos_smart_rollup_host::Error::StoreReadonlyValue
+            ))
+        );
+    }
+
+    #[test]
+    fn strict_mode_read_size() {
+        let mut mock = MockHost::default();
+        const PATH: RefPath<'static> = RefPath::assert_from(b"/some/path");
+        mock.store_write(&PATH, &[1; 16], 0).unwrap();
+        let mut buffer = [0_u8; MAX_FILE_CHUNK_SIZE + 1];
+
+        assert_eq!(mock.store_read_slice(&PATH, 0, &mut buffer), Ok(16));
+
+        mock.strict_mode(true);
+        assert_eq!(
+            mock.store_read_slice(&PATH, 0, &mut buffer),
+            Err(RuntimeError::HostErr(
+                tezos_smart_rollup_host::Error::InputOutputTooLarge
+            ))
+        );
+        assert_eq!(
+            mock.store_read_slice(&PATH, 0, &mut buffer[..MAX_FILE_CHUNK_SIZE]),
+            Ok(16)
+        );
+    }
+
+    #[test]
+    fn ticks_consumed() {
+        let mut mock = MockHost::default();
+        const PATH: RefPath<'static> = RefPath::assert_from(b"/a");
+
+        let before = mock.ticks_consumed();
+        mock.store_write(&PATH, &[0; 4], 0).unwrap();
+        // key and value read from memory, plus one tree access.
+        assert_eq!(mock.ticks_consumed() - before, 2 * 42 + 1 + 4 * 42);
+
+        // Errors don't consume ticks.
+        const MISSING: RefPath<'static> = RefPath::assert_from(b"/b");
+        let before = mock.ticks_consumed();
+        let result = unsafe {
+            tezos_smart_rollup_core::smart_rollup_core::SmartRollupCore::store_value_size(
+                &mock,
+                MISSING.as_ptr(),
+                MISSING.size(),
+            )
+        };
+        assert!(result < 0);
+        assert_eq!(mock.ticks_consumed(), before);
+    }
+
+    #[test]
+    fn strict_mode_tick_budget() {
+        fn kernel_run(host: &mut MockHost) {
+            host.store_write(&RefPath::assert_from(b"/a"), &[0; 100], 0)
+                .unwrap();
+            host.write_output(&[0; 10]).unwrap();
+        }
+
+        let mut mock = MockHost::default();
+        mock.set_max_ticks_per_reboot(1000);
+        let level = mock.run_level(kernel_run);
+        assert!(mock.ticks_consumed() > 1000);
+        assert!(mock
+            .store_has(&RefPath::assert_from(b"/a"))
+            .unwrap()
+            .is_some());
+        assert_eq!(mock.outbox_at(level).len(), 1);
+
+        let mut mock = MockHost::default();
+        mock.set_debug_handler(std::io::sink());
+        mock.set_max_ticks_per_reboot(1000);
+        mock.strict_mode(true);
+        let level = mock.run_level(kernel_run);
+        assert!(mock
+            .store_has(&RefPath::assert_from(b"/a"))
+            .unwrap()
+            .is_none());
+        assert!(mock.outbox_at(level).is_empty());
+    }
+
+    #[test]
+    fn strict_mode_charged_ticks() {
+        fn kernel_run(host: &mut MockHost) {
+            host.store_write(&RefPath::assert_from(b"/a"), &[0; 100], 0)
+                .unwrap();
+            host.charge_ticks(host.ticks_remaining());
+        }
+
+        fn expensive_kernel_run(host: &mut MockHost) {
+            kernel_run(host);
+            host.charge_ticks(1);
+        }
+
+        // Within the default budget of the WASM PVM.
+        let mut mock = MockHost::default();
+        mock.strict_mode(true);
+        mock.run_level(kernel_run);
+        assert_eq!(mock.ticks_consumed(), MAX_TICKS_PER_REBOOT);
+        assert!(mock
+            .store_has(&RefPath::assert_from(b"/a"))
+            .unwrap()
+            .is_some());
+
+        let mut mock = MockHost::default();
+        mock.set_debug_handler(std::io::sink());
+        mock.strict_mode(true);
+        mock.run_level(expensive_kernel_run);
+        assert_eq!(mock.ticks_consumed(), MAX_TICKS_PER_REBOOT + 1);
+        assert!(mock
+            .store_has(&RefPath::assert_from(b"/a"))
+            .unwrap()
+            .is_none());
+    }
 }
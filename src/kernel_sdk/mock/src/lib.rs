@@ -7,7 +7,9 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 
 mod host;
+mod snapshot;
 mod state;
+mod ticks;
 
 extern crate tezos_crypto_rs as crypto;
 
@@ -46,6 +48,7 @@ const NAIROBI_BLOCK_TIME: i64 = 15;
 // Nairobi activated approximately at 0:07AM UTC on June 24th 2023.
 const NAIROBI_ACTIVATION_TIMESTAMP: i64 = 1_687_561_630;
 
+pub use snapshot::MockHostSnapshot;
 pub use state::InMemoryStore;
 
 /// The runtime host when _not_ running in **wasm**.
@@ -204,7 +207,31 @@ impl MockHost {
             let bytes = reboots.to_le_bytes().to_vec();
             self.as_mut().store.0.set_value(REBOOT_COUNTER_KEY, bytes);
 
+            let state = self.as_mut();
+            state.ticks = 0;
+            let durable_before = state.store.0.durable.clone();
+            let outbox_len_before = state.store.0.outbox_at(state.curr_level).len();
+
             kernel_run(self);
+
+            let state = self.as_mut();
+            if state.strict && state.ticks > state.max_ticks_per_reboot {
+                // The PVM discards the changes made by a `kernel_run` that
+                // took too many ticks, and waits for the next level.
+                let (ticks, max_ticks) = (state.ticks, state.max_ticks_per_reboot);
+                let curr_level = state.curr_level;
+                state.store.0.durable = durable_before;
+                state.store.0.outbox_truncate(curr_level, outbox_len_before);
+
+                writeln!(
+                    self.debug_log.borrow_mut(),
+                    "kernel_run exceeded the tick budget ({ticks} > {max_ticks}), \
+                     discarding its changes"
+                )
+                .unwrap();
+                break;
+            }
+
             self.as_mut().store.0.node_delete(TOO_MANY_REBOOT_FLAG_KEY);
 
             reboots -= 1;
@@ -270,6 +297,61 @@ impl MockHost {
         self.keep_going = keep_going;
     }
 
+    /// Control whether the host enforces the limits of the WASM PVM.
+    ///
+    /// In strict mode, host functions fail with the same errors as in the PVM
+    /// when:
+    /// - writing, deleting or moving paths under `/readonly`;
+    /// - reading more than [`MAX_FILE_CHUNK_SIZE`] bytes at once, rather than
+    ///   truncating the read;
+    /// - growing a value beyond `i32::MAX` bytes.
+    ///
+    /// Additionally, the changes made by a `kernel_run` that consumed more than
+    /// the tick budget (see [`MockHost::set_max_ticks_per_reboot`]) are
+    /// discarded, and the rest of the level is skipped. Host functions alone
+    /// rarely exhaust the budget of the WASM PVM: the ticks of the kernel's own
+    /// computation must be charged with [`MockHost::charge_ticks`].
+    ///
+    /// The outbox and input size limits are always enforced.
+    ///
+    /// [`MAX_FILE_CHUNK_SIZE`]: tezos_smart_rollup_core::MAX_FILE_CHUNK_SIZE
+    pub fn strict_mode(&mut self, strict: bool) {
+        self.as_mut().strict = strict;
+    }
+
+    /// Set the number of ticks a single `kernel_run` may consume in strict
+    /// mode. Defaults to the limit of the WASM PVM.
+    pub fn set_max_ticks_per_reboot(&mut self, max_ticks: u64) {
+        self.as_mut().max_ticks_per_reboot = max_ticks;
+    }
+
+    /// Approximate number of ticks consumed by host functions since the start
+    /// of the current (or last) `kernel_run`.
+    ///
+    /// The cost of host functions is accounted for, following the tick model
+    /// of the WASM PVM; the ticks of the kernel's own computation are only
+    /// included once charged with [`MockHost::charge_ticks`].
+    pub fn ticks_consumed(&self) -> u64 {
+        self.state.borrow().ticks
+    }
+
+    /// Charge `ticks` to the current `kernel_run`, for computation done by the
+    /// kernel itself rather than by host functions.
+    ///
+    /// The mock host can't measure the ticks of native code, so a `kernel_run`
+    /// passed to [`MockHost::run_level`] may call this with an estimate of its
+    /// own cost, to check its behaviour when running out of ticks.
+    pub fn charge_ticks(&mut self, ticks: u64) {
+        self.as_mut().consume_ticks(ticks);
+    }
+
+    /// Approximate number of ticks left for the current `kernel_run`, see
+    /// [`MockHost::ticks_consumed`].
+    pub fn ticks_remaining(&self) -> u64 {
+        let state = self.state.borrow();
+        state.max_ticks_per_reboot.saturating_sub(state.ticks)
+    }
+
     fn bump_level(&mut self) {
         let state = self.as_mut();
         state.curr_level += 1;
@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Persisting the state of a [`MockHost`] across test runs.
+//!
+//! A [`MockHostSnapshot`] captures the durable storage, the outbox and the
+//! preimages of a host, together with the level of the next `kernel_run`.
+//! Snapshots are serialized as JSON, so that long-running kernel scenarios can
+//! be checkpointed to a file and resumed later.
+//!
+//! Durable storage exported from a rollup node, with
+//! `octez-smart-rollup-node dump durable storage`, can also be loaded into a
+//! host using [`MockHost::import_durable_storage`].
+
+use crate::MockHost;
+use crypto::hash::SmartRollupHash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
+use tezos_smart_rollup_host::metadata::RollupMetadata;
+use tezos_smart_rollup_installer_config::yaml::{Instr, YamlConfig};
+
+/// Serializable state of a [`MockHost`].
+///
+/// Byte values are hex-encoded. The inbox of the current level isn't part of
+/// the snapshot: a restored host starts with a fresh inbox at [`level`].
+///
+/// [`level`]: MockHostSnapshot::level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockHostSnapshot {
+    /// Level of the next `kernel_run`.
+    pub level: u32,
+    /// Address of the rollup, in base58-check encoding.
+    pub rollup_address: String,
+    /// Origination level of the rollup.
+    pub origination_level: u32,
+    /// Values of the durable storage, by path.
+    pub durable: BTreeMap<String, String>,
+    /// Outbox messages, by level.
+    pub outbox: BTreeMap<u32, Vec<String>>,
+    /// Preimages available to the _reveal_data_ channel, by hash.
+    pub preimages: BTreeMap<String, String>,
+}
+
+fn invalid_data(msg: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn decode_hex(what: &str, value: &str) -> io::Result<Vec<u8>> {
+    hex::decode(value).map_err(|e| invalid_data(format!("invalid {what} {value}: {e}")))
+}
+
+impl MockHostSnapshot {
+    /// Write the snapshot as JSON.
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)
+    }
+
+    /// Read a snapshot written by [`MockHostSnapshot::write`].
+    pub fn read(reader: impl Read) -> io::Result<Self> {
+        serde_json::from_reader(reader).map_err(io::Error::from)
+    }
+}
+
+impl MockHost {
+    /// Capture the durable storage, outbox and preimages of the host.
+    pub fn snapshot(&self) -> MockHostSnapshot {
+        let state = self.state.borrow();
+        let store = &state.store.0;
+
+        MockHostSnapshot {
+            level: state.curr_level,
+            rollup_address: state.metadata.address().to_base58_check(),
+            origination_level: state.metadata.origination_level,
+            durable: store
+                .values()
+                .into_iter()
+                .map(|(path, value)| (path, hex::encode(value)))
+                .collect(),
+            outbox: store
+                .outbox()
+                .map(|(level, messages)| {
+                    (*level, messages.iter().map(hex::encode).collect())
+                })
+                .collect(),
+            preimages: store
+                .preimages()
+                .map(|(hash, preimage)| (hex::encode(hash), hex::encode(preimage)))
+                .collect(),
+        }
+    }
+
+    /// Create a host from a snapshot, with a fresh inbox at the snapshot level.
+    pub fn from_snapshot(snapshot: &MockHostSnapshot) -> io::Result<Self> {
+        let address = SmartRollupHash::from_base58_check(&snapshot.rollup_address)
+            .map_err(|e| {
+                invalid_data(format!(
+                    "invalid rollup address {}: {e}",
+                    snapshot.rollup_address
+                ))
+            })?;
+        let raw_rollup_address = address
+            .as_ref()
+            .try_into()
+            .expect("Incorrect length for SmartRollupHash");
+
+        let mut host = MockHost::default();
+        let state = host.as_mut();
+        state.metadata = RollupMetadata {
+            raw_rollup_address,
+            origination_level: snapshot.origination_level,
+        };
+
+        let store = &mut state.store.0;
+        *store = Default::default();
+        for (path, value) in snapshot.durable.iter() {
+            store.set_value(path, decode_hex("durable value", value)?);
+        }
+        for (level, messages) in snapshot.outbox.iter() {
+            for message in messages.iter() {
+                store.outbox_insert(*level, decode_hex("outbox message", message)?);
+            }
+        }
+        for (hash, preimage) in snapshot.preimages.iter() {
+            let hash: [u8; PREIMAGE_HASH_SIZE] = decode_hex("preimage hash", hash)?
+                .try_into()
+                .map_err(|_| invalid_data(format!("invalid preimage hash {hash}")))?;
+            store.insert_preimage(hash, decode_hex("preimage", preimage)?);
+        }
+
+        // `bump_level` moves to the next level, and sets up its inbox.
+        state.curr_level = snapshot
+            .level
+            .checked_sub(1)
+            .ok_or_else(|| invalid_data(format!("invalid level {}", snapshot.level)))?;
+        host.bump_level();
+
+        Ok(host)
+    }
+
+    /// Write a snapshot of the host to a file.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.snapshot().write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Create a host from a snapshot file written by [`MockHost::save_snapshot`].
+    pub fn load_snapshot(path: impl AsRef<Path>) -> io::Result<Self> {
+        let snapshot = MockHostSnapshot::read(BufReader::new(File::open(path)?))?;
+        Self::from_snapshot(&snapshot)
+    }
+
+    /// Import durable storage exported from a rollup node.
+    ///
+    /// The input is an installer configuration, in YAML or JSON, as produced by
+    /// `octez-smart-rollup-node dump durable storage`. `set` and `move`
+    /// instructions are applied to the durable storage, `reveal` instructions
+    /// are applied using the preimages already known to the host.
+    pub fn import_durable_storage(&mut self, reader: impl Read) -> io::Result<()> {
+        let config = YamlConfig::from_reader(reader).map_err(invalid_data)?;
+        let store = &mut self.as_mut().store.0;
+
+        for instr in config.instructions {
+            match instr {
+                Instr::Set(args) => {
+                    store.set_value(&args.to, decode_hex("value", &args.value)?)
+                }
+                Instr::Move(args) => {
+                    let node =
+                        store.node_from_path(&args.from).cloned().ok_or_else(|| {
+                            invalid_data(format!("cannot move {}: not found", args.from))
+                        })?;
+                    store.node_delete(&args.from);
+                    store.node_delete(&args.to);
+                    store.node_insert(&args.to, node);
+                }
+                Instr::Reveal(args) => {
+                    let hash: [u8; PREIMAGE_HASH_SIZE] =
+                        decode_hex("preimage hash", &args.reveal)?
+                            .try_into()
+                            .map_err(|_| {
+                                invalid_data(format!(
+                                    "invalid preimage hash {}",
+                                    args.reveal
+                                ))
+                            })?;
+                    let preimage = store
+                        .preimages()
+                        .find(|(h, _)| **h == hash)
+                        .map(|(_, preimage)| preimage.clone())
+                        .ok_or_else(|| {
+                            invalid_data(format!("unknown preimage {}", args.reveal))
+                        })?;
+                    store.set_value(&args.to, preimage);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_host::runtime::Runtime;
+
+    fn kernel_run(host: &mut MockHost) {
+        let level = host.level();
+        host.store_write(
+            &RefPath::assert_from(b"/last_level"),
+            &level.to_le_bytes(),
+            0,
+        )
+        .unwrap();
+        host.write_output(&level.to_le_bytes()).unwrap();
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let mut host = MockHost::default();
+        let hash = host.set_preimage(b"some preimage".to_vec());
+        host.run_level(kernel_run);
+        let level = host.run_level(kernel_run);
+
+        let mut file = Vec::new();
+        host.snapshot().write(&mut file).unwrap();
+        let snapshot = MockHostSnapshot::read(file.as_slice()).unwrap();
+        assert_eq!(snapshot, host.snapshot());
+
+        let mut restored = MockHost::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.level(), host.level());
+        assert_eq!(restored.info_per_level(), host.info_per_level());
+        assert_eq!(
+            restored.outbox_at(level),
+            vec![level.to_le_bytes().to_vec()]
+        );
+        assert_eq!(
+            restored
+                .store_read(&RefPath::assert_from(b"/last_level"), 0, 4)
+                .unwrap(),
+            level.to_le_bytes()
+        );
+
+        let mut buffer = [0; 13];
+        let size = restored.reveal_preimage(&hash, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], b"some preimage");
+
+        // The restored host keeps running from where the original one stopped.
+        assert_eq!(restored.run_level(kernel_run), level + 1);
+        assert_eq!(host.run_level(kernel_run), level + 1);
+        assert_eq!(restored.snapshot(), host.snapshot());
+
+        let invalid = MockHostSnapshot {
+            level: 0,
+            ..snapshot
+        };
+        let err = MockHost::from_snapshot(&invalid).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn import_durable_storage() {
+        let mut host = MockHost::default();
+        let hash = hex::encode(host.set_preimage(b"kernel".to_vec()));
+
+        let dump = format!(
+            r#"
+instructions:
+  - set:
+      value: "0102"
+      to: /a/b
+  - set:
+      value: "03"
+      to: /a/b/c
+  - move:
+      from: /a
+      to: /moved
+  - reveal: {hash}
+    to: /kernel/boot.wasm
+"#
+        );
+        host.import_durable_storage(dump.as_bytes()).unwrap();
+
+        let snapshot = host.snapshot();
+        assert_eq!(snapshot.durable["/moved/b"], "0102");
+        assert_eq!(snapshot.durable["/moved/b/c"], "03");
+        assert!(!snapshot.durable.contains_key("/a/b"));
+        assert_eq!(
+            snapshot.durable["/kernel/boot.wasm"],
+            hex::encode(b"kernel")
+        );
+
+        let json = r#"{"instructions": [{"set": {"value": "ff", "to": "/json"}}]}"#;
+        host.import_durable_storage(json.as_bytes()).unwrap();
+        assert_eq!(host.snapshot().durable["/json"], "ff");
+
+        let invalid = r#"{"instructions": [{"set": {"value": "f", "to": "/json"}}]}"#;
+        let err = host.import_durable_storage(invalid.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -4,7 +4,7 @@
 //
 // SPDX-License-Identifier: MIT
 
-use super::store::Store;
+use super::store::{Store, VALUE_NAME};
 use core::slice::{from_raw_parts, from_raw_parts_mut};
 use tezos_smart_rollup_core::MAX_FILE_CHUNK_SIZE;
 use tezos_smart_rollup_host::{
@@ -91,6 +91,29 @@ impl InMemoryStore {
             .unwrap_or_else(|e| e.code() as i64)
     }
 
+    pub unsafe fn store_get_nth_key(
+        &self,
+        path: *const u8,
+        len: usize,
+        index: i64,
+        dst: *mut u8,
+        max_size: usize,
+    ) -> i32 {
+        let path = from_raw_parts(path, len);
+
+        match self.handle_store_get_nth_key(path, index) {
+            Ok(key) => {
+                let size = usize::min(key.len(), max_size);
+
+                let slice = from_raw_parts_mut(dst, size);
+                slice.copy_from_slice(&key.as_bytes()[..size]);
+
+                size.try_into().unwrap()
+            }
+            Err(e) => e.code(),
+        }
+    }
+
     pub unsafe fn store_move(
         &mut self,
         from_path: *const u8,
@@ -171,6 +194,31 @@ impl InMemoryStore {
             .ok_or(Error::StoreNotANode)
     }
 
+    /// Name of the `index`-th subkey of `prefix`, in lexicographic order. The
+    /// value at `prefix` itself is named `""`.
+    pub(crate) fn handle_store_get_nth_key(
+        &self,
+        prefix: &[u8],
+        index: i64,
+    ) -> Result<String, Error> {
+        let prefix = validate_path(prefix)?;
+        let node = self.0.node_from_path(&prefix).ok_or(Error::StoreNotANode)?;
+
+        let mut keys: Vec<_> = node.inner.keys().collect();
+        keys.sort();
+
+        let key = usize::try_from(index)
+            .ok()
+            .and_then(|index| keys.get(index))
+            .ok_or(Error::StoreInvalidSubkeyIndex)?;
+
+        if key.as_str() == VALUE_NAME {
+            Ok(String::new())
+        } else {
+            Ok(key.to_string())
+        }
+    }
+
     pub(crate) fn handle_store_has(&self, raw_path: &[u8]) -> Result<i32, Error> {
         let path = validate_path(raw_path)?;
 
@@ -8,7 +8,7 @@
 
 use crypto::hash::SmartRollupHash;
 use tezos_smart_rollup_core::{
-    MAX_INPUT_MESSAGE_SIZE, MAX_OUTPUT_SIZE, PREIMAGE_HASH_SIZE,
+    MAX_FILE_CHUNK_SIZE, MAX_INPUT_MESSAGE_SIZE, MAX_OUTPUT_SIZE, PREIMAGE_HASH_SIZE,
 };
 use tezos_smart_rollup_host::{
     dal_parameters::RollupDalParameters, metadata::RollupMetadata, Error,
@@ -21,6 +21,9 @@ pub use self::in_memory_store::InMemoryStore;
 
 const MAX_OUTPUTS_PER_LEVEL: usize = 100;
 
+/// The size of durable storage values is reported as an `i32`.
+const MAX_VALUE_SIZE: usize = i32::MAX as usize;
+
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct NextInput {
     pub level: u32,
@@ -39,6 +42,10 @@ pub(crate) struct HostState {
     pub(crate) curr_level: u32,
     pub(crate) curr_input_id: usize,
     pub(crate) input: Vec<Vec<u8>>,
+    // PVM limits
+    pub(crate) strict: bool,
+    pub(crate) ticks: u64,
+    pub(crate) max_ticks_per_reboot: u64,
 }
 
 impl Default for HostState {
@@ -69,6 +76,9 @@ impl Default for HostState {
             curr_level: crate::NAIROBI_ACTIVATION_LEVEL,
             curr_input_id: 0,
             input: vec![],
+            strict: false,
+            ticks: 0,
+            max_ticks_per_reboot: crate::ticks::MAX_TICKS_PER_REBOOT,
         }
     }
 }
@@ -95,6 +105,47 @@ impl HostState {
         }
     }
 
+    pub(crate) fn consume_ticks(&mut self, ticks: u64) {
+        self.ticks = self.ticks.saturating_add(ticks);
+    }
+
+    /// In strict mode, paths under `/readonly` can't be modified by the kernel.
+    pub(crate) fn check_writeable(&self, path: &[u8]) -> Result<(), Error> {
+        const READONLY_PREFIX: &[u8] = b"/readonly";
+
+        let readonly = path.starts_with(READONLY_PREFIX)
+            && matches!(path.get(READONLY_PREFIX.len()), None | Some(b'/'));
+
+        if self.strict && readonly {
+            Err(Error::StoreReadonlyValue)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// In strict mode, reads of more than [`MAX_FILE_CHUNK_SIZE`] bytes are
+    /// rejected rather than truncated.
+    pub(crate) fn check_read_size(&self, max_bytes: usize) -> Result<(), Error> {
+        if self.strict && max_bytes > MAX_FILE_CHUNK_SIZE {
+            Err(Error::InputOutputTooLarge)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// In strict mode, values can't grow beyond [`MAX_VALUE_SIZE`] bytes.
+    pub(crate) fn check_value_size(
+        &self,
+        offset: usize,
+        num_bytes: usize,
+    ) -> Result<(), Error> {
+        if self.strict && offset.saturating_add(num_bytes) > MAX_VALUE_SIZE {
+            Err(Error::StoreValueSizeExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn add_input(&mut self, input: Vec<u8>) {
         if input.len() > MAX_INPUT_MESSAGE_SIZE {
             panic!(
@@ -440,6 +491,50 @@ mod tests {
         assert_eq!(11, with_value, "Expected 10 subkeys of prefix, plus value");
     }
 
+    #[test]
+    fn store_get_nth_key() {
+        // Arrange
+        let mut state = HostState::default();
+        let prefix = "/a/prefix";
+
+        for subkey in ["c", "a", "b"] {
+            let path = format!("{}/{}", prefix, subkey);
+            state
+                .store
+                .handle_store_write(path.as_bytes(), 0, &[])
+                .unwrap();
+        }
+        state
+            .store
+            .handle_store_write(prefix.as_bytes(), 0, &[1])
+            .unwrap();
+
+        // Act
+        let keys: Vec<_> = (0..4)
+            .map(|i| {
+                state
+                    .store
+                    .handle_store_get_nth_key(prefix.as_bytes(), i)
+                    .unwrap()
+            })
+            .collect();
+
+        // Assert
+        assert_eq!(
+            vec!["", "a", "b", "c"],
+            keys,
+            "Expected value then sorted subkeys"
+        );
+        assert_eq!(
+            Err(Error::StoreInvalidSubkeyIndex),
+            state.store.handle_store_get_nth_key(prefix.as_bytes(), 4)
+        );
+        assert_eq!(
+            Err(Error::StoreNotANode),
+            state.store.handle_store_get_nth_key(b"/not/a/node", 0)
+        );
+    }
+
     #[test]
     fn store_move() {
         // Arrange
@@ -44,6 +44,21 @@ impl Node {
     }
 }
 
+impl Node {
+    fn collect_values(&self, prefix: &str, values: &mut Vec<(String, Vec<u8>)>) {
+        if let Some(v) = &self.value {
+            values.push((prefix.to_string(), v.as_ref().clone()));
+        }
+
+        for (k, v) in self.inner.iter() {
+            if k == VALUE_NAME {
+                continue;
+            }
+            v.collect_values(&format!("{}/{}", prefix, k), values);
+        }
+    }
+}
+
 impl std::fmt::Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.print("", f)
@@ -63,6 +78,24 @@ impl Store {
         }
     }
 
+    pub fn outbox(&self) -> impl Iterator<Item = (&u32, &Vec<Vec<u8>>)> {
+        self.outbox.iter()
+    }
+
+    /// All values of the durable storage, with their paths, sorted by path.
+    pub fn values(&self) -> Vec<(String, Vec<u8>)> {
+        let mut values = Vec::new();
+        self.durable.collect_values("", &mut values);
+        values.sort_by(|(p1, _), (p2, _)| p1.cmp(p2));
+        values
+    }
+
+    pub fn outbox_truncate(&mut self, level: u32, len: usize) {
+        if let Some(l) = self.outbox.get_mut(&level) {
+            l.truncate(len);
+        }
+    }
+
     pub fn node_from_path(&self, path: &str) -> Option<&Rc<Node>> {
         let steps = path_steps(path);
         let mut node = &self.durable;
@@ -182,6 +215,16 @@ impl Store {
         hash_with_prefix
     }
 
+    pub fn insert_preimage(&mut self, hash: [u8; PREIMAGE_HASH_SIZE], preimage: Vec<u8>) {
+        self.preimages.insert(hash, preimage);
+    }
+
+    pub fn preimages(
+        &self,
+    ) -> impl Iterator<Item = (&[u8; PREIMAGE_HASH_SIZE], &Vec<u8>)> {
+        self.preimages.iter()
+    }
+
     pub fn retrieve_preimage(&self, hash: &[u8; PREIMAGE_HASH_SIZE]) -> &[u8] {
         self.preimages
             .get(hash)
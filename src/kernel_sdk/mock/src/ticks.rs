@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Approximation of the ticks consumed by host functions.
+//!
+//! Follows the tick model of the WASM PVM host functions (see `Tick_model` in
+//! `lib_scoru_wasm/host_funcs.ml`): host functions pay for the bytes copied
+//! between the kernel memory and the host, and for each access to the durable
+//! storage tree. Calls returning an error consume no ticks.
+//!
+//! Ticks consumed by the kernel's own instructions are not accounted for,
+//! unless charged with [`MockHost::charge_ticks`].
+//!
+//! [`MockHost::charge_ticks`]: crate::MockHost::charge_ticks
+
+/// Maximum number of ticks a single `kernel_run` may consume in the WASM PVM.
+pub(crate) const MAX_TICKS_PER_REBOOT: u64 = 11_000_000_000;
+
+const TICKS_PER_BYTE_READ: u64 = 42;
+const TICKS_PER_BYTE_WRITTEN: u64 = 42;
+const TREE_ACCESS: u64 = 1;
+
+fn read_key_in_memory(key_len: usize) -> u64 {
+    key_len as u64 * TICKS_PER_BYTE_READ
+}
+
+fn value_written_in_memory(size: usize) -> u64 {
+    size as u64 * TICKS_PER_BYTE_WRITTEN
+}
+
+fn value_read_from_memory(size: usize) -> u64 {
+    size as u64 * TICKS_PER_BYTE_READ
+}
+
+fn with_error(result: i64, compute_ticks: impl FnOnce() -> u64) -> u64 {
+    if result < 0 {
+        0
+    } else {
+        compute_ticks()
+    }
+}
+
+pub(crate) fn read_input(result: i32) -> u64 {
+    with_error(result.into(), || value_written_in_memory(result as usize))
+}
+
+pub(crate) fn write_output(num_bytes: usize, result: i32) -> u64 {
+    with_error(result.into(), || value_read_from_memory(num_bytes))
+}
+
+/// Ticks of `store_has`, `store_list_size`, `store_get_nth_key`,
+/// `store_value_size`.
+pub(crate) fn store_access(key_len: usize, result: i64) -> u64 {
+    with_error(result, || read_key_in_memory(key_len) + TREE_ACCESS)
+}
+
+/// Ticks of `store_delete` & `store_delete_value`.
+pub(crate) fn store_delete(key_len: usize, result: i32) -> u64 {
+    with_error(result.into(), || read_key_in_memory(key_len) + TREE_ACCESS)
+}
+
+/// Ticks of `store_copy` & `store_move`.
+pub(crate) fn store_copy(from_key_len: usize, to_key_len: usize, result: i32) -> u64 {
+    with_error(result.into(), || {
+        read_key_in_memory(from_key_len) + read_key_in_memory(to_key_len) + TREE_ACCESS
+    })
+}
+
+pub(crate) fn store_read(key_len: usize, result: i32) -> u64 {
+    with_error(result.into(), || {
+        read_key_in_memory(key_len)
+            + TREE_ACCESS
+            + value_written_in_memory(result as usize)
+    })
+}
+
+pub(crate) fn store_write(key_len: usize, num_bytes: usize, result: i32) -> u64 {
+    with_error(result.into(), || {
+        read_key_in_memory(key_len) + TREE_ACCESS + value_read_from_memory(num_bytes)
+    })
+}
+
+/// Ticks of the `reveal_*` functions, which write the revealed data to memory.
+pub(crate) fn reveal(result: i32) -> u64 {
+    with_error(result.into(), || value_written_in_memory(result as usize))
+}
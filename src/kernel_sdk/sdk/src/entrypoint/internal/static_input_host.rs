@@ -11,7 +11,7 @@
 
 use crate::core_unsafe::PREIMAGE_HASH_SIZE;
 use crate::host::{Runtime, RuntimeError, ValueType};
-use crate::storage::path::Path;
+use crate::storage::path::{OwnedPath, Path};
 use crate::types::RollupDalParameters;
 use crate::types::{Message, RollupMetadata};
 use crate::utils::inbox::{file::InboxFile, Inbox, InboxBuilder};
@@ -137,6 +137,15 @@ impl<'runtime, R: Runtime> Runtime for StaticInputHost<'runtime, R> {
         self.host.store_count_subkeys(prefix)
     }
 
+    #[inline(always)]
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError> {
+        self.host.store_get_subkey(prefix, index)
+    }
+
     #[inline(always)]
     fn store_move(
         &mut self,
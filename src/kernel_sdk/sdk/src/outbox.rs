@@ -67,7 +67,13 @@
 //! # assert_eq!(3, host.outbox_at(level).len());
 //! ```
 //!
+//! Alternatively, [`write_or_queue_message`] writes messages straight to the outbox while
+//! there is room at the current level, and only queues them once the outbox is full. The
+//! depth of the queue can be monitored with [`metrics`].
+//!
 //! [inbox message]: crate::inbox
+//! [`write_or_queue_message`]: OutboxQueue::write_or_queue_message
+//! [`metrics`]: OutboxQueue::metrics
 //! [outbox queue]: OutboxQueue
 //! [default]: OUTBOX_QUEUE
 
@@ -149,22 +155,67 @@ impl<'a, P: Path> OutboxQueue<'a, P> {
             return Err(RuntimeError::HostErr(Error::FullOutbox));
         }
 
-        let end = start.wrapping_add(len);
+        let buffer = encode_message(message)?;
+        self.push_encoded(host, start, len, &buffer)
+    }
+
+    /// Writes a message directly to the outbox, queueing it instead if the outbox is full
+    /// at the current level.
+    ///
+    /// Messages are only written directly when the queue is empty, so that they reach the
+    /// outbox in the order they were produced. Any message queued is carried over to later
+    /// levels by [`flush_queue`].
+    ///
+    /// Returns the length of the queue after the message was handled - `0` if it was written
+    /// directly to the outbox.
+    ///
+    /// # SAFETY
+    ///
+    /// The same restrictions as for [`flush_queue`] apply.
+    ///
+    /// [`flush_queue`]: Self::flush_queue
+    pub fn write_or_queue_message<Batch: AtomicBatch>(
+        &self,
+        host: &mut impl Runtime,
+        message: impl Into<OutboxMessageFull<Batch>>,
+    ) -> Result<usize, RuntimeError> {
+        let (start, len) = self.read_meta(host);
 
-        let message = message.into();
+        if len >= self.max {
+            return Err(RuntimeError::HostErr(Error::FullOutbox));
+        }
 
-        let mut buffer = Vec::with_capacity(MAX_OUTPUT_SIZE);
-        message
-            .bin_write(&mut buffer)
-            .expect("outbox message always serializable");
+        let buffer = encode_message(message)?;
 
-        if buffer.len() > MAX_OUTPUT_SIZE {
-            return Err(RuntimeError::HostErr(Error::InputOutputTooLarge));
+        if len == 0 {
+            match host.write_output(&buffer) {
+                Ok(()) => return Ok(0),
+                Err(RuntimeError::HostErr(Error::FullOutbox)) => (),
+                Err(err) => return Err(err),
+            }
         }
 
+        self.push_encoded(host, start, len, &buffer)
+    }
+
+    /// Metrics on the current state of the queue.
+    pub fn metrics(&self, host: &impl Runtime) -> OutboxQueueMetrics {
+        let (_, len) = self.read_meta(host);
+
+        OutboxQueueMetrics { len, max: self.max }
+    }
+
+    fn push_encoded(
+        &self,
+        host: &mut impl Runtime,
+        start: u32,
+        len: u32,
+        buffer: &[u8],
+    ) -> Result<usize, RuntimeError> {
+        let end = start.wrapping_add(len);
         let path = self.message_path(end);
 
-        host.store_write_all(&path, buffer.as_slice())?;
+        host.store_write_all(&path, buffer)?;
 
         let len = len.saturating_add(1);
         self.save_meta(host, start, len);
@@ -275,6 +326,39 @@ impl<'a, P: Path> OutboxQueue<'a, P> {
     }
 }
 
+/// Metrics on the depth of an [`OutboxQueue`], see [`OutboxQueue::metrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxQueueMetrics {
+    /// The number of messages waiting in the queue.
+    pub len: u32,
+    /// The maximum number of messages the queue can hold.
+    pub max: u32,
+}
+
+impl OutboxQueueMetrics {
+    /// The number of messages that can still be queued.
+    pub fn remaining(&self) -> u32 {
+        self.max.saturating_sub(self.len)
+    }
+}
+
+fn encode_message<Batch: AtomicBatch>(
+    message: impl Into<OutboxMessageFull<Batch>>,
+) -> Result<Vec<u8>, RuntimeError> {
+    let message = message.into();
+
+    let mut buffer = Vec::with_capacity(MAX_OUTPUT_SIZE);
+    message
+        .bin_write(&mut buffer)
+        .expect("outbox message always serializable");
+
+    if buffer.len() > MAX_OUTPUT_SIZE {
+        return Err(RuntimeError::HostErr(Error::InputOutputTooLarge));
+    }
+
+    Ok(buffer)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -366,6 +450,46 @@ mod test {
         assert!(matches!(host.store_has(&root), Ok(None)));
     }
 
+    #[test]
+    fn write_or_queue_carries_over_to_next_level() {
+        let mut host = MockHost::default();
+
+        for i in 0u32..150 {
+            let msg = make_outbox_message(vec![i as u8]);
+            let expected_len = i.saturating_sub(99) as usize;
+            assert_eq!(
+                Ok(expected_len),
+                OUTBOX_QUEUE.write_or_queue_message(&mut host, msg)
+            );
+        }
+
+        assert_eq!(
+            OutboxQueueMetrics {
+                len: 50,
+                max: u16::MAX as u32
+            },
+            OUTBOX_QUEUE.metrics(&host)
+        );
+
+        let level = host.run_level(|_| {});
+        assert_eq!(100, host.outbox_at(level).len());
+
+        // Messages are queued behind the ones carried over, even if the outbox has room.
+        let msg = make_outbox_message(vec![150]);
+        assert_eq!(Ok(51), OUTBOX_QUEUE.write_or_queue_message(&mut host, msg));
+
+        assert_eq!(51, OUTBOX_QUEUE.flush_queue(&mut host));
+        let level = host.run_level(|_| {});
+        let outbox = host.outbox_at(level);
+        assert_eq!(51, outbox.len());
+
+        let (_, first) = OutboxMessage::nom_read(&outbox[0]).unwrap();
+        assert_eq!(first, make_outbox_message(vec![100]).into());
+
+        assert_eq!(0, OUTBOX_QUEUE.metrics(&host).len);
+        assert!(matches!(host.store_has(&OUTBOX_QUEUE_ROOT), Ok(None)));
+    }
+
     fn make_outbox_message(msg: Vec<u8>) -> OutboxMessageTransaction<MichelsonBytes> {
         OutboxMessageTransaction {
             parameters: msg.into(),
@@ -0,0 +1,276 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Map in durable storage.
+//!
+//! Keys are hashed to fit in a path step: the entry for `key` in a map at `/m`
+//! is stored under `/m/entries/<id>`, where `<id>` is the hex-encoded blake2b
+//! hash of the encoded key. Each entry holds the encoded `key` and `value`,
+//! and its `index` in the list of entry ids kept at `/m/ids/<i>`, which is
+//! used to iterate over the map. The number of entries is stored at
+//! `/m/length`.
+
+use super::{child, delete_all, read_u64, read_value, write_u64, write_value};
+use crate::StorageError;
+use core::fmt::Write;
+use core::marker::PhantomData;
+use tezos_crypto_rs::blake2b::digest_256;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_host::path::{OwnedPath, Path};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+extern crate alloc;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A map from `K`s to `V`s in durable storage.
+///
+/// Iteration follows insertion order, except that removing an entry moves the
+/// last entry in its place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurableMap<K, V> {
+    path: OwnedPath,
+    phantom: PhantomData<(K, V)>,
+}
+
+impl<K, V> From<OwnedPath> for DurableMap<K, V> {
+    fn from(path: OwnedPath) -> Self {
+        Self {
+            path,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Identifies the entry of a key, as a path step.
+fn entry_id(key: &impl BinWriter) -> Result<String, StorageError> {
+    let mut bytes = Vec::new();
+    key.bin_write(&mut bytes)
+        .map_err(|_| StorageError::EncodingError)?;
+
+    let mut id = String::with_capacity(64);
+    for byte in digest_256(&bytes) {
+        write!(id, "{byte:02x}").expect("writing to a String can't fail");
+    }
+    Ok(id)
+}
+
+impl<K, V> DurableMap<K, V>
+where
+    K: BinWriter + for<'a> NomReader<'a>,
+    V: BinWriter + for<'a> NomReader<'a>,
+{
+    /// Map stored under `path`.
+    pub fn new(path: &impl Path) -> Self {
+        Self::from(OwnedPath::from(path))
+    }
+
+    /// Path under which the map is stored.
+    pub fn path(&self) -> &OwnedPath {
+        &self.path
+    }
+
+    fn length_path(&self) -> Result<OwnedPath, StorageError> {
+        child(&self.path, "length")
+    }
+
+    fn entry_path(&self, id: &str, field: &str) -> Result<OwnedPath, StorageError> {
+        child(&child(&child(&self.path, "entries")?, id)?, field)
+    }
+
+    fn id_path(&self, index: u64) -> Result<OwnedPath, StorageError> {
+        child(&child(&self.path, "ids")?, &format!("{index}"))
+    }
+
+    fn read_id(&self, host: &impl Runtime, index: u64) -> Result<String, StorageError> {
+        let bytes = host.store_read_all(&self.id_path(index)?)?;
+        String::from_utf8(bytes).map_err(|_| StorageError::DecodingError)
+    }
+
+    /// Number of entries in the map.
+    pub fn len(&self, host: &impl Runtime) -> Result<u64, StorageError> {
+        read_u64(host, &self.length_path()?)
+    }
+
+    /// Whether the map has no entries.
+    pub fn is_empty(&self, host: &impl Runtime) -> Result<bool, StorageError> {
+        Ok(self.len(host)? == 0)
+    }
+
+    /// Whether the map has an entry for `key`.
+    pub fn contains_key(
+        &self,
+        host: &impl Runtime,
+        key: &K,
+    ) -> Result<bool, StorageError> {
+        let path = self.entry_path(&entry_id(key)?, "value")?;
+        Ok(host.store_has(&path)?.is_some())
+    }
+
+    /// Value associated to `key`.
+    pub fn get(&self, host: &impl Runtime, key: &K) -> Result<Option<V>, StorageError> {
+        let path = self.entry_path(&entry_id(key)?, "value")?;
+        if host.store_has(&path)?.is_some() {
+            read_value(host, &path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Associate `value` to `key`, replacing any previous value.
+    pub fn insert(
+        &mut self,
+        host: &mut impl Runtime,
+        key: &K,
+        value: &V,
+    ) -> Result<(), StorageError> {
+        let id = entry_id(key)?;
+        let value_path = self.entry_path(&id, "value")?;
+
+        if host.store_has(&value_path)?.is_none() {
+            let len = self.len(host)?;
+            write_value(host, &self.entry_path(&id, "key")?, key)?;
+            write_u64(host, &self.entry_path(&id, "index")?, len)?;
+            host.store_write_all(&self.id_path(len)?, id.as_bytes())?;
+            write_u64(host, &self.length_path()?, len + 1)?;
+        }
+
+        write_value(host, &value_path, value)
+    }
+
+    /// Remove the entry for `key`, and return its value.
+    pub fn remove(
+        &mut self,
+        host: &mut impl Runtime,
+        key: &K,
+    ) -> Result<Option<V>, StorageError> {
+        let id = entry_id(key)?;
+        let value = match self.get(host, key)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        // Move the last id in place of the removed one.
+        let index = read_u64(host, &self.entry_path(&id, "index")?)?;
+        let last = self.len(host)? - 1;
+        if index != last {
+            let last_id = self.read_id(host, last)?;
+            host.store_write_all(&self.id_path(index)?, last_id.as_bytes())?;
+            write_u64(host, &self.entry_path(&last_id, "index")?, index)?;
+        }
+        host.store_delete(&self.id_path(last)?)?;
+        host.store_delete(&child(&child(&self.path, "entries")?, &id)?)?;
+
+        if last == 0 {
+            self.clear(host)?;
+        } else {
+            write_u64(host, &self.length_path()?, last)?;
+        }
+        Ok(Some(value))
+    }
+
+    /// Remove all entries of the map.
+    pub fn clear(&mut self, host: &mut impl Runtime) -> Result<(), StorageError> {
+        delete_all(host, &self.path)
+    }
+
+    /// Iterate over the entries of the map.
+    pub fn iter<'a, Host: Runtime>(
+        &'a self,
+        host: &'a Host,
+    ) -> Result<impl Iterator<Item = Result<(K, V), StorageError>> + 'a, StorageError>
+    {
+        let len = self.len(host)?;
+        Ok((0..len).map(move |index| {
+            let id = self.read_id(host, index)?;
+            let key = read_value(host, &self.entry_path(&id, "key")?)?;
+            let value = read_value(host, &self.entry_path(&id, "value")?)?;
+            Ok((key, value))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::tests::{element, Element};
+    use crate::storage::Storage;
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    const MAP_PATH: RefPath = RefPath::assert_from(b"/map");
+
+    #[test]
+    fn insert_get_remove() {
+        let mut host = MockHost::default();
+        let mut map = DurableMap::<Element, Element>::new(&MAP_PATH);
+
+        for i in 0..4 {
+            map.insert(&mut host, &element(i), &element(i * 10))
+                .unwrap();
+        }
+        map.insert(&mut host, &element(1), &element(11)).unwrap();
+
+        assert_eq!(map.len(&host), Ok(4));
+        assert_eq!(map.get(&host, &element(1)), Ok(Some(element(11))));
+        assert_eq!(map.get(&host, &element(4)), Ok(None));
+        assert_eq!(map.contains_key(&host, &element(2)), Ok(true));
+
+        assert_eq!(map.remove(&mut host, &element(1)), Ok(Some(element(11))));
+        assert_eq!(map.remove(&mut host, &element(1)), Ok(None));
+        assert_eq!(map.len(&host), Ok(3));
+
+        // The last entry takes the place of the removed one.
+        let entries: Result<Vec<_>, _> = map.iter(&host).unwrap().collect();
+        assert_eq!(
+            entries,
+            Ok(vec![
+                (element(0), element(0)),
+                (element(3), element(30)),
+                (element(2), element(20)),
+            ])
+        );
+
+        for i in [0, 2, 3] {
+            map.remove(&mut host, &element(i)).unwrap();
+        }
+        assert_eq!(map.is_empty(&host), Ok(true));
+        assert_eq!(host.store_has(&MAP_PATH), Ok(None));
+    }
+
+    #[test]
+    fn nested_transactions() {
+        let mut host = MockHost::default();
+        let mut storage = Storage::<DurableMap<Element, Element>>::init(
+            &RefPath::assert_from(b"/maps"),
+        )
+        .unwrap();
+        let id = RefPath::assert_from(b"/balances");
+
+        let mut map = storage.get_or_create(&host, &id).unwrap();
+        map.insert(&mut host, &element(0), &element(0)).unwrap();
+
+        storage.begin_transaction(&mut host).unwrap();
+        let mut map = storage.get_or_create(&host, &id).unwrap();
+        map.insert(&mut host, &element(1), &element(1)).unwrap();
+
+        storage.begin_transaction(&mut host).unwrap();
+        let mut map = storage.get_or_create(&host, &id).unwrap();
+        map.remove(&mut host, &element(0)).unwrap();
+        map.insert(&mut host, &element(2), &element(2)).unwrap();
+        assert_eq!(map.len(&host), Ok(2));
+        storage.rollback_transaction(&mut host).unwrap();
+
+        storage.commit_transaction(&mut host).unwrap();
+
+        let map = storage.get_or_create(&host, &id).unwrap();
+        let entries: Result<Vec<_>, _> = map.iter(&host).unwrap().collect();
+        assert_eq!(
+            entries,
+            Ok(vec![(element(0), element(0)), (element(1), element(1))])
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Typed collections in durable storage.
+//!
+//! [`DurableVec`], [`DurableQueue`] and [`DurableMap`] keep all their contents,
+//! including their length, under a single path of the durable storage. Elements
+//! are encoded with [`BinWriter`] and decoded with [`NomReader`].
+//!
+//! Each collection implements `From<OwnedPath>`, so they can also be used as
+//! the objects of a [`Storage`]. The collection then lives in the current
+//! transaction layer, and rolling back a transaction restores it as a whole.
+//!
+//! ```
+//! use tezos_data_encoding::enc::BinWriter;
+//! use tezos_data_encoding::encoding::HasEncoding;
+//! use tezos_data_encoding::nom::NomReader;
+//! use tezos_smart_rollup_host::path::RefPath;
+//! use tezos_smart_rollup_mock::MockHost;
+//! use tezos_smart_rollup_storage::collections::DurableVec;
+//! use tezos_smart_rollup_storage::storage::Storage;
+//!
+//! #[derive(Debug, PartialEq, HasEncoding, NomReader, BinWriter)]
+//! struct Deposit {
+//!     amount: i64,
+//! }
+//!
+//! let mut host = MockHost::default();
+//! let mut storage = Storage::<DurableVec<Deposit>>::init(&RefPath::assert_from(b"/deposits"))
+//!     .unwrap();
+//! let id = RefPath::assert_from(b"/alice");
+//!
+//! let mut deposits = storage.get_or_create(&host, &id).unwrap();
+//! deposits.push(&mut host, &Deposit { amount: 10 }).unwrap();
+//!
+//! storage.begin_transaction(&mut host).unwrap();
+//! let mut deposits = storage.get_or_create(&host, &id).unwrap();
+//! deposits.push(&mut host, &Deposit { amount: 20 }).unwrap();
+//! assert_eq!(deposits.len(&host).unwrap(), 2);
+//! storage.rollback_transaction(&mut host).unwrap();
+//!
+//! let deposits = storage.get_or_create(&host, &id).unwrap();
+//! assert_eq!(deposits.len(&host).unwrap(), 1);
+//! assert_eq!(deposits.get(&host, 0).unwrap(), Some(Deposit { amount: 10 }));
+//! ```
+//!
+//! [`Storage`]: crate::storage::Storage
+
+mod map;
+mod queue;
+mod vec;
+
+pub use map::DurableMap;
+pub use queue::DurableQueue;
+pub use vec::DurableVec;
+
+use crate::StorageError;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_host::path::{concat, OwnedPath, Path};
+use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError};
+
+extern crate alloc;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// Path of the child `name` of `path`, where `name` is a single path step.
+fn child(path: &impl Path, name: &str) -> Result<OwnedPath, StorageError> {
+    let step = OwnedPath::try_from(format!("/{name}"))?;
+    Ok(concat(path, &step)?)
+}
+
+/// Read a counter, which defaults to zero when it was never written.
+fn read_u64(host: &impl Runtime, path: &impl Path) -> Result<u64, StorageError> {
+    if host.store_has(path)?.is_none() {
+        return Ok(0);
+    }
+
+    let mut bytes = [0_u8; 8];
+    match host.store_read_slice(path, 0, &mut bytes)? {
+        8 => Ok(u64::from_le_bytes(bytes)),
+        _ => Err(StorageError::DecodingError),
+    }
+}
+
+fn write_u64(
+    host: &mut impl Runtime,
+    path: &impl Path,
+    value: u64,
+) -> Result<(), StorageError> {
+    Ok(host.store_write_all(path, &value.to_le_bytes())?)
+}
+
+fn read_value<T: for<'a> NomReader<'a>>(
+    host: &impl Runtime,
+    path: &impl Path,
+) -> Result<T, StorageError> {
+    let bytes = host.store_read_all(path)?;
+    match T::nom_read(&bytes) {
+        Ok(([], value)) => Ok(value),
+        _ => Err(StorageError::DecodingError),
+    }
+}
+
+fn write_value<T: BinWriter>(
+    host: &mut impl Runtime,
+    path: &impl Path,
+    value: &T,
+) -> Result<(), StorageError> {
+    let mut bytes = Vec::new();
+    value
+        .bin_write(&mut bytes)
+        .map_err(|_| StorageError::EncodingError)?;
+    Ok(host.store_write_all(path, &bytes)?)
+}
+
+/// Delete everything under `path`, if there is anything.
+fn delete_all(host: &mut impl Runtime, path: &impl Path) -> Result<(), StorageError> {
+    match host.store_delete(path) {
+        Ok(()) | Err(RuntimeError::PathNotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use tezos_data_encoding::enc::BinWriter;
+    use tezos_data_encoding::encoding::HasEncoding;
+    use tezos_data_encoding::nom::NomReader;
+
+    #[derive(Debug, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+    pub(crate) struct Element {
+        value: i64,
+    }
+
+    pub(crate) fn element(value: i64) -> Element {
+        Element { value }
+    }
+}
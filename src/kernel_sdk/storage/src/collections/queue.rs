@@ -0,0 +1,172 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! FIFO queue in durable storage.
+//!
+//! A queue at `/q` stores the index of its first element at `/q/head`, and the
+//! index after its last element at `/q/tail`. The element at index `i` is
+//! stored at `/q/elements/<i>`. Indices only ever grow, so pushing and popping
+//! never moves elements around.
+
+use super::{child, delete_all, read_u64, read_value, write_u64, write_value};
+use crate::StorageError;
+use core::marker::PhantomData;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_host::path::{OwnedPath, Path};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+extern crate alloc;
+use alloc::format;
+
+/// A first-in first-out queue of `T`s in durable storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurableQueue<T> {
+    path: OwnedPath,
+    phantom: PhantomData<T>,
+}
+
+impl<T> From<OwnedPath> for DurableQueue<T> {
+    fn from(path: OwnedPath) -> Self {
+        Self {
+            path,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: BinWriter + for<'a> NomReader<'a>> DurableQueue<T> {
+    /// Queue stored under `path`.
+    pub fn new(path: &impl Path) -> Self {
+        Self::from(OwnedPath::from(path))
+    }
+
+    /// Path under which the queue is stored.
+    pub fn path(&self) -> &OwnedPath {
+        &self.path
+    }
+
+    fn head_path(&self) -> Result<OwnedPath, StorageError> {
+        child(&self.path, "head")
+    }
+
+    fn tail_path(&self) -> Result<OwnedPath, StorageError> {
+        child(&self.path, "tail")
+    }
+
+    fn element_path(&self, index: u64) -> Result<OwnedPath, StorageError> {
+        child(&child(&self.path, "elements")?, &format!("{index}"))
+    }
+
+    fn bounds(&self, host: &impl Runtime) -> Result<(u64, u64), StorageError> {
+        Ok((
+            read_u64(host, &self.head_path()?)?,
+            read_u64(host, &self.tail_path()?)?,
+        ))
+    }
+
+    /// Number of elements in the queue.
+    pub fn len(&self, host: &impl Runtime) -> Result<u64, StorageError> {
+        let (head, tail) = self.bounds(host)?;
+        Ok(tail - head)
+    }
+
+    /// Whether the queue has no elements.
+    pub fn is_empty(&self, host: &impl Runtime) -> Result<bool, StorageError> {
+        Ok(self.len(host)? == 0)
+    }
+
+    /// Add an element at the back of the queue.
+    pub fn push_back(
+        &mut self,
+        host: &mut impl Runtime,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let tail = read_u64(host, &self.tail_path()?)?;
+        write_value(host, &self.element_path(tail)?, value)?;
+        write_u64(host, &self.tail_path()?, tail + 1)
+    }
+
+    /// The element at the front of the queue, without removing it.
+    pub fn front(&self, host: &impl Runtime) -> Result<Option<T>, StorageError> {
+        let (head, tail) = self.bounds(host)?;
+        if head < tail {
+            read_value(host, &self.element_path(head)?).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Remove the element at the front of the queue, and return it.
+    pub fn pop_front(
+        &mut self,
+        host: &mut impl Runtime,
+    ) -> Result<Option<T>, StorageError> {
+        let (head, tail) = self.bounds(host)?;
+        if head == tail {
+            return Ok(None);
+        }
+
+        let path = self.element_path(head)?;
+        let value = read_value(host, &path)?;
+        host.store_delete(&path)?;
+
+        if head + 1 == tail {
+            // Reset the indices once the queue is drained.
+            self.clear(host)?;
+        } else {
+            write_u64(host, &self.head_path()?, head + 1)?;
+        }
+        Ok(Some(value))
+    }
+
+    /// Remove all elements of the queue.
+    pub fn clear(&mut self, host: &mut impl Runtime) -> Result<(), StorageError> {
+        delete_all(host, &self.path)
+    }
+
+    /// Iterate over the elements of the queue, from the front.
+    pub fn iter<'a, Host: Runtime>(
+        &'a self,
+        host: &'a Host,
+    ) -> Result<impl Iterator<Item = Result<T, StorageError>> + 'a, StorageError> {
+        let (head, tail) = self.bounds(host)?;
+        Ok((head..tail).map(move |index| read_value(host, &self.element_path(index)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::tests::{element, Element};
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    const QUEUE_PATH: RefPath = RefPath::assert_from(b"/queue");
+
+    #[test]
+    fn fifo_order() {
+        let mut host = MockHost::default();
+        let mut queue = DurableQueue::<Element>::new(&QUEUE_PATH);
+
+        assert_eq!(queue.pop_front(&mut host), Ok(None));
+        assert_eq!(queue.front(&host), Ok(None));
+
+        for i in 0..4 {
+            queue.push_back(&mut host, &element(i)).unwrap();
+        }
+        assert_eq!(queue.pop_front(&mut host), Ok(Some(element(0))));
+        assert_eq!(queue.pop_front(&mut host), Ok(Some(element(1))));
+        queue.push_back(&mut host, &element(4)).unwrap();
+
+        assert_eq!(queue.len(&host), Ok(3));
+        assert_eq!(queue.front(&host), Ok(Some(element(2))));
+        let elements: Result<Vec<_>, _> = queue.iter(&host).unwrap().collect();
+        assert_eq!(elements, Ok(vec![element(2), element(3), element(4)]));
+
+        while queue.pop_front(&mut host).unwrap().is_some() {}
+        assert_eq!(queue.is_empty(&host), Ok(true));
+        assert_eq!(host.store_has(&QUEUE_PATH), Ok(None));
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Vector in durable storage.
+//!
+//! The length of a vector at `/v` is stored at `/v/length`, and its element at
+//! index `i` at `/v/elements/<i>`.
+
+use super::{child, delete_all, read_u64, read_value, write_u64, write_value};
+use crate::StorageError;
+use core::marker::PhantomData;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::nom::NomReader;
+use tezos_smart_rollup_host::path::{OwnedPath, Path};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+extern crate alloc;
+use alloc::format;
+
+/// A growable array of `T`s in durable storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurableVec<T> {
+    path: OwnedPath,
+    phantom: PhantomData<T>,
+}
+
+impl<T> From<OwnedPath> for DurableVec<T> {
+    fn from(path: OwnedPath) -> Self {
+        Self {
+            path,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: BinWriter + for<'a> NomReader<'a>> DurableVec<T> {
+    /// Vector stored under `path`.
+    pub fn new(path: &impl Path) -> Self {
+        Self::from(OwnedPath::from(path))
+    }
+
+    /// Path under which the vector is stored.
+    pub fn path(&self) -> &OwnedPath {
+        &self.path
+    }
+
+    fn length_path(&self) -> Result<OwnedPath, StorageError> {
+        child(&self.path, "length")
+    }
+
+    fn element_path(&self, index: u64) -> Result<OwnedPath, StorageError> {
+        child(&child(&self.path, "elements")?, &format!("{index}"))
+    }
+
+    /// Number of elements in the vector.
+    pub fn len(&self, host: &impl Runtime) -> Result<u64, StorageError> {
+        read_u64(host, &self.length_path()?)
+    }
+
+    /// Whether the vector has no elements.
+    pub fn is_empty(&self, host: &impl Runtime) -> Result<bool, StorageError> {
+        Ok(self.len(host)? == 0)
+    }
+
+    /// Element at `index`, or `None` if `index` is out of bounds.
+    pub fn get(
+        &self,
+        host: &impl Runtime,
+        index: u64,
+    ) -> Result<Option<T>, StorageError> {
+        if index < self.len(host)? {
+            read_value(host, &self.element_path(index)?).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Replace the element at `index`.
+    pub fn set(
+        &mut self,
+        host: &mut impl Runtime,
+        index: u64,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        if index < self.len(host)? {
+            write_value(host, &self.element_path(index)?, value)
+        } else {
+            Err(StorageError::IndexOutOfBounds)
+        }
+    }
+
+    /// Append an element at the end of the vector.
+    pub fn push(
+        &mut self,
+        host: &mut impl Runtime,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let len = self.len(host)?;
+        write_value(host, &self.element_path(len)?, value)?;
+        write_u64(host, &self.length_path()?, len + 1)
+    }
+
+    /// Remove the last element of the vector, and return it.
+    pub fn pop(&mut self, host: &mut impl Runtime) -> Result<Option<T>, StorageError> {
+        let len = self.len(host)?;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let path = self.element_path(len - 1)?;
+        let value = read_value(host, &path)?;
+        host.store_delete(&path)?;
+        write_u64(host, &self.length_path()?, len - 1)?;
+        Ok(Some(value))
+    }
+
+    /// Remove all elements of the vector.
+    pub fn clear(&mut self, host: &mut impl Runtime) -> Result<(), StorageError> {
+        delete_all(host, &self.path)
+    }
+
+    /// Iterate over the elements of the vector, from the first one.
+    pub fn iter<'a, Host: Runtime>(
+        &'a self,
+        host: &'a Host,
+    ) -> Result<impl Iterator<Item = Result<T, StorageError>> + 'a, StorageError> {
+        let len = self.len(host)?;
+        Ok((0..len).map(move |index| read_value(host, &self.element_path(index)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::tests::{element, Element};
+    use tezos_smart_rollup_host::path::RefPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    const VEC_PATH: RefPath = RefPath::assert_from(b"/vec");
+
+    #[test]
+    fn push_pop() {
+        let mut host = MockHost::default();
+        let mut vec = DurableVec::<Element>::new(&VEC_PATH);
+
+        assert_eq!(vec.len(&host), Ok(0));
+        assert_eq!(vec.pop(&mut host), Ok(None));
+
+        for i in 0..5 {
+            vec.push(&mut host, &element(i)).unwrap();
+        }
+        assert_eq!(vec.len(&host), Ok(5));
+        assert_eq!(vec.get(&host, 3), Ok(Some(element(3))));
+        assert_eq!(vec.get(&host, 5), Ok(None));
+
+        assert_eq!(vec.pop(&mut host), Ok(Some(element(4))));
+        assert_eq!(vec.len(&host), Ok(4));
+
+        let elements: Result<Vec<_>, _> = vec.iter(&host).unwrap().collect();
+        assert_eq!(elements, Ok((0..4).map(element).collect()));
+    }
+
+    #[test]
+    fn set_and_clear() {
+        let mut host = MockHost::default();
+        let mut vec = DurableVec::<Element>::new(&VEC_PATH);
+
+        vec.push(&mut host, &element(1)).unwrap();
+        vec.set(&mut host, 0, &element(10)).unwrap();
+        assert_eq!(vec.get(&host, 0), Ok(Some(element(10))));
+        assert_eq!(
+            vec.set(&mut host, 1, &element(10)),
+            Err(StorageError::IndexOutOfBounds)
+        );
+
+        vec.clear(&mut host).unwrap();
+        assert_eq!(vec.is_empty(&host), Ok(true));
+        vec.clear(&mut host).unwrap();
+    }
+}
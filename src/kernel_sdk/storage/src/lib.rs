@@ -41,6 +41,15 @@ pub enum StorageError {
     /// happen when doing some transaction operation.
     #[error("Runtrime error")]
     RuntimeError(host::runtime::RuntimeError),
+    /// A value could not be encoded before being written to durable storage.
+    #[error("Failed to encode value")]
+    EncodingError,
+    /// A value read from durable storage could not be decoded.
+    #[error("Failed to decode value")]
+    DecodingError,
+    /// Tried to access an element past the end of a collection.
+    #[error("Index out of bounds")]
+    IndexOutOfBounds,
 }
 
 impl From<host::path::PathError> for StorageError {
@@ -55,5 +64,6 @@ impl From<host::runtime::RuntimeError> for StorageError {
     }
 }
 
+pub mod collections;
 mod layer;
 pub mod storage;
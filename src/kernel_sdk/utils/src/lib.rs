@@ -8,3 +8,4 @@
 pub mod console;
 pub mod inbox;
 pub mod native_cli;
+pub mod replay;
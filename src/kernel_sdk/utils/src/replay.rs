@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Recording of the host calls made by a kernel, and deterministic replay.
+//!
+//! [`RecordingRuntime`] wraps a [`Runtime`], and records every host call made
+//! through it - along with its result - to a [`HostCallLog`]. When running in
+//! a rollup node, the calls can instead be written to the kernel debug log, and
+//! recovered from it with [`HostCallLog::from_debug_log`].
+//!
+//! [`ReplayRuntime`] feeds a [`HostCallLog`] back to a kernel running under a
+//! [`MockHost`]: the results of host calls are taken from the log, and the
+//! first call which differs from the recorded one is reported as a
+//! [`Divergence`].
+//!
+//! [`Runtime`]: tezos_smart_rollup_host::runtime::Runtime
+//! [`MockHost`]: tezos_smart_rollup_mock::MockHost
+
+pub mod log;
+mod recorder;
+mod replayer;
+
+pub use log::{HostCall, HostCallLog};
+pub use recorder::RecordingRuntime;
+pub use replayer::{Divergence, ReplayRuntime};
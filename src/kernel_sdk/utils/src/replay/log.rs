@@ -0,0 +1,446 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Host call logs are stored as JSON lines, one host call per line.
+
+use crate::inbox::file::{InboxFile, Message as InboxMessage};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+use tezos_smart_rollup_host::{
+    path::Path as StoragePath,
+    runtime::{RuntimeError, ValueType},
+};
+
+/// Prefix of the kernel debug log lines containing a host call.
+pub const DEBUG_LOG_PREFIX: &str = "[host-call] ";
+
+/// Bytes, hex-encoded in the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bytes(#[serde(with = "hex::serde")] pub Vec<u8>);
+
+/// Recorded [`RuntimeError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedError {
+    /// See [`RuntimeError::PathNotFound`].
+    PathNotFound,
+    /// See [`RuntimeError::StoreListIndexOutOfBounds`].
+    StoreListIndexOutOfBounds,
+    /// See [`RuntimeError::DecodingError`].
+    DecodingError,
+    /// Error code of a [`RuntimeError::HostErr`].
+    HostErr(i32),
+}
+
+impl From<RuntimeError> for RecordedError {
+    fn from(error: RuntimeError) -> Self {
+        match error {
+            RuntimeError::PathNotFound => Self::PathNotFound,
+            RuntimeError::StoreListIndexOutOfBounds => Self::StoreListIndexOutOfBounds,
+            RuntimeError::DecodingError => Self::DecodingError,
+            RuntimeError::HostErr(error) => Self::HostErr(error.code()),
+        }
+    }
+}
+
+impl From<RecordedError> for RuntimeError {
+    fn from(error: RecordedError) -> Self {
+        match error {
+            RecordedError::PathNotFound => Self::PathNotFound,
+            RecordedError::StoreListIndexOutOfBounds => Self::StoreListIndexOutOfBounds,
+            RecordedError::DecodingError => Self::DecodingError,
+            RecordedError::HostErr(code) => Self::HostErr(code.into()),
+        }
+    }
+}
+
+/// Recorded [`ValueType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedValueType {
+    /// See [`ValueType::Value`].
+    Value,
+    /// See [`ValueType::Subtree`].
+    Subtree,
+    /// See [`ValueType::ValueWithSubtree`].
+    ValueWithSubtree,
+}
+
+impl From<ValueType> for RecordedValueType {
+    fn from(value_type: ValueType) -> Self {
+        match value_type {
+            ValueType::Value => Self::Value,
+            ValueType::Subtree => Self::Subtree,
+            ValueType::ValueWithSubtree => Self::ValueWithSubtree,
+        }
+    }
+}
+
+impl From<RecordedValueType> for ValueType {
+    fn from(value_type: RecordedValueType) -> Self {
+        match value_type {
+            RecordedValueType::Value => Self::Value,
+            RecordedValueType::Subtree => Self::Subtree,
+            RecordedValueType::ValueWithSubtree => Self::ValueWithSubtree,
+        }
+    }
+}
+
+/// Recorded inbox message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    /// Inbox level of the message.
+    pub level: u32,
+    /// Index of the message in the inbox level.
+    pub id: u32,
+    /// Contents of the message.
+    pub payload: Bytes,
+}
+
+/// Result of a recorded host call.
+pub type Outcome<T> = Result<T, RecordedError>;
+
+/// A host call, made through one of the methods of [`Runtime`], with its
+/// arguments and result.
+///
+/// Debug messages are not recorded.
+///
+/// [`Runtime`]: tezos_smart_rollup_host::runtime::Runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum HostCall {
+    WriteOutput {
+        output: Bytes,
+        result: Outcome<()>,
+    },
+    ReadInput {
+        result: Outcome<Option<RecordedMessage>>,
+    },
+    StoreHas {
+        path: String,
+        result: Outcome<Option<RecordedValueType>>,
+    },
+    /// Made by both `store_read` and `store_read_slice`.
+    StoreRead {
+        path: String,
+        offset: usize,
+        max_bytes: usize,
+        result: Outcome<Bytes>,
+    },
+    StoreReadAll {
+        path: String,
+        result: Outcome<Bytes>,
+    },
+    StoreWrite {
+        path: String,
+        offset: usize,
+        data: Bytes,
+        result: Outcome<()>,
+    },
+    StoreWriteAll {
+        path: String,
+        data: Bytes,
+        result: Outcome<()>,
+    },
+    StoreDelete {
+        path: String,
+        result: Outcome<()>,
+    },
+    StoreDeleteValue {
+        path: String,
+        result: Outcome<()>,
+    },
+    StoreCountSubkeys {
+        path: String,
+        result: Outcome<u64>,
+    },
+    StoreGetSubkey {
+        path: String,
+        index: u64,
+        result: Outcome<Option<String>>,
+    },
+    StoreMove {
+        from: String,
+        to: String,
+        result: Outcome<()>,
+    },
+    StoreCopy {
+        from: String,
+        to: String,
+        result: Outcome<()>,
+    },
+    StoreValueSize {
+        path: String,
+        result: Outcome<usize>,
+    },
+    RevealPreimage {
+        hash: Bytes,
+        max_bytes: usize,
+        result: Outcome<Bytes>,
+    },
+    RevealDalPage {
+        published_level: i32,
+        slot_index: u8,
+        page_index: i16,
+        max_bytes: usize,
+        result: Outcome<Bytes>,
+    },
+    RevealDalParameters {
+        result: Bytes,
+    },
+    RevealMetadata {
+        result: Bytes,
+    },
+    MarkForReboot {
+        result: Outcome<()>,
+    },
+    LastRunAborted {
+        result: Outcome<bool>,
+    },
+    UpgradeFailed {
+        result: Outcome<bool>,
+    },
+    RestartForced {
+        result: Outcome<bool>,
+    },
+    RebootLeft {
+        result: Outcome<u32>,
+    },
+    RuntimeVersion {
+        result: Outcome<String>,
+    },
+}
+
+impl HostCall {
+    /// Line of the kernel debug log containing this call.
+    pub fn to_debug_line(&self) -> String {
+        let json = serde_json::to_string(self).expect("Host calls can be serialised");
+        format!("{DEBUG_LOG_PREFIX}{json}\n")
+    }
+}
+
+/// The host calls made by a kernel, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostCallLog(pub Vec<HostCall>);
+
+impl HostCallLog {
+    /// Load the log from a file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::read(BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Write the log to a file.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Read a log, one host call per line.
+    pub fn read(reader: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        let mut calls = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                calls.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self(calls))
+    }
+
+    /// Write the log, one host call per line.
+    pub fn write(&self, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+        for call in self.0.iter() {
+            serde_json::to_writer(&mut writer, call)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Extract the host calls from a kernel debug log, such as the one written
+    /// by a rollup node, ignoring all other debug messages.
+    pub fn from_debug_log(reader: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        let mut calls = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if let Some(start) = line.find(DEBUG_LOG_PREFIX) {
+                let json = &line[start + DEBUG_LOG_PREFIX.len()..];
+                calls.push(serde_json::from_str(json)?);
+            }
+        }
+        Ok(Self(calls))
+    }
+
+    /// The inbox messages read by the kernel, grouped by level.
+    ///
+    /// The *start of level*, *info per level* and *end of level* messages are
+    /// left out, as they are added back when building an inbox from the file.
+    /// Levels at which no message was read are empty.
+    pub fn inbox(&self) -> InboxFile {
+        let mut levels: Vec<Vec<InboxMessage>> = Vec::new();
+        let mut first_level = None;
+
+        for call in self.0.iter() {
+            let HostCall::ReadInput {
+                result: Ok(Some(message)),
+            } = call
+            else {
+                continue;
+            };
+
+            let payload = &message.payload.0;
+            if matches!(payload.as_slice(), [0, 1..=3, ..]) {
+                continue;
+            }
+
+            let first_level = *first_level.get_or_insert(message.level);
+            let index = message.level.saturating_sub(first_level) as usize;
+            if levels.len() <= index {
+                levels.resize_with(index + 1, Vec::new);
+            }
+            levels[index].push(InboxMessage::Raw(payload.clone()));
+        }
+
+        InboxFile(levels)
+    }
+}
+
+pub(crate) fn path_string(path: &impl StoragePath) -> String {
+    String::from_utf8_lossy(path.as_bytes()).into_owned()
+}
+
+/// Record the result of a host call, converted with `f`.
+pub(crate) fn record<T, U>(
+    result: &Result<T, RuntimeError>,
+    f: impl FnOnce(&T) -> U,
+) -> Outcome<U> {
+    match result {
+        Ok(value) => Ok(f(value)),
+        Err(error) => Err((*error).into()),
+    }
+}
+
+/// Recover the result of a host call from the log, converted with `f`.
+pub(crate) fn replay<T, U>(
+    outcome: &Outcome<U>,
+    f: impl FnOnce(&U) -> Result<T, RuntimeError>,
+) -> Result<T, RuntimeError> {
+    match outcome {
+        Ok(value) => f(value),
+        Err(error) => Err((*error).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_input(level: u32, id: u32, payload: &[u8]) -> HostCall {
+        HostCall::ReadInput {
+            result: Ok(Some(RecordedMessage {
+                level,
+                id,
+                payload: Bytes(payload.to_vec()),
+            })),
+        }
+    }
+
+    fn raw_levels(inbox: InboxFile) -> Vec<Vec<Vec<u8>>> {
+        inbox
+            .0
+            .into_iter()
+            .map(|level| {
+                level
+                    .into_iter()
+                    .map(|message| match message {
+                        InboxMessage::Raw(bytes) => bytes,
+                        InboxMessage::External { .. } => panic!("Expected a raw message"),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_debug_log_ignores_other_output() {
+        let calls = vec![
+            HostCall::StoreHas {
+                path: "/counter".to_owned(),
+                result: Ok(None),
+            },
+            read_input(3, 0, &[0, 1]),
+            HostCall::StoreWriteAll {
+                path: "/counter".to_owned(),
+                data: Bytes(vec![1]),
+                result: Err(RecordedError::HostErr(-13)),
+            },
+        ];
+
+        let mut debug_log = String::from("Kernel started\n");
+        debug_log.push_str(&calls[0].to_debug_line());
+        debug_log.push_str("Processing level 3\n\n");
+        debug_log.push_str(&calls[1].to_debug_line());
+        // Prefixes added by whatever collected the log are skipped.
+        debug_log.push_str("2024-05-07T12:00:00Z ");
+        debug_log.push_str(&calls[2].to_debug_line());
+        debug_log.push_str("Kernel done");
+
+        let log = HostCallLog::from_debug_log(debug_log.as_bytes()).unwrap();
+        assert_eq!(log, HostCallLog(calls));
+
+        let invalid = format!("{DEBUG_LOG_PREFIX}{{\"call\":\"unknown\"}}\n");
+        assert!(HostCallLog::from_debug_log(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn inbox_skips_internal_messages() {
+        let start_of_level = [0, 1];
+        let info_per_level = [0, 3, 7, 7, 7];
+        let end_of_level = [0, 2];
+        let transfer = [0, 0, 42];
+
+        let log = HostCallLog(vec![
+            read_input(5, 0, &start_of_level),
+            read_input(5, 1, &info_per_level),
+            read_input(5, 2, &[1, 0xaa]),
+            HostCall::StoreHas {
+                path: "/counter".to_owned(),
+                result: Ok(None),
+            },
+            read_input(5, 3, &end_of_level),
+            HostCall::ReadInput { result: Ok(None) },
+            // No message other than the internal ones at level 6.
+            read_input(6, 0, &start_of_level),
+            read_input(6, 1, &info_per_level),
+            read_input(6, 2, &end_of_level),
+            HostCall::ReadInput {
+                result: Err(RecordedError::DecodingError),
+            },
+            // Level 7 isn't read at all.
+            read_input(8, 0, &start_of_level),
+            read_input(8, 1, &info_per_level),
+            read_input(8, 2, &transfer),
+            read_input(8, 3, &[1, 0xbb]),
+            read_input(8, 4, &[1, 0xcc]),
+            read_input(8, 5, &end_of_level),
+        ]);
+
+        assert_eq!(
+            raw_levels(log.inbox()),
+            vec![
+                vec![vec![1, 0xaa]],
+                vec![],
+                vec![],
+                vec![transfer.to_vec(), vec![1, 0xbb], vec![1, 0xcc]],
+            ]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! [`Runtime`] wrapper recording host calls.
+
+use super::log::{path_string, record, Bytes, HostCall, HostCallLog, RecordedMessage};
+use std::cell::RefCell;
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
+use tezos_smart_rollup_host::{
+    dal_parameters::{RollupDalParameters, DAL_PARAMETERS_SIZE},
+    input::Message,
+    metadata::{RollupMetadata, METADATA_SIZE},
+    path::{OwnedPath, Path},
+    runtime::{Runtime, RuntimeError, ValueType},
+};
+
+/// Wrapper for a [`Runtime`] that records every host call made through it.
+pub struct RecordingRuntime<'a, Host> {
+    host: &'a mut Host,
+    log: RefCell<HostCallLog>,
+    to_debug_log: bool,
+}
+
+impl<'a, Host: Runtime> RecordingRuntime<'a, Host> {
+    /// Record the host calls made through `host` in memory.
+    pub fn new(host: &'a mut Host) -> Self {
+        Self {
+            host,
+            log: RefCell::new(HostCallLog::default()),
+            to_debug_log: false,
+        }
+    }
+
+    /// Write the host calls made through `host` to its debug log, as soon as
+    /// they are made. They can be recovered with
+    /// [`HostCallLog::from_debug_log`].
+    pub fn with_debug_log(host: &'a mut Host) -> Self {
+        Self {
+            to_debug_log: true,
+            ..Self::new(host)
+        }
+    }
+
+    /// The host calls recorded in memory so far.
+    pub fn into_log(self) -> HostCallLog {
+        self.log.into_inner()
+    }
+
+    fn record(&self, call: HostCall) {
+        if self.to_debug_log {
+            self.host.write_debug(&call.to_debug_line());
+        } else {
+            self.log.borrow_mut().0.push(call);
+        }
+    }
+}
+
+impl<'a, Host: Runtime> Runtime for RecordingRuntime<'a, Host> {
+    fn write_output(&mut self, from: &[u8]) -> Result<(), RuntimeError> {
+        let result = self.host.write_output(from);
+        self.record(HostCall::WriteOutput {
+            output: Bytes(from.to_vec()),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn write_debug(&self, msg: &str) {
+        self.host.write_debug(msg)
+    }
+
+    fn read_input(&mut self) -> Result<Option<Message>, RuntimeError> {
+        let result = self.host.read_input();
+        self.record(HostCall::ReadInput {
+            result: record(&result, |message| {
+                message.as_ref().map(|message| RecordedMessage {
+                    level: message.level,
+                    id: message.id,
+                    payload: Bytes(message.as_ref().to_vec()),
+                })
+            }),
+        });
+        result
+    }
+
+    fn store_has<T: Path>(&self, path: &T) -> Result<Option<ValueType>, RuntimeError> {
+        let result = self.host.store_has(path);
+        self.record(HostCall::StoreHas {
+            path: path_string(path),
+            result: record(&result, |value_type| value_type.map(Into::into)),
+        });
+        result
+    }
+
+    fn store_read<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        let result = self.host.store_read(path, from_offset, max_bytes);
+        self.record(HostCall::StoreRead {
+            path: path_string(path),
+            offset: from_offset,
+            max_bytes,
+            result: record(&result, |bytes| Bytes(bytes.clone())),
+        });
+        result
+    }
+
+    fn store_read_slice<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let result = self.host.store_read_slice(path, from_offset, buffer);
+        self.record(HostCall::StoreRead {
+            path: path_string(path),
+            offset: from_offset,
+            max_bytes: buffer.len(),
+            result: record(&result, |size| Bytes(buffer[..*size].to_vec())),
+        });
+        result
+    }
+
+    fn store_read_all(&self, path: &impl Path) -> Result<Vec<u8>, RuntimeError> {
+        let result = self.host.store_read_all(path);
+        self.record(HostCall::StoreReadAll {
+            path: path_string(path),
+            result: record(&result, |bytes| Bytes(bytes.clone())),
+        });
+        result
+    }
+
+    fn store_write<T: Path>(
+        &mut self,
+        path: &T,
+        src: &[u8],
+        at_offset: usize,
+    ) -> Result<(), RuntimeError> {
+        let result = self.host.store_write(path, src, at_offset);
+        self.record(HostCall::StoreWrite {
+            path: path_string(path),
+            offset: at_offset,
+            data: Bytes(src.to_vec()),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn store_write_all<T: Path>(
+        &mut self,
+        path: &T,
+        src: &[u8],
+    ) -> Result<(), RuntimeError> {
+        let result = self.host.store_write_all(path, src);
+        self.record(HostCall::StoreWriteAll {
+            path: path_string(path),
+            data: Bytes(src.to_vec()),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn store_delete<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
+        let result = self.host.store_delete(path);
+        self.record(HostCall::StoreDelete {
+            path: path_string(path),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn store_delete_value<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
+        let result = self.host.store_delete_value(path);
+        self.record(HostCall::StoreDeleteValue {
+            path: path_string(path),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn store_count_subkeys<T: Path>(&self, prefix: &T) -> Result<u64, RuntimeError> {
+        let result = self.host.store_count_subkeys(prefix);
+        self.record(HostCall::StoreCountSubkeys {
+            path: path_string(prefix),
+            result: record(&result, |count| *count),
+        });
+        result
+    }
+
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError> {
+        let result = self.host.store_get_subkey(prefix, index);
+        self.record(HostCall::StoreGetSubkey {
+            path: path_string(prefix),
+            index,
+            result: record(&result, |subkey| subkey.as_ref().map(path_string)),
+        });
+        result
+    }
+
+    fn store_move(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        let result = self.host.store_move(from_path, to_path);
+        self.record(HostCall::StoreMove {
+            from: path_string(from_path),
+            to: path_string(to_path),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn store_copy(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        let result = self.host.store_copy(from_path, to_path);
+        self.record(HostCall::StoreCopy {
+            from: path_string(from_path),
+            to: path_string(to_path),
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn reveal_preimage(
+        &self,
+        hash: &[u8; PREIMAGE_HASH_SIZE],
+        destination: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let result = self.host.reveal_preimage(hash, destination);
+        self.record(HostCall::RevealPreimage {
+            hash: Bytes(hash.to_vec()),
+            max_bytes: destination.len(),
+            result: record(&result, |size| Bytes(destination[..*size].to_vec())),
+        });
+        result
+    }
+
+    fn reveal_dal_page(
+        &self,
+        published_level: i32,
+        slot_index: u8,
+        page_index: i16,
+        destination: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let result = self.host.reveal_dal_page(
+            published_level,
+            slot_index,
+            page_index,
+            destination,
+        );
+        self.record(HostCall::RevealDalPage {
+            published_level,
+            slot_index,
+            page_index,
+            max_bytes: destination.len(),
+            result: record(&result, |size| Bytes(destination[..*size].to_vec())),
+        });
+        result
+    }
+
+    fn reveal_dal_parameters(&self) -> RollupDalParameters {
+        let result = self.host.reveal_dal_parameters();
+        let bytes: [u8; DAL_PARAMETERS_SIZE] = (&result).into();
+        self.record(HostCall::RevealDalParameters {
+            result: Bytes(bytes.to_vec()),
+        });
+        result
+    }
+
+    fn store_value_size(&self, path: &impl Path) -> Result<usize, RuntimeError> {
+        let result = self.host.store_value_size(path);
+        self.record(HostCall::StoreValueSize {
+            path: path_string(path),
+            result: record(&result, |size| *size),
+        });
+        result
+    }
+
+    fn mark_for_reboot(&mut self) -> Result<(), RuntimeError> {
+        let result = self.host.mark_for_reboot();
+        self.record(HostCall::MarkForReboot {
+            result: record(&result, |_| ()),
+        });
+        result
+    }
+
+    fn reveal_metadata(&self) -> RollupMetadata {
+        let result = self.host.reveal_metadata();
+        let bytes: [u8; METADATA_SIZE] = result.clone().into();
+        self.record(HostCall::RevealMetadata {
+            result: Bytes(bytes.to_vec()),
+        });
+        result
+    }
+
+    fn last_run_aborted(&self) -> Result<bool, RuntimeError> {
+        let result = self.host.last_run_aborted();
+        self.record(HostCall::LastRunAborted {
+            result: record(&result, |flag| *flag),
+        });
+        result
+    }
+
+    fn upgrade_failed(&self) -> Result<bool, RuntimeError> {
+        let result = self.host.upgrade_failed();
+        self.record(HostCall::UpgradeFailed {
+            result: record(&result, |flag| *flag),
+        });
+        result
+    }
+
+    fn restart_forced(&self) -> Result<bool, RuntimeError> {
+        let result = self.host.restart_forced();
+        self.record(HostCall::RestartForced {
+            result: record(&result, |flag| *flag),
+        });
+        result
+    }
+
+    fn reboot_left(&self) -> Result<u32, RuntimeError> {
+        let result = self.host.reboot_left();
+        self.record(HostCall::RebootLeft {
+            result: record(&result, |reboots| *reboots),
+        });
+        result
+    }
+
+    fn runtime_version(&self) -> Result<String, RuntimeError> {
+        let result = self.host.runtime_version();
+        self.record(HostCall::RuntimeVersion {
+            result: record(&result, |version| version.clone()),
+        });
+        result
+    }
+}
@@ -0,0 +1,805 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! [`Runtime`] replaying a [`HostCallLog`] on top of a [`MockHost`].
+
+use super::log::{
+    path_string, record, replay, Bytes, HostCall, HostCallLog, RecordedMessage,
+};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
+use tezos_smart_rollup_host::{
+    dal_parameters::{RollupDalParameters, DAL_PARAMETERS_SIZE},
+    input::Message,
+    metadata::{RollupMetadata, METADATA_SIZE},
+    path::{OwnedPath, Path},
+    runtime::{Runtime, RuntimeError, ValueType},
+};
+use tezos_smart_rollup_mock::MockHost;
+
+/// The first host call of a replay that differs from the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the call in the log.
+    pub index: usize,
+    /// The recorded call, or `None` if the kernel made more calls than
+    /// recorded.
+    pub expected: Option<HostCall>,
+    /// The call made by the kernel, or `None` if the kernel stopped making
+    /// calls before the end of the log.
+    pub actual: Option<HostCall>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Replay diverged at host call {}", self.index)?;
+        writeln!(f, "  expected: {:?}", self.expected)?;
+        write!(f, "  actual:   {:?}", self.actual)
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// [`Runtime`] feeding the results of a [`HostCallLog`] back to a kernel.
+///
+/// As long as the kernel makes the same host calls as recorded, in the same
+/// order and with the same arguments, it is given the recorded results.
+/// Calls that modify the state of the host are also applied to the wrapped
+/// [`MockHost`], so that its storage & outbox can be inspected afterwards.
+///
+/// After the first [`Divergence`], all host calls are handled by the
+/// [`MockHost`].
+pub struct ReplayRuntime {
+    host: MockHost,
+    log: HostCallLog,
+    position: Cell<usize>,
+    divergence: RefCell<Option<Divergence>>,
+}
+
+impl ReplayRuntime {
+    /// Replay `log` on top of `host`.
+    pub fn new(host: MockHost, log: HostCallLog) -> Self {
+        Self {
+            host,
+            log,
+            position: Cell::new(0),
+            divergence: RefCell::new(None),
+        }
+    }
+
+    /// The wrapped host.
+    pub fn host(&self) -> &MockHost {
+        &self.host
+    }
+
+    /// Consume the replay, returning the wrapped host.
+    pub fn into_host(self) -> MockHost {
+        self.host
+    }
+
+    /// Number of host calls replayed so far.
+    pub fn position(&self) -> usize {
+        self.position.get()
+    }
+
+    /// Whether every host call of the log has been replayed.
+    pub fn is_complete(&self) -> bool {
+        self.position.get() == self.log.0.len()
+    }
+
+    /// The first divergence from the log, if any.
+    pub fn divergence(&self) -> Option<Divergence> {
+        self.divergence.borrow().clone()
+    }
+
+    /// Run `kernel_run` until the whole log has been replayed, or the kernel
+    /// diverges from it.
+    pub fn run(
+        &mut self,
+        mut kernel_run: impl FnMut(&mut Self),
+    ) -> Result<(), Box<Divergence>> {
+        while self.divergence.borrow().is_none() && !self.is_complete() {
+            let position = self.position.get();
+            kernel_run(self);
+
+            if self.position.get() == position {
+                self.diverge(None);
+            }
+        }
+
+        match self.divergence() {
+            Some(divergence) => Err(Box::new(divergence)),
+            None => Ok(()),
+        }
+    }
+
+    /// Consume the next host call of the log if `recorded` accepts it, and
+    /// return its recorded result.
+    fn expect<R>(&self, recorded: impl FnOnce(&HostCall) -> Option<R>) -> Option<R> {
+        if self.divergence.borrow().is_some() {
+            return None;
+        }
+
+        let position = self.position.get();
+        let result = self.log.0.get(position).and_then(recorded);
+        if result.is_some() {
+            self.position.set(position + 1);
+        }
+        result
+    }
+
+    fn diverge(&self, actual: Option<HostCall>) {
+        let mut divergence = self.divergence.borrow_mut();
+        if divergence.is_none() {
+            let index = self.position.get();
+            *divergence = Some(Divergence {
+                index,
+                expected: self.log.0.get(index).cloned(),
+                actual,
+            });
+        }
+    }
+}
+
+impl Runtime for ReplayRuntime {
+    fn write_output(&mut self, from: &[u8]) -> Result<(), RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::WriteOutput { output, result } if output.0 == from => {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.write_output(from);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::WriteOutput {
+                output: Bytes(from.to_vec()),
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn write_debug(&self, msg: &str) {
+        self.host.write_debug(msg)
+    }
+
+    fn read_input(&mut self) -> Result<Option<Message>, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::ReadInput { result } => Some(replay(result, |message| {
+                Ok(message.as_ref().map(|message| {
+                    Message::new(message.level, message.id, message.payload.0.clone())
+                }))
+            })),
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.read_input();
+            self.diverge(Some(HostCall::ReadInput {
+                result: record(&result, |message| {
+                    message.as_ref().map(|message| RecordedMessage {
+                        level: message.level,
+                        id: message.id,
+                        payload: Bytes(message.as_ref().to_vec()),
+                    })
+                }),
+            }));
+            result
+        })
+    }
+
+    fn store_has<T: Path>(&self, path: &T) -> Result<Option<ValueType>, RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreHas { path, result } if *path == actual_path => {
+                Some(replay(result, |value_type| Ok(value_type.map(Into::into))))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_has(path);
+            self.diverge(Some(HostCall::StoreHas {
+                path: actual_path,
+                result: record(&result, |value_type| value_type.map(Into::into)),
+            }));
+            result
+        })
+    }
+
+    fn store_read<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreRead {
+                path,
+                offset,
+                max_bytes: recorded_max_bytes,
+                result,
+            } if *path == actual_path
+                && *offset == from_offset
+                && *recorded_max_bytes == max_bytes =>
+            {
+                Some(replay(result, |bytes| Ok(bytes.0.clone())))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_read(path, from_offset, max_bytes);
+            self.diverge(Some(HostCall::StoreRead {
+                path: actual_path,
+                offset: from_offset,
+                max_bytes,
+                result: record(&result, |bytes| Bytes(bytes.clone())),
+            }));
+            result
+        })
+    }
+
+    fn store_read_slice<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let actual_path = path_string(path);
+        let max_bytes = buffer.len();
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreRead {
+                path,
+                offset,
+                max_bytes: recorded_max_bytes,
+                result,
+            } if *path == actual_path
+                && *offset == from_offset
+                && *recorded_max_bytes == max_bytes =>
+            {
+                Some(replay(result, |bytes| {
+                    let size = usize::min(bytes.0.len(), buffer.len());
+                    buffer[..size].copy_from_slice(&bytes.0[..size]);
+                    Ok(size)
+                }))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_read_slice(path, from_offset, buffer);
+            self.diverge(Some(HostCall::StoreRead {
+                path: actual_path,
+                offset: from_offset,
+                max_bytes,
+                result: record(&result, |size| Bytes(buffer[..*size].to_vec())),
+            }));
+            result
+        })
+    }
+
+    fn store_read_all(&self, path: &impl Path) -> Result<Vec<u8>, RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreReadAll { path, result } if *path == actual_path => {
+                Some(replay(result, |bytes| Ok(bytes.0.clone())))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_read_all(path);
+            self.diverge(Some(HostCall::StoreReadAll {
+                path: actual_path,
+                result: record(&result, |bytes| Bytes(bytes.clone())),
+            }));
+            result
+        })
+    }
+
+    fn store_write<T: Path>(
+        &mut self,
+        path: &T,
+        src: &[u8],
+        at_offset: usize,
+    ) -> Result<(), RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreWrite {
+                path,
+                offset,
+                data,
+                result,
+            } if *path == actual_path && *offset == at_offset && data.0 == src => {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_write(path, src, at_offset);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreWrite {
+                path: actual_path,
+                offset: at_offset,
+                data: Bytes(src.to_vec()),
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn store_write_all<T: Path>(
+        &mut self,
+        path: &T,
+        src: &[u8],
+    ) -> Result<(), RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreWriteAll { path, data, result }
+                if *path == actual_path && data.0 == src =>
+            {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_write_all(path, src);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreWriteAll {
+                path: actual_path,
+                data: Bytes(src.to_vec()),
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn store_delete<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreDelete { path, result } if *path == actual_path => {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_delete(path);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreDelete {
+                path: actual_path,
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn store_delete_value<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreDeleteValue { path, result } if *path == actual_path => {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_delete_value(path);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreDeleteValue {
+                path: actual_path,
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn store_count_subkeys<T: Path>(&self, prefix: &T) -> Result<u64, RuntimeError> {
+        let actual_path = path_string(prefix);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreCountSubkeys { path, result } if *path == actual_path => {
+                Some(replay(result, |count| Ok(*count)))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_count_subkeys(prefix);
+            self.diverge(Some(HostCall::StoreCountSubkeys {
+                path: actual_path,
+                result: record(&result, |count| *count),
+            }));
+            result
+        })
+    }
+
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: u64,
+    ) -> Result<Option<OwnedPath>, RuntimeError> {
+        let actual_path = path_string(prefix);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreGetSubkey {
+                path,
+                index: recorded_index,
+                result,
+            } if *path == actual_path && *recorded_index == index => {
+                Some(replay(result, |subkey| match subkey {
+                    Some(subkey) => OwnedPath::try_from(subkey.clone())
+                        .map(Some)
+                        .map_err(|_| RuntimeError::DecodingError),
+                    None => Ok(None),
+                }))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_get_subkey(prefix, index);
+            self.diverge(Some(HostCall::StoreGetSubkey {
+                path: actual_path,
+                index,
+                result: record(&result, |subkey| subkey.as_ref().map(path_string)),
+            }));
+            result
+        })
+    }
+
+    fn store_move(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        let (actual_from, actual_to) = (path_string(from_path), path_string(to_path));
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreMove { from, to, result }
+                if *from == actual_from && *to == actual_to =>
+            {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_move(from_path, to_path);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreMove {
+                from: actual_from,
+                to: actual_to,
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn store_copy(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        let (actual_from, actual_to) = (path_string(from_path), path_string(to_path));
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreCopy { from, to, result }
+                if *from == actual_from && *to == actual_to =>
+            {
+                Some(replay(result, |_| Ok(())))
+            }
+            _ => None,
+        });
+
+        let result = self.host.store_copy(from_path, to_path);
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::StoreCopy {
+                from: actual_from,
+                to: actual_to,
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn reveal_preimage(
+        &self,
+        hash: &[u8; PREIMAGE_HASH_SIZE],
+        destination: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let max_bytes = destination.len();
+        let recorded = self.expect(|call| match call {
+            HostCall::RevealPreimage {
+                hash: recorded_hash,
+                max_bytes: recorded_max_bytes,
+                result,
+            } if recorded_hash.0 == hash && *recorded_max_bytes == max_bytes => {
+                Some(replay(result, |bytes| {
+                    let size = usize::min(bytes.0.len(), destination.len());
+                    destination[..size].copy_from_slice(&bytes.0[..size]);
+                    Ok(size)
+                }))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.reveal_preimage(hash, destination);
+            self.diverge(Some(HostCall::RevealPreimage {
+                hash: Bytes(hash.to_vec()),
+                max_bytes,
+                result: record(&result, |size| Bytes(destination[..*size].to_vec())),
+            }));
+            result
+        })
+    }
+
+    fn reveal_dal_page(
+        &self,
+        published_level: i32,
+        slot_index: u8,
+        page_index: i16,
+        destination: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let max_bytes = destination.len();
+        let recorded = self.expect(|call| match call {
+            HostCall::RevealDalPage {
+                published_level: recorded_level,
+                slot_index: recorded_slot,
+                page_index: recorded_page,
+                max_bytes: recorded_max_bytes,
+                result,
+            } if *recorded_level == published_level
+                && *recorded_slot == slot_index
+                && *recorded_page == page_index
+                && *recorded_max_bytes == max_bytes =>
+            {
+                Some(replay(result, |bytes| {
+                    let size = usize::min(bytes.0.len(), destination.len());
+                    destination[..size].copy_from_slice(&bytes.0[..size]);
+                    Ok(size)
+                }))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.reveal_dal_page(
+                published_level,
+                slot_index,
+                page_index,
+                destination,
+            );
+            self.diverge(Some(HostCall::RevealDalPage {
+                published_level,
+                slot_index,
+                page_index,
+                max_bytes,
+                result: record(&result, |size| Bytes(destination[..*size].to_vec())),
+            }));
+            result
+        })
+    }
+
+    fn reveal_dal_parameters(&self) -> RollupDalParameters {
+        let recorded = self.expect(|call| match call {
+            HostCall::RevealDalParameters { result } => {
+                let bytes: [u8; DAL_PARAMETERS_SIZE] =
+                    result.0.as_slice().try_into().ok()?;
+                RollupDalParameters::try_from(bytes).ok()
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.reveal_dal_parameters();
+            let bytes: [u8; DAL_PARAMETERS_SIZE] = (&result).into();
+            self.diverge(Some(HostCall::RevealDalParameters {
+                result: Bytes(bytes.to_vec()),
+            }));
+            result
+        })
+    }
+
+    fn store_value_size(&self, path: &impl Path) -> Result<usize, RuntimeError> {
+        let actual_path = path_string(path);
+        let recorded = self.expect(|call| match call {
+            HostCall::StoreValueSize { path, result } if *path == actual_path => {
+                Some(replay(result, |size| Ok(*size)))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.store_value_size(path);
+            self.diverge(Some(HostCall::StoreValueSize {
+                path: actual_path,
+                result: record(&result, |size| *size),
+            }));
+            result
+        })
+    }
+
+    fn mark_for_reboot(&mut self) -> Result<(), RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::MarkForReboot { result } => Some(replay(result, |_| Ok(()))),
+            _ => None,
+        });
+
+        let result = self.host.mark_for_reboot();
+        recorded.unwrap_or_else(|| {
+            self.diverge(Some(HostCall::MarkForReboot {
+                result: record(&result, |_| ()),
+            }));
+            result
+        })
+    }
+
+    fn reveal_metadata(&self) -> RollupMetadata {
+        let recorded = self.expect(|call| match call {
+            HostCall::RevealMetadata { result } => {
+                let bytes: [u8; METADATA_SIZE] = result.0.as_slice().try_into().ok()?;
+                Some(RollupMetadata::from(bytes))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.reveal_metadata();
+            let bytes: [u8; METADATA_SIZE] = result.clone().into();
+            self.diverge(Some(HostCall::RevealMetadata {
+                result: Bytes(bytes.to_vec()),
+            }));
+            result
+        })
+    }
+
+    fn last_run_aborted(&self) -> Result<bool, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::LastRunAborted { result } => Some(replay(result, |flag| Ok(*flag))),
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.last_run_aborted();
+            self.diverge(Some(HostCall::LastRunAborted {
+                result: record(&result, |flag| *flag),
+            }));
+            result
+        })
+    }
+
+    fn upgrade_failed(&self) -> Result<bool, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::UpgradeFailed { result } => Some(replay(result, |flag| Ok(*flag))),
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.upgrade_failed();
+            self.diverge(Some(HostCall::UpgradeFailed {
+                result: record(&result, |flag| *flag),
+            }));
+            result
+        })
+    }
+
+    fn restart_forced(&self) -> Result<bool, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::RestartForced { result } => Some(replay(result, |flag| Ok(*flag))),
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.restart_forced();
+            self.diverge(Some(HostCall::RestartForced {
+                result: record(&result, |flag| *flag),
+            }));
+            result
+        })
+    }
+
+    fn reboot_left(&self) -> Result<u32, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::RebootLeft { result } => {
+                Some(replay(result, |reboots| Ok(*reboots)))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.reboot_left();
+            self.diverge(Some(HostCall::RebootLeft {
+                result: record(&result, |reboots| *reboots),
+            }));
+            result
+        })
+    }
+
+    fn runtime_version(&self) -> Result<String, RuntimeError> {
+        let recorded = self.expect(|call| match call {
+            HostCall::RuntimeVersion { result } => {
+                Some(replay(result, |version| Ok(version.clone())))
+            }
+            _ => None,
+        });
+
+        recorded.unwrap_or_else(|| {
+            let result = self.host.runtime_version();
+            self.diverge(Some(HostCall::RuntimeVersion {
+                result: record(&result, |version| version.clone()),
+            }));
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::RecordingRuntime;
+    use tezos_smart_rollup_host::path::RefPath;
+
+    const COUNTER: RefPath = RefPath::assert_from(b"/counter");
+
+    /// Counts the messages read so far, and outputs the count.
+    fn counting_kernel<Host: Runtime>(host: &mut Host) {
+        let mut count = match host.store_has(&COUNTER).unwrap() {
+            Some(_) => host.store_read_all(&COUNTER).unwrap()[0],
+            None => 0,
+        };
+        while host.read_input().unwrap().is_some() {
+            count += 1;
+        }
+        host.store_write_all(&COUNTER, &[count]).unwrap();
+        host.write_output(&[count]).unwrap();
+    }
+
+    fn off_by_one_kernel<Host: Runtime>(host: &mut Host) {
+        counting_kernel(host);
+        let count = host.store_read_all(&COUNTER).unwrap()[0];
+        host.store_write_all(&COUNTER, &[count + 1]).unwrap();
+    }
+
+    fn record_counting_kernel() -> HostCallLog {
+        let mut host = MockHost::default();
+        let mut recorder = RecordingRuntime::new(&mut host);
+        counting_kernel(&mut recorder);
+        recorder.into_log()
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let log = record_counting_kernel();
+
+        let mut bytes = Vec::new();
+        log.write(&mut bytes).unwrap();
+        let log = HostCallLog::read(bytes.as_slice()).unwrap();
+
+        // The replay does not depend on the inbox of the mock host.
+        let mut replay = ReplayRuntime::new(MockHost::default(), log.clone());
+        assert_eq!(Ok(()), replay.run(counting_kernel));
+        assert_eq!(replay.position(), log.0.len());
+
+        let HostCall::StoreWriteAll { data, .. } = &log.0[log.0.len() - 2] else {
+            panic!("Expected a write to the counter");
+        };
+        let host = replay.into_host();
+        assert_eq!(host.store_read_all(&COUNTER), Ok(data.0.clone()));
+    }
+
+    #[test]
+    fn first_divergence() {
+        let log = record_counting_kernel();
+
+        let mut replay = ReplayRuntime::new(MockHost::default(), log.clone());
+        let divergence = replay.run(off_by_one_kernel).unwrap_err();
+
+        // The kernel reads the counter back, instead of stopping.
+        assert_eq!(divergence.index, log.0.len());
+        assert_eq!(divergence.expected, None);
+        assert!(matches!(
+            divergence.actual,
+            Some(HostCall::StoreReadAll { .. })
+        ));
+    }
+}
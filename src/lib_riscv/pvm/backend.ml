@@ -81,3 +81,17 @@ let set_metadata state address origination_level =
 
 let reveal_raw_data state raw_data =
   Lwt.return (Api.octez_riscv_reveal_raw_data state raw_data)
+
+let reveal_dal_page state page =
+  Lwt.return (Api.octez_riscv_reveal_dal_page state page)
+
+let get_reveal_request state =
+  Lwt.return (Api.octez_riscv_get_reveal_request state)
+
+let get_outbox state level =
+  let rec go index acc =
+    match Api.octez_riscv_get_outbox_message state level index with
+    | None -> List.rev acc
+    | Some message -> go (index + 1) (message :: acc)
+  in
+  Lwt.return (go 0 [])
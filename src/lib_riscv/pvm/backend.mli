@@ -47,3 +47,9 @@ val set_input : state -> int32 -> int64 -> string -> state Lwt.t
 val set_metadata : state -> bytes -> int32 -> state Lwt.t
 
 val reveal_raw_data : state -> string -> state Lwt.t
+
+val reveal_dal_page : state -> bytes -> state Lwt.t
+
+val get_reveal_request : state -> bytes option Lwt.t
+
+val get_outbox : state -> int32 -> bytes list Lwt.t
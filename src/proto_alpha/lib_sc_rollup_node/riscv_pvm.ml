@@ -75,6 +75,22 @@ module PVM :
               (Sc_rollup.First_after
                  (Raw_level.of_int32_exn level, Z.of_int64 message_counter)))
     | WaitingForMetadata -> return Sc_rollup.(Needs_reveal Reveal_metadata)
+    | WaitingForReveal -> (
+        let* request = Backend.get_reveal_request state in
+        let reveal =
+          Option.bind
+            request
+            (Data_encoding.Binary.of_bytes_opt Sc_rollup.reveal_encoding)
+        in
+        match reveal with
+        | Some reveal -> return (Sc_rollup.Needs_reveal reveal)
+        | None ->
+            (* Like the WASM PVM, fall back to requesting the preimage of
+               [well_known_reveal_hash] for requests that cannot be decoded. *)
+            return
+              Sc_rollup.(
+                Needs_reveal
+                  (Reveal_raw_data Wasm_2_0_0PVM.well_known_reveal_hash)))
 
   let set_input input state =
     match input with
@@ -90,6 +106,11 @@ module PVM :
           (Sc_rollup.Address.to_bytes address)
           (Raw_level.to_int32 origination_level)
     | Sc_rollup.(Reveal (Raw_data data)) -> Backend.reveal_raw_data state data
+    | Sc_rollup.(Reveal (Dal_page content)) ->
+        (* Pages that are not available are revealed as empty. *)
+        Backend.reveal_dal_page
+          state
+          (Option.value ~default:Bytes.empty content)
     | _ -> assert false
 
   let eval state = Backend.compute_step state
@@ -140,7 +161,20 @@ let get_status ~is_reveal_enabled:_ state = Backend.get_status state
 
 let string_of_status status = Backend.string_of_status status
 
-let get_outbox _level _state = Lwt.return []
+let get_outbox outbox_level state =
+  let open Lwt_syntax in
+  let+ messages = Backend.get_outbox state (Raw_level.to_int32 outbox_level) in
+  List.mapi (fun index message -> (Z.of_int index, message)) messages
+  |> List.filter_map (fun (message_index, message) ->
+         let serialized =
+           Sc_rollup.Outbox.Message.unsafe_of_string (Bytes.to_string message)
+         in
+         match Sc_rollup.Outbox.Message.deserialize serialized with
+         | Error _ ->
+             (* Like for the WASM PVM, outputs that are not valid encodings of
+                outbox messages are ignored. *)
+             None
+         | Ok message -> Some Sc_rollup.{outbox_level; message_index; message})
 
 let eval_many ~reveal_builtins:_ ~write_debug ~is_reveal_enabled:_
     ?stop_at_snapshot ~max_steps initial_state =
@@ -7,7 +7,7 @@ open! Bigarray
 type repo
 type state
 type id
-type status = Evaluating | WaitingForInput | WaitingForMetadata
+type status = Evaluating | WaitingForInput | WaitingForMetadata | WaitingForReveal
 external octez_riscv_id_unsafe_of_raw_bytes: bytes -> id = "octez_riscv_id_unsafe_of_raw_bytes"
 external octez_riscv_storage_id_to_raw_bytes: id -> bytes = "octez_riscv_storage_id_to_raw_bytes"
 external octez_riscv_storage_id_equal: id -> id -> bool = "octez_riscv_storage_id_equal"
@@ -30,5 +30,8 @@ external octez_riscv_state_hash: state -> bytes = "octez_riscv_state_hash"
 external octez_riscv_set_input_message: state -> int32 -> int64 -> bytes -> state = "octez_riscv_set_input_message"
 external octez_riscv_set_metadata: state -> bytes -> int32 -> state = "octez_riscv_set_metadata"
 external octez_riscv_reveal_raw_data: state -> string -> state = "octez_riscv_reveal_raw_data"
+external octez_riscv_reveal_dal_page: state -> bytes -> state = "octez_riscv_reveal_dal_page"
+external octez_riscv_get_reveal_request: state -> bytes option = "octez_riscv_get_reveal_request"
+external octez_riscv_get_outbox_message: state -> int32 -> int -> bytes option = "octez_riscv_get_outbox_message"
 external octez_riscv_get_message_counter: state -> int64 = "octez_riscv_get_message_counter"
 external octez_riscv_storage_export_snapshot: repo -> id -> string -> (unit, [`Msg of string]) result = "octez_riscv_storage_export_snapshot"
@@ -101,6 +101,7 @@ pub fn generate_custom(
                 fdt.property_u32("reg", 0x0)?;
                 fdt.property_string("status", "okay")?;
                 fdt.property_string("compatible", "riscv")?;
+                fdt.property_string("riscv,isa", "rv64imafdc_zicond_zicsr_zifencei_zba_zbb_zbs")?;
             });
         });
     });
@@ -18,5 +18,9 @@ pub mod rv64f;
 pub mod rv64i;
 pub mod rv64m;
 pub mod rv64priv;
+pub mod rv64zba;
+pub mod rv64zbb;
+pub mod rv64zbs;
+pub mod rv64zicond;
 pub mod rv64zicsr;
 pub mod rv64zifencei;
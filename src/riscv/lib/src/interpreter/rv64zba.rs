@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Implementation of Zba extension for RISC-V
+//!
+//! Chapter 28.4.1 - Unprivileged spec
+
+use crate::{
+    machine_state::registers::{XRegister, XRegisters},
+    state_backend as backend,
+};
+
+impl<M> XRegisters<M>
+where
+    M: backend::ManagerReadWrite,
+{
+    /// `ADD.UW` R-type instruction
+    ///
+    /// Add val(rs2) to the lower 32 bits of val(rs1), zero-extended, and store
+    /// the result in `rd`.
+    pub fn run_add_uw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let result = self.read(rs2).wrapping_add(self.read(rs1) as u32 as u64);
+        self.write(rd, result)
+    }
+
+    /// Add val(rs2) to val(rs1) shifted left by `shift` bits, only considering
+    /// the lower 32 bits of val(rs1) if `unsigned_word` is set.
+    fn run_shadd(
+        &mut self,
+        shift: u32,
+        unsigned_word: bool,
+        rs1: XRegister,
+        rs2: XRegister,
+        rd: XRegister,
+    ) {
+        let rval1 = if unsigned_word {
+            self.read(rs1) as u32 as u64
+        } else {
+            self.read(rs1)
+        };
+        let result = self.read(rs2).wrapping_add(rval1 << shift);
+        self.write(rd, result)
+    }
+
+    /// `SH1ADD` R-type instruction
+    ///
+    /// Shift val(rs1) left by 1 bit, add val(rs2) and store the result in `rd`.
+    pub fn run_sh1add(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(1, false, rs1, rs2, rd)
+    }
+
+    /// `SH1ADD.UW` R-type instruction
+    ///
+    /// Shift the lower 32 bits of val(rs1), zero-extended, left by 1 bit,
+    /// add val(rs2) and store the result in `rd`.
+    pub fn run_sh1add_uw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(1, true, rs1, rs2, rd)
+    }
+
+    /// `SH2ADD` R-type instruction
+    ///
+    /// Shift val(rs1) left by 2 bits, add val(rs2) and store the result in `rd`.
+    pub fn run_sh2add(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(2, false, rs1, rs2, rd)
+    }
+
+    /// `SH2ADD.UW` R-type instruction
+    ///
+    /// Shift the lower 32 bits of val(rs1), zero-extended, left by 2 bits,
+    /// add val(rs2) and store the result in `rd`.
+    pub fn run_sh2add_uw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(2, true, rs1, rs2, rd)
+    }
+
+    /// `SH3ADD` R-type instruction
+    ///
+    /// Shift val(rs1) left by 3 bits, add val(rs2) and store the result in `rd`.
+    pub fn run_sh3add(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(3, false, rs1, rs2, rd)
+    }
+
+    /// `SH3ADD.UW` R-type instruction
+    ///
+    /// Shift the lower 32 bits of val(rs1), zero-extended, left by 3 bits,
+    /// add val(rs2) and store the result in `rd`.
+    pub fn run_sh3add_uw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.run_shadd(3, true, rs1, rs2, rd)
+    }
+
+    /// `SLLI.UW` I-type instruction
+    ///
+    /// Shift the lower 32 bits of val(rs1), zero-extended, left by `imm` bits
+    /// and store the result in `rd`.
+    ///
+    /// NOTE: The shift amount (shamt) is 6 bits wide
+    pub fn run_slli_uw(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        let result = (self.read(rs1) as u32 as u64) << (imm & 0b11_1111);
+        self.write(rd, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend_test, create_backend, create_state,
+        machine_state::registers::{a0, a1, a2, XRegisters, XRegistersLayout},
+    };
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    backend_test!(test_shadd, F, {
+        proptest!(|(
+            r1_val in any::<u64>(),
+            r2_val in any::<u64>(),
+            imm in 0i64..64,
+        )| {
+            let mut backend = create_backend!(XRegistersLayout, F);
+            let mut state = create_state!(XRegisters, F, backend);
+            let word = r1_val & 0xFFFF_FFFF;
+
+            state.write(a0, r1_val);
+            state.write(a1, r2_val);
+
+            state.run_add_uw(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(word));
+
+            state.run_sh1add(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(r1_val << 1));
+            state.run_sh2add(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(r1_val << 2));
+            state.run_sh3add(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(r1_val << 3));
+
+            state.run_sh1add_uw(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(word << 1));
+            state.run_sh2add_uw(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(word << 2));
+            state.run_sh3add_uw(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r2_val.wrapping_add(word << 3));
+
+            state.run_slli_uw(imm, a0, a2);
+            prop_assert_eq!(state.read(a2), word << imm);
+        })
+    });
+}
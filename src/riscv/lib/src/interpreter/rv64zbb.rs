@@ -0,0 +1,334 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Implementation of Zbb extension for RISC-V
+//!
+//! Chapter 28.4.2 - Unprivileged spec
+
+use crate::{
+    machine_state::registers::{XRegister, XRegisters},
+    state_backend as backend,
+};
+
+impl<M> XRegisters<M>
+where
+    M: backend::ManagerReadWrite,
+{
+    /// `ANDN` R-type instruction
+    ///
+    /// Perform bitwise AND between val(rs1) and the bitwise inversion of
+    /// val(rs2) and store the result in `rd`.
+    pub fn run_andn(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) & !self.read(rs2))
+    }
+
+    /// `ORN` R-type instruction
+    ///
+    /// Perform bitwise OR between val(rs1) and the bitwise inversion of
+    /// val(rs2) and store the result in `rd`.
+    pub fn run_orn(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) | !self.read(rs2))
+    }
+
+    /// `XNOR` R-type instruction
+    ///
+    /// Perform bitwise XOR between val(rs1) and val(rs2), invert the result
+    /// and store it in `rd`.
+    pub fn run_xnor(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, !(self.read(rs1) ^ self.read(rs2)))
+    }
+
+    /// `CLZ` instruction
+    ///
+    /// Count the number of leading zero bits of val(rs1) and store it in `rd`.
+    pub fn run_clz(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).leading_zeros() as u64)
+    }
+
+    /// `CLZW` instruction
+    ///
+    /// Count the number of leading zero bits of the lower 32 bits of val(rs1)
+    /// and store it in `rd`.
+    pub fn run_clzw(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, (self.read(rs1) as u32).leading_zeros() as u64)
+    }
+
+    /// `CTZ` instruction
+    ///
+    /// Count the number of trailing zero bits of val(rs1) and store it in `rd`.
+    pub fn run_ctz(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).trailing_zeros() as u64)
+    }
+
+    /// `CTZW` instruction
+    ///
+    /// Count the number of trailing zero bits of the lower 32 bits of val(rs1)
+    /// and store it in `rd`.
+    pub fn run_ctzw(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, (self.read(rs1) as u32).trailing_zeros() as u64)
+    }
+
+    /// `CPOP` instruction
+    ///
+    /// Count the number of bits set in val(rs1) and store it in `rd`.
+    pub fn run_cpop(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).count_ones() as u64)
+    }
+
+    /// `CPOPW` instruction
+    ///
+    /// Count the number of bits set in the lower 32 bits of val(rs1) and store
+    /// it in `rd`.
+    pub fn run_cpopw(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, (self.read(rs1) as u32).count_ones() as u64)
+    }
+
+    /// `MAX` R-type instruction
+    ///
+    /// Store the larger of val(rs1) and val(rs2) in `rd`. The values are
+    /// _signed integers_.
+    pub fn run_max(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let result = (self.read(rs1) as i64).max(self.read(rs2) as i64);
+        self.write(rd, result as u64)
+    }
+
+    /// `MAXU` R-type instruction
+    ///
+    /// Store the larger of val(rs1) and val(rs2) in `rd`. The values are
+    /// _unsigned integers_.
+    pub fn run_maxu(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).max(self.read(rs2)))
+    }
+
+    /// `MIN` R-type instruction
+    ///
+    /// Store the smaller of val(rs1) and val(rs2) in `rd`. The values are
+    /// _signed integers_.
+    pub fn run_min(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let result = (self.read(rs1) as i64).min(self.read(rs2) as i64);
+        self.write(rd, result as u64)
+    }
+
+    /// `MINU` R-type instruction
+    ///
+    /// Store the smaller of val(rs1) and val(rs2) in `rd`. The values are
+    /// _unsigned integers_.
+    pub fn run_minu(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).min(self.read(rs2)))
+    }
+
+    /// `SEXT.B` instruction
+    ///
+    /// Sign-extend the lowest byte of val(rs1) and store the result in `rd`.
+    pub fn run_sext_b(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) as i8 as u64)
+    }
+
+    /// `SEXT.H` instruction
+    ///
+    /// Sign-extend the lower 16 bits of val(rs1) and store the result in `rd`.
+    pub fn run_sext_h(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) as i16 as u64)
+    }
+
+    /// `ZEXT.H` instruction
+    ///
+    /// Zero-extend the lower 16 bits of val(rs1) and store the result in `rd`.
+    pub fn run_zext_h(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) as u16 as u64)
+    }
+
+    /// `ROL` R-type instruction
+    ///
+    /// Rotate val(rs1) left by the amount given by the lower 6 bits of
+    /// val(rs2) and store the result in `rd`.
+    pub fn run_rol(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let shamt = self.read(rs2) & 0b11_1111;
+        self.write(rd, self.read(rs1).rotate_left(shamt as u32))
+    }
+
+    /// `ROLW` R-type instruction
+    ///
+    /// Rotate the lower 32 bits of val(rs1) left by the amount given by the
+    /// lower 5 bits of val(rs2), sign-extend the result and store it in `rd`.
+    pub fn run_rolw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let shamt = self.read(rs2) & 0b1_1111;
+        let result = (self.read(rs1) as u32).rotate_left(shamt as u32);
+        self.write(rd, result as i32 as u64)
+    }
+
+    /// `ROR` R-type instruction
+    ///
+    /// Rotate val(rs1) right by the amount given by the lower 6 bits of
+    /// val(rs2) and store the result in `rd`.
+    pub fn run_ror(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let shamt = self.read(rs2) & 0b11_1111;
+        self.write(rd, self.read(rs1).rotate_right(shamt as u32))
+    }
+
+    /// `RORW` R-type instruction
+    ///
+    /// Rotate the lower 32 bits of val(rs1) right by the amount given by the
+    /// lower 5 bits of val(rs2), sign-extend the result and store it in `rd`.
+    pub fn run_rorw(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let shamt = self.read(rs2) & 0b1_1111;
+        let result = (self.read(rs1) as u32).rotate_right(shamt as u32);
+        self.write(rd, result as i32 as u64)
+    }
+
+    /// `RORI` I-type instruction
+    ///
+    /// Rotate val(rs1) right by `imm` bits and store the result in `rd`.
+    ///
+    /// NOTE: The shift amount (shamt) is 6 bits wide
+    pub fn run_rori(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        let shamt = imm & 0b11_1111;
+        self.write(rd, self.read(rs1).rotate_right(shamt as u32))
+    }
+
+    /// `RORIW` I-type instruction
+    ///
+    /// Rotate the lower 32 bits of val(rs1) right by `imm` bits, sign-extend
+    /// the result and store it in `rd`.
+    ///
+    /// NOTE: The shift amount (shamt) is 5 bits wide
+    pub fn run_roriw(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        let shamt = imm & 0b1_1111;
+        let result = (self.read(rs1) as u32).rotate_right(shamt as u32);
+        self.write(rd, result as i32 as u64)
+    }
+
+    /// `ORC.B` instruction
+    ///
+    /// Set each byte of the result to all ones if the corresponding byte of
+    /// val(rs1) is non-zero, and to zero otherwise. Store the result in `rd`.
+    pub fn run_orc_b(&mut self, rs1: XRegister, rd: XRegister) {
+        let bytes = self
+            .read(rs1)
+            .to_le_bytes()
+            .map(|b| if b == 0 { 0 } else { 0xFF });
+        self.write(rd, u64::from_le_bytes(bytes))
+    }
+
+    /// `REV8` instruction
+    ///
+    /// Reverse the order of the bytes of val(rs1) and store the result in `rd`.
+    pub fn run_rev8(&mut self, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1).swap_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend_test, create_backend, create_state,
+        machine_state::registers::{a0, a1, a2, XRegisters, XRegistersLayout},
+    };
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    backend_test!(test_count, F, {
+        let mut backend = create_backend!(XRegistersLayout, F);
+        let mut state = create_state!(XRegisters, F, backend);
+
+        for (val, clz, clzw, ctz, ctzw, cpop, cpopw) in [
+            (0, 64, 32, 64, 32, 0, 0),
+            (1, 63, 31, 0, 0, 1, 1),
+            (0xFFFF_FFFF_0000_0000, 0, 32, 32, 32, 32, 0),
+            (0x0000_0100_8000_0000, 23, 0, 31, 31, 2, 1),
+        ] {
+            state.write(a0, val);
+
+            state.run_clz(a0, a1);
+            assert_eq!(state.read(a1), clz);
+            state.run_clzw(a0, a1);
+            assert_eq!(state.read(a1), clzw);
+            state.run_ctz(a0, a1);
+            assert_eq!(state.read(a1), ctz);
+            state.run_ctzw(a0, a1);
+            assert_eq!(state.read(a1), ctzw);
+            state.run_cpop(a0, a1);
+            assert_eq!(state.read(a1), cpop);
+            state.run_cpopw(a0, a1);
+            assert_eq!(state.read(a1), cpopw);
+        }
+    });
+
+    backend_test!(test_extend_and_bytes, F, {
+        let mut backend = create_backend!(XRegistersLayout, F);
+        let mut state = create_state!(XRegisters, F, backend);
+
+        state.write(a0, 0x0011_2233_4455_8680);
+
+        state.run_sext_b(a0, a1);
+        assert_eq!(state.read(a1), 0xFFFF_FFFF_FFFF_FF80);
+        state.run_sext_h(a0, a1);
+        assert_eq!(state.read(a1), 0xFFFF_FFFF_FFFF_8680);
+        state.run_zext_h(a0, a1);
+        assert_eq!(state.read(a1), 0x8680);
+        state.run_orc_b(a0, a1);
+        assert_eq!(state.read(a1), 0x00FF_FFFF_FFFF_FFFF);
+        state.run_rev8(a0, a1);
+        assert_eq!(state.read(a1), 0x8086_5544_3322_1100);
+    });
+
+    backend_test!(test_rotate, F, {
+        proptest!(|(
+            r1_val in any::<u64>(),
+            r2_val in any::<u64>(),
+        )| {
+            let mut backend = create_backend!(XRegistersLayout, F);
+            let mut state = create_state!(XRegisters, F, backend);
+
+            state.write(a0, r1_val);
+            state.write(a1, r2_val);
+
+            // Rotating left then right by the same amount is the identity
+            state.run_rol(a0, a1, a2);
+            state.run_ror(a2, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val);
+
+            state.run_rolw(a0, a1, a2);
+            state.run_rorw(a2, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val as i32 as u64);
+
+            state.run_rori(r2_val as i64 & 0b11_1111, a0, a2);
+            prop_assert_eq!(state.read(a2), r1_val.rotate_right(r2_val as u32 & 0b11_1111));
+
+            state.run_roriw(r2_val as i64 & 0b1_1111, a0, a2);
+            prop_assert_eq!(
+                state.read(a2),
+                (r1_val as u32).rotate_right(r2_val as u32 & 0b1_1111) as i32 as u64
+            );
+        })
+    });
+
+    backend_test!(test_logical_and_min_max, F, {
+        proptest!(|(
+            r1_val in any::<u64>(),
+            r2_val in any::<u64>(),
+        )| {
+            let mut backend = create_backend!(XRegistersLayout, F);
+            let mut state = create_state!(XRegisters, F, backend);
+
+            state.write(a0, r1_val);
+            state.write(a1, r2_val);
+
+            state.run_andn(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val & !r2_val);
+            state.run_orn(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val | !r2_val);
+            state.run_xnor(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), !(r1_val ^ r2_val));
+
+            state.run_max(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), (r1_val as i64).max(r2_val as i64) as u64);
+            state.run_maxu(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val.max(r2_val));
+            state.run_min(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), (r1_val as i64).min(r2_val as i64) as u64);
+            state.run_minu(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val.min(r2_val));
+        })
+    });
+}
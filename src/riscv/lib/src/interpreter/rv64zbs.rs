@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Implementation of Zbs extension for RISC-V
+//!
+//! Chapter 28.4.4 - Unprivileged spec
+
+use crate::{
+    machine_state::registers::{XRegister, XRegisters},
+    state_backend as backend,
+};
+
+/// Mask selecting the bit of a register given by the lower 6 bits of `index`
+#[inline(always)]
+fn bit_mask(index: u64) -> u64 {
+    1 << (index & 0b11_1111)
+}
+
+impl<M> XRegisters<M>
+where
+    M: backend::ManagerReadWrite,
+{
+    /// `BCLR` R-type instruction
+    ///
+    /// Clear the bit of val(rs1) at the index given by val(rs2) and store the
+    /// result in `rd`.
+    pub fn run_bclr(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) & !bit_mask(self.read(rs2)))
+    }
+
+    /// `BCLRI` I-type instruction
+    ///
+    /// Clear the bit of val(rs1) at index `imm` and store the result in `rd`.
+    pub fn run_bclri(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) & !bit_mask(imm as u64))
+    }
+
+    /// `BEXT` R-type instruction
+    ///
+    /// Extract the bit of val(rs1) at the index given by val(rs2) and store it
+    /// in `rd`.
+    pub fn run_bext(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let bit = self.read(rs1) & bit_mask(self.read(rs2)) != 0;
+        self.write(rd, bit as u64)
+    }
+
+    /// `BEXTI` I-type instruction
+    ///
+    /// Extract the bit of val(rs1) at index `imm` and store it in `rd`.
+    pub fn run_bexti(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        let bit = self.read(rs1) & bit_mask(imm as u64) != 0;
+        self.write(rd, bit as u64)
+    }
+
+    /// `BINV` R-type instruction
+    ///
+    /// Invert the bit of val(rs1) at the index given by val(rs2) and store the
+    /// result in `rd`.
+    pub fn run_binv(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) ^ bit_mask(self.read(rs2)))
+    }
+
+    /// `BINVI` I-type instruction
+    ///
+    /// Invert the bit of val(rs1) at index `imm` and store the result in `rd`.
+    pub fn run_binvi(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) ^ bit_mask(imm as u64))
+    }
+
+    /// `BSET` R-type instruction
+    ///
+    /// Set the bit of val(rs1) at the index given by val(rs2) and store the
+    /// result in `rd`.
+    pub fn run_bset(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) | bit_mask(self.read(rs2)))
+    }
+
+    /// `BSETI` I-type instruction
+    ///
+    /// Set the bit of val(rs1) at index `imm` and store the result in `rd`.
+    pub fn run_bseti(&mut self, imm: i64, rs1: XRegister, rd: XRegister) {
+        self.write(rd, self.read(rs1) | bit_mask(imm as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend_test, create_backend, create_state,
+        machine_state::registers::{a0, a1, a2, XRegisters, XRegistersLayout},
+    };
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    backend_test!(test_single_bit, F, {
+        proptest!(|(
+            r1_val in any::<u64>(),
+            index in 0u64..64,
+            high_bits in any::<u64>(),
+        )| {
+            let mut backend = create_backend!(XRegistersLayout, F);
+            let mut state = create_state!(XRegisters, F, backend);
+            let bit = (r1_val >> index) & 1;
+
+            // Only the lower 6 bits of the index register are considered
+            state.write(a0, r1_val);
+            state.write(a1, (high_bits << 6) | index);
+
+            state.run_bext(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), bit);
+            state.run_bexti(index as i64, a0, a2);
+            prop_assert_eq!(state.read(a2), bit);
+
+            state.run_bset(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val | (1 << index));
+            state.run_bseti(index as i64, a0, a2);
+            prop_assert_eq!(state.read(a2), r1_val | (1 << index));
+
+            state.run_bclr(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val & !(1 << index));
+            state.run_bclri(index as i64, a0, a2);
+            prop_assert_eq!(state.read(a2), r1_val & !(1 << index));
+
+            state.run_binv(a0, a1, a2);
+            prop_assert_eq!(state.read(a2), r1_val ^ (1 << index));
+            state.run_binvi(index as i64, a0, a2);
+            prop_assert_eq!(state.read(a2), r1_val ^ (1 << index));
+        })
+    });
+}
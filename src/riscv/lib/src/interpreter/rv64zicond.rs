@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Implementation of Zicond extension for RISC-V
+//!
+//! Chapter 12 - Unprivileged spec
+
+use crate::{
+    machine_state::registers::{XRegister, XRegisters},
+    state_backend as backend,
+};
+
+impl<M> XRegisters<M>
+where
+    M: backend::ManagerReadWrite,
+{
+    /// `CZERO.EQZ` R-type instruction
+    ///
+    /// Store zero in `rd` if val(rs2) is zero, otherwise store val(rs1).
+    pub fn run_czero_eqz(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let result = if self.read(rs2) == 0 {
+            0
+        } else {
+            self.read(rs1)
+        };
+        self.write(rd, result)
+    }
+
+    /// `CZERO.NEZ` R-type instruction
+    ///
+    /// Store zero in `rd` if val(rs2) is non-zero, otherwise store val(rs1).
+    pub fn run_czero_nez(&mut self, rs1: XRegister, rs2: XRegister, rd: XRegister) {
+        let result = if self.read(rs2) != 0 {
+            0
+        } else {
+            self.read(rs1)
+        };
+        self.write(rd, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend_test, create_backend, create_state,
+        machine_state::registers::{a0, a1, a2, XRegisters, XRegistersLayout},
+    };
+
+    backend_test!(test_czero, F, {
+        let mut backend = create_backend!(XRegistersLayout, F);
+        let mut state = create_state!(XRegisters, F, backend);
+
+        for (r1_val, r2_val, eqz, nez) in [
+            (42, 0, 0, 42),
+            (42, 1, 42, 0),
+            (u64::MAX, 1 << 63, u64::MAX, 0),
+        ] {
+            state.write(a0, r1_val);
+            state.write(a1, r2_val);
+
+            state.run_czero_eqz(a0, a1, a2);
+            assert_eq!(state.read(a2), eqz);
+            state.run_czero_nez(a0, a1, a2);
+            assert_eq!(state.read(a2), nez);
+        }
+    });
+}
@@ -99,6 +99,14 @@ macro_rules! run_i_type_instr {
     }};
 }
 
+/// Runs an instruction with a single source register over [`XRegisters`]
+macro_rules! run_r1_type_instr {
+    ($state: ident, $instr: ident, $args: ident, $run_fn: ident) => {{
+        $state.hart.xregisters.$run_fn($args.rs1, $args.rd);
+        Ok(Add($instr.width()))
+    }};
+}
+
 /// Runs a B-type instruction over [`HartState`]
 macro_rules! run_b_type_instr {
     ($state: ident, $args: ident, $run_fn: ident) => {{
@@ -500,6 +508,56 @@ impl<ML: main_memory::MainMemoryLayout, ICL: InstructionCacheLayout, M: backend:
             Instr::Mulhu(args) => run_r_type_instr!(self, instr, args, run_mulhu),
             Instr::Mulw(args) => run_r_type_instr!(self, instr, args, run_mulw),
 
+            // RV64 Zba instructions
+            Instr::AddUw(args) => run_r_type_instr!(self, instr, args, run_add_uw),
+            Instr::Sh1add(args) => run_r_type_instr!(self, instr, args, run_sh1add),
+            Instr::Sh1addUw(args) => run_r_type_instr!(self, instr, args, run_sh1add_uw),
+            Instr::Sh2add(args) => run_r_type_instr!(self, instr, args, run_sh2add),
+            Instr::Sh2addUw(args) => run_r_type_instr!(self, instr, args, run_sh2add_uw),
+            Instr::Sh3add(args) => run_r_type_instr!(self, instr, args, run_sh3add),
+            Instr::Sh3addUw(args) => run_r_type_instr!(self, instr, args, run_sh3add_uw),
+            Instr::SlliUw(args) => run_i_type_instr!(self, instr, args, run_slli_uw),
+
+            // RV64 Zbb instructions
+            Instr::Andn(args) => run_r_type_instr!(self, instr, args, run_andn),
+            Instr::Orn(args) => run_r_type_instr!(self, instr, args, run_orn),
+            Instr::Xnor(args) => run_r_type_instr!(self, instr, args, run_xnor),
+            Instr::Clz(args) => run_r1_type_instr!(self, instr, args, run_clz),
+            Instr::Clzw(args) => run_r1_type_instr!(self, instr, args, run_clzw),
+            Instr::Ctz(args) => run_r1_type_instr!(self, instr, args, run_ctz),
+            Instr::Ctzw(args) => run_r1_type_instr!(self, instr, args, run_ctzw),
+            Instr::Cpop(args) => run_r1_type_instr!(self, instr, args, run_cpop),
+            Instr::Cpopw(args) => run_r1_type_instr!(self, instr, args, run_cpopw),
+            Instr::Max(args) => run_r_type_instr!(self, instr, args, run_max),
+            Instr::Maxu(args) => run_r_type_instr!(self, instr, args, run_maxu),
+            Instr::Min(args) => run_r_type_instr!(self, instr, args, run_min),
+            Instr::Minu(args) => run_r_type_instr!(self, instr, args, run_minu),
+            Instr::SextB(args) => run_r1_type_instr!(self, instr, args, run_sext_b),
+            Instr::SextH(args) => run_r1_type_instr!(self, instr, args, run_sext_h),
+            Instr::ZextH(args) => run_r1_type_instr!(self, instr, args, run_zext_h),
+            Instr::Rol(args) => run_r_type_instr!(self, instr, args, run_rol),
+            Instr::Rolw(args) => run_r_type_instr!(self, instr, args, run_rolw),
+            Instr::Ror(args) => run_r_type_instr!(self, instr, args, run_ror),
+            Instr::Rorw(args) => run_r_type_instr!(self, instr, args, run_rorw),
+            Instr::Rori(args) => run_i_type_instr!(self, instr, args, run_rori),
+            Instr::Roriw(args) => run_i_type_instr!(self, instr, args, run_roriw),
+            Instr::OrcB(args) => run_r1_type_instr!(self, instr, args, run_orc_b),
+            Instr::Rev8(args) => run_r1_type_instr!(self, instr, args, run_rev8),
+
+            // RV64 Zbs instructions
+            Instr::Bclr(args) => run_r_type_instr!(self, instr, args, run_bclr),
+            Instr::Bclri(args) => run_i_type_instr!(self, instr, args, run_bclri),
+            Instr::Bext(args) => run_r_type_instr!(self, instr, args, run_bext),
+            Instr::Bexti(args) => run_i_type_instr!(self, instr, args, run_bexti),
+            Instr::Binv(args) => run_r_type_instr!(self, instr, args, run_binv),
+            Instr::Binvi(args) => run_i_type_instr!(self, instr, args, run_binvi),
+            Instr::Bset(args) => run_r_type_instr!(self, instr, args, run_bset),
+            Instr::Bseti(args) => run_i_type_instr!(self, instr, args, run_bseti),
+
+            // RV64 Zicond instructions
+            Instr::CzeroEqz(args) => run_r_type_instr!(self, instr, args, run_czero_eqz),
+            Instr::CzeroNez(args) => run_r_type_instr!(self, instr, args, run_czero_nez),
+
             // RV64F instructions
             Instr::FclassS(args) => run_f_x_instr!(self, instr, args, run_fclass_s),
             Instr::Feqs(args) => run_f_r_instr!(self, instr, args, run_feq_s),
@@ -947,7 +1005,7 @@ mod tests {
             registers::{a0, a1, a2, t0, t1, t2, zero},
         },
         parser::{
-            instruction::{CIBTypeArgs, ITypeArgs, Instr, SBTypeArgs},
+            instruction::{CIBTypeArgs, ITypeArgs, Instr, RTypeArgs, SBTypeArgs},
             parse_block,
         },
         traps::{EnvironException, Exception, Interrupt, TrapContext},
@@ -1575,4 +1633,65 @@ mod tests {
         assert_eq!(result, alt_result);
         assert_eq!(backend, alt_backend);
     });
+
+    // Test that the Zicond instructions are decoded and run, including when
+    // the destination is one of the sources or the zero register.
+    backend_test!(test_step_zicond, F, {
+        // czero.eqz t0, a0, a1
+        const I_CZERO_EQZ_T0: u32 = 0b00001110101101010101001010110011;
+        // czero.eqz t1, a0, a2
+        const I_CZERO_EQZ_T1: u32 = 0b00001110110001010101001100110011;
+        // czero.nez t2, a0, a1
+        const I_CZERO_NEZ_T2: u32 = 0b00001110101101010111001110110011;
+        // czero.eqz zero, a0, a2
+        const I_CZERO_EQZ_ZERO: u32 = 0b00001110110001010101000000110011;
+        // czero.nez a0, a0, a2
+        const I_CZERO_NEZ_A0: u32 = 0b00001110110001010111010100110011;
+
+        let instrs: Vec<u8> = [
+            I_CZERO_EQZ_T0,
+            I_CZERO_EQZ_T1,
+            I_CZERO_NEZ_T2,
+            I_CZERO_EQZ_ZERO,
+            I_CZERO_NEZ_A0,
+        ]
+        .into_iter()
+        .flat_map(u32::to_le_bytes)
+        .collect();
+
+        let rtype = |rd, rs2| RTypeArgs { rd, rs1: a0, rs2 };
+        assert_eq!(
+            parse_block(&instrs),
+            [
+                Instr::CzeroEqz(rtype(t0, a1)),
+                Instr::CzeroEqz(rtype(t1, a2)),
+                Instr::CzeroNez(rtype(t2, a1)),
+                Instr::CzeroEqz(rtype(zero, a2)),
+                Instr::CzeroNez(rtype(a0, a2)),
+            ]
+        );
+
+        let mut backend = create_backend!(MachineStateLayout<M1K, TestInstructionCacheLayout>, F);
+        let mut state = create_state!(MachineState, MachineStateLayout<M1K, TestInstructionCacheLayout>, F, backend, M1K, TestInstructionCacheLayout);
+        state.reset();
+
+        let start_ram = start_of_main_memory::<M1K>();
+        state.hart.pc.write(start_ram);
+        state.bus.write_all(start_ram, &instrs).unwrap();
+
+        state.hart.xregisters.write(a0, 42);
+        state.hart.xregisters.write(a1, 0);
+        state.hart.xregisters.write(a2, 1 << 63);
+
+        for _ in 0..5 {
+            state.step().expect("should not raise trap to EE");
+        }
+
+        assert_eq!(state.hart.pc.read(), start_ram + 20);
+        assert_eq!(state.hart.xregisters.read(t0), 0);
+        assert_eq!(state.hart.xregisters.read(t1), 42);
+        assert_eq!(state.hart.xregisters.read(t2), 42);
+        assert_eq!(state.hart.xregisters.read(zero), 0);
+        assert_eq!(state.hart.xregisters.read(a0), 0);
+    });
 }
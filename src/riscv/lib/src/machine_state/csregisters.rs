@@ -571,8 +571,9 @@ impl CSRegister {
     const WARL_MISA_VALUE: CSRRepr = {
         /* MXLEN encoding of 64 bits */
         const MXL_MASK: u64 = CSRegister::MXL_ENCODING << 62;
-        /* Extensions (A + C + D + F + I + M + S + U) */
+        /* Extensions (A + B + C + D + F + I + M + S + U) */
         const ATOMIC_EXT: u64 = 1 << 0;
+        const BIT_MANIP_EXT: u64 = 1 << 1;
         const COMPRESSED_EXT: u64 = 1 << 2;
         const DOUBLE_EXT: u64 = 1 << 3;
         const SINGLE_EXT: u64 = 1 << 5;
@@ -584,6 +585,7 @@ impl CSRegister {
         MXL_MASK |
         /* Extensions */
         ATOMIC_EXT |
+        BIT_MANIP_EXT |
         COMPRESSED_EXT |
         DOUBLE_EXT |
         SINGLE_EXT |
@@ -1788,9 +1790,9 @@ mod tests {
         // misa field
         assert_eq!(
             check(csreg::misa, 0xFFFF_FFFF_FFFF_FFFF),
-            0x8000_0000_0014_112D
+            0x8000_0000_0014_112F
         );
-        assert_eq!(check(csreg::misa, 0x0), 0x8000_0000_0014_112D);
+        assert_eq!(check(csreg::misa, 0x0), 0x8000_0000_0014_112F);
 
         // medeleg / mideleg
         assert!(check(csreg::medeleg, 0x0) == 0x0);
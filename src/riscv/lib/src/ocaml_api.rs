@@ -29,12 +29,13 @@ ocaml::custom!(State);
 ocaml::custom!(Id);
 
 #[derive(ocaml::FromValue, ocaml::ToValue, IntoPrimitive, TryFromPrimitive)]
-#[ocaml::sig("Evaluating | WaitingForInput | WaitingForMetadata")]
+#[ocaml::sig("Evaluating | WaitingForInput | WaitingForMetadata | WaitingForReveal")]
 #[repr(u8)]
 pub enum Status {
     Evaluating,
     WaitingForInput,
     WaitingForMetadata,
+    WaitingForReveal,
 }
 
 impl From<PvmStatus> for Status {
@@ -284,9 +285,40 @@ pub fn octez_riscv_set_metadata(
 
 #[ocaml::func]
 #[ocaml::sig("state -> string -> state")]
-pub fn octez_riscv_reveal_raw_data(_state: Pointer<State>, _data: &[u8]) -> Pointer<State> {
-    // TODO: RV-110. Support all revelations in set_input method
-    todo!()
+pub fn octez_riscv_reveal_raw_data(state: Pointer<State>, data: &[u8]) -> Pointer<State> {
+    State(state.as_ref().0.provide_reveal_response(data)).into()
+}
+
+#[ocaml::func]
+#[ocaml::sig("state -> bytes -> state")]
+pub fn octez_riscv_reveal_dal_page(state: Pointer<State>, page: &[u8]) -> Pointer<State> {
+    State(state.as_ref().0.provide_reveal_response(page)).into()
+}
+
+/// Reveal request of the kernel, using the protocol's encoding
+#[ocaml::func]
+#[ocaml::sig("state -> bytes option")]
+pub unsafe fn octez_riscv_get_reveal_request(state: Pointer<State>) -> Option<ocaml::Value> {
+    state
+        .as_ref()
+        .0
+        .get_reveal_request()
+        .map(|request| request.to_bytes().as_slice().to_value(gc))
+}
+
+/// Message at position `index` in the outbox of the given level, if any
+#[ocaml::func]
+#[ocaml::sig("state -> int32 -> int -> bytes option")]
+pub unsafe fn octez_riscv_get_outbox_message(
+    state: Pointer<State>,
+    level: u32,
+    index: ocaml::Int,
+) -> Option<ocaml::Value> {
+    state
+        .as_ref()
+        .0
+        .get_outbox_message(level, index as usize)
+        .map(|message| message.as_slice().to_value(gc))
 }
 
 #[ocaml::func]
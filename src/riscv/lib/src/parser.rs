@@ -108,6 +108,11 @@ const fn imm_11_6(instr: u32) -> u32 {
     bits(instr, 26, 6) << 1
 }
 
+#[inline(always)]
+const fn funct12(instr: u32) -> u32 {
+    bits(instr, 20, 12)
+}
+
 #[inline(always)]
 const fn fm(instr: u32) -> u32 {
     bits(instr, 28, 4)
@@ -190,6 +195,27 @@ macro_rules! i_instr {
     };
 }
 
+macro_rules! r1_instr {
+    ($enum_variant:ident, $instr:expr) => {
+        $enum_variant(instruction::XRegToXRegArgs {
+            rd: rd($instr),
+            rs1: rs1($instr),
+        })
+    };
+}
+
+/// I-type instruction whose immediate is a shift amount or bit index of
+/// `$width` bits; the remaining bits of the immediate select the operation.
+macro_rules! i_shamt_instr {
+    ($enum_variant:ident, $instr:expr, $width:expr) => {
+        $enum_variant(instruction::ITypeArgs {
+            rd: rd($instr),
+            rs1: rs1($instr),
+            imm: bits($instr, 20, $width) as i64,
+        })
+    };
+}
+
 macro_rules! s_instr {
     ($enum_variant:ident, $instr:expr) => {
         $enum_variant(instruction::SBTypeArgs {
@@ -452,6 +478,22 @@ const F7_8: u32 = 0b000_1000;
 const F7_20: u32 = 0b10_0000;
 const F7_24: u32 = 0b001_1000;
 const F7_56: u32 = 0b011_1000;
+const F7_ADDUW: u32 = 0b000_0100;
+const F7_MINMAX: u32 = 0b000_0101;
+const F7_CZERO: u32 = 0b000_0111;
+const F7_SHADD: u32 = 0b001_0000;
+const F7_BSET: u32 = 0b001_0100;
+const F7_BCLR: u32 = 0b010_0100;
+const F7_ROT: u32 = 0b011_0000;
+const F7_BINV: u32 = 0b011_0100;
+
+const F12_CLZ: u32 = 0b0110_0000_0000;
+const F12_CTZ: u32 = 0b0110_0000_0001;
+const F12_CPOP: u32 = 0b0110_0000_0010;
+const F12_SEXTB: u32 = 0b0110_0000_0100;
+const F12_SEXTH: u32 = 0b0110_0000_0101;
+const F12_ORCB: u32 = 0b0010_1000_0111;
+const F12_REV8: u32 = 0b0110_1011_1000;
 
 const FMT_S: u32 = 0b0;
 const FMT_D: u32 = 0b01;
@@ -498,33 +540,51 @@ pub const fn parse_uncompressed_instruction(instr: u32) -> Instr {
             F3_4 => match funct7(instr) {
                 F7_0 => r_instr!(Xor, instr),
                 F7_1 => r_instr!(Div, instr),
+                F7_SHADD => r_instr!(Sh2add, instr),
+                F7_20 => r_instr!(Xnor, instr),
+                F7_MINMAX => r_instr!(Min, instr),
                 _ => Unknown { instr },
             },
             F3_6 => match funct7(instr) {
                 F7_0 => r_instr!(Or, instr),
                 F7_1 => r_instr!(Rem, instr),
+                F7_SHADD => r_instr!(Sh3add, instr),
+                F7_20 => r_instr!(Orn, instr),
+                F7_MINMAX => r_instr!(Max, instr),
                 _ => Unknown { instr },
             },
             F3_7 => match funct7(instr) {
                 F7_0 => r_instr!(And, instr),
                 F7_1 => r_instr!(Remu, instr),
+                F7_20 => r_instr!(Andn, instr),
+                F7_MINMAX => r_instr!(Maxu, instr),
+                F7_CZERO => r_instr!(CzeroNez, instr),
                 _ => Unknown { instr },
             },
             F3_1 => match funct7(instr) {
                 F7_0 => r_instr!(Sll, instr),
                 F7_1 => r_instr!(Mulh, instr),
+                F7_ROT => r_instr!(Rol, instr),
+                F7_BCLR => r_instr!(Bclr, instr),
+                F7_BINV => r_instr!(Binv, instr),
+                F7_BSET => r_instr!(Bset, instr),
                 _ => Unknown { instr },
             },
             F3_5 => match funct7(instr) {
                 F7_0 => r_instr!(Srl, instr),
                 F7_1 => r_instr!(Divu, instr),
                 F7_20 => r_instr!(Sra, instr),
+                F7_MINMAX => r_instr!(Minu, instr),
+                F7_ROT => r_instr!(Ror, instr),
+                F7_BCLR => r_instr!(Bext, instr),
+                F7_CZERO => r_instr!(CzeroEqz, instr),
                 _ => Unknown { instr },
             },
 
             F3_2 => match funct7(instr) {
                 F7_0 => r_instr!(Slt, instr),
                 F7_1 => r_instr!(Mulhsu, instr),
+                F7_SHADD => r_instr!(Sh1add, instr),
                 _ => Unknown { instr },
             },
 
@@ -541,22 +601,35 @@ pub const fn parse_uncompressed_instruction(instr: u32) -> Instr {
                 F7_0 => r_instr!(Addw, instr),
                 F7_1 => r_instr!(Mulw, instr),
                 F7_20 => r_instr!(Subw, instr),
+                F7_ADDUW => r_instr!(AddUw, instr),
+                _ => Unknown { instr },
+            },
+            F3_1 => match funct7(instr) {
+                F7_0 => r_instr!(Sllw, instr),
+                F7_ROT => r_instr!(Rolw, instr),
+                _ => Unknown { instr },
+            },
+            F3_2 => match funct7(instr) {
+                F7_SHADD => r_instr!(Sh1addUw, instr),
                 _ => Unknown { instr },
             },
-            F3_1 => r_instr!(Sllw, instr),
             F3_4 => match funct7(instr) {
                 F7_1 => r_instr!(Divw, instr),
+                F7_SHADD => r_instr!(Sh2addUw, instr),
+                F7_ADDUW if rs2_bits(instr) == RS2_0 => r1_instr!(ZextH, instr),
                 _ => Unknown { instr },
             },
             F3_5 => match funct7(instr) {
                 F7_0 => r_instr!(Srlw, instr),
                 F7_1 => r_instr!(Divuw, instr),
                 F7_20 => r_instr!(Sraw, instr),
+                F7_ROT => r_instr!(Rorw, instr),
                 _ => Unknown { instr },
             },
 
             F3_6 => match funct7(instr) {
                 F7_1 => r_instr!(Remw, instr),
+                F7_SHADD => r_instr!(Sh3addUw, instr),
                 _ => Unknown { instr },
             },
             F3_7 => match funct7(instr) {
@@ -576,12 +649,30 @@ pub const fn parse_uncompressed_instruction(instr: u32) -> Instr {
             F3_1 => match imm_11_6(instr) {
                 // imm[0:5] -> shift amount
                 F7_0 => i_instr!(Slli, instr),
+                // imm[6:11] -> type of operation, imm[0:5] -> bit index
+                F7_BCLR => i_shamt_instr!(Bclri, instr, 6),
+                F7_BINV => i_shamt_instr!(Binvi, instr, 6),
+                F7_BSET => i_shamt_instr!(Bseti, instr, 6),
+                // imm[0:11] -> type of unary operation
+                F7_ROT => match funct12(instr) {
+                    F12_CLZ => r1_instr!(Clz, instr),
+                    F12_CTZ => r1_instr!(Ctz, instr),
+                    F12_CPOP => r1_instr!(Cpop, instr),
+                    F12_SEXTB => r1_instr!(SextB, instr),
+                    F12_SEXTH => r1_instr!(SextH, instr),
+                    _ => Unknown { instr },
+                },
                 _ => Unknown { instr },
             },
             F3_5 => match imm_11_6(instr) {
                 // imm[6:11] -> type of shift, imm[0:5] -> shift amount
                 F7_0 => i_instr!(Srli, instr),
                 F7_20 => i_instr!(Srai, instr),
+                F7_ROT => i_shamt_instr!(Rori, instr, 6),
+                F7_BCLR => i_shamt_instr!(Bexti, instr, 6),
+                // imm[0:11] -> type of unary operation
+                F7_BSET if funct12(instr) == F12_ORCB => r1_instr!(OrcB, instr),
+                F7_BINV if funct12(instr) == F12_REV8 => r1_instr!(Rev8, instr),
                 _ => Unknown { instr },
             },
             F3_2 => i_instr!(Slti, instr),
@@ -603,12 +694,22 @@ pub const fn parse_uncompressed_instruction(instr: u32) -> Instr {
             F3_1 => match imm_11_6(instr) {
                 // imm[0:4] -> shift amount
                 F7_0 => i_instr!(Slliw, instr),
+                // imm[0:5] -> shift amount
+                F7_ADDUW => i_shamt_instr!(SlliUw, instr, 6),
+                // imm[0:11] -> type of unary operation
+                F7_ROT => match funct12(instr) {
+                    F12_CLZ => r1_instr!(Clzw, instr),
+                    F12_CTZ => r1_instr!(Ctzw, instr),
+                    F12_CPOP => r1_instr!(Cpopw, instr),
+                    _ => Unknown { instr },
+                },
                 _ => Unknown { instr },
             },
             F3_5 => match imm_11_6(instr) {
                 // imm[6:11] -> type of shift, imm[0:4] -> shift amount
                 F7_0 => i_instr!(Srliw, instr),
                 F7_20 => i_instr!(Sraiw, instr),
+                F7_ROT if funct7(instr) == F7_ROT => i_shamt_instr!(Roriw, instr, 5),
                 _ => Unknown { instr },
             },
             _ => Unknown { instr },
@@ -1289,7 +1390,9 @@ pub fn parse_segment(contents: &[u8], range: Range<usize>) -> Vec<Instr> {
 #[cfg(test)]
 mod tests {
     use super::{
-        instruction::{CsrArgs, ITypeArgs, Instr::*, SBTypeArgs, UJTypeArgs},
+        instruction::{
+            CsrArgs, ITypeArgs, Instr::*, RTypeArgs, SBTypeArgs, UJTypeArgs, XRegToXRegArgs,
+        },
         parse_block,
     };
     use crate::{
@@ -1434,6 +1537,49 @@ mod tests {
         assert_eq!(instructions, expected)
     }
 
+    // Bit-manipulation and conditional zero instructions, as assembled by
+    // `llvm-mc -mattr=+zba,+zbb,+zbs`. The last instruction is `roriw` with
+    // bit 5 of the shift amount set, which is reserved.
+    #[test]
+    fn test_bitmanip() {
+        let bytes: [u8; 52] = [
+            0x3b, 0xa5, 0xc5, 0x20, 0x3b, 0xc5, 0x05, 0x08, 0x1b, 0x95, 0x15, 0x0a, 0x1b, 0xd5,
+            0x55, 0x60, 0x1b, 0x95, 0x05, 0x60, 0x13, 0xd5, 0x85, 0x6b, 0x13, 0xd5, 0x75, 0x28,
+            0x13, 0xd5, 0x85, 0x4a, 0x13, 0x95, 0x35, 0x28, 0x33, 0xf5, 0xc5, 0x40, 0x33, 0xf5,
+            0xc5, 0x0a, 0x33, 0xd5, 0xc5, 0x0e, 0x1b, 0xd5, 0x65, 0x62,
+        ];
+        let rtype = RTypeArgs {
+            rd: x10,
+            rs1: x11,
+            rs2: x12,
+        };
+        let unary = XRegToXRegArgs { rd: x10, rs1: x11 };
+        let itype = |imm| ITypeArgs {
+            rd: x10,
+            rs1: x11,
+            imm,
+        };
+        let expected = [
+            Sh1addUw(rtype),
+            ZextH(unary),
+            SlliUw(itype(33)),
+            Roriw(itype(5)),
+            Clzw(unary),
+            Rev8(unary),
+            OrcB(unary),
+            Bexti(itype(40)),
+            Bseti(itype(3)),
+            Andn(rtype),
+            Maxu(rtype),
+            CzeroEqz(rtype),
+            Unknown {
+                instr: u32::from_le_bytes([0x1b, 0xd5, 0x65, 0x62]),
+            },
+        ];
+        let instructions = parse_block(&bytes);
+        assert_eq!(instructions, expected)
+    }
+
     // Ensure the u16 jump table is initialised correctly.
     #[test]
     fn parser_compressed() {
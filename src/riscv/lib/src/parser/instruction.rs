@@ -68,6 +68,12 @@ pub struct FenceArgs {
     pub succ: FenceSet,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct XRegToXRegArgs {
+    pub rd: XRegister,
+    pub rs1: XRegister,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct FRegToXRegArgs {
     pub rd: XRegister,
@@ -335,6 +341,56 @@ pub enum Instr {
     Mulhu(RTypeArgs),
     Mulw(RTypeArgs),
 
+    // RV64 Zba address generation instructions
+    AddUw(RTypeArgs),
+    Sh1add(RTypeArgs),
+    Sh1addUw(RTypeArgs),
+    Sh2add(RTypeArgs),
+    Sh2addUw(RTypeArgs),
+    Sh3add(RTypeArgs),
+    Sh3addUw(RTypeArgs),
+    SlliUw(ITypeArgs),
+
+    // RV64 Zbb basic bit-manipulation instructions
+    Andn(RTypeArgs),
+    Orn(RTypeArgs),
+    Xnor(RTypeArgs),
+    Clz(XRegToXRegArgs),
+    Clzw(XRegToXRegArgs),
+    Ctz(XRegToXRegArgs),
+    Ctzw(XRegToXRegArgs),
+    Cpop(XRegToXRegArgs),
+    Cpopw(XRegToXRegArgs),
+    Max(RTypeArgs),
+    Maxu(RTypeArgs),
+    Min(RTypeArgs),
+    Minu(RTypeArgs),
+    SextB(XRegToXRegArgs),
+    SextH(XRegToXRegArgs),
+    ZextH(XRegToXRegArgs),
+    Rol(RTypeArgs),
+    Rolw(RTypeArgs),
+    Ror(RTypeArgs),
+    Rorw(RTypeArgs),
+    Rori(ITypeArgs),
+    Roriw(ITypeArgs),
+    OrcB(XRegToXRegArgs),
+    Rev8(XRegToXRegArgs),
+
+    // RV64 Zbs single-bit instructions
+    Bclr(RTypeArgs),
+    Bclri(ITypeArgs),
+    Bext(RTypeArgs),
+    Bexti(ITypeArgs),
+    Binv(RTypeArgs),
+    Binvi(ITypeArgs),
+    Bset(RTypeArgs),
+    Bseti(ITypeArgs),
+
+    // RV64 Zicond conditional operations
+    CzeroEqz(RTypeArgs),
+    CzeroNez(RTypeArgs),
+
     // RV64F instructions
     FclassS(FRegToXRegArgs),
     Feqs(FCmpArgs),
@@ -564,6 +620,48 @@ impl Instr {
             | Mulhsu(_)
             | Mulhu(_)
             | Mulw(_)
+            | AddUw(_)
+            | Sh1add(_)
+            | Sh1addUw(_)
+            | Sh2add(_)
+            | Sh2addUw(_)
+            | Sh3add(_)
+            | Sh3addUw(_)
+            | SlliUw(_)
+            | Andn(_)
+            | Orn(_)
+            | Xnor(_)
+            | Clz(_)
+            | Clzw(_)
+            | Ctz(_)
+            | Ctzw(_)
+            | Cpop(_)
+            | Cpopw(_)
+            | Max(_)
+            | Maxu(_)
+            | Min(_)
+            | Minu(_)
+            | SextB(_)
+            | SextH(_)
+            | ZextH(_)
+            | Rol(_)
+            | Rolw(_)
+            | Ror(_)
+            | Rorw(_)
+            | Rori(_)
+            | Roriw(_)
+            | OrcB(_)
+            | Rev8(_)
+            | Bclr(_)
+            | Bclri(_)
+            | Bext(_)
+            | Bexti(_)
+            | Binv(_)
+            | Binvi(_)
+            | Bset(_)
+            | Bseti(_)
+            | CzeroEqz(_)
+            | CzeroNez(_)
             | FmvXW(_)
             | FmvWX(_)
             | Fcvtsw(_)
@@ -999,6 +1097,57 @@ impl fmt::Display for Instr {
             Mulhu(args) => r_instr!(f, "mulhu", args),
             Mulw(args) => r_instr!(f, "mulw", args),
 
+            // RV64 Zba instructions
+            AddUw(args) if args.rs2 == XRegister::x0 => r2_instr!(f, "zext.w", args),
+            AddUw(args) => r_instr!(f, "add.uw", args),
+            Sh1add(args) => r_instr!(f, "sh1add", args),
+            Sh1addUw(args) => r_instr!(f, "sh1add.uw", args),
+            Sh2add(args) => r_instr!(f, "sh2add", args),
+            Sh2addUw(args) => r_instr!(f, "sh2add.uw", args),
+            Sh3add(args) => r_instr!(f, "sh3add", args),
+            Sh3addUw(args) => r_instr!(f, "sh3add.uw", args),
+            SlliUw(args) => i_instr_hex!(f, "slli.uw", args),
+
+            // RV64 Zbb instructions
+            Andn(args) => r_instr!(f, "andn", args),
+            Orn(args) => r_instr!(f, "orn", args),
+            Xnor(args) => r_instr!(f, "xnor", args),
+            Clz(args) => r2_instr!(f, "clz", args),
+            Clzw(args) => r2_instr!(f, "clzw", args),
+            Ctz(args) => r2_instr!(f, "ctz", args),
+            Ctzw(args) => r2_instr!(f, "ctzw", args),
+            Cpop(args) => r2_instr!(f, "cpop", args),
+            Cpopw(args) => r2_instr!(f, "cpopw", args),
+            Max(args) => r_instr!(f, "max", args),
+            Maxu(args) => r_instr!(f, "maxu", args),
+            Min(args) => r_instr!(f, "min", args),
+            Minu(args) => r_instr!(f, "minu", args),
+            SextB(args) => r2_instr!(f, "sext.b", args),
+            SextH(args) => r2_instr!(f, "sext.h", args),
+            ZextH(args) => r2_instr!(f, "zext.h", args),
+            Rol(args) => r_instr!(f, "rol", args),
+            Rolw(args) => r_instr!(f, "rolw", args),
+            Ror(args) => r_instr!(f, "ror", args),
+            Rorw(args) => r_instr!(f, "rorw", args),
+            Rori(args) => i_instr_hex!(f, "rori", args),
+            Roriw(args) => i_instr_hex!(f, "roriw", args),
+            OrcB(args) => r2_instr!(f, "orc.b", args),
+            Rev8(args) => r2_instr!(f, "rev8", args),
+
+            // RV64 Zbs instructions
+            Bclr(args) => r_instr!(f, "bclr", args),
+            Bclri(args) => i_instr_hex!(f, "bclri", args),
+            Bext(args) => r_instr!(f, "bext", args),
+            Bexti(args) => i_instr_hex!(f, "bexti", args),
+            Binv(args) => r_instr!(f, "binv", args),
+            Binvi(args) => i_instr_hex!(f, "binvi", args),
+            Bset(args) => r_instr!(f, "bset", args),
+            Bseti(args) => i_instr_hex!(f, "bseti", args),
+
+            // RV64 Zicond instructions
+            CzeroEqz(args) => r_instr!(f, "czero.eqz", args),
+            CzeroNez(args) => r_instr!(f, "czero.nez", args),
+
             // RV64F instructions
             FclassS(args) => f_s1_instr!(f, "fclass.s", args),
             Feqs(args) => r_instr!(f, "feq.s", args),
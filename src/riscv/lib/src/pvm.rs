@@ -4,6 +4,8 @@
 
 mod common;
 pub mod node_pvm;
+mod outbox;
 mod sbi;
 
 pub use common::*;
+pub use outbox::*;
@@ -4,7 +4,10 @@
 
 use crate::{
     machine_state::{self, bus::main_memory, instruction_cache},
-    pvm::sbi,
+    pvm::{
+        outbox::{Outbox, OutboxLayout},
+        sbi,
+    },
     state_backend::{self, EnumCell, EnumCellLayout},
     traps::EnvironException,
 };
@@ -14,10 +17,16 @@ use std::{
     io::{stdout, Write},
     ops::Bound,
 };
+use tezos_smart_rollup_constants::core::PREIMAGE_HASH_SIZE;
 
 /// PVM configuration
 pub struct PvmHooks<'a> {
     pub putchar_hook: Box<dyn FnMut(u8) + 'a>,
+
+    /// Provides the data for reveal requests. When it returns `None`, the PVM
+    /// stops with [`PvmStatus::WaitingForReveal`] and the data must be
+    /// provided using [`Pvm::provide_reveal_response`].
+    pub reveal_hook: Box<dyn FnMut(&RevealRequest) -> Option<Vec<u8>> + 'a>,
 }
 
 impl<'a> PvmHooks<'a> {
@@ -25,8 +34,18 @@ impl<'a> PvmHooks<'a> {
     pub fn new<F: FnMut(u8) + 'a>(putchar: F) -> Self {
         Self {
             putchar_hook: Box::new(putchar),
+            reveal_hook: Box::new(|_| None),
         }
     }
+
+    /// Use the given function to provide data for reveal requests.
+    pub fn with_reveal_hook<F>(mut self, reveal: F) -> Self
+    where
+        F: FnMut(&RevealRequest) -> Option<Vec<u8>> + 'a,
+    {
+        self.reveal_hook = Box::new(reveal);
+        self
+    }
 }
 
 /// The default PVM configuration prints all debug information from the kernel
@@ -41,11 +60,52 @@ impl<'a> Default for PvmHooks<'a> {
     }
 }
 
+/// Tag of raw data reveals in the protocol's encoding of reveal requests
+const REVEAL_RAW_DATA_TAG: u8 = 0;
+
+/// Tag of DAL page reveals in the protocol's encoding of reveal requests
+const REQUEST_DAL_PAGE_TAG: u8 = 2;
+
+/// Data requested by the kernel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevealRequest {
+    /// Preimage of the given hash
+    Preimage([u8; PREIMAGE_HASH_SIZE]),
+
+    /// Page of a DAL slot
+    DalPage {
+        published_level: i32,
+        slot_index: u8,
+        page_index: i16,
+    },
+}
+
+impl RevealRequest {
+    /// Encode the request the same way as the protocol does.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Preimage(hash) => [&[REVEAL_RAW_DATA_TAG], hash.as_slice()].concat(),
+            Self::DalPage {
+                published_level,
+                slot_index,
+                page_index,
+            } => [
+                &[REQUEST_DAL_PAGE_TAG],
+                published_level.to_be_bytes().as_slice(),
+                &[*slot_index],
+                page_index.to_be_bytes().as_slice(),
+            ]
+            .concat(),
+        }
+    }
+}
+
 /// PVM state layout
 pub type PvmLayout<ML, ICL> = (
     state_backend::Atom<u64>,
     machine_state::MachineStateLayout<ML, ICL>,
     EnumCellLayout<u8>,
+    OutboxLayout,
 );
 
 /// PVM status
@@ -55,6 +115,7 @@ pub enum PvmStatus {
     Evaluating,
     WaitingForInput,
     WaitingForMetadata,
+    WaitingForReveal,
 }
 
 impl Default for PvmStatus {
@@ -70,6 +131,7 @@ impl fmt::Display for PvmStatus {
             PvmStatus::Evaluating => "Evaluating",
             PvmStatus::WaitingForInput => "Waiting for input message",
             PvmStatus::WaitingForMetadata => "Waiting for metadata",
+            PvmStatus::WaitingForReveal => "Waiting for reveal",
         };
         f.write_str(status)
     }
@@ -81,11 +143,13 @@ impl From<u8> for PvmStatus {
         const EVALUATING: u8 = PvmStatus::Evaluating as u8;
         const WAITING_FOR_INPUT: u8 = PvmStatus::WaitingForInput as u8;
         const WAITING_FOR_METADATA: u8 = PvmStatus::WaitingForMetadata as u8;
+        const WAITING_FOR_REVEAL: u8 = PvmStatus::WaitingForReveal as u8;
 
         match value {
             EVALUATING => Self::Evaluating,
             WAITING_FOR_INPUT => Self::WaitingForInput,
             WAITING_FOR_METADATA => Self::WaitingForMetadata,
+            WAITING_FOR_REVEAL => Self::WaitingForReveal,
             _ => Self::default(),
         }
     }
@@ -110,6 +174,7 @@ pub struct Pvm<
     version: state_backend::Cell<u64, M>,
     pub(crate) machine_state: machine_state::MachineState<ML, ICL, M>,
     status: EnumCell<PvmStatus, u8, M>,
+    outbox: Outbox<M>,
 }
 
 impl<
@@ -124,6 +189,7 @@ impl<
             version: space.0,
             machine_state: machine_state::MachineState::bind(space.1),
             status: EnumCell::bind(space.2),
+            outbox: Outbox::bind(space.3),
         }
     }
 
@@ -135,6 +201,7 @@ impl<
             self.version.struct_ref(),
             self.machine_state.struct_ref(),
             self.status.struct_ref(),
+            self.outbox.struct_ref(),
         )
     }
 
@@ -146,6 +213,7 @@ impl<
         self.version.write(INITIAL_VERSION);
         self.machine_state.reset();
         self.status.reset();
+        self.outbox.reset();
     }
 
     /// Handle an exception using the defined Execution Environment.
@@ -157,7 +225,13 @@ impl<
     where
         M: state_backend::ManagerReadWrite,
     {
-        sbi::handle_call(&mut self.status, &mut self.machine_state, hooks, exception)
+        sbi::handle_call(
+            &mut self.status,
+            &mut self.outbox,
+            &mut self.machine_state,
+            hooks,
+            exception,
+        )
     }
 
     /// Perform one evaluation step.
@@ -192,6 +266,7 @@ impl<
             .step_max_handle::<Infallible>(step_bounds, |machine_state, exc| {
                 Ok(sbi::handle_call(
                     &mut self.status,
+                    &mut self.outbox,
                     machine_state,
                     hooks,
                     exc,
@@ -215,13 +290,20 @@ impl<
     where
         M: state_backend::ManagerReadWrite,
     {
-        sbi::provide_input(
+        let accepted = sbi::provide_input(
             &mut self.status,
             &mut self.machine_state,
             level,
             counter,
             payload,
-        )
+        );
+
+        // Outbox messages are written at the level of the input being processed.
+        if accepted {
+            self.outbox.start_level(level);
+        }
+
+        accepted
     }
 
     /// Provide metadata in response to a metadata request. Returns `false`
@@ -238,6 +320,29 @@ impl<
         )
     }
 
+    /// Get the data requested by the kernel, if the machine is waiting for a
+    /// reveal.
+    pub fn reveal_request(&self) -> Option<RevealRequest>
+    where
+        M: state_backend::ManagerRead,
+    {
+        sbi::reveal_request(&self.status, &self.machine_state)
+    }
+
+    /// Provide the data requested by the kernel. Returns `false` if the
+    /// machine is not expecting a reveal.
+    pub fn provide_reveal_response(&mut self, data: &[u8]) -> bool
+    where
+        M: state_backend::ManagerReadWrite,
+    {
+        sbi::provide_reveal_response(&mut self.status, &mut self.machine_state, data)
+    }
+
+    /// Messages written by the kernel at the current level.
+    pub fn outbox(&self) -> &Outbox<M> {
+        &self.outbox
+    }
+
     /// Get the current machine status.
     pub fn status(&self) -> PvmStatus
     where
@@ -255,11 +360,12 @@ mod tests {
         machine_state::{
             bus::{
                 main_memory::{M1K, M1M},
-                start_of_main_memory, AddressableRead,
+                start_of_main_memory, AddressableRead, AddressableWrite,
             },
             instruction_cache::TestInstructionCacheLayout,
-            registers::{a0, a1, a2, a3, a6, a7},
+            registers::{a0, a1, a2, a3, a4, a6, a7},
         },
+        pvm::MAX_OUTBOX_MESSAGES_PER_LEVEL,
         state_backend::{
             memory_backend::InMemoryBackend,
             tests::{test_determinism, ManagerFor},
@@ -268,8 +374,12 @@ mod tests {
     };
     use rand::{thread_rng, Fill};
     use std::mem;
-    use tezos_smart_rollup_constants::riscv::{
-        SBI_CONSOLE_PUTCHAR, SBI_FIRMWARE_TEZOS, SBI_TEZOS_INBOX_NEXT,
+    use tezos_smart_rollup_constants::{
+        core::MAX_OUTPUT_SIZE,
+        riscv::{
+            SbiError, SBI_CONSOLE_PUTCHAR, SBI_FIRMWARE_TEZOS, SBI_TEZOS_INBOX_NEXT,
+            SBI_TEZOS_OUTBOX_WRITE, SBI_TEZOS_REVEAL_DAL_PAGE, SBI_TEZOS_REVEAL_PREIMAGE,
+        },
     };
 
     #[test]
@@ -354,6 +464,185 @@ mod tests {
             .all(|b: u8| b == 0));
     }
 
+    #[test]
+    fn test_reveal() {
+        type ML = M1M;
+        type L = PvmLayout<ML, TestInstructionCacheLayout>;
+
+        // Setup PVM
+        let (mut backend, placed) = InMemoryBackend::<L>::new();
+        let space = backend.allocate(placed);
+        let mut pvm = Pvm::<ML, TestInstructionCacheLayout, _>::bind(space);
+        pvm.reset();
+
+        let hash_addr = start_of_main_memory::<ML>();
+        let buffer_addr = hash_addr + PREIMAGE_HASH_SIZE as u64;
+
+        const BUFFER_LEN: usize = 1024;
+
+        let mut hash = [0u8; PREIMAGE_HASH_SIZE];
+        hash.try_fill(&mut thread_rng()).unwrap();
+        pvm.machine_state.bus.write_all(hash_addr, &hash).unwrap();
+
+        // Configure machine for 'sbi_tezos_reveal_preimage'
+        pvm.machine_state.hart.xregisters.write(a0, hash_addr);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a1, PREIMAGE_HASH_SIZE as u64);
+        pvm.machine_state.hart.xregisters.write(a2, buffer_addr);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a3, BUFFER_LEN as u64);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a7, SBI_FIRMWARE_TEZOS);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a6, SBI_TEZOS_REVEAL_PREIMAGE);
+
+        // Without a reveal hook, the PVM waits for the preimage
+        let outcome =
+            pvm.handle_exception(&mut Default::default(), EnvironException::EnvCallFromUMode);
+        assert!(!outcome);
+        assert_eq!(pvm.status(), PvmStatus::WaitingForReveal);
+        assert_eq!(pvm.reveal_request(), Some(RevealRequest::Preimage(hash)));
+        assert_eq!(pvm.reveal_request().unwrap().to_bytes()[1..], hash);
+
+        // Inputs can't be provided instead of the preimage
+        assert!(!pvm.provide_input(0, 0, &[]));
+
+        let mut preimage = [0u8; BUFFER_LEN + 10];
+        preimage.try_fill(&mut thread_rng()).unwrap();
+        assert!(pvm.provide_reveal_response(&preimage));
+        assert_eq!(pvm.status(), PvmStatus::Evaluating);
+        assert_eq!(pvm.reveal_request(), None);
+
+        // The preimage is truncated to the size of the buffer
+        assert_eq!(
+            pvm.machine_state.hart.xregisters.read(a0) as usize,
+            BUFFER_LEN
+        );
+        let mut written = [0u8; BUFFER_LEN + 10];
+        pvm.machine_state
+            .bus
+            .read_all(buffer_addr, &mut written)
+            .unwrap();
+        assert_eq!(written[..BUFFER_LEN], preimage[..BUFFER_LEN]);
+        assert!(written[BUFFER_LEN..].iter().all(|&b| b == 0));
+
+        // Configure machine for 'sbi_tezos_reveal_dal_page'
+        pvm.machine_state.hart.xregisters.write(a0, -5i64 as u64);
+        pvm.machine_state.hart.xregisters.write(a1, 3);
+        pvm.machine_state.hart.xregisters.write(a2, 7);
+        pvm.machine_state.hart.xregisters.write(a3, buffer_addr);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a4, BUFFER_LEN as u64);
+        pvm.machine_state
+            .hart
+            .xregisters
+            .write(a6, SBI_TEZOS_REVEAL_DAL_PAGE);
+
+        // The reveal hook answers the request directly
+        let mut requests = Vec::new();
+        let mut hooks = PvmHooks::new(|_| {}).with_reveal_hook(|request| {
+            requests.push(request.clone());
+            Some(vec![42; 16])
+        });
+        let outcome = pvm.handle_exception(&mut hooks, EnvironException::EnvCallFromUMode);
+        assert!(outcome);
+        mem::drop(hooks);
+
+        assert_eq!(pvm.status(), PvmStatus::Evaluating);
+        assert_eq!(pvm.machine_state.hart.xregisters.read(a0), 16);
+        assert_eq!(pvm.machine_state.bus.read(buffer_addr + 15), Ok(42u8));
+
+        let request = RevealRequest::DalPage {
+            published_level: -5,
+            slot_index: 3,
+            page_index: 7,
+        };
+        assert_eq!(request.to_bytes(), [2, 255, 255, 255, 251, 3, 0, 7]);
+        assert_eq!(requests, [request]);
+    }
+
+    #[test]
+    fn test_outbox_write() {
+        type ML = M1M;
+        type L = PvmLayout<ML, TestInstructionCacheLayout>;
+
+        // Setup PVM
+        let (mut backend, placed) = InMemoryBackend::<L>::new();
+        let space = backend.allocate(placed);
+        let mut pvm = Pvm::<ML, TestInstructionCacheLayout, _>::bind(space);
+        pvm.reset();
+
+        let buffer_addr = start_of_main_memory::<ML>();
+
+        let mut message = [0u8; MAX_OUTPUT_SIZE];
+        message.try_fill(&mut thread_rng()).unwrap();
+        pvm.machine_state
+            .bus
+            .write_all(buffer_addr, &message)
+            .unwrap();
+
+        // Messages are written at the level of the last input
+        pvm.status.write(PvmStatus::WaitingForInput);
+        pvm.machine_state.hart.xregisters.write(a0, buffer_addr);
+        pvm.machine_state.hart.xregisters.write(a1, 0);
+        pvm.machine_state.hart.xregisters.write(a2, buffer_addr);
+        pvm.machine_state.hart.xregisters.write(a3, buffer_addr);
+        assert!(pvm.provide_input(7, 0, &[]));
+
+        // Configure machine for 'sbi_tezos_outbox_write'
+        let write_message = |pvm: &mut Pvm<ML, TestInstructionCacheLayout, _>, len: usize| {
+            pvm.machine_state.hart.xregisters.write(a0, buffer_addr);
+            pvm.machine_state.hart.xregisters.write(a1, len as u64);
+            pvm.machine_state
+                .hart
+                .xregisters
+                .write(a7, SBI_FIRMWARE_TEZOS);
+            pvm.machine_state
+                .hart
+                .xregisters
+                .write(a6, SBI_TEZOS_OUTBOX_WRITE);
+
+            let outcome =
+                pvm.handle_exception(&mut Default::default(), EnvironException::EnvCallFromUMode);
+            assert!(outcome);
+            pvm.machine_state.hart.xregisters.read(a0) as i64
+        };
+
+        for index in 0..MAX_OUTBOX_MESSAGES_PER_LEVEL {
+            assert_eq!(write_message(&mut pvm, index), 0);
+        }
+
+        // The outbox is full for this level
+        assert_eq!(write_message(&mut pvm, 1), SbiError::Denied as i64);
+
+        assert_eq!(pvm.outbox().level(), 7);
+        assert_eq!(pvm.outbox().len(), MAX_OUTBOX_MESSAGES_PER_LEVEL);
+        assert_eq!(pvm.outbox().message(42).unwrap(), message[..42]);
+
+        // Inputs of the next level start a new outbox
+        pvm.status.write(PvmStatus::WaitingForInput);
+        assert!(pvm.provide_input(8, 0, &[]));
+        assert!(pvm.outbox().is_empty());
+
+        // Messages larger than the maximum output size are rejected
+        assert_eq!(
+            write_message(&mut pvm, MAX_OUTPUT_SIZE + 1),
+            SbiError::InvalidParam as i64
+        );
+        assert_eq!(write_message(&mut pvm, MAX_OUTPUT_SIZE), 0);
+        assert_eq!(pvm.outbox().messages(), [message.to_vec()]);
+    }
+
     #[test]
     fn test_write_debug() {
         type ML = M1M;
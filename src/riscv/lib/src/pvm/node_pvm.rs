@@ -8,15 +8,22 @@ use crate::{
         bus::main_memory::M100M, instruction_cache::DefaultInstructionCacheLayout, mode::Mode,
     },
     program::Program,
-    pvm::common::{Pvm, PvmHooks, PvmLayout, PvmStatus},
+    pvm::common::{Pvm, PvmHooks, PvmLayout, PvmStatus, RevealRequest},
     state_backend::{
         self,
         memory_backend::{InMemoryBackend, SliceManager, SliceManagerRO},
+        merkle::{MerkleLayout, MerkleProof, ProofError},
+        proof_backend::{AccessLog, ProofManager},
         Backend, Layout,
     },
     storage::{self, Hash, Repo},
 };
-use std::{ops::Bound, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::{Bound, Range},
+    path::Path,
+    sync::OnceLock,
+};
 use thiserror::Error;
 
 pub type StateLayout = (
@@ -67,6 +74,21 @@ impl<M: state_backend::ManagerBase> State<M> {
         self.message_counter.write(0);
         self.tick.write(0);
     }
+
+    /// Perform one evaluation step.
+    fn step(&mut self, pvm_hooks: &mut PvmHooks)
+    where
+        M: state_backend::ManagerReadWrite,
+    {
+        self.pvm.eval_one(pvm_hooks);
+        self.tick.write(self.tick.read() + 1);
+    }
+}
+
+/// Leaves of the Merkle tree of the PVM state
+fn merkle_layout() -> &'static MerkleLayout {
+    static LAYOUT: OnceLock<MerkleLayout> = OnceLock::new();
+    LAYOUT.get_or_init(MerkleLayout::of::<StateLayout>)
 }
 
 #[derive(Error, Debug)]
@@ -75,6 +97,31 @@ pub enum PvmError {
     SerializationError(String),
 }
 
+/// Proof of a single evaluation step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepProof {
+    /// State before the step, revealing every leaf that the step accesses
+    pub initial_state: MerkleProof,
+
+    /// Hash of the state after the step
+    pub final_state_hash: Hash,
+}
+
+impl StepProof {
+    /// Hash of the state before the step.
+    pub fn initial_state_hash(&self) -> Hash {
+        self.initial_state.root_hash()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PvmError> {
+        bincode::serialize(self).map_err(|e| PvmError::SerializationError(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PvmError> {
+        bincode::deserialize(bytes).map_err(|e| PvmError::SerializationError(e.to_string()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NodePvm {
     backend: InMemoryBackend<StateLayout>,
@@ -146,10 +193,68 @@ impl NodePvm {
     }
 
     pub fn compute_step(&self, pvm_hooks: &mut PvmHooks) -> Self {
-        self.with_new_backend(|state| {
-            state.pvm.eval_one(pvm_hooks);
-            state.tick.write(state.tick.read() + 1);
-        })
+        self.with_new_backend(|state| state.step(pvm_hooks))
+    }
+
+    /// Run one step on `backend`, returning the byte ranges of the state it accessed.
+    fn recorded_step(
+        backend: &mut InMemoryBackend<StateLayout>,
+        pvm_hooks: &mut PvmHooks,
+    ) -> Vec<Range<usize>> {
+        let log = AccessLog::default();
+        {
+            let placed = <StateLayout as Layout>::placed().into_location();
+            let mut manager = ProofManager::new(backend.borrow_mut(), &log);
+            let mut state = State::bind(StateLayout::allocate(&mut manager, placed));
+            state.step(pvm_hooks);
+        }
+        log.into_accesses()
+    }
+
+    /// Perform one evaluation step, like [`NodePvm::compute_step`], and produce
+    /// a proof of it.
+    pub fn prove_step(&self, pvm_hooks: &mut PvmHooks) -> (Self, StepProof) {
+        let mut backend = self.backend.clone();
+        let accesses = Self::recorded_step(&mut backend, pvm_hooks);
+
+        let layout = merkle_layout();
+        let accessed = layout.leaves_overlapping(&accesses);
+        let initial_state = layout.prove(self.backend.borrow(), &accessed);
+        let final_state_hash = layout.update(&initial_state, backend.borrow()).root_hash();
+
+        let proof = StepProof {
+            initial_state,
+            final_state_hash,
+        };
+        (Self { backend }, proof)
+    }
+
+    /// Re-execute the step proven by `proof`, using only the state it reveals,
+    /// and check that it results in the claimed final state.
+    ///
+    /// The caller must still check that [`StepProof::initial_state_hash`] is the
+    /// hash of the state the step is supposed to start from.
+    pub fn verify_step(proof: &StepProof, pvm_hooks: &mut PvmHooks) -> bool {
+        Self::replay_step(proof, pvm_hooks).is_ok_and(|hash| hash == proof.final_state_hash)
+    }
+
+    fn replay_step(proof: &StepProof, pvm_hooks: &mut PvmHooks) -> Result<Hash, ProofError> {
+        let layout = merkle_layout();
+        let mut backend = InMemoryBackend::<StateLayout>::new().0;
+        let revealed = layout.reveal(&proof.initial_state, backend.borrow_mut())?;
+
+        let accesses = Self::recorded_step(&mut backend, pvm_hooks);
+
+        // Leaves that are not revealed have been read as zeroes, so the
+        // re-execution is only faithful if the step did not access any of them.
+        let accessed = layout.leaves_overlapping(&accesses);
+        if let Some(leaf) = accessed.difference(&revealed).next() {
+            return Err(ProofError::MissingLeaf(*leaf));
+        }
+
+        Ok(layout
+            .update(&proof.initial_state, backend.borrow())
+            .root_hash())
     }
 
     pub fn compute_step_many(&self, pvm_hooks: &mut PvmHooks, max_steps: usize) -> (Self, i64) {
@@ -162,10 +267,9 @@ impl NodePvm {
         (Self { backend }, steps as i64)
     }
 
+    /// Root hash of the Merkle tree of the state.
     pub fn hash(&self) -> Hash {
-        tezos_crypto_rs::blake2b::digest_256(self.to_bytes())
-            .try_into()
-            .unwrap()
+        merkle_layout().root_hash(self.to_bytes())
     }
 
     pub fn set_input_message(&self, level: u32, message_counter: u64, input: Vec<u8>) -> Self {
@@ -197,6 +301,47 @@ impl NodePvm {
         })
     }
 
+    /// Get the data requested by the kernel, if it is waiting for a reveal.
+    pub fn get_reveal_request(&self) -> Option<RevealRequest> {
+        self.with_backend(|state| state.pvm.reveal_request())
+    }
+
+    /// Get the messages written by the kernel at the given level. Only the outbox
+    /// of the level currently being processed is kept in the state.
+    pub fn get_outbox(&self, level: u32) -> Vec<Vec<u8>> {
+        self.with_backend(|state| {
+            let outbox = state.pvm.outbox();
+            if outbox.level() == level {
+                outbox.messages()
+            } else {
+                Vec::new()
+            }
+        })
+    }
+
+    /// Get the message at position `index` in the outbox of the given level.
+    pub fn get_outbox_message(&self, level: u32, index: usize) -> Option<Vec<u8>> {
+        self.with_backend(|state| {
+            let outbox = state.pvm.outbox();
+            if outbox.level() == level {
+                outbox.message(index)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn provide_reveal_response(&self, data: &[u8]) -> Self {
+        self.with_new_backend(|state| {
+            assert!(
+                state.pvm.provide_reveal_response(data),
+                "Cannot accept reveal response in current state ({})",
+                state.pvm.status()
+            );
+            state.tick.write(state.tick.read() + 1);
+        })
+    }
+
     pub fn to_bytes(&self) -> &[u8] {
         self.backend.borrow()
     }
@@ -0,0 +1,196 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Outbox of the PVM
+//!
+//! Messages written by the kernel are kept in the PVM state, so that they are
+//! part of its commitment. Only the messages of the level currently being
+//! processed are kept: they are discarded when the first input of another
+//! level is provided.
+
+use crate::state_backend::{
+    AllocatedOf, Array, Atom, Cell, Cells, ManagerBase, ManagerRead, ManagerReadWrite,
+    ManagerWrite, Ref,
+};
+use tezos_smart_rollup_constants::core::MAX_OUTPUT_SIZE;
+use thiserror::Error;
+
+/// Maximum number of messages the kernel can write at each level. This matches
+/// the protocol's default `max_outbox_messages_per_level`.
+pub const MAX_OUTBOX_MESSAGES_PER_LEVEL: usize = 100;
+
+/// Number of bytes reserved for the messages of a level
+const OUTBOX_SIZE: usize = MAX_OUTBOX_MESSAGES_PER_LEVEL * MAX_OUTPUT_SIZE;
+
+/// Outbox state layout
+pub type OutboxLayout = (
+    Atom<u32>,
+    Atom<u32>,
+    Array<u32, MAX_OUTBOX_MESSAGES_PER_LEVEL>,
+    Array<u8, OUTBOX_SIZE>,
+);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum OutboxError {
+    #[error("Outbox message is larger than {MAX_OUTPUT_SIZE} bytes")]
+    MessageTooLarge,
+
+    #[error("Outbox already holds {MAX_OUTBOX_MESSAGES_PER_LEVEL} messages for this level")]
+    Full,
+}
+
+/// Messages written by the kernel at the current level
+pub struct Outbox<M: ManagerBase> {
+    level: Cell<u32, M>,
+    count: Cell<u32, M>,
+    lengths: Cells<u32, MAX_OUTBOX_MESSAGES_PER_LEVEL, M>,
+    messages: Cells<u8, OUTBOX_SIZE, M>,
+}
+
+impl<M: ManagerBase> Outbox<M> {
+    /// Bind the outbox to the given allocated region.
+    pub fn bind(space: AllocatedOf<OutboxLayout, M>) -> Self {
+        Self {
+            level: space.0,
+            count: space.1,
+            lengths: Cells::bind(space.2),
+            messages: Cells::bind(space.3),
+        }
+    }
+
+    /// Obtain a structure with references to the bound regions of this type.
+    pub fn struct_ref(&self) -> AllocatedOf<OutboxLayout, Ref<'_, M>> {
+        (
+            self.level.struct_ref(),
+            self.count.struct_ref(),
+            self.lengths.struct_ref(),
+            self.messages.struct_ref(),
+        )
+    }
+
+    /// Reset the outbox to an empty outbox at level 0.
+    pub fn reset(&mut self)
+    where
+        M: ManagerWrite,
+    {
+        self.level.write(0);
+        self.count.write(0);
+    }
+
+    /// Level of the messages in the outbox.
+    pub fn level(&self) -> u32
+    where
+        M: ManagerRead,
+    {
+        self.level.read()
+    }
+
+    /// Number of messages in the outbox.
+    pub fn len(&self) -> usize
+    where
+        M: ManagerRead,
+    {
+        self.count.read() as usize
+    }
+
+    /// Whether the outbox holds no messages.
+    pub fn is_empty(&self) -> bool
+    where
+        M: ManagerRead,
+    {
+        self.len() == 0
+    }
+
+    /// Message at position `index` in the outbox, if any.
+    pub fn message(&self, index: usize) -> Option<Vec<u8>>
+    where
+        M: ManagerRead,
+    {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut message = vec![0; self.lengths.read(index) as usize];
+        self.messages
+            .read_some(index * MAX_OUTPUT_SIZE, &mut message);
+        Some(message)
+    }
+
+    /// All messages in the outbox, in the order they were written.
+    pub fn messages(&self) -> Vec<Vec<u8>>
+    where
+        M: ManagerRead,
+    {
+        (0..self.len())
+            .filter_map(|index| self.message(index))
+            .collect()
+    }
+
+    /// Discard the messages of previous levels if `level` is a new level.
+    pub fn start_level(&mut self, level: u32)
+    where
+        M: ManagerReadWrite,
+    {
+        if self.level.read() != level {
+            self.level.write(level);
+            self.count.write(0);
+        }
+    }
+
+    /// Append a message to the outbox, enforcing the per-level limits.
+    pub fn push(&mut self, message: &[u8]) -> Result<(), OutboxError>
+    where
+        M: ManagerReadWrite,
+    {
+        if message.len() > MAX_OUTPUT_SIZE {
+            return Err(OutboxError::MessageTooLarge);
+        }
+
+        let index = self.len();
+        if index >= MAX_OUTBOX_MESSAGES_PER_LEVEL {
+            return Err(OutboxError::Full);
+        }
+
+        self.messages.write_some(index * MAX_OUTPUT_SIZE, message);
+        self.lengths.write(index, message.len() as u32);
+        self.count.write(index as u32 + 1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend_test, create_backend, create_state};
+
+    backend_test!(test_outbox_limits, F, {
+        let mut backend = create_backend!(OutboxLayout, F);
+        let mut outbox = create_state!(Outbox, OutboxLayout, F, backend);
+        outbox.reset();
+        outbox.start_level(5);
+
+        for index in 0..MAX_OUTBOX_MESSAGES_PER_LEVEL {
+            assert_eq!(outbox.push(&vec![index as u8; index * 41]), Ok(()));
+        }
+        assert_eq!(outbox.push(&[]), Err(OutboxError::Full));
+
+        assert_eq!(outbox.level(), 5);
+        assert_eq!(outbox.len(), MAX_OUTBOX_MESSAGES_PER_LEVEL);
+        assert_eq!(outbox.message(3), Some(vec![3; 123]));
+        assert_eq!(outbox.message(MAX_OUTBOX_MESSAGES_PER_LEVEL), None);
+
+        // Messages are kept for inputs of the same level only
+        outbox.start_level(5);
+        assert_eq!(outbox.len(), MAX_OUTBOX_MESSAGES_PER_LEVEL);
+        outbox.start_level(6);
+        assert!(outbox.is_empty());
+
+        assert_eq!(
+            outbox.push(&[0; MAX_OUTPUT_SIZE + 1]),
+            Err(OutboxError::MessageTooLarge)
+        );
+        assert_eq!(outbox.push(&[1, 2, 3]), Ok(()));
+        assert_eq!(outbox.messages(), [vec![1, 2, 3]]);
+    });
+}
@@ -2,26 +2,27 @@
 //
 // SPDX-License-Identifier: MIT
 
-use super::{PvmHooks, PvmStatus};
+use super::{Outbox, OutboxError, PvmHooks, PvmStatus, RevealRequest};
 use crate::{
     machine_state::{
         bus::{main_memory::MainMemoryLayout, AddressableRead, AddressableWrite},
         instruction_cache::InstructionCacheLayout,
-        registers::{a0, a1, a2, a3, a6, a7, XValue},
+        registers::{a0, a1, a2, a3, a4, a6, a7, XValue},
         AccessType, MachineState,
     },
     parser::instruction::Instr,
-    state_backend::{CellRead, CellReadWrite, CellWrite, ManagerReadWrite},
+    state_backend::{CellRead, CellReadWrite, CellWrite, ManagerRead, ManagerReadWrite},
     traps::EnvironException,
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
 use tezos_smart_rollup_constants::{
-    core::MAX_INPUT_MESSAGE_SIZE,
+    core::{MAX_INPUT_MESSAGE_SIZE, MAX_OUTPUT_SIZE, PREIMAGE_HASH_SIZE},
     riscv::{
-        SbiError, SBI_CONSOLE_PUTCHAR, SBI_DBCN, SBI_DBCN_CONSOLE_WRITE_BYTE, SBI_FIRMWARE_TEZOS,
-        SBI_SHUTDOWN, SBI_SRST, SBI_SRST_SYSTEM_RESET, SBI_TEZOS_BLAKE2B_HASH256,
-        SBI_TEZOS_ED25519_SIGN, SBI_TEZOS_ED25519_VERIFY, SBI_TEZOS_INBOX_NEXT,
-        SBI_TEZOS_METADATA_REVEAL,
+        SbiError, MAX_REVEAL_SIZE, SBI_CONSOLE_PUTCHAR, SBI_DBCN, SBI_DBCN_CONSOLE_WRITE_BYTE,
+        SBI_FIRMWARE_TEZOS, SBI_SHUTDOWN, SBI_SRST, SBI_SRST_SYSTEM_RESET,
+        SBI_TEZOS_BLAKE2B_HASH256, SBI_TEZOS_ED25519_SIGN, SBI_TEZOS_ED25519_VERIFY,
+        SBI_TEZOS_INBOX_NEXT, SBI_TEZOS_METADATA_REVEAL, SBI_TEZOS_OUTBOX_WRITE,
+        SBI_TEZOS_REVEAL_DAL_PAGE, SBI_TEZOS_REVEAL_PREIMAGE,
     },
 };
 
@@ -205,6 +206,128 @@ where
     true
 }
 
+/// Decode the request made by the reveal SBI call `sbi_function`.
+fn read_reveal_request<ML, ICL, M>(
+    machine: &MachineState<ML, ICL, M>,
+    sbi_function: u64,
+) -> Result<RevealRequest, SbiError>
+where
+    ML: MainMemoryLayout,
+    ICL: InstructionCacheLayout,
+    M: ManagerRead,
+{
+    match sbi_function {
+        SBI_TEZOS_REVEAL_PREIMAGE => {
+            let arg_hash_addr = machine.hart.xregisters.read(a0);
+            let arg_hash_len = machine.hart.xregisters.read(a1);
+
+            if arg_hash_len != PREIMAGE_HASH_SIZE as u64 {
+                return Err(SbiError::InvalidParam);
+            }
+
+            let hash_addr = machine.translate_without_cache(arg_hash_addr, AccessType::Load)?;
+            let mut hash = [0u8; PREIMAGE_HASH_SIZE];
+            machine.bus.read_all(hash_addr, &mut hash)?;
+
+            Ok(RevealRequest::Preimage(hash))
+        }
+        SBI_TEZOS_REVEAL_DAL_PAGE => Ok(RevealRequest::DalPage {
+            published_level: machine.hart.xregisters.read(a0) as i32,
+            slot_index: machine.hart.xregisters.read(a1) as u8,
+            page_index: machine.hart.xregisters.read(a2) as i16,
+        }),
+        _ => Err(SbiError::NotSupported),
+    }
+}
+
+/// Write `data` to the buffer given to the reveal SBI call `sbi_function`.
+/// Returns the number of bytes written.
+fn write_reveal_response<ML, ICL, M>(
+    machine: &mut MachineState<ML, ICL, M>,
+    sbi_function: u64,
+    data: &[u8],
+) -> Result<u64, SbiError>
+where
+    ML: MainMemoryLayout,
+    ICL: InstructionCacheLayout,
+    M: ManagerReadWrite,
+{
+    let (arg_buffer_addr, arg_buffer_size) = match sbi_function {
+        SBI_TEZOS_REVEAL_PREIMAGE => (
+            machine.hart.xregisters.read(a2),
+            machine.hart.xregisters.read(a3),
+        ),
+        SBI_TEZOS_REVEAL_DAL_PAGE => (
+            machine.hart.xregisters.read(a3),
+            machine.hart.xregisters.read(a4),
+        ),
+        _ => return Err(SbiError::NotSupported),
+    };
+
+    let phys_buffer_addr = machine.translate(arg_buffer_addr, AccessType::Store)?;
+
+    // Like inputs, reveals are capped to keep the proofs of reveal steps small.
+    let len = data
+        .len()
+        .min(arg_buffer_size as usize)
+        .min(MAX_REVEAL_SIZE);
+    machine.bus.write_all(phys_buffer_addr, &data[..len])?;
+
+    Ok(len as u64)
+}
+
+/// Get the data requested by the kernel, if the machine is waiting for a reveal.
+pub fn reveal_request<S, ML, ICL, M>(
+    status: &S,
+    machine: &MachineState<ML, ICL, M>,
+) -> Option<RevealRequest>
+where
+    S: CellRead<Value = PvmStatus>,
+    ML: MainMemoryLayout,
+    ICL: InstructionCacheLayout,
+    M: ManagerRead,
+{
+    match status.read() {
+        PvmStatus::WaitingForReveal => {}
+        _ => return None,
+    }
+
+    // The arguments have been validated by the SBI call.
+    let sbi_function = machine.hart.xregisters.read(a6);
+    read_reveal_request(machine, sbi_function).ok()
+}
+
+/// Provide the data requested by the kernel. Returns `false` if the machine
+/// is not expecting a reveal.
+pub fn provide_reveal_response<S, ML, ICL, M>(
+    status: &mut S,
+    machine: &mut MachineState<ML, ICL, M>,
+    data: &[u8],
+) -> bool
+where
+    S: CellReadWrite<Value = PvmStatus>,
+    ICL: InstructionCacheLayout,
+    ML: MainMemoryLayout,
+    M: ManagerReadWrite,
+{
+    // This method should only do something when we're waiting for a reveal.
+    match status.read() {
+        PvmStatus::WaitingForReveal => {}
+        _ => return false,
+    }
+
+    // We're evaluating again after this.
+    status.write(PvmStatus::Evaluating);
+
+    // These arguments should have been set by the previous SBI call.
+    let sbi_function = machine.hart.xregisters.read(a6);
+    sbi_wrap(machine, |machine| {
+        write_reveal_response(machine, sbi_function, data)
+    });
+
+    true
+}
+
 /// Handle a [SBI_TEZOS_INBOX_NEXT] call.
 #[inline]
 fn handle_tezos_inbox_next<S>(status: &mut S)
@@ -225,6 +348,64 @@ where
     status.write(PvmStatus::WaitingForMetadata);
 }
 
+/// Handle a [SBI_TEZOS_REVEAL_PREIMAGE] or [SBI_TEZOS_REVEAL_DAL_PAGE] call.
+#[inline]
+fn handle_tezos_reveal<S, ML, ICL, M>(
+    status: &mut S,
+    machine: &mut MachineState<ML, ICL, M>,
+    hooks: &mut PvmHooks,
+    sbi_function: u64,
+) where
+    S: CellWrite<Value = PvmStatus>,
+    ML: MainMemoryLayout,
+    ICL: InstructionCacheLayout,
+    M: ManagerReadWrite,
+{
+    let request = match read_reveal_request(machine, sbi_function) {
+        Ok(request) => request,
+        Err(error) => return sbi_return_error(machine, error),
+    };
+
+    match (hooks.reveal_hook)(&request) {
+        Some(data) => sbi_wrap(machine, |machine| {
+            write_reveal_response(machine, sbi_function, &data)
+        }),
+        // Prepare the EE state for a reveal tick.
+        None => status.write(PvmStatus::WaitingForReveal),
+    }
+}
+
+/// Handle a [SBI_TEZOS_OUTBOX_WRITE] call.
+#[inline]
+fn handle_tezos_outbox_write<ML, ICL, M>(
+    outbox: &mut Outbox<M>,
+    machine: &mut MachineState<ML, ICL, M>,
+) -> Result<u64, SbiError>
+where
+    ML: MainMemoryLayout,
+    ICL: InstructionCacheLayout,
+    M: ManagerReadWrite,
+{
+    let arg_msg_addr = machine.hart.xregisters.read(a0);
+    let arg_msg_len = machine.hart.xregisters.read(a1);
+
+    // Check the length before reading the message, as it determines how much memory we read.
+    if arg_msg_len > MAX_OUTPUT_SIZE as u64 {
+        return Err(SbiError::InvalidParam);
+    }
+
+    let msg_addr = machine.translate(arg_msg_addr, AccessType::Load)?;
+    let mut msg_bytes = vec![0u8; arg_msg_len as usize];
+    machine.bus.read_all(msg_addr, &mut msg_bytes)?;
+
+    outbox.push(&msg_bytes).map_err(|error| match error {
+        OutboxError::MessageTooLarge => SbiError::InvalidParam,
+        OutboxError::Full => SbiError::Denied,
+    })?;
+
+    Ok(0)
+}
+
 /// Produce a Ed25519 signature.
 #[inline]
 fn handle_tezos_ed25519_sign<ML, ICL, M>(
@@ -394,6 +575,7 @@ where
 #[inline]
 pub fn handle_call<S, ML, ICL, M>(
     status: &mut S,
+    outbox: &mut Outbox<M>,
     machine: &mut MachineState<ML, ICL, M>,
     hooks: &mut PvmHooks,
     env_exception: EnvironException,
@@ -442,6 +624,12 @@ where
                 SBI_TEZOS_ED25519_SIGN => sbi_wrap(machine, handle_tezos_ed25519_sign),
                 SBI_TEZOS_ED25519_VERIFY => sbi_wrap(machine, handle_tezos_ed25519_verify),
                 SBI_TEZOS_BLAKE2B_HASH256 => sbi_wrap(machine, handle_tezos_blake2b_hash256),
+                SBI_TEZOS_REVEAL_PREIMAGE | SBI_TEZOS_REVEAL_DAL_PAGE => {
+                    handle_tezos_reveal(status, machine, hooks, sbi_function)
+                }
+                SBI_TEZOS_OUTBOX_WRITE => sbi_wrap(machine, |machine| {
+                    handle_tezos_outbox_write(outbox, machine)
+                }),
                 _ => handle_not_supported(machine),
             }
         }
@@ -61,7 +61,9 @@ mod elems;
 mod enums;
 mod layout;
 pub mod memory_backend;
+pub mod merkle;
 pub mod owned_backend;
+pub mod proof_backend;
 mod region;
 
 pub use alloc::*;
@@ -0,0 +1,358 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Merkle commitments over state layouts
+//!
+//! The leaves of the tree are the regions allocated by a [`Layout`], in the
+//! order in which they are placed. Regions larger than [`MERKLE_LEAF_SIZE`]
+//! are split into several leaves, so that proving an access to a region only
+//! requires revealing the part of it that has been accessed. Padding between
+//! regions is not part of the commitment.
+//!
+//! A [`MerkleProof`] is a partial tree: subtrees that are not needed are
+//! replaced by their hash.
+
+use super::{Elem, Layout, Location, ManagerAlloc, ManagerBase};
+use crate::storage::Hash;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, ops::Range};
+use thiserror::Error;
+
+/// Maximum number of bytes in a leaf of the Merkle tree
+pub const MERKLE_LEAF_SIZE: usize = 4096;
+
+/// Prefix of the pre-image of leaf hashes
+const LEAF_TAG: u8 = 0;
+
+/// Prefix of the pre-image of inner node hashes
+const NODE_TAG: u8 = 1;
+
+fn hash(tag: u8, parts: &[&[u8]]) -> Hash {
+    let mut preimage = Vec::with_capacity(1 + parts.iter().map(|part| part.len()).sum::<usize>());
+    preimage.push(tag);
+    for part in parts {
+        preimage.extend_from_slice(part);
+    }
+
+    // This is safe to unwrap because `digest_256` always returns
+    // a `DIGEST_SIZE`-long `Vec<u8>`.
+    tezos_crypto_rs::blake2b::digest_256(&preimage)
+        .try_into()
+        .unwrap()
+}
+
+fn leaf_hash(data: &[u8]) -> Hash {
+    hash(LEAF_TAG, &[data])
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    hash(NODE_TAG, &[left, right])
+}
+
+/// Splits the leaves `lo..hi` of a subtree between its two children.
+fn split(lo: usize, hi: usize) -> usize {
+    lo + (hi - lo).div_ceil(2)
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    #[error("Proof does not have the shape of the state tree")]
+    InvalidShape,
+
+    #[error("Leaf {0} of the proof has an unexpected size")]
+    InvalidLeaf(usize),
+
+    #[error("Leaf {0} is accessed but not revealed by the proof")]
+    MissingLeaf(usize),
+}
+
+/// Partial Merkle tree of a state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MerkleProof {
+    /// Subtree that is not revealed, given by its hash
+    Blinded(Hash),
+
+    /// Leaf whose contents are revealed
+    Leaf(Vec<u8>),
+
+    /// Inner node with its left and right subtrees
+    Node(Box<MerkleProof>, Box<MerkleProof>),
+}
+
+impl MerkleProof {
+    /// Root hash of the tree.
+    pub fn root_hash(&self) -> Hash {
+        match self {
+            Self::Blinded(hash) => *hash,
+            Self::Leaf(data) => leaf_hash(data),
+            Self::Node(left, right) => node_hash(&left.root_hash(), &right.root_hash()),
+        }
+    }
+}
+
+/// Manager that only records the regions that are allocated
+struct RegionCollector {
+    regions: Vec<Range<usize>>,
+}
+
+impl ManagerBase for RegionCollector {
+    type Region<E: Elem, const LEN: usize> = ();
+
+    type DynRegion<const LEN: usize> = ();
+}
+
+impl ManagerAlloc for RegionCollector {
+    fn allocate_region<E: Elem, const LEN: usize>(
+        &mut self,
+        loc: Location<[E; LEN]>,
+    ) -> Self::Region<E, LEN> {
+        self.regions.push(loc.offset()..loc.offset() + loc.size());
+    }
+
+    fn allocate_dyn_region<const LEN: usize>(
+        &mut self,
+        loc: Location<[u8; LEN]>,
+    ) -> Self::DynRegion<LEN> {
+        self.allocate_region(loc)
+    }
+}
+
+/// Leaves of the Merkle tree of a [`Layout`], as byte ranges of the state storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleLayout {
+    leaves: Vec<Range<usize>>,
+}
+
+impl MerkleLayout {
+    /// Compute the leaves of the Merkle tree of layout `L`.
+    pub fn of<L: Layout>() -> Self {
+        let mut collector = RegionCollector {
+            regions: Vec::new(),
+        };
+        L::allocate(&mut collector, L::placed().into_location());
+
+        let mut regions = collector.regions;
+        regions.sort_by_key(|region| region.start);
+
+        let leaves = regions
+            .into_iter()
+            .flat_map(|region| {
+                region
+                    .clone()
+                    .step_by(MERKLE_LEAF_SIZE)
+                    .map(move |start| start..region.end.min(start + MERKLE_LEAF_SIZE))
+            })
+            .collect();
+
+        Self { leaves }
+    }
+
+    /// Byte ranges of the leaves, in order.
+    pub fn leaves(&self) -> &[Range<usize>] {
+        &self.leaves
+    }
+
+    /// Indices of the leaves that overlap with any of the given byte ranges.
+    pub fn leaves_overlapping<'a>(
+        &self,
+        ranges: impl IntoIterator<Item = &'a Range<usize>>,
+    ) -> BTreeSet<usize> {
+        let mut touched = BTreeSet::new();
+
+        for range in ranges {
+            let first = self.leaves.partition_point(|leaf| leaf.end <= range.start);
+            touched.extend(
+                self.leaves[first..]
+                    .iter()
+                    .take_while(|leaf| leaf.start < range.end)
+                    .enumerate()
+                    .map(|(i, _)| first + i),
+            );
+        }
+
+        touched
+    }
+
+    /// Root hash of the tree for the state storage `data`.
+    pub fn root_hash(&self, data: &[u8]) -> Hash {
+        self.subtree_hash(data, 0, self.leaves.len())
+    }
+
+    fn subtree_hash(&self, data: &[u8], lo: usize, hi: usize) -> Hash {
+        if hi - lo == 1 {
+            leaf_hash(&data[self.leaves[lo].clone()])
+        } else {
+            let mid = split(lo, hi);
+            node_hash(
+                &self.subtree_hash(data, lo, mid),
+                &self.subtree_hash(data, mid, hi),
+            )
+        }
+    }
+
+    /// Produce a proof revealing the leaves `revealed` of the state storage `data`.
+    pub fn prove(&self, data: &[u8], revealed: &BTreeSet<usize>) -> MerkleProof {
+        self.prove_subtree(data, revealed, 0, self.leaves.len())
+    }
+
+    fn prove_subtree(
+        &self,
+        data: &[u8],
+        revealed: &BTreeSet<usize>,
+        lo: usize,
+        hi: usize,
+    ) -> MerkleProof {
+        if revealed.range(lo..hi).next().is_none() {
+            MerkleProof::Blinded(self.subtree_hash(data, lo, hi))
+        } else if hi - lo == 1 {
+            MerkleProof::Leaf(data[self.leaves[lo].clone()].to_vec())
+        } else {
+            let mid = split(lo, hi);
+            MerkleProof::Node(
+                Box::new(self.prove_subtree(data, revealed, lo, mid)),
+                Box::new(self.prove_subtree(data, revealed, mid, hi)),
+            )
+        }
+    }
+
+    /// Copy the leaves revealed by `proof` into the state storage `data`,
+    /// returning their indices.
+    pub fn reveal(
+        &self,
+        proof: &MerkleProof,
+        data: &mut [u8],
+    ) -> Result<BTreeSet<usize>, ProofError> {
+        let mut revealed = BTreeSet::new();
+        self.reveal_subtree(proof, data, &mut revealed, 0, self.leaves.len())?;
+        Ok(revealed)
+    }
+
+    fn reveal_subtree(
+        &self,
+        proof: &MerkleProof,
+        data: &mut [u8],
+        revealed: &mut BTreeSet<usize>,
+        lo: usize,
+        hi: usize,
+    ) -> Result<(), ProofError> {
+        match proof {
+            MerkleProof::Blinded(_) => Ok(()),
+            MerkleProof::Leaf(leaf) if hi - lo == 1 => {
+                let target = &mut data[self.leaves[lo].clone()];
+                if target.len() != leaf.len() {
+                    return Err(ProofError::InvalidLeaf(lo));
+                }
+                target.copy_from_slice(leaf);
+                revealed.insert(lo);
+                Ok(())
+            }
+            MerkleProof::Node(left, right) if hi - lo > 1 => {
+                let mid = split(lo, hi);
+                self.reveal_subtree(left, data, revealed, lo, mid)?;
+                self.reveal_subtree(right, data, revealed, mid, hi)
+            }
+            _ => Err(ProofError::InvalidShape),
+        }
+    }
+
+    /// Replace the leaves revealed by `proof` with their contents in the state
+    /// storage `data`. The proof must have the shape of this layout's tree.
+    pub fn update(&self, proof: &MerkleProof, data: &[u8]) -> MerkleProof {
+        self.update_subtree(proof, data, 0, self.leaves.len())
+    }
+
+    fn update_subtree(
+        &self,
+        proof: &MerkleProof,
+        data: &[u8],
+        lo: usize,
+        hi: usize,
+    ) -> MerkleProof {
+        match proof {
+            MerkleProof::Blinded(hash) => MerkleProof::Blinded(*hash),
+            MerkleProof::Leaf(_) => MerkleProof::Leaf(data[self.leaves[lo].clone()].to_vec()),
+            MerkleProof::Node(left, right) => {
+                let mid = split(lo, hi);
+                MerkleProof::Node(
+                    Box::new(self.update_subtree(left, data, lo, mid)),
+                    Box::new(self.update_subtree(right, data, mid, hi)),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_backend::{Array, Atom};
+
+    type TestLayout = (Atom<u64>, Array<u8, 10000>, Atom<u8>);
+
+    #[test]
+    fn test_leaves_follow_layout() {
+        let layout = MerkleLayout::of::<TestLayout>();
+
+        assert_eq!(
+            layout.leaves(),
+            [0..8, 8..4104, 4104..8200, 8200..10008, 10008..10009]
+        );
+        assert_eq!(
+            layout.leaves_overlapping(&[0..1, 4100..4110]),
+            BTreeSet::from([0, 1, 2])
+        );
+        assert_eq!(
+            layout.leaves_overlapping(&[8200..8200, 10008..10009]),
+            BTreeSet::from([4])
+        );
+    }
+
+    #[test]
+    fn test_proof_reveals_leaves() {
+        let layout = MerkleLayout::of::<TestLayout>();
+        let size = TestLayout::placed().size();
+        let data: Vec<u8> = (0..size).map(|_| rand::random()).collect();
+        let root = layout.root_hash(&data);
+
+        let proof = layout.prove(&data, &BTreeSet::from([1, 4]));
+        assert_eq!(proof.root_hash(), root);
+
+        let mut partial = vec![0; size];
+        let revealed = layout.reveal(&proof, &mut partial).unwrap();
+        assert_eq!(revealed, BTreeSet::from([1, 4]));
+        assert_eq!(partial[8..4104], data[8..4104]);
+        assert_eq!(partial[10008], data[10008]);
+
+        // Updating the revealed leaves must match hashing the whole state.
+        let mut updated = data.clone();
+        updated[10008] = updated[10008].wrapping_add(1);
+        partial[10008] = updated[10008];
+        assert_eq!(
+            layout.update(&proof, &partial).root_hash(),
+            layout.root_hash(&updated)
+        );
+
+        // The shape of the proof must match the layout.
+        let leaf = MerkleProof::Leaf(data[0..8].to_vec());
+        assert_eq!(
+            layout.reveal(&leaf, &mut partial),
+            Err(ProofError::InvalidShape)
+        );
+        let blinded = || Box::new(MerkleProof::Blinded([0; 32]));
+        let short = MerkleProof::Node(
+            Box::new(MerkleProof::Node(
+                Box::new(MerkleProof::Node(
+                    Box::new(MerkleProof::Leaf(vec![0; 7])),
+                    blinded(),
+                )),
+                blinded(),
+            )),
+            blinded(),
+        );
+        assert_eq!(
+            layout.reveal(&short, &mut partial),
+            Err(ProofError::InvalidLeaf(0))
+        );
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Manager that records accesses to the state storage
+//!
+//! The [`ProofManager`] behaves like a [`SliceManager`], but logs the byte
+//! ranges of every read and write into an [`AccessLog`]. This tells which
+//! leaves of the [Merkle tree] must be revealed to prove a step.
+//!
+//! [Merkle tree]: super::merkle
+
+use super::{
+    memory_backend::SliceManager, Elem, Location, ManagerAlloc, ManagerBase, ManagerRead,
+    ManagerReadWrite, ManagerWrite,
+};
+use std::{cell::RefCell, mem, ops::Range};
+
+/// Byte ranges of the state storage that have been accessed
+#[derive(Debug, Default)]
+pub struct AccessLog {
+    accesses: RefCell<Vec<Range<usize>>>,
+}
+
+impl AccessLog {
+    /// Record an access to `len` bytes at `offset`.
+    #[inline]
+    fn record(&self, offset: usize, len: usize) {
+        self.accesses.borrow_mut().push(offset..offset + len);
+    }
+
+    /// Obtain the accessed byte ranges.
+    pub fn into_accesses(self) -> Vec<Range<usize>> {
+        self.accesses.into_inner()
+    }
+}
+
+/// Region of the state storage whose accesses are recorded
+pub struct ProofRegion<'backend, E, const LEN: usize> {
+    region: &'backend mut [E; LEN],
+    offset: usize,
+    log: &'backend AccessLog,
+}
+
+impl<'backend, E, const LEN: usize> ProofRegion<'backend, E, LEN> {
+    /// Record an access to `count` elements starting at element `index`.
+    #[inline]
+    fn record(&self, index: usize, count: usize) {
+        let size = mem::size_of::<E>();
+        self.log.record(self.offset + index * size, count * size)
+    }
+
+    /// Record an access to `len` bytes at byte `address`.
+    #[inline]
+    fn record_bytes(&self, address: usize, len: usize) {
+        self.log.record(self.offset + address, len)
+    }
+}
+
+/// Manager for in-memory backing storage that records accesses
+pub struct ProofManager<'backend> {
+    inner: SliceManager<'backend>,
+    log: &'backend AccessLog,
+}
+
+impl<'backend> ProofManager<'backend> {
+    /// Manage the given slice, recording accesses into `log`.
+    pub fn new(backing_storage: &'backend mut [u8], log: &'backend AccessLog) -> Self {
+        Self {
+            inner: SliceManager::new(backing_storage),
+            log,
+        }
+    }
+}
+
+impl<'backend> ManagerBase for ProofManager<'backend> {
+    type Region<E: Elem, const LEN: usize> = ProofRegion<'backend, E, LEN>;
+
+    type DynRegion<const LEN: usize> = ProofRegion<'backend, u8, LEN>;
+}
+
+impl<'backend> ManagerAlloc for ProofManager<'backend> {
+    fn allocate_region<E: Elem, const LEN: usize>(
+        &mut self,
+        loc: Location<[E; LEN]>,
+    ) -> Self::Region<E, LEN> {
+        let offset = loc.offset();
+        ProofRegion {
+            region: self.inner.allocate_region(loc),
+            offset,
+            log: self.log,
+        }
+    }
+
+    fn allocate_dyn_region<const LEN: usize>(
+        &mut self,
+        loc: Location<[u8; LEN]>,
+    ) -> Self::DynRegion<LEN> {
+        self.allocate_region(loc)
+    }
+}
+
+impl<'backend> ManagerRead for ProofManager<'backend> {
+    #[inline]
+    fn region_read<E: Elem, const LEN: usize>(region: &Self::Region<E, LEN>, index: usize) -> E {
+        region.record(index, 1);
+        SliceManager::region_read(&region.region, index)
+    }
+
+    #[inline]
+    fn region_read_all<E: Elem, const LEN: usize>(region: &Self::Region<E, LEN>) -> Vec<E> {
+        region.record(0, LEN);
+        SliceManager::region_read_all(&region.region)
+    }
+
+    #[inline]
+    fn region_read_some<E: Elem, const LEN: usize>(
+        region: &Self::Region<E, LEN>,
+        offset: usize,
+        buffer: &mut [E],
+    ) {
+        region.record(offset, buffer.len());
+        SliceManager::region_read_some(&region.region, offset, buffer)
+    }
+
+    #[inline]
+    fn dyn_region_read<E: Elem, const LEN: usize>(
+        region: &Self::DynRegion<LEN>,
+        address: usize,
+    ) -> E {
+        region.record_bytes(address, mem::size_of::<E>());
+        SliceManager::dyn_region_read(&region.region, address)
+    }
+
+    #[inline]
+    fn dyn_region_read_all<E: Elem, const LEN: usize>(
+        region: &Self::DynRegion<LEN>,
+        address: usize,
+        values: &mut [E],
+    ) {
+        region.record_bytes(address, mem::size_of_val(values));
+        SliceManager::dyn_region_read_all(&region.region, address, values)
+    }
+}
+
+impl<'backend> ManagerWrite for ProofManager<'backend> {
+    #[inline]
+    fn region_write<E: Elem, const LEN: usize>(
+        region: &mut Self::Region<E, LEN>,
+        index: usize,
+        value: E,
+    ) {
+        region.record(index, 1);
+        SliceManager::region_write(&mut region.region, index, value)
+    }
+
+    #[inline]
+    fn region_write_all<E: Elem, const LEN: usize>(region: &mut Self::Region<E, LEN>, value: &[E]) {
+        region.record(0, LEN);
+        SliceManager::region_write_all(&mut region.region, value)
+    }
+
+    #[inline]
+    fn region_write_some<E: Elem, const LEN: usize>(
+        region: &mut Self::Region<E, LEN>,
+        index: usize,
+        buffer: &[E],
+    ) {
+        region.record(index, buffer.len());
+        SliceManager::region_write_some(&mut region.region, index, buffer)
+    }
+
+    #[inline]
+    fn dyn_region_write<E: Elem, const LEN: usize>(
+        region: &mut Self::DynRegion<LEN>,
+        address: usize,
+        value: E,
+    ) {
+        region.record_bytes(address, mem::size_of::<E>());
+        SliceManager::dyn_region_write(&mut region.region, address, value)
+    }
+
+    #[inline]
+    fn dyn_region_write_all<E: Elem, const LEN: usize>(
+        region: &mut Self::DynRegion<LEN>,
+        address: usize,
+        values: &[E],
+    ) {
+        region.record_bytes(address, mem::size_of_val(values));
+        SliceManager::dyn_region_write_all(&mut region.region, address, values)
+    }
+}
+
+impl<'backend> ManagerReadWrite for ProofManager<'backend> {
+    #[inline]
+    fn region_replace<E: Elem, const LEN: usize>(
+        region: &mut Self::Region<E, LEN>,
+        index: usize,
+        value: E,
+    ) -> E {
+        region.record(index, 1);
+        SliceManager::region_replace(&mut region.region, index, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_backend::{memory_backend::InMemoryBackend, Array, Atom, DynCells, Layout};
+
+    #[test]
+    fn test_accesses_are_recorded() {
+        type TestLayout = (Atom<u64>, Array<u32, 8>, Atom<u16>);
+
+        let (mut backend, placed) = InMemoryBackend::<TestLayout>::new();
+        let log = AccessLog::default();
+
+        {
+            let mut manager = ProofManager::new(backend.borrow_mut(), &log);
+            let (mut first, mut second, third) = TestLayout::allocate(&mut manager, placed);
+
+            first.write(42);
+            second.write_some(2, &[1, 2, 3]);
+            assert_eq!(second.read(7), 0);
+            assert_eq!(third.read(), 0);
+            assert_eq!(first.read(), 42);
+        }
+
+        assert_eq!(log.into_accesses(), [0..8, 16..28, 36..40, 40..42, 0..8]);
+
+        let log = AccessLog::default();
+        let mut storage = vec![0u8; 64];
+        {
+            let mut manager = ProofManager::new(&mut storage, &log);
+            let loc = Atom::<[u8; 64]>::placed().into_location();
+            let mut cells: DynCells<64, _> = DynCells::bind(manager.allocate_dyn_region(loc));
+            cells.write(10, 0u32);
+        }
+        assert_eq!(log.into_accesses(), [10..14]);
+    }
+}
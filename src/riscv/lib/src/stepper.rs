@@ -87,6 +87,11 @@ pub trait Stepper {
         &self,
     ) -> &MachineState<Self::MainMemoryLayout, Self::InstructionCacheLayout, Self::Manager>;
 
+    /// Obtain a mutable reference to the underlying machine state.
+    fn machine_state_mut(
+        &mut self,
+    ) -> &mut MachineState<Self::MainMemoryLayout, Self::InstructionCacheLayout, Self::Manager>;
+
     /// Result of one or more steps
     type StepResult: StepResult;
 
@@ -44,6 +44,7 @@ pub struct PvmStepper<
     inbox: Inbox,
     rollup_address: [u8; 20],
     origination_level: u32,
+    past_outboxes: Vec<(u32, Vec<Vec<u8>>)>,
 }
 
 impl<'backend, 'hooks, ML: MainMemoryLayout, ICL: InstructionCacheLayout>
@@ -78,9 +79,22 @@ impl<'backend, 'hooks, ML: MainMemoryLayout, ICL: InstructionCacheLayout>
             inbox,
             rollup_address,
             origination_level,
+            past_outboxes: Vec::new(),
         })
     }
 
+    /// Outbox messages written by the kernel so far, grouped by level.
+    pub fn outbox(&self) -> Vec<(u32, Vec<Vec<u8>>)> {
+        let mut outboxes = self.past_outboxes.clone();
+
+        let outbox = self.pvm.outbox();
+        if !outbox.is_empty() {
+            outboxes.push((outbox.level(), outbox.messages()));
+        }
+
+        outboxes
+    }
+
     /// Non-continuing variant of [`Stepper::step_max`]
     fn step_max_once(&mut self, steps: Bound<usize>) -> StepperStatus {
         match self.pvm.status() {
@@ -91,6 +105,12 @@ impl<'backend, 'hooks, ML: MainMemoryLayout, ICL: InstructionCacheLayout>
 
             PvmStatus::WaitingForInput => match self.inbox.next() {
                 Some((level, counter, payload)) => {
+                    // The PVM only keeps the outbox of the current level.
+                    let outbox = self.pvm.outbox();
+                    if outbox.level() != level && !outbox.is_empty() {
+                        self.past_outboxes.push((outbox.level(), outbox.messages()));
+                    }
+
                     let success = self.pvm.provide_input(level, counter, payload.as_slice());
 
                     if success {
@@ -133,6 +153,17 @@ impl<'backend, 'hooks, ML: MainMemoryLayout, ICL: InstructionCacheLayout>
                     }
                 }
             }
+
+            // The reveal hook is consulted during evaluation. We only end up
+            // here if it had no data for the request.
+            PvmStatus::WaitingForReveal => StepperStatus::Errored {
+                steps: 0,
+                cause: "PVM was waiting for a reveal".to_owned(),
+                message: match self.pvm.reveal_request() {
+                    Some(request) => format!("No data available for {request:?}"),
+                    None => "Reveal request is invalid".to_owned(),
+                },
+            },
         }
     }
 }
@@ -152,6 +183,13 @@ impl<'backend, 'hooks, ML: MainMemoryLayout, ICL: InstructionCacheLayout> Steppe
         &self.pvm.machine_state
     }
 
+    fn machine_state_mut(
+        &mut self,
+    ) -> &mut MachineState<Self::MainMemoryLayout, Self::InstructionCacheLayout, Self::Manager>
+    {
+        &mut self.pvm.machine_state
+    }
+
     type StepResult = StepperStatus;
 
     fn step_max(&mut self, mut step_bounds: Bound<usize>) -> Self::StepResult {
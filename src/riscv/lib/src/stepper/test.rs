@@ -184,6 +184,14 @@ impl<'a, ML: MainMemoryLayout> Stepper for TestStepper<'a, ML, TestInstructionCa
         &self.machine_state
     }
 
+    #[inline(always)]
+    fn machine_state_mut(
+        &mut self,
+    ) -> &mut MachineState<Self::MainMemoryLayout, Self::InstructionCacheLayout, Self::Manager>
+    {
+        &mut self.machine_state
+    }
+
     type StepResult = TestStepperResult;
 
     fn step_max(&mut self, steps: Bound<usize>) -> Self::StepResult {
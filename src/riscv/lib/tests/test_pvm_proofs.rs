@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+use octez_riscv::{
+    pvm::{
+        node_pvm::{NodePvm, StepProof},
+        PvmHooks,
+    },
+    state_backend::merkle::MerkleProof,
+};
+
+const HERMIT_LOADER: &[u8] = include_bytes!("../../assets/hermit-loader");
+const DUMMY_KERNEL: &[u8] = include_bytes!("../../assets/riscv-dummy.elf");
+
+/// Upper bound on the size of the encoding of a single step proof. The whole
+/// state is over 100 MiB, most of which is main memory.
+const MAX_PROOF_SIZE: usize = 128 * 1024;
+
+/// Replace the first revealed leaf of the tree by its hash.
+fn blind_first_leaf(tree: &mut MerkleProof) -> bool {
+    match tree {
+        MerkleProof::Blinded(_) => false,
+        MerkleProof::Leaf(_) => {
+            *tree = MerkleProof::Blinded(tree.root_hash());
+            true
+        }
+        MerkleProof::Node(left, right) => blind_first_leaf(left) || blind_first_leaf(right),
+    }
+}
+
+#[test]
+fn test_step_proofs() {
+    let mut hooks = PvmHooks::new(|_| {});
+    let mut pvm = NodePvm::empty().install_boot_sector(HERMIT_LOADER, DUMMY_KERNEL);
+
+    // Prove steps at various points of the execution of the kernel, so that
+    // the proofs cover booting, paging and the kernel itself.
+    for skip in [0, 1000, 10000] {
+        (pvm, _) = pvm.compute_step_many(&mut hooks, skip);
+
+        for _ in 0..4 {
+            let (next, proof) = pvm.prove_step(&mut hooks);
+            assert_eq!(next, pvm.compute_step(&mut hooks));
+            assert_eq!(proof.initial_state_hash(), pvm.hash());
+            assert_eq!(proof.final_state_hash, next.hash());
+
+            let bytes = proof.to_bytes().unwrap();
+            assert!(
+                bytes.len() <= MAX_PROOF_SIZE,
+                "Proof of step {} is too large: {} bytes",
+                pvm.get_tick(),
+                bytes.len()
+            );
+
+            let proof = StepProof::from_bytes(&bytes).unwrap();
+            assert!(NodePvm::verify_step(&proof, &mut hooks));
+
+            pvm = next;
+        }
+    }
+}
+
+#[test]
+fn test_invalid_step_proofs() {
+    let mut hooks = PvmHooks::new(|_| {});
+    let pvm = NodePvm::empty().install_boot_sector(HERMIT_LOADER, DUMMY_KERNEL);
+    let (_, proof) = pvm.prove_step(&mut hooks);
+    assert!(NodePvm::verify_step(&proof, &mut hooks));
+
+    // Claiming a different final state must be rejected.
+    let mut wrong_final_state = proof.clone();
+    wrong_final_state.final_state_hash = pvm.hash();
+    assert!(!NodePvm::verify_step(&wrong_final_state, &mut hooks));
+
+    // Omitting part of the state accessed by the step must be rejected, even
+    // though the proof still commits to the same initial state.
+    let mut incomplete = proof.clone();
+    assert!(blind_first_leaf(&mut incomplete.initial_state));
+    assert_eq!(incomplete.initial_state_hash(), proof.initial_state_hash());
+    assert!(!NodePvm::verify_step(&incomplete, &mut hooks));
+}
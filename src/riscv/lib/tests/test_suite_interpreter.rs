@@ -389,6 +389,98 @@ test_case!(test_suite_rv64um_v_remu, "rv64um-v-remu");
 test_case!(test_suite_rv64um_v_remuw, "rv64um-v-remuw");
 test_case!(test_suite_rv64um_v_remw, "rv64um-v-remw");
 
+// The bit-manipulation suites are ignored until their binaries and expected
+// results are generated with `gen_riscv_tests.sh`. Zicond is exercised by
+// `test_step_zicond` in the machine state tests in the meantime.
+// RV64-UZBA
+test_case!(#[ignore], test_suite_rv64uzba_p_add_uw, "rv64uzba-p-add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh1add, "rv64uzba-p-sh1add");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh1add_uw, "rv64uzba-p-sh1add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh2add, "rv64uzba-p-sh2add");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh2add_uw, "rv64uzba-p-sh2add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh3add, "rv64uzba-p-sh3add");
+test_case!(#[ignore], test_suite_rv64uzba_p_sh3add_uw, "rv64uzba-p-sh3add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_p_slli_uw, "rv64uzba-p-slli_uw");
+
+test_case!(#[ignore], test_suite_rv64uzba_v_add_uw, "rv64uzba-v-add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh1add, "rv64uzba-v-sh1add");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh1add_uw, "rv64uzba-v-sh1add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh2add, "rv64uzba-v-sh2add");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh2add_uw, "rv64uzba-v-sh2add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh3add, "rv64uzba-v-sh3add");
+test_case!(#[ignore], test_suite_rv64uzba_v_sh3add_uw, "rv64uzba-v-sh3add_uw");
+test_case!(#[ignore], test_suite_rv64uzba_v_slli_uw, "rv64uzba-v-slli_uw");
+
+// RV64-UZBB
+test_case!(#[ignore], test_suite_rv64uzbb_p_andn, "rv64uzbb-p-andn");
+test_case!(#[ignore], test_suite_rv64uzbb_p_clz, "rv64uzbb-p-clz");
+test_case!(#[ignore], test_suite_rv64uzbb_p_clzw, "rv64uzbb-p-clzw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_cpop, "rv64uzbb-p-cpop");
+test_case!(#[ignore], test_suite_rv64uzbb_p_cpopw, "rv64uzbb-p-cpopw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_ctz, "rv64uzbb-p-ctz");
+test_case!(#[ignore], test_suite_rv64uzbb_p_ctzw, "rv64uzbb-p-ctzw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_max, "rv64uzbb-p-max");
+test_case!(#[ignore], test_suite_rv64uzbb_p_maxu, "rv64uzbb-p-maxu");
+test_case!(#[ignore], test_suite_rv64uzbb_p_min, "rv64uzbb-p-min");
+test_case!(#[ignore], test_suite_rv64uzbb_p_minu, "rv64uzbb-p-minu");
+test_case!(#[ignore], test_suite_rv64uzbb_p_orc_b, "rv64uzbb-p-orc_b");
+test_case!(#[ignore], test_suite_rv64uzbb_p_orn, "rv64uzbb-p-orn");
+test_case!(#[ignore], test_suite_rv64uzbb_p_rev8, "rv64uzbb-p-rev8");
+test_case!(#[ignore], test_suite_rv64uzbb_p_rol, "rv64uzbb-p-rol");
+test_case!(#[ignore], test_suite_rv64uzbb_p_rolw, "rv64uzbb-p-rolw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_ror, "rv64uzbb-p-ror");
+test_case!(#[ignore], test_suite_rv64uzbb_p_rori, "rv64uzbb-p-rori");
+test_case!(#[ignore], test_suite_rv64uzbb_p_roriw, "rv64uzbb-p-roriw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_rorw, "rv64uzbb-p-rorw");
+test_case!(#[ignore], test_suite_rv64uzbb_p_sext_b, "rv64uzbb-p-sext_b");
+test_case!(#[ignore], test_suite_rv64uzbb_p_sext_h, "rv64uzbb-p-sext_h");
+test_case!(#[ignore], test_suite_rv64uzbb_p_xnor, "rv64uzbb-p-xnor");
+test_case!(#[ignore], test_suite_rv64uzbb_p_zext_h, "rv64uzbb-p-zext_h");
+
+test_case!(#[ignore], test_suite_rv64uzbb_v_andn, "rv64uzbb-v-andn");
+test_case!(#[ignore], test_suite_rv64uzbb_v_clz, "rv64uzbb-v-clz");
+test_case!(#[ignore], test_suite_rv64uzbb_v_clzw, "rv64uzbb-v-clzw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_cpop, "rv64uzbb-v-cpop");
+test_case!(#[ignore], test_suite_rv64uzbb_v_cpopw, "rv64uzbb-v-cpopw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_ctz, "rv64uzbb-v-ctz");
+test_case!(#[ignore], test_suite_rv64uzbb_v_ctzw, "rv64uzbb-v-ctzw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_max, "rv64uzbb-v-max");
+test_case!(#[ignore], test_suite_rv64uzbb_v_maxu, "rv64uzbb-v-maxu");
+test_case!(#[ignore], test_suite_rv64uzbb_v_min, "rv64uzbb-v-min");
+test_case!(#[ignore], test_suite_rv64uzbb_v_minu, "rv64uzbb-v-minu");
+test_case!(#[ignore], test_suite_rv64uzbb_v_orc_b, "rv64uzbb-v-orc_b");
+test_case!(#[ignore], test_suite_rv64uzbb_v_orn, "rv64uzbb-v-orn");
+test_case!(#[ignore], test_suite_rv64uzbb_v_rev8, "rv64uzbb-v-rev8");
+test_case!(#[ignore], test_suite_rv64uzbb_v_rol, "rv64uzbb-v-rol");
+test_case!(#[ignore], test_suite_rv64uzbb_v_rolw, "rv64uzbb-v-rolw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_ror, "rv64uzbb-v-ror");
+test_case!(#[ignore], test_suite_rv64uzbb_v_rori, "rv64uzbb-v-rori");
+test_case!(#[ignore], test_suite_rv64uzbb_v_roriw, "rv64uzbb-v-roriw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_rorw, "rv64uzbb-v-rorw");
+test_case!(#[ignore], test_suite_rv64uzbb_v_sext_b, "rv64uzbb-v-sext_b");
+test_case!(#[ignore], test_suite_rv64uzbb_v_sext_h, "rv64uzbb-v-sext_h");
+test_case!(#[ignore], test_suite_rv64uzbb_v_xnor, "rv64uzbb-v-xnor");
+test_case!(#[ignore], test_suite_rv64uzbb_v_zext_h, "rv64uzbb-v-zext_h");
+
+// RV64-UZBS
+test_case!(#[ignore], test_suite_rv64uzbs_p_bclr, "rv64uzbs-p-bclr");
+test_case!(#[ignore], test_suite_rv64uzbs_p_bclri, "rv64uzbs-p-bclri");
+test_case!(#[ignore], test_suite_rv64uzbs_p_bext, "rv64uzbs-p-bext");
+test_case!(#[ignore], test_suite_rv64uzbs_p_bexti, "rv64uzbs-p-bexti");
+test_case!(#[ignore], test_suite_rv64uzbs_p_binv, "rv64uzbs-p-binv");
+test_case!(#[ignore], test_suite_rv64uzbs_p_binvi, "rv64uzbs-p-binvi");
+test_case!(#[ignore], test_suite_rv64uzbs_p_bset, "rv64uzbs-p-bset");
+test_case!(#[ignore], test_suite_rv64uzbs_p_bseti, "rv64uzbs-p-bseti");
+
+test_case!(#[ignore], test_suite_rv64uzbs_v_bclr, "rv64uzbs-v-bclr");
+test_case!(#[ignore], test_suite_rv64uzbs_v_bclri, "rv64uzbs-v-bclri");
+test_case!(#[ignore], test_suite_rv64uzbs_v_bext, "rv64uzbs-v-bext");
+test_case!(#[ignore], test_suite_rv64uzbs_v_bexti, "rv64uzbs-v-bexti");
+test_case!(#[ignore], test_suite_rv64uzbs_v_binv, "rv64uzbs-v-binv");
+test_case!(#[ignore], test_suite_rv64uzbs_v_binvi, "rv64uzbs-v-binvi");
+test_case!(#[ignore], test_suite_rv64uzbs_v_bset, "rv64uzbs-v-bset");
+test_case!(#[ignore], test_suite_rv64uzbs_v_bseti, "rv64uzbs-v-bseti");
+
 // RV64-UZFH
 test_case!(#[ignore], test_suite_rv64uzfh_p_fadd, "rv64uzfh-p-fadd");
 test_case!(#[ignore], test_suite_rv64uzfh_p_fclass, "rv64uzfh-p-fclass");
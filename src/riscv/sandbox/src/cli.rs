@@ -14,6 +14,10 @@ pub enum Mode {
     Debug(DebugOptions),
     /// Benchmark a program
     Bench(BenchOptions),
+    /// Serve a program to GDB over the remote serial protocol
+    Gdbserver(GdbServerOptions),
+    /// Profile a program, attributing retired instructions to its functions
+    Profile(ProfileOptions),
 }
 
 #[derive(Clone, ValueEnum, Debug)]
@@ -41,6 +45,11 @@ pub struct RunOptions {
     /// Path to the initrd
     #[arg(long)]
     pub initrd: Option<Box<Path>>,
+
+    /// Write the outbox produced by the kernel to this file as JSON instead of
+    /// printing it. Only applies when running as PVM.
+    #[arg(long)]
+    pub outbox_file: Option<Box<Path>>,
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -57,6 +66,46 @@ pub struct DebugOptions {
     pub initrd: Option<Box<Path>>,
 }
 
+#[derive(Debug, Clone, Parser)]
+pub struct GdbServerOptions {
+    #[command(flatten)]
+    pub common: CommonOptions,
+
+    /// Path to the input ELF executable
+    #[arg(long, short)]
+    pub input: Box<Path>,
+
+    /// Path to the initrd
+    #[arg(long)]
+    pub initrd: Option<Box<Path>>,
+
+    /// Address on which to listen for GDB
+    #[arg(long, default_value = "127.0.0.1:1234")]
+    pub listen: String,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ProfileOptions {
+    #[command(flatten)]
+    pub common: CommonOptions,
+
+    /// Path to the input ELF executable
+    #[arg(long, short)]
+    pub input: Box<Path>,
+
+    /// Path to the initrd
+    #[arg(long)]
+    pub initrd: Option<Box<Path>>,
+
+    /// Number of functions to list, by decreasing exclusive cost
+    #[arg(long, default_value_t = 20)]
+    pub top: usize,
+
+    /// Write the collapsed stacks to this file, e.g. for `flamegraph.pl`
+    #[arg(long)]
+    pub collapsed: Option<Box<Path>>,
+}
+
 #[derive(Clone, ValueEnum, Debug)]
 pub enum BenchMode {
     Simple,
@@ -5,8 +5,12 @@
 
 pub mod bench;
 mod debug;
+mod gdbserver;
+mod profile;
 pub mod run;
 
 pub use bench::bench;
 pub use debug::debug;
+pub use gdbserver::gdbserver;
+pub use profile::profile;
 pub use run::run;
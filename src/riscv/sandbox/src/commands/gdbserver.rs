@@ -0,0 +1,594 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! GDB remote serial protocol stub
+//!
+//! Lets GDB connect to the sandbox over TCP and debug a program running on top
+//! of a [`Stepper`]. Breakpoints are implemented by single-stepping until the
+//! program counter hits one, and write watchpoints by comparing the watched
+//! memory after every step.
+
+use crate::{
+    cli::GdbServerOptions,
+    commands::run::{run_pvm, run_test},
+    format_status,
+};
+use goblin::elf::{header::ET_DYN, Elf};
+use octez_riscv::{
+    machine_state::{
+        bus::{main_memory::M1G, start_of_main_memory, Address, AddressableRead, AddressableWrite},
+        csregisters::CSRegister,
+        registers::{FRegister, FValue, XRegister},
+        AccessType, MachineState,
+    },
+    state_backend::ManagerReadWrite,
+    stepper::{StepResult, Stepper, StepperStatus},
+};
+use packet::{parse_hex, Connection, Packet};
+use std::{collections::HashSet, error::Error, fmt::Write, fs, io, net::TcpListener, ops::Bound};
+use strum::IntoEnumIterator;
+
+mod packet;
+
+/// Maximum size of the packets we accept
+const MAX_PACKET_SIZE: usize = 0x4000;
+
+/// Memory is translated one page at a time.
+const PAGE_SIZE: u64 = 4096;
+
+/// Number of steps between checks for an interrupt request while continuing
+const INTERRUPT_CHECK_INTERVAL: usize = 1 << 12;
+
+/// Register number of the program counter
+const PC_REGNUM: usize = 32;
+
+/// Register number of `f0`
+const FPU_REGNUM: usize = 33;
+
+/// Register number of the first floating-point CSR
+const FPU_CSR_REGNUM: usize = FPU_REGNUM + 32;
+
+/// Floating-point CSRs, following the floating-point registers
+const FPU_CSRS: [(&str, CSRegister); 3] = [
+    ("fflags", CSRegister::fflags),
+    ("frm", CSRegister::frm),
+    ("fcsr", CSRegister::fcsr),
+];
+
+/// Total number of registers exposed to GDB
+const NUM_REGISTERS: usize = FPU_CSR_REGNUM + FPU_CSRS.len();
+
+pub fn gdbserver(opts: GdbServerOptions) -> Result<(), Box<dyn Error>> {
+    let program = fs::read(&opts.input)?;
+    let initrd = opts.initrd.as_ref().map(fs::read).transpose()?;
+
+    // Relocatable executables are loaded at the start of the main memory.
+    let load_offset = match Elf::parse(&program)?.header.e_type {
+        ET_DYN => start_of_main_memory::<M1G>(),
+        _ => 0,
+    };
+
+    let listener = TcpListener::bind(opts.listen.as_str())?;
+    eprintln!("Listening for GDB on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    eprintln!("GDB connected from {peer}");
+    let connection = Connection::new(stream)?;
+
+    if opts.common.pvm {
+        run_pvm(
+            program.as_slice(),
+            initrd.as_deref(),
+            &opts.common,
+            |mut stepper| GdbServer::new(&mut stepper, connection, load_offset).serve(),
+        )??;
+    } else {
+        run_test(
+            program.as_slice(),
+            initrd.as_deref(),
+            &opts.common,
+            |mut stepper| GdbServer::new(&mut stepper, connection, load_offset).serve(),
+        )??;
+    }
+
+    Ok(())
+}
+
+/// Region of memory whose value is watched for writes
+struct Watchpoint {
+    address: Address,
+    value: Vec<u8>,
+}
+
+/// Reason for stopping the target
+enum Stop {
+    /// Single step completed or breakpoint hit
+    Trap,
+
+    /// Interrupted by GDB
+    Interrupt,
+
+    /// The watched memory at the given address changed
+    Watch(Address),
+}
+
+struct GdbServer<'a, S: Stepper> {
+    stepper: &'a mut S,
+    connection: Connection,
+    load_offset: u64,
+    breakpoints: HashSet<Address>,
+    watchpoints: Vec<Watchpoint>,
+
+    /// Stop reply once the program has exited
+    exit: Option<&'static str>,
+}
+
+impl<'a, S: Stepper> GdbServer<'a, S>
+where
+    S::Manager: ManagerReadWrite,
+{
+    fn new(stepper: &'a mut S, connection: Connection, load_offset: u64) -> Self {
+        Self {
+            stepper,
+            connection,
+            load_offset,
+            breakpoints: HashSet::new(),
+            watchpoints: Vec::new(),
+            exit: None,
+        }
+    }
+
+    /// Answer requests from GDB until it detaches or kills the program.
+    fn serve(mut self) -> io::Result<()> {
+        while let Some(packet) = self.connection.read_packet()? {
+            // Interrupts only matter while the program is running.
+            let Packet::Command(command) = packet else {
+                continue;
+            };
+
+            match command.as_str() {
+                "k" => break,
+                _ if command.starts_with('D') => {
+                    self.connection.write_packet("OK")?;
+                    break;
+                }
+                _ => {
+                    let reply = self.handle_command(&command)?;
+                    self.connection.write_packet(&reply)?;
+
+                    if command == "QStartNoAckMode" {
+                        self.connection.set_no_ack();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_command(&mut self, command: &str) -> io::Result<String> {
+        let reply = if command == "?" {
+            self.stop_reply(Stop::Trap)
+        } else if command.starts_with("qSupported") {
+            format!("PacketSize={MAX_PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+")
+        } else if let Some(range) = command.strip_prefix("qXfer:features:read:target.xml:") {
+            self.read_target_description(range)
+                .unwrap_or_else(|| "E00".to_owned())
+        } else if command == "QStartNoAckMode" {
+            "OK".to_owned()
+        } else if command == "qOffsets" {
+            let offset = self.load_offset;
+            format!("Text={offset:x};Data={offset:x};Bss={offset:x}")
+        } else if command == "qAttached" {
+            "1".to_owned()
+        } else if command == "qC" {
+            "QC1".to_owned()
+        } else if command == "qfThreadInfo" {
+            "m1".to_owned()
+        } else if command == "qsThreadInfo" {
+            "l".to_owned()
+        } else if command.starts_with('H') || command.starts_with('T') {
+            // There is only one thread.
+            "OK".to_owned()
+        } else if command == "g" {
+            (0..NUM_REGISTERS)
+                .filter_map(|regnum| self.read_register(regnum))
+                .map(hex::encode)
+                .collect()
+        } else if let Some(values) = command.strip_prefix('G') {
+            self.write_registers(values)
+        } else if let Some(regnum) = command.strip_prefix('p') {
+            parse_hex(regnum)
+                .and_then(|regnum| self.read_register(regnum as usize))
+                .map(hex::encode)
+                .unwrap_or_else(|| "E00".to_owned())
+        } else if let Some(assignment) = command.strip_prefix('P') {
+            self.write_register(assignment)
+        } else if let Some(range) = command.strip_prefix('m') {
+            self.read_memory_command(range)
+        } else if let Some(range_and_data) = command.strip_prefix('M') {
+            self.write_memory_command(range_and_data)
+        } else if command.starts_with('Z') || command.starts_with('z') {
+            self.update_breakpoint(command)
+        } else if command == "vCont?" {
+            "vCont;c;C;s;S".to_owned()
+        } else if let Some(actions) = command.strip_prefix("vCont;") {
+            // Only the first action matters as there is only one thread.
+            let single_step = actions.starts_with(['s', 'S']);
+            self.resume(single_step)?
+        } else if let Some(address) = command.strip_prefix('c') {
+            self.resume_at(address, false)?
+        } else if let Some(address) = command.strip_prefix('s') {
+            self.resume_at(address, true)?
+        } else {
+            // Unsupported packets get an empty reply.
+            String::new()
+        };
+
+        Ok(reply)
+    }
+
+    fn stop_reply(&self, stop: Stop) -> String {
+        if let Some(exit) = self.exit {
+            return exit.to_owned();
+        }
+
+        match stop {
+            Stop::Trap => "S05".to_owned(),
+            Stop::Interrupt => "S02".to_owned(),
+            Stop::Watch(address) => format!("T05watch:{address:x};"),
+        }
+    }
+
+    /// Serve a chunk of the target description, given as `offset,length`.
+    fn read_target_description(&self, range: &str) -> Option<String> {
+        let (offset, length) = range.split_once(',')?;
+        let (offset, length) = (parse_hex(offset)? as usize, parse_hex(length)? as usize);
+
+        let description = target_description();
+        let chunk = description.get(offset..).unwrap_or_default();
+        Some(if chunk.len() > length {
+            format!("m{}", &chunk[..length])
+        } else {
+            format!("l{chunk}")
+        })
+    }
+
+    fn machine_state(
+        &self,
+    ) -> &MachineState<S::MainMemoryLayout, S::InstructionCacheLayout, S::Manager> {
+        self.stepper.machine_state()
+    }
+
+    fn machine_state_mut(
+        &mut self,
+    ) -> &mut MachineState<S::MainMemoryLayout, S::InstructionCacheLayout, S::Manager> {
+        self.stepper.machine_state_mut()
+    }
+
+    /// Little-endian value of the register numbered `regnum` in the target
+    /// description.
+    fn read_register(&self, regnum: usize) -> Option<Vec<u8>> {
+        let hart = &self.machine_state().hart;
+
+        let value = match regnum {
+            _ if regnum < PC_REGNUM => hart.xregisters.read(xregister(regnum)?).to_le_bytes(),
+            PC_REGNUM => hart.pc.read().to_le_bytes(),
+            _ if regnum < FPU_CSR_REGNUM => {
+                u64::from(hart.fregisters.read(fregister(regnum - FPU_REGNUM)?)).to_le_bytes()
+            }
+            _ => {
+                let (_, csr) = FPU_CSRS.get(regnum - FPU_CSR_REGNUM)?;
+                return Some(hart.csregisters.read::<u32>(*csr).to_le_bytes().to_vec());
+            }
+        };
+
+        Some(value.to_vec())
+    }
+
+    /// Write the register numbered `regnum` from its little-endian value.
+    fn write_register_bytes(&mut self, regnum: usize, bytes: &[u8]) -> Option<()> {
+        let mut value = [0u8; 8];
+        value.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        let value = u64::from_le_bytes(value);
+
+        let hart = &mut self.machine_state_mut().hart;
+        match regnum {
+            _ if regnum < PC_REGNUM => hart.xregisters.write(xregister(regnum)?, value),
+            PC_REGNUM => hart.pc.write(value),
+            _ if regnum < FPU_CSR_REGNUM => hart
+                .fregisters
+                .write(fregister(regnum - FPU_REGNUM)?, FValue::from(value)),
+            _ => {
+                let (_, csr) = FPU_CSRS.get(regnum - FPU_CSR_REGNUM)?;
+                hart.csregisters.write(*csr, value)
+            }
+        }
+
+        Some(())
+    }
+
+    /// Handle a `G` packet, writing all registers.
+    fn write_registers(&mut self, values: &str) -> String {
+        let Ok(bytes) = hex::decode(values) else {
+            return "E00".to_owned();
+        };
+
+        let mut bytes = bytes.as_slice();
+        for regnum in 0..NUM_REGISTERS {
+            let size = register_size(regnum).min(bytes.len());
+            let (value, rest) = bytes.split_at(size);
+            if value.is_empty() {
+                break;
+            }
+
+            self.write_register_bytes(regnum, value);
+            bytes = rest;
+        }
+
+        "OK".to_owned()
+    }
+
+    /// Handle a `P` packet of the form `regnum=value`.
+    fn write_register(&mut self, assignment: &str) -> String {
+        let written = assignment.split_once('=').and_then(|(regnum, value)| {
+            let regnum = parse_hex(regnum)? as usize;
+            let value = hex::decode(value).ok()?;
+            self.write_register_bytes(regnum, &value)
+        });
+
+        match written {
+            Some(()) => "OK".to_owned(),
+            None => "E00".to_owned(),
+        }
+    }
+
+    /// Read `length` bytes of virtual memory at `address`.
+    fn read_memory(&self, address: Address, length: usize) -> Option<Vec<u8>> {
+        let machine = self.machine_state();
+        let mut data = vec![0u8; length];
+
+        for (virt_addr, chunk) in page_chunks(address, &mut data) {
+            let phys_addr = machine
+                .translate_without_cache(virt_addr, AccessType::Load)
+                .ok()?;
+            machine.bus.read_all(phys_addr, chunk).ok()?;
+        }
+
+        Some(data)
+    }
+
+    /// Write `data` to virtual memory at `address`.
+    fn write_memory(&mut self, address: Address, mut data: Vec<u8>) -> Option<()> {
+        let machine = self.machine_state_mut();
+
+        for (virt_addr, chunk) in page_chunks(address, &mut data) {
+            // The debugger may write to pages the program itself can't write to,
+            // such as the ones holding its code.
+            let phys_addr = machine
+                .translate_without_cache(virt_addr, AccessType::Load)
+                .ok()?;
+            machine.bus.write_all(phys_addr, chunk).ok()?;
+        }
+
+        // The written memory may hold instructions.
+        machine.instruction_cache.invalidate();
+        Some(())
+    }
+
+    /// Handle an `m` packet of the form `address,length`.
+    fn read_memory_command(&self, range: &str) -> String {
+        range
+            .split_once(',')
+            .and_then(|(address, length)| {
+                let length = (parse_hex(length)? as usize).min(MAX_PACKET_SIZE / 2);
+                self.read_memory(parse_hex(address)?, length)
+            })
+            .map(hex::encode)
+            .unwrap_or_else(|| "E14".to_owned())
+    }
+
+    /// Handle an `M` packet of the form `address,length:data`.
+    fn write_memory_command(&mut self, range_and_data: &str) -> String {
+        let written = range_and_data.split_once(':').and_then(|(range, data)| {
+            let (address, _length) = range.split_once(',')?;
+            let data = hex::decode(data).ok()?;
+            self.write_memory(parse_hex(address)?, data)
+        });
+
+        match written {
+            Some(()) => "OK".to_owned(),
+            None => "E14".to_owned(),
+        }
+    }
+
+    /// Handle a `Z` or `z` packet of the form `type,address,kind`.
+    fn update_breakpoint(&mut self, command: &str) -> String {
+        let insert = command.starts_with('Z');
+        let mut fields = command[1..].split(',');
+        let (Some(kind), Some(address), Some(length)) = (
+            fields.next(),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            return "E00".to_owned();
+        };
+        let length = (length as usize).min(MAX_PACKET_SIZE / 2);
+
+        match (kind, insert) {
+            // Software and hardware breakpoints are handled alike.
+            ("0" | "1", true) => {
+                self.breakpoints.insert(address);
+            }
+            ("0" | "1", false) => {
+                self.breakpoints.remove(&address);
+            }
+            ("2", true) => match self.read_memory(address, length) {
+                Some(value) => self.watchpoints.push(Watchpoint { address, value }),
+                None => return "E14".to_owned(),
+            },
+            ("2", false) => self
+                .watchpoints
+                .retain(|watchpoint| watchpoint.address != address),
+            // Read and access watchpoints can't be detected by looking at memory.
+            _ => return String::new(),
+        }
+
+        "OK".to_owned()
+    }
+
+    /// Find a watchpoint whose memory has changed, updating its value.
+    fn triggered_watchpoint(&mut self) -> Option<Address> {
+        let mut watchpoints = std::mem::take(&mut self.watchpoints);
+
+        let triggered = watchpoints.iter_mut().find_map(|watchpoint| {
+            let value = self.read_memory(watchpoint.address, watchpoint.value.len())?;
+            if value == watchpoint.value {
+                return None;
+            }
+
+            watchpoint.value = value;
+            Some(watchpoint.address)
+        });
+
+        self.watchpoints = watchpoints;
+        triggered
+    }
+
+    /// Handle a `c` or `s` packet, optionally resuming at `address`.
+    fn resume_at(&mut self, address: &str, single_step: bool) -> io::Result<String> {
+        if let Some(address) = parse_hex(address) {
+            self.machine_state_mut().hart.pc.write(address);
+        }
+
+        self.resume(single_step)
+    }
+
+    /// Run the program until it stops, returning the stop reply.
+    fn resume(&mut self, single_step: bool) -> io::Result<String> {
+        let mut steps = 0usize;
+
+        while self.exit.is_none() {
+            let status = self
+                .stepper
+                .step_max(Bound::Included(1))
+                .to_stepper_status();
+
+            match status {
+                StepperStatus::Running { steps: 0 } => {
+                    // The stepper can't make progress, give control back to GDB.
+                    return Ok(self.stop_reply(Stop::Trap));
+                }
+                StepperStatus::Running { .. } => {}
+                StepperStatus::Exited { success, .. } => {
+                    eprintln!("{}", format_status(&status));
+                    self.exit = Some(if success { "W00" } else { "W01" });
+                    break;
+                }
+                StepperStatus::Errored { .. } => {
+                    eprintln!("{}", format_status(&status));
+                    // Report the program as aborted.
+                    self.exit = Some("X06");
+                    break;
+                }
+            }
+
+            if let Some(address) = self.triggered_watchpoint() {
+                return Ok(self.stop_reply(Stop::Watch(address)));
+            }
+
+            let pc = self.machine_state().hart.pc.read();
+            if single_step || self.breakpoints.contains(&pc) {
+                return Ok(self.stop_reply(Stop::Trap));
+            }
+
+            steps += 1;
+            if steps % INTERRUPT_CHECK_INTERVAL == 0 && self.connection.poll_interrupt()? {
+                return Ok(self.stop_reply(Stop::Interrupt));
+            }
+        }
+
+        Ok(self.stop_reply(Stop::Trap))
+    }
+}
+
+fn xregister(regnum: usize) -> Option<XRegister> {
+    XRegister::iter().nth(regnum)
+}
+
+fn fregister(regnum: usize) -> Option<FRegister> {
+    FRegister::iter().nth(regnum)
+}
+
+/// Size in bytes of the register numbered `regnum`
+fn register_size(regnum: usize) -> usize {
+    if regnum < FPU_CSR_REGNUM {
+        8
+    } else {
+        4
+    }
+}
+
+/// Split `data`, to be accessed at virtual address `address`, at page boundaries.
+fn page_chunks(address: Address, data: &mut [u8]) -> impl Iterator<Item = (Address, &mut [u8])> {
+    let first_len = ((PAGE_SIZE - address % PAGE_SIZE) as usize).min(data.len());
+    let (first, rest) = data.split_at_mut(first_len);
+
+    let first = std::iter::once((address, first));
+    let rest = rest
+        .chunks_mut(PAGE_SIZE as usize)
+        .enumerate()
+        .map(move |(index, chunk)| {
+            let offset = (first_len + index * PAGE_SIZE as usize) as u64;
+            (address.wrapping_add(offset), chunk)
+        });
+
+    first.chain(rest)
+}
+
+/// Target description listing the registers in the order of their numbers
+fn target_description() -> String {
+    let mut description = String::from(
+        "<?xml version=\"1.0\"?>\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\">\
+         <architecture>riscv:rv64</architecture>\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">",
+    );
+
+    for (regnum, xreg) in XRegister::iter().enumerate() {
+        let reg_type = match regnum {
+            1 => "code_ptr",
+            2 => "data_ptr",
+            _ => "int",
+        };
+        let _ = write!(
+            description,
+            "<reg name=\"{xreg}\" bitsize=\"64\" type=\"{reg_type}\" regnum=\"{regnum}\"/>"
+        );
+    }
+    let _ = write!(
+        description,
+        "<reg name=\"pc\" bitsize=\"64\" type=\"code_ptr\" regnum=\"{PC_REGNUM}\"/>\
+         </feature>\
+         <feature name=\"org.gnu.gdb.riscv.fpu\">"
+    );
+
+    for (index, freg) in FRegister::iter().enumerate() {
+        let regnum = FPU_REGNUM + index;
+        let _ = write!(
+            description,
+            "<reg name=\"{freg}\" bitsize=\"64\" type=\"ieee_double\" regnum=\"{regnum}\"/>"
+        );
+    }
+    for (index, (name, _)) in FPU_CSRS.iter().enumerate() {
+        let regnum = FPU_CSR_REGNUM + index;
+        let _ = write!(
+            description,
+            "<reg name=\"{name}\" bitsize=\"32\" type=\"int\" regnum=\"{regnum}\"/>"
+        );
+    }
+
+    description.push_str("</feature></target>");
+    description
+}
@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Framing of the GDB remote serial protocol
+//!
+//! Packets have the form `$<data>#<checksum>`, where the checksum is the sum of
+//! the data bytes modulo 256, written as two hex digits. Unless no-ack mode has
+//! been negotiated, each packet is acknowledged with `+` (or `-` to request a
+//! retransmission). A single `0x03` byte outside of a packet interrupts the
+//! target.
+
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::TcpStream,
+};
+
+/// Byte sent by GDB to interrupt the target
+const INTERRUPT: u8 = 0x03;
+
+/// Message received from GDB
+pub enum Packet {
+    /// Command packet, without framing
+    Command(String),
+
+    /// Request to interrupt the running target
+    Interrupt,
+}
+
+/// Connection to a GDB client
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    no_ack: bool,
+    last_sent: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+            no_ack: false,
+            last_sent: Vec::new(),
+        })
+    }
+
+    /// Stop sending and expecting acknowledgements.
+    pub fn set_no_ack(&mut self) {
+        self.no_ack = true;
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Read the next packet. Returns `None` once GDB has closed the connection.
+    pub fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            let Some(byte) = self.read_byte()? else {
+                return Ok(None);
+            };
+
+            match byte {
+                INTERRUPT => return Ok(Some(Packet::Interrupt)),
+                b'-' if !self.no_ack => self.writer.write_all(&self.last_sent)?,
+                b'$' => {
+                    let mut data = Vec::new();
+                    self.reader.read_until(b'#', &mut data)?;
+                    if data.pop() != Some(b'#') {
+                        return Ok(None);
+                    }
+
+                    let mut checksum = [0u8; 2];
+                    self.reader.read_exact(&mut checksum)?;
+                    let valid = std::str::from_utf8(&checksum)
+                        .ok()
+                        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                        == Some(compute_checksum(&data));
+
+                    if !self.no_ack {
+                        self.writer.write_all(if valid { b"+" } else { b"-" })?;
+                    }
+
+                    if valid {
+                        let data = String::from_utf8_lossy(&data).into_owned();
+                        return Ok(Some(Packet::Command(data)));
+                    }
+                }
+                // Acknowledgements and stray bytes
+                _ => {}
+            }
+        }
+    }
+
+    /// Send a packet containing `data`.
+    pub fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let checksum = compute_checksum(data.as_bytes());
+        self.last_sent = format!("${data}#{checksum:02x}").into_bytes();
+        self.writer.write_all(&self.last_sent)?;
+        self.writer.flush()
+    }
+
+    /// Check, without blocking, whether GDB asked to interrupt the target. A
+    /// closed connection also stops the target.
+    pub fn poll_interrupt(&mut self) -> io::Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let result = match self.reader.fill_buf() {
+            Ok([]) => Ok(true),
+            // While the target runs, GDB only sends acknowledgements besides
+            // interrupts, so the bytes before an interrupt can be dropped.
+            Ok(data) => {
+                let interrupt = data.iter().position(|byte| *byte == INTERRUPT);
+                let consumed = interrupt.map_or(data.len(), |index| index + 1);
+                self.reader.consume(consumed);
+                Ok(interrupt.is_some())
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        };
+        self.reader.get_ref().set_nonblocking(false)?;
+        result
+    }
+}
+
+fn compute_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Parse a hex number, as used for addresses, lengths and register numbers.
+pub fn parse_hex(data: &str) -> Option<u64> {
+    u64::from_str_radix(data, 16).ok()
+}
@@ -0,0 +1,392 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Profiler attributing the retired instructions of a program to its functions
+//!
+//! The program is run one step at a time. A jump which sets `ra` to the address
+//! of the following instruction is considered a call, and a jump to the return
+//! address of an active call a return. Steps are counted in a call tree, from
+//! which the exclusive and inclusive cost of each function and the collapsed
+//! stacks are derived.
+
+use crate::{
+    cli::ProfileOptions,
+    commands::run::{general_run, UseStepper},
+    format_status,
+    table::utils::thousand_format,
+};
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, CellAlignment, Color,
+    ContentArrangement, Table,
+};
+use octez_riscv::{
+    kernel_loader,
+    machine_state::{
+        bus::{main_memory::M1G, Address},
+        registers::ra,
+        AccessType,
+    },
+    stepper::{StepResult, Stepper, StepperStatus},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, BufWriter, Write},
+    ops::Bound,
+};
+
+/// Name used for instructions which are not covered by any symbol
+const UNKNOWN_SYMBOL: &str = "[unknown]";
+
+/// Calls deeper than this are attributed to the deepest tracked call.
+const MAX_CALL_DEPTH: usize = 1024;
+
+pub fn profile(opts: ProfileOptions) -> Result<(), Box<dyn Error>> {
+    let program = fs::read(&opts.input)?;
+    let initrd = opts.initrd.as_ref().map(fs::read).transpose()?;
+    let symbols = Symbols::new(kernel_loader::get_elf_symbols::<M1G>(&program)?);
+
+    struct Runner<'a>(&'a ProfileOptions, &'a Symbols);
+
+    impl UseStepper<(CallTree, StepperStatus)> for Runner<'_> {
+        fn advance<S: Stepper>(self, mut stepper: S) -> (CallTree, StepperStatus) {
+            let mut profiler = Profiler::new(self.1);
+            let status = profiler.run(&mut stepper, self.0.common.max_steps);
+            (profiler.tree, status)
+        }
+    }
+
+    let (tree, status) = general_run(&opts.common, program, initrd, Runner(&opts, &symbols))?;
+    eprintln!("{}", format_status(&status));
+
+    let costs = tree.function_costs(symbols.names.len());
+    println!("{}", top_table(&symbols, &costs, tree.total(), opts.top));
+
+    if let Some(path) = &opts.collapsed {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        tree.write_collapsed(&symbols, &mut writer)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Symbols of the program, sorted by address
+struct Symbols {
+    addresses: Vec<Address>,
+
+    /// Names of the symbols, followed by [`UNKNOWN_SYMBOL`]
+    names: Vec<String>,
+}
+
+impl Symbols {
+    fn new(symbols: HashMap<u64, &str>) -> Self {
+        let mut symbols: Vec<_> = symbols
+            .into_iter()
+            // Skip mapping symbols and local labels, which don't start functions.
+            .filter(|(_, name)| !name.starts_with('$') && !name.starts_with(".L"))
+            .collect();
+        symbols.sort_unstable();
+
+        let (addresses, mut names): (Vec<_>, Vec<_>) = symbols
+            .into_iter()
+            .map(|(address, name)| (address, name.to_owned()))
+            .unzip();
+        names.push(UNKNOWN_SYMBOL.to_owned());
+
+        Self { addresses, names }
+    }
+
+    /// Index of the symbol covering `address`
+    fn resolve(&self, address: Address) -> usize {
+        match self.addresses.partition_point(|start| *start <= address) {
+            0 => self.addresses.len(),
+            index => index - 1,
+        }
+    }
+}
+
+/// Node of the [`CallTree`], for a function called from the path leading to it
+struct Node {
+    /// Index of the function's symbol, `None` for the root
+    function: Option<usize>,
+    children: HashMap<usize, usize>,
+
+    /// Steps retired in the function itself along this path
+    steps: u64,
+}
+
+/// Steps retired along each call path
+struct CallTree {
+    nodes: Vec<Node>,
+}
+
+/// Exclusive and inclusive cost of a function
+#[derive(Clone, Copy, Default)]
+struct Cost {
+    exclusive: u64,
+    inclusive: u64,
+}
+
+impl CallTree {
+    const ROOT: usize = 0;
+
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                function: None,
+                children: HashMap::new(),
+                steps: 0,
+            }],
+        }
+    }
+
+    /// Node for `function` called from `parent`, created if needed
+    fn child(&mut self, parent: usize, function: usize) -> usize {
+        if let Some(&node) = self.nodes[parent].children.get(&function) {
+            return node;
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            function: Some(function),
+            children: HashMap::new(),
+            steps: 0,
+        });
+        self.nodes[parent].children.insert(function, node);
+        node
+    }
+
+    /// Total number of steps recorded
+    fn total(&self) -> u64 {
+        self.nodes.iter().map(|node| node.steps).sum()
+    }
+
+    /// Steps recorded in each node and its descendants
+    fn subtree_steps(&self) -> Vec<u64> {
+        let mut totals: Vec<u64> = self.nodes.iter().map(|node| node.steps).collect();
+
+        // Children are always created after their parent, so their totals are
+        // complete by the time they're added to it.
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            let children: u64 = node.children.values().map(|&child| totals[child]).sum();
+            totals[index] += children;
+        }
+
+        totals
+    }
+
+    /// Cost of every function, indexed by symbol. Recursive calls count once
+    /// towards the inclusive cost.
+    fn function_costs(&self, num_functions: usize) -> Vec<Cost> {
+        let totals = self.subtree_steps();
+        let mut costs = vec![Cost::default(); num_functions];
+        let mut active = vec![0usize; num_functions];
+
+        // Depth-first traversal, keeping track of the functions on the path
+        let mut stack = vec![(Self::ROOT, false)];
+        while let Some((index, visited)) = stack.pop() {
+            let node = &self.nodes[index];
+            let Some(function) = node.function else {
+                stack.extend(node.children.values().map(|&child| (child, false)));
+                continue;
+            };
+
+            if visited {
+                active[function] -= 1;
+                continue;
+            }
+
+            costs[function].exclusive += node.steps;
+            if active[function] == 0 {
+                costs[function].inclusive += totals[index];
+            }
+
+            active[function] += 1;
+            stack.push((index, true));
+            stack.extend(node.children.values().map(|&child| (child, false)));
+        }
+
+        costs
+    }
+
+    /// Write the steps of each call path as collapsed stacks, one
+    /// `caller;callee steps` line per path.
+    fn write_collapsed(&self, symbols: &Symbols, writer: &mut impl Write) -> io::Result<()> {
+        let mut lines = Vec::new();
+        let mut stack = vec![(Self::ROOT, String::new())];
+        while let Some((index, path)) = stack.pop() {
+            let node = &self.nodes[index];
+
+            if node.steps > 0 {
+                lines.push(format!("{path} {}", node.steps));
+            }
+
+            for (&function, &child) in node.children.iter() {
+                let name = &symbols.names[function];
+                let path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path};{name}")
+                };
+                stack.push((child, path));
+            }
+        }
+
+        lines.sort_unstable();
+        for line in lines {
+            writeln!(writer, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Call which has not returned yet
+struct Frame {
+    /// Node of the calling function
+    node: usize,
+    return_address: Address,
+}
+
+struct Profiler<'a> {
+    symbols: &'a Symbols,
+    tree: CallTree,
+    frames: Vec<Frame>,
+}
+
+impl<'a> Profiler<'a> {
+    fn new(symbols: &'a Symbols) -> Self {
+        Self {
+            symbols,
+            tree: CallTree::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Run the program, recording every step.
+    fn run<S: Stepper>(&mut self, stepper: &mut S, max_steps: Option<usize>) -> StepperStatus {
+        let mut status = StepperStatus::default();
+
+        for _step in 0..max_steps.unwrap_or(usize::MAX) {
+            let pc = stepper.machine_state().hart.pc.read();
+            let node = self.record_step(stepper, pc);
+
+            let step_status = stepper.step_max(Bound::Included(1)).to_stepper_status();
+            let made_progress = !matches!(step_status, StepperStatus::Running { steps: 0 });
+            status += step_status;
+
+            match status {
+                StepperStatus::Running { .. } if made_progress => {}
+                _ => break,
+            }
+
+            let machine_state = stepper.machine_state();
+            let next_pc = machine_state.hart.pc.read();
+            let return_address = machine_state.hart.xregisters.read(ra);
+            self.track_call(node, pc, next_pc, return_address);
+        }
+
+        status
+    }
+
+    /// Attribute the step at `pc` to the current call path. Returns the node
+    /// the step was recorded in.
+    fn record_step<S: Stepper>(&mut self, stepper: &S, pc: Address) -> usize {
+        // Symbols refer to physical addresses, like in the debugger.
+        let phys_pc = stepper
+            .machine_state()
+            .translate_without_cache(pc, AccessType::Instruction)
+            .unwrap_or(pc);
+
+        let parent = self
+            .frames
+            .last()
+            .map_or(CallTree::ROOT, |frame| frame.node);
+        let node = self.tree.child(parent, self.symbols.resolve(phys_pc));
+        self.tree.nodes[node].steps += 1;
+        node
+    }
+
+    /// Detect calls and returns made by the step at `pc`.
+    fn track_call(&mut self, node: usize, pc: Address, next_pc: Address, return_address: Address) {
+        let following = |width| pc.wrapping_add(width);
+        if next_pc == following(2) || next_pc == following(4) {
+            return;
+        }
+
+        // Returning from a call also ends the calls it made that are still
+        // active, e.g. after a `longjmp`.
+        if let Some(depth) = self
+            .frames
+            .iter()
+            .rposition(|frame| frame.return_address == next_pc)
+        {
+            self.frames.truncate(depth);
+            return;
+        }
+
+        let is_call = return_address == following(2) || return_address == following(4);
+        if is_call && self.frames.len() < MAX_CALL_DEPTH {
+            self.frames.push(Frame {
+                node,
+                return_address,
+            });
+        }
+    }
+}
+
+/// Table of the `top` functions with the highest exclusive cost
+fn top_table(symbols: &Symbols, costs: &[Cost], total: u64, top: usize) -> Table {
+    let mut functions: Vec<_> = costs
+        .iter()
+        .enumerate()
+        .filter(|(_, cost)| cost.inclusive > 0)
+        .collect();
+    functions.sort_by(|(_, a), (_, b)| {
+        b.exclusive
+            .cmp(&a.exclusive)
+            .then(b.inclusive.cmp(&a.inclusive))
+    });
+
+    let percentage = |steps: u64| {
+        let percentage = steps as f64 * 100.0 / total.max(1) as f64;
+        Cell::new(format!("{percentage:.2}%")).set_alignment(CellAlignment::Right)
+    };
+    let steps =
+        |steps: u64| Cell::new(thousand_format(steps, 0)).set_alignment(CellAlignment::Right);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(
+            [
+                "Function",
+                "Exclusive",
+                "Exclusive %",
+                "Inclusive",
+                "Inclusive %",
+            ]
+            .map(|header| {
+                Cell::new(header)
+                    .add_attribute(Attribute::Bold)
+                    .fg(Color::DarkCyan)
+            }),
+        );
+
+    for (function, cost) in functions.into_iter().take(top) {
+        table.add_row(vec![
+            Cell::new(&symbols.names[function]),
+            steps(cost.exclusive),
+            percentage(cost.exclusive),
+            steps(cost.inclusive),
+            percentage(cost.inclusive),
+        ]);
+    }
+
+    table
+}
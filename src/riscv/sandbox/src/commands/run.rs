@@ -12,7 +12,8 @@ use octez_riscv::{
     pvm::PvmHooks,
     stepper::{pvm::PvmStepper, test::TestStepper, StepResult, Stepper, StepperStatus},
 };
-use std::{error::Error, fs, io::Write, ops::Bound};
+use serde::Serialize;
+use std::{error::Error, fs, io::Write, ops::Bound, path::Path};
 use tezos_smart_rollup::utils::{console::Console, inbox::InboxBuilder};
 use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
 
@@ -20,17 +21,75 @@ pub fn run(opts: RunOptions) -> Result<(), Box<dyn Error>> {
     let program = fs::read(&opts.input)?;
     let initrd = opts.initrd.as_ref().map(fs::read).transpose()?;
 
+    if opts.common.pvm {
+        // The outbox is specific to the PVM stepper.
+        return run_pvm(
+            program.as_slice(),
+            initrd.as_deref(),
+            &opts.common,
+            |mut stepper| {
+                let result = run_stepper(&mut stepper, opts.common.max_steps);
+                write_outbox(stepper.outbox(), opts.outbox_file.as_deref())?;
+                result
+            },
+        )?;
+    }
+
     struct Runner<'a>(&'a RunOptions);
 
     impl UseStepper<Result<(), Box<dyn Error>>> for Runner<'_> {
-        fn advance<S: Stepper>(self, stepper: S) -> Result<(), Box<dyn Error>> {
-            run_stepper(stepper, self.0.common.max_steps)
+        fn advance<S: Stepper>(self, mut stepper: S) -> Result<(), Box<dyn Error>> {
+            run_stepper(&mut stepper, self.0.common.max_steps)
         }
     }
 
     general_run(&opts.common, program, initrd, Runner(&opts))?
 }
 
+/// Outbox message produced by the kernel, as written to the outbox file
+#[derive(Serialize)]
+struct OutboxMessage {
+    level: u32,
+    index: usize,
+    message: String,
+}
+
+/// Print the outbox, or write it to `outbox_file` if given.
+fn write_outbox(
+    outbox: Vec<(u32, Vec<Vec<u8>>)>,
+    outbox_file: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let messages = outbox.into_iter().flat_map(|(level, messages)| {
+        messages
+            .into_iter()
+            .enumerate()
+            .map(move |(index, message)| OutboxMessage {
+                level,
+                index,
+                message: hex::encode(message),
+            })
+    });
+
+    match outbox_file {
+        Some(path) => {
+            let messages: Vec<_> = messages.collect();
+            serde_json::to_writer_pretty(fs::File::create(path)?, &messages)?;
+        }
+        None => {
+            for OutboxMessage {
+                level,
+                index,
+                message,
+            } in messages
+            {
+                println!("Outbox message {index} at level {level}: {message}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// XXX: Trait used to pass a function for using the generic stepper.
 /// (Couldn't use a trait object + impl FnOnce(...) since [`Stepper`] is not object safe)
 pub trait UseStepper<R> {
@@ -54,7 +113,7 @@ pub fn general_run<F: UseStepper<R>, R>(
     }
 }
 
-fn run_test<R>(
+pub fn run_test<R>(
     program: &[u8],
     initrd: Option<&[u8]>,
     common: &CommonOptions,
@@ -71,7 +130,7 @@ fn run_test<R>(
     Ok(f_stepper(stepper))
 }
 
-fn run_pvm<R>(
+pub fn run_pvm<R>(
     program: &[u8],
     initrd: Option<&[u8]>,
     common: &CommonOptions,
@@ -108,7 +167,10 @@ fn run_pvm<R>(
     Ok(f_stepper(stepper))
 }
 
-fn run_stepper(mut stepper: impl Stepper, max_steps: Option<usize>) -> Result<(), Box<dyn Error>> {
+fn run_stepper<S: Stepper>(
+    stepper: &mut S,
+    max_steps: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
     let max_steps = match max_steps {
         Some(max_steps) => Bound::Included(max_steps),
         None => Bound::Unbounded,
@@ -34,11 +34,13 @@ fn posix_exit_mode(exit_mode: &ExitMode) -> Mode {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    use commands::{bench, debug, run};
+    use commands::{bench, debug, gdbserver, profile, run};
     let cli = cli::parse();
     match cli.command {
         cli::Mode::Run(opts) => run(opts),
         cli::Mode::Debug(opts) => debug(opts),
         cli::Mode::Bench(opts) => bench(opts),
+        cli::Mode::Gdbserver(opts) => gdbserver(opts),
+        cli::Mode::Profile(opts) => profile(opts),
     }
 }